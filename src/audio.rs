@@ -0,0 +1,458 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use eframe::egui::{self, Ui};
+use log::info;
+use stream_desk::{
+    protocol::{is_audio_header, is_audio_stream_start, Packet},
+    secure_channel::SecureChannel,
+    LOG_TARGET,
+};
+
+/// The length of the fixed part of an Ogg page header, up to the segment count.
+const OGG_HEADER_LENGTH: usize = 27;
+/// Parts of device names that are usually loopback devices, which capture the system audio.
+const LOOPBACK_DEVICE_NAMES: [&str; 3] = ["Stereo Mix", "virtual-audio-capturer", "Loopback"];
+
+/// What an audio capture is used for, which decides the encoder settings.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AudioKind {
+    /// The host's system audio, in stereo and tuned for music and video.
+    System,
+    /// A participant's microphone, in mono and tuned for speech.
+    Microphone,
+}
+
+/// Lists the audio input devices of the computer.
+///
+/// # Returns
+///
+/// The names of the DirectShow audio devices, as `ffmpeg` expects them.
+pub fn list_audio_devices() -> Vec<String> {
+    let output = Command::new("ffmpeg")
+        .args([
+            "-hide_banner",
+            "-list_devices",
+            "true",
+            "-f",
+            "dshow",
+            "-i",
+            "dummy",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .output();
+
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+
+    // ffmpeg prints the devices to stderr, either tagged with "(audio)" or in an audio section
+    let mut devices = Vec::new();
+    let mut in_audio_section = false;
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        if line.contains("DirectShow audio devices") {
+            in_audio_section = true;
+            continue;
+        }
+        if line.contains("DirectShow video devices") {
+            in_audio_section = false;
+            continue;
+        }
+        if line.contains("Alternative name") {
+            continue;
+        }
+
+        if in_audio_section || line.contains("(audio)") {
+            let mut parts = line.split('"');
+            if let (Some(_), Some(name)) = (parts.next(), parts.next()) {
+                devices.push(name.to_string());
+            }
+        }
+    }
+
+    devices
+}
+
+/// Picks the device that most likely captures the system audio.
+///
+/// # Arguments
+///
+/// * `devices` - The audio input devices.
+///
+/// # Returns
+///
+/// The name of a loopback device, or `None` if there is none.
+pub fn default_system_device(devices: &[String]) -> Option<String> {
+    devices
+        .iter()
+        .find(|device| {
+            LOOPBACK_DEVICE_NAMES
+                .iter()
+                .any(|name| device.contains(name))
+        })
+        .cloned()
+}
+
+/// Renders a choice between the audio input devices, or none of them.
+///
+/// # Arguments
+///
+/// * `ui` - The `Ui` to draw on.
+/// * `label` - The label of the choice.
+/// * `devices` - The audio input devices.
+/// * `device` - The chosen device, `None` if audio is off.
+///
+/// # Returns
+///
+/// Whether the choice was changed.
+pub fn device_picker(
+    ui: &mut Ui,
+    label: &str,
+    devices: &[String],
+    device: &mut Option<String>,
+) -> bool {
+    let before = device.clone();
+
+    egui::ComboBox::from_label(label)
+        .selected_text(device.as_deref().unwrap_or("Off"))
+        .width(200.0)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(device, None, "Off");
+            for name in devices {
+                ui.selectable_value(device, Some(name.clone()), name);
+            }
+        });
+
+    *device != before
+}
+
+/// Splits a byte stream into Ogg pages.
+struct OggPageReader {
+    buffer: Vec<u8>,
+}
+
+impl OggPageReader {
+    fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    /// Adds bytes to the stream.
+    fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Takes the next complete page from the stream.
+    ///
+    /// # Returns
+    ///
+    /// The page with its header, or `None` if no complete page was pushed yet.
+    fn next_page(&mut self) -> Option<Vec<u8>> {
+        // skip anything before the capture pattern
+        let start = self.buffer.windows(4).position(|bytes| bytes == b"OggS")?;
+        self.buffer.drain(..start);
+
+        let segments = *self.buffer.get(OGG_HEADER_LENGTH - 1)? as usize;
+        let segment_table = self
+            .buffer
+            .get(OGG_HEADER_LENGTH..OGG_HEADER_LENGTH + segments)?;
+        let body_length: usize = segment_table.iter().map(|length| *length as usize).sum();
+
+        let page_length = OGG_HEADER_LENGTH + segments + body_length;
+        if self.buffer.len() < page_length {
+            return None;
+        }
+
+        Some(self.buffer.drain(..page_length).collect())
+    }
+}
+
+/// Starts `ffmpeg` to capture an audio device as an Ogg Opus stream.
+///
+/// Pages are flushed every 20ms so they can be sent as soon as they are encoded.
+///
+/// # Arguments
+///
+/// * `device` - The DirectShow name of the audio device.
+/// * `kind` - What the audio is used for.
+///
+/// # Returns
+///
+/// The `Child` process handle of `ffmpeg`.
+///
+/// # Panics
+///
+/// Panics if `ffmpeg` fails to spawn.
+fn start_ffmpeg(device: &str, kind: AudioKind) -> Child {
+    let (channels, bitrate, application) = match kind {
+        AudioKind::System => ("2", "96k", "audio"),
+        AudioKind::Microphone => ("1", "32k", "voip"),
+    };
+
+    let ffmpeg = Command::new("ffmpeg")
+        .args(["-f", "dshow", "-audio_buffer_size", "20", "-i"])
+        .arg(format!("audio={}", device))
+        .args([
+            "-ac",
+            channels,
+            "-c:a",
+            "libopus",
+            "-b:a",
+            bitrate,
+            "-application",
+            application,
+            "-frame_duration",
+            "20",
+            "-page_duration",
+            "20000",
+            "-flush_packets",
+            "1",
+            "-f",
+            "ogg",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to spawn ffmpeg");
+
+    ffmpeg
+}
+
+/// Background thread for sending captured audio
+///
+/// Sends every Ogg page as a `Packet::Audio`, and a `Packet::AudioEnd` once `ffmpeg` exits.
+///
+/// # Arguments
+///
+/// * `channel` - Secure communication channel for sending packets
+/// * `stdout` - FFmpeg process stdout handle for reading the Ogg stream
+///
+/// # Returns
+///
+/// A `JoinHandle` for the spawned thread
+fn thread_send_audio(mut channel: SecureChannel, mut stdout: ChildStdout) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = OggPageReader::new();
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            match stdout.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => reader.push(&buffer[..n]),
+            }
+
+            while let Some(page) = reader.next_page() {
+                let packet = Packet::Audio {
+                    username: String::new(),
+                    bytes: page,
+                };
+                channel.send(packet).unwrap();
+            }
+        }
+
+        let packet = Packet::AudioEnd {
+            username: String::new(),
+        };
+        channel.send(packet).unwrap();
+    })
+}
+
+/// Captures an audio device and streams it to the server.
+pub struct AudioCapture {
+    /// The `ffmpeg` process capturing and encoding the audio
+    ffmpeg: Child,
+    /// Background thread handle for sending the audio
+    thread_send_audio: Option<JoinHandle<()>>,
+}
+
+impl AudioCapture {
+    /// Starts capturing an audio device.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Communication channel for the audio stream
+    /// * `device` - The DirectShow name of the audio device
+    /// * `kind` - What the audio is used for
+    pub fn start(channel: &SecureChannel, device: &str, kind: AudioKind) -> Self {
+        let mut ffmpeg = start_ffmpeg(device, kind);
+        let stdout = ffmpeg.stdout.take().unwrap();
+
+        info!(target: LOG_TARGET, "Started capturing audio from {}.", device);
+
+        Self {
+            ffmpeg,
+            thread_send_audio: Some(thread_send_audio(channel.clone(), stdout)),
+        }
+    }
+
+    /// Stops capturing, which ends the audio stream.
+    pub fn stop(&mut self) {
+        let _ = self.ffmpeg.kill();
+        let _ = self.ffmpeg.wait();
+
+        if let Some(handle) = self.thread_send_audio.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Starts `ffplay` to play an Ogg Opus stream from its stdin, without a window.
+///
+/// # Returns
+///
+/// The `Child` process handle of `ffplay`, or `None` if it couldn't be started.
+fn start_ffplay() -> Option<Child> {
+    Command::new("ffplay")
+        .args([
+            "-nodisp",
+            "-autoexit",
+            "-loglevel",
+            "quiet",
+            "-fflags",
+            "nobuffer",
+            "-flags",
+            "low_delay",
+            "-probesize",
+            "32",
+            "-f",
+            "ogg",
+            "-",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()
+}
+
+/// Plays the audio streams of the other users in the session, one `ffplay` per speaker.
+///
+/// The header pages of every stream are kept, so playback can start (or restart
+/// after unmuting) in the middle of a stream.
+pub struct AudioPlayer {
+    /// Whether incoming audio is ignored.
+    muted: bool,
+    /// The running `ffplay` processes, by speaker.
+    players: HashMap<String, (Child, ChildStdin)>,
+    /// The header pages of the current stream of every speaker.
+    headers: HashMap<String, Vec<Vec<u8>>>,
+}
+
+/// The audio player shared between the UI and the network thread.
+pub type SharedAudioPlayer = Arc<Mutex<AudioPlayer>>;
+
+impl AudioPlayer {
+    /// Creates a player that isn't playing anything yet.
+    pub fn new() -> Self {
+        Self {
+            muted: false,
+            players: HashMap::new(),
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Returns whether incoming audio is ignored.
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Mutes or unmutes all incoming audio.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        if muted {
+            let speakers: Vec<String> = self.players.keys().cloned().collect();
+            for speaker in speakers {
+                self.stop_player(&speaker);
+            }
+        }
+    }
+
+    /// Plays a page of a speaker's audio stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `speaker` - The username of the speaker.
+    /// * `page` - The Ogg page.
+    pub fn play(&mut self, speaker: &str, page: Vec<u8>) {
+        if is_audio_stream_start(&page) {
+            self.stop_player(speaker);
+            self.headers.insert(speaker.to_string(), Vec::new());
+        }
+
+        let is_header = is_audio_header(&page);
+        if is_header {
+            if let Some(headers) = self.headers.get_mut(speaker) {
+                headers.push(page.clone());
+            }
+        }
+
+        if self.muted {
+            return;
+        }
+
+        if !self.players.contains_key(speaker) {
+            // the decoder can't start without the headers
+            let Some(headers) = self.headers.get(speaker) else {
+                return;
+            };
+            let Some(mut ffplay) = start_ffplay() else {
+                return;
+            };
+            let mut stdin = ffplay.stdin.take().unwrap();
+
+            for header in headers {
+                let _ = stdin.write_all(header);
+            }
+            self.players.insert(speaker.to_string(), (ffplay, stdin));
+
+            if is_header {
+                // it was just written with the other headers
+                return;
+            }
+        }
+
+        let (_, stdin) = self.players.get_mut(speaker).unwrap();
+        if stdin.write_all(&page).is_err() {
+            self.stop_player(speaker);
+        }
+    }
+
+    /// Stops playing a speaker's audio, after the stream ended or the speaker left.
+    ///
+    /// # Arguments
+    ///
+    /// * `speaker` - The username of the speaker.
+    pub fn stop(&mut self, speaker: &str) {
+        self.stop_player(speaker);
+        self.headers.remove(speaker);
+    }
+
+    /// Stops playing all audio.
+    pub fn stop_all(&mut self) {
+        let speakers: Vec<String> = self
+            .players
+            .keys()
+            .chain(self.headers.keys())
+            .cloned()
+            .collect();
+        for speaker in speakers {
+            self.stop(&speaker);
+        }
+    }
+
+    /// Kills a speaker's `ffplay`, keeping the stream headers.
+    fn stop_player(&mut self, speaker: &str) {
+        if let Some((mut ffplay, stdin)) = self.players.remove(speaker) {
+            drop(stdin);
+            let _ = ffplay.kill();
+            let _ = ffplay.wait();
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension};
+
+use crate::structs::Recording;
+
+/// Gives users the right to watch a recording.
+///
+/// Users that don't exist, or already have access, are skipped.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `recording_id` - The ID of the recording.
+/// * `usernames` - The usernames of the users.
+pub fn grant_access<'a>(
+    db_pool: &Pool<SqliteConnectionManager>,
+    recording_id: i32,
+    usernames: impl IntoIterator<Item = &'a String>,
+) {
+    let db_pool = db_pool.get().unwrap();
+
+    for username in usernames {
+        let _ = db_pool.execute(
+            "INSERT OR IGNORE INTO recording_access (recording_id, user_id)
+                SELECT ?1, user_id FROM users WHERE username = ?2",
+            params![recording_id, username],
+        );
+    }
+}
+
+/// Shares a recording with another user, on behalf of its owner.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `recording_id` - The ID of the recording.
+/// * `owner_id` - The ID of the owner of the recording.
+/// * `username` - The username of the user to share with.
+///
+/// # Returns
+///
+/// A `Result<(), String>` with the message to show the owner if the recording couldn't be shared.
+pub fn share_recording(
+    db_pool: &Pool<SqliteConnectionManager>,
+    recording_id: i32,
+    owner_id: i32,
+    username: &str,
+) -> Result<(), String> {
+    let db_pool = db_pool.get().unwrap();
+
+    let user_id: Option<i32> = db_pool
+        .query_row(
+            "SELECT user_id FROM users WHERE username = ?1",
+            [username],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|_| "Error sharing the recording.".to_string())?;
+
+    match user_id {
+        None => Err(format!("There's no user named {}.", username)),
+
+        Some(user_id) if user_id == owner_id => Err("You own this recording.".to_string()),
+
+        Some(user_id) => db_pool
+            .execute(
+                "INSERT OR IGNORE INTO recording_access (recording_id, user_id) VALUES (?1, ?2)",
+                params![recording_id, user_id],
+            )
+            .map(|_| ())
+            .map_err(|_| "Error sharing the recording.".to_string()),
+    }
+}
+
+/// Checks if a user may watch a recording, as its owner or through `recording_access`.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user.
+/// * `recording_id` - The ID of the recording.
+///
+/// # Returns
+///
+/// `true` if the user has access to the recording.
+pub fn has_access(
+    db_pool: &Pool<SqliteConnectionManager>,
+    user_id: i32,
+    recording_id: i32,
+) -> bool {
+    db_pool
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT EXISTS (
+                SELECT 1 FROM recordings WHERE recording_id = ?1 AND user_id = ?2
+                UNION
+                SELECT 1 FROM recording_access WHERE recording_id = ?1 AND user_id = ?2
+            )",
+            params![recording_id, user_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(false)
+}
+
+/// Retrieves the recordings other users shared with a user.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user.
+///
+/// # Returns
+///
+/// A `HashMap<i32, Recording>` where keys are recording IDs, with the owners filled in.
+pub fn query_shared_recordings(
+    db_pool: &Pool<SqliteConnectionManager>,
+    user_id: i32,
+) -> HashMap<i32, Recording> {
+    let db_pool = db_pool.get().unwrap();
+
+    let mut query = db_pool
+        .prepare(
+            "SELECT recordings.recording_id, filename, time, title, description, username, retention_days
+                FROM recording_access
+                JOIN recordings ON recordings.recording_id = recording_access.recording_id
+                JOIN users ON users.user_id = recordings.user_id
+                WHERE recording_access.user_id = ?1 AND in_progress = 0",
+        )
+        .unwrap();
+
+    let recordings = query
+        .query_map([user_id], |row| {
+            let id: i32 = row.get(0)?;
+            let recording = Recording {
+                filename: row.get(1)?,
+                time: row.get(2)?,
+                title: row.get(3)?,
+                description: row.get(4)?,
+                owner: Some(row.get(5)?),
+                // kept forever unless the owner chose otherwise
+                retention_days: row.get::<_, Option<u32>>(6)?.unwrap_or(0),
+            };
+            Ok((id, recording))
+        })
+        .unwrap()
+        .collect::<Result<HashMap<_, _>, _>>()
+        .unwrap();
+
+    recordings
+}
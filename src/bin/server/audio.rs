@@ -0,0 +1,553 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use log::info;
+use stream_desk::{
+    protocol::{is_audio_header, is_audio_stream_start, Packet},
+    LOG_TARGET,
+};
+
+use crate::{
+    has_ffmpeg,
+    mp4::{remux, Chapter, OpusConfig, OpusPacket, OpusStream},
+    recording::RecordingClock,
+    recordings_folder,
+    structs::Session,
+};
+
+/// The flag of an Ogg page that continues the last packet of the page before it.
+const OGG_CONTINUED_PACKET: u8 = 0x01;
+
+/// A recorded audio stream, waiting to be mixed into the session recording.
+pub struct AudioTrack {
+    /// The Ogg file of the stream.
+    path: PathBuf,
+    /// When the stream started, relative to the start of the recording.
+    offset: Duration,
+}
+
+/// The audio streams of a session: the stream headers for users who join in the
+/// middle of a stream, and the recorded tracks.
+pub struct SessionAudio {
+    /// The recording the tracks belong to, once the host started it.
+    recording: Option<String>,
+    /// The time into the recording, which stands still while the host paused it.
+    /// No tracks are recorded until it's resumed.
+    clock: RecordingClock,
+    /// The header pages of the current stream of every speaker.
+    headers: HashMap<String, Vec<Vec<u8>>>,
+    /// The tracks being recorded, by speaker.
+    open_tracks: HashMap<String, (File, AudioTrack)>,
+    /// The tracks of the streams that ended.
+    finished_tracks: Vec<AudioTrack>,
+}
+
+impl SessionAudio {
+    /// Creates the audio state of a new session, which records nothing yet.
+    pub fn new() -> Self {
+        Self {
+            recording: None,
+            clock: RecordingClock::start(),
+            headers: HashMap::new(),
+            open_tracks: HashMap::new(),
+            finished_tracks: Vec::new(),
+        }
+    }
+
+    /// Starts recording the audio streams along with the video.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The filename of the recording.
+    pub fn start_recording(&mut self, filename: &str) {
+        self.recording = Some(filename.to_string());
+        self.clock = RecordingClock::start();
+    }
+
+    /// Pauses the recording, which finishes the tracks of the streams in progress.
+    pub fn pause_recording(&mut self) {
+        self.clock.pause();
+
+        for (_, track) in std::mem::take(&mut self.open_tracks).into_values() {
+            self.finished_tracks.push(track);
+        }
+    }
+
+    /// Resumes the recording, which starts new tracks for the streams in progress
+    /// with their headers.
+    pub fn resume_recording(&mut self) {
+        self.clock.resume();
+
+        let speakers: Vec<String> = self.headers.keys().cloned().collect();
+        for speaker in speakers {
+            self.open_track(&speaker);
+
+            if let Some((file, _)) = self.open_tracks.get_mut(&speaker) {
+                for page in &self.headers[&speaker] {
+                    let _ = file.write_all(page);
+                }
+            }
+        }
+    }
+
+    /// Starts recording a track of a speaker's stream, unless the recording is paused.
+    ///
+    /// # Arguments
+    ///
+    /// * `speaker` - The username of the speaker.
+    fn open_track(&mut self, speaker: &str) {
+        let Some(recording) = &self.recording else {
+            return;
+        };
+        if self.clock.is_paused() {
+            return;
+        }
+
+        let index = self.finished_tracks.len() + self.open_tracks.len();
+        let offset = self.clock.elapsed();
+
+        // the offset is in the name, so the track can be mixed in after a crash
+        let path = recordings_folder().join(format!(
+            "{}.{}.{}.ogg",
+            recording,
+            index,
+            offset.as_millis()
+        ));
+
+        if let Ok(file) = File::create(&path) {
+            let track = AudioTrack { path, offset };
+            self.open_tracks.insert(speaker.to_string(), (file, track));
+        }
+    }
+
+    /// Keeps the headers of a speaker's stream and records the page.
+    ///
+    /// # Arguments
+    ///
+    /// * `speaker` - The username of the speaker.
+    /// * `page` - The Ogg page.
+    fn add_page(&mut self, speaker: &str, page: &[u8]) {
+        if is_audio_stream_start(page) {
+            self.end_stream(speaker);
+            self.headers.insert(speaker.to_string(), Vec::new());
+            self.open_track(speaker);
+        }
+
+        if is_audio_header(page) {
+            if let Some(headers) = self.headers.get_mut(speaker) {
+                headers.push(page.to_vec());
+            }
+        }
+
+        if let Some((file, _)) = self.open_tracks.get_mut(speaker) {
+            let _ = file.write_all(page);
+        }
+    }
+
+    /// Forgets a speaker's stream and finishes its track.
+    ///
+    /// # Arguments
+    ///
+    /// * `speaker` - The username of the speaker.
+    pub fn end_stream(&mut self, speaker: &str) {
+        self.headers.remove(speaker);
+
+        if let Some((_, track)) = self.open_tracks.remove(speaker) {
+            self.finished_tracks.push(track);
+        }
+    }
+
+    /// Creates the packets a user needs to start playing the streams that are in progress.
+    ///
+    /// # Returns
+    ///
+    /// The header pages of every stream as `Packet::Audio`.
+    pub fn header_packets(&self) -> Vec<Packet> {
+        self.headers
+            .iter()
+            .flat_map(|(speaker, pages)| {
+                pages.iter().map(|page| Packet::Audio {
+                    username: speaker.clone(),
+                    bytes: page.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Finishes all tracks, at the end of the session.
+    ///
+    /// # Returns
+    ///
+    /// Every recorded track.
+    pub fn finish(&mut self) -> Vec<AudioTrack> {
+        let speakers: Vec<String> = self.open_tracks.keys().cloned().collect();
+        for speaker in speakers {
+            self.end_stream(&speaker);
+        }
+
+        std::mem::take(&mut self.finished_tracks)
+    }
+}
+
+/// Records an audio packet and forwards it to everyone in the session except the speaker.
+///
+/// # Arguments
+///
+/// * `session` - The session the speaker is in.
+/// * `packet` - A `Packet::Audio` or `Packet::AudioEnd`.
+/// * `speaker` - The username of the speaker, filled into the packet.
+///
+/// # Returns
+///
+/// An `std::io::Result<()>` that signifies if something went wrong.
+pub fn forward_audio(session: &mut Session, packet: Packet, speaker: &str) -> std::io::Result<()> {
+    let packet = match packet {
+        Packet::Audio { bytes, .. } => {
+            session.audio.add_page(speaker, &bytes);
+            Packet::Audio {
+                username: speaker.to_string(),
+                bytes,
+            }
+        }
+
+        Packet::AudioEnd { .. } => {
+            session.audio.end_stream(speaker);
+            Packet::AudioEnd {
+                username: speaker.to_string(),
+            }
+        }
+
+        _ => return Ok(()),
+    };
+
+    for (username, connection) in &mut session.connections {
+        if username != speaker {
+            connection.channel.send(packet.clone())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the audio tracks a recording left behind when the server stopped during its session.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording.
+///
+/// # Returns
+///
+/// The recorded tracks, in the order their streams started.
+pub fn recover_tracks(filename: &str) -> Vec<AudioTrack> {
+    let Ok(entries) = fs::read_dir(recordings_folder()) else {
+        return Vec::new();
+    };
+
+    let prefix = format!("{filename}.");
+    let mut tracks: Vec<(u32, AudioTrack)> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+
+            // {filename}.{index}.{offset_ms}.ogg
+            let (index, offset_ms) = name
+                .strip_prefix(&prefix)?
+                .strip_suffix(".ogg")?
+                .split_once('.')?;
+            let index = index.parse().ok()?;
+            let offset = Duration::from_millis(offset_ms.parse().ok()?);
+
+            Some((index, AudioTrack { path, offset }))
+        })
+        .collect();
+
+    tracks.sort_by_key(|(index, _)| *index);
+    tracks.into_iter().map(|(_, track)| track).collect()
+}
+
+/// Removes the files of recorded audio tracks.
+///
+/// # Arguments
+///
+/// * `tracks` - The recorded audio tracks.
+pub fn remove_tracks(tracks: Vec<AudioTrack>) {
+    for track in tracks {
+        let _ = fs::remove_file(track.path);
+    }
+}
+
+/// Moves the files of recorded audio tracks to another folder.
+///
+/// # Arguments
+///
+/// * `tracks` - The recorded audio tracks.
+/// * `folder` - The folder to move them to.
+pub fn move_tracks(tracks: Vec<AudioTrack>, folder: &Path) {
+    for track in tracks {
+        if let Some(name) = track.path.file_name() {
+            let _ = fs::rename(&track.path, folder.join(name));
+        }
+    }
+}
+
+/// Splits the pages of an Ogg stream into its packets.
+///
+/// # Arguments
+///
+/// * `bytes` - The pages of the stream.
+///
+/// # Returns
+///
+/// The complete packets, in order. The parts of packets whose other pages are
+/// missing are dropped.
+fn ogg_packets(bytes: &[u8]) -> Vec<Vec<u8>> {
+    let mut packets = Vec::new();
+    let mut packet = Vec::new();
+    let mut rest = bytes;
+
+    while rest.len() >= 27 && rest.starts_with(b"OggS") {
+        let segment_count = rest[26] as usize;
+        let Some(lacing) = rest.get(27..27 + segment_count) else {
+            break;
+        };
+        let data_size: usize = lacing.iter().map(|&size| size as usize).sum();
+        let Some(mut data) = rest.get(27 + segment_count..27 + segment_count + data_size) else {
+            break;
+        };
+
+        // a packet can't be completed if its start isn't in the stream
+        let continued = rest[5] & OGG_CONTINUED_PACKET != 0;
+        let mut is_complete = !continued || !packet.is_empty();
+        if !continued {
+            packet.clear();
+        }
+
+        // a packet ends with the first segment shorter than 255 bytes
+        for &size in lacing {
+            let (segment, after) = data.split_at(size as usize);
+            packet.extend_from_slice(segment);
+            data = after;
+
+            if size < 255 {
+                if is_complete {
+                    packets.push(std::mem::take(&mut packet));
+                }
+                packet.clear();
+                is_complete = true;
+            }
+        }
+
+        rest = &rest[27 + segment_count + data_size..];
+    }
+
+    packets
+}
+
+/// Gets the number of samples an Opus packet decodes to, from its TOC byte.
+///
+/// # Arguments
+///
+/// * `packet` - The Opus packet.
+///
+/// # Returns
+///
+/// The number of samples at 48 kHz, or 0 if the packet is empty.
+fn opus_packet_duration(packet: &[u8]) -> u32 {
+    let Some(&toc) = packet.first() else {
+        return 0;
+    };
+
+    // the SILK, hybrid and CELT modes have different frame sizes
+    let config = (toc >> 3) as usize;
+    let frame_samples = match config {
+        0..=11 => [480, 960, 1920, 2880][config % 4],
+        12..=15 => [480, 960][config % 2],
+        _ => [120, 240, 480, 960][config % 4],
+    };
+    let frames = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => packet.get(1).map_or(0, |count| (count & 0x3F) as u32),
+    };
+
+    frame_samples * frames
+}
+
+/// Reads a recorded Ogg Opus file as a stream to mux into a recording.
+///
+/// # Arguments
+///
+/// * `path` - The path of the Ogg file.
+/// * `start_ms` - When the stream starts in the recording, in milliseconds.
+///
+/// # Returns
+///
+/// The stream, or `None` if the file can't be read or doesn't start with an `OpusHead`.
+fn read_ogg_opus(path: &Path, start_ms: u64) -> Option<OpusStream> {
+    let packets = ogg_packets(&fs::read(path).ok()?);
+    let config = OpusConfig::from_opus_head(packets.first()?)?;
+
+    // the OpusTags packet follows the OpusHead packet
+    let packets = packets
+        .into_iter()
+        .skip(2)
+        .map(|data| OpusPacket {
+            duration: opus_packet_duration(&data),
+            data,
+        })
+        .collect();
+
+    Some(OpusStream {
+        config,
+        packets,
+        start_ms,
+    })
+}
+
+/// Mixes audio tracks into a single Ogg Opus file with FFmpeg.
+///
+/// Every track is delayed to the time its stream started (or the recording was resumed).
+///
+/// # Arguments
+///
+/// * `tracks` - The recorded audio tracks.
+/// * `mixed_path` - The path of the mixed file.
+///
+/// # Returns
+///
+/// `true` if FFmpeg mixed the tracks.
+fn mix_tracks(tracks: &[AudioTrack], mixed_path: &Path) -> bool {
+    let mut command = Command::new("ffmpeg");
+    command.arg("-y");
+    for track in tracks {
+        command.arg("-i").arg(&track.path);
+    }
+
+    let mut filter = String::new();
+    for (index, track) in tracks.iter().enumerate() {
+        filter += &format!(
+            "[{}:a]asetpts=PTS-STARTPTS,adelay={}:all=1[a{}];",
+            index,
+            track.offset.as_millis(),
+            index
+        );
+    }
+    for index in 0..tracks.len() {
+        filter += &format!("[a{}]", index);
+    }
+    filter += &format!("amix=inputs={}:normalize=0[audio]", tracks.len());
+
+    command
+        .args([
+            "-filter_complex",
+            &filter,
+            "-map",
+            "[audio]",
+            "-c:a",
+            "libopus",
+            "-b:a",
+            "128k",
+        ])
+        .arg(mixed_path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Muxes the recorded audio tracks and the chapters into the session recording, and
+/// removes the tracks.
+///
+/// A single track is muxed as it is. Several tracks are mixed into one with FFmpeg,
+/// or muxed as tracks of their own if FFmpeg isn't installed, as the server can't
+/// decode Opus to mix them itself.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording.
+/// * `tracks` - The recorded audio tracks.
+/// * `chapters` - The chapters of the recording.
+pub fn mix_into_recording(filename: &str, tracks: Vec<AudioTrack>, chapters: &[Chapter]) {
+    if tracks.is_empty() && chapters.is_empty() {
+        return;
+    }
+
+    let mixed_path = recordings_folder().join(format!("{filename}.mixed.ogg"));
+    let audio: Vec<OpusStream> =
+        if tracks.len() > 1 && has_ffmpeg() && mix_tracks(&tracks, &mixed_path) {
+            read_ogg_opus(&mixed_path, 0).into_iter().collect()
+        } else {
+            tracks
+                .iter()
+                .filter_map(|track| read_ogg_opus(&track.path, track.offset.as_millis() as u64))
+                .collect()
+        };
+    let _ = fs::remove_file(&mixed_path);
+
+    // keep the video as it was if it can't be rewritten
+    if !remux(filename, &audio, chapters) {
+        info!(
+            target: LOG_TARGET,
+            "Couldn't mux the audio and the chapters into recording {}.", filename
+        );
+    }
+
+    remove_tracks(tracks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an Ogg page with the given segments.
+    fn page(continued: bool, lacing: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS".to_vec();
+        page.push(0);
+        page.push(if continued { OGG_CONTINUED_PACKET } else { 0 });
+        page.extend_from_slice(&[0; 20]);
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(lacing);
+        page.extend_from_slice(data);
+        page
+    }
+
+    #[test]
+    fn packets_are_joined_across_pages() {
+        let long_packet = vec![7; 300];
+        let stream = [
+            page(
+                false,
+                &[2, 255],
+                &[[1, 2].as_slice(), &long_packet[..255]].concat(),
+            ),
+            page(true, &[45, 1], &[&long_packet[255..], &[9]].concat()),
+        ]
+        .concat();
+
+        assert_eq!(ogg_packets(&stream), vec![vec![1, 2], long_packet, vec![9]]);
+    }
+
+    #[test]
+    fn packet_without_its_start_is_dropped() {
+        let stream = page(true, &[3, 1], &[1, 2, 3, 4]);
+
+        assert_eq!(ogg_packets(&stream), vec![vec![4]]);
+    }
+
+    #[test]
+    fn packet_duration_is_read_from_its_toc() {
+        // CELT 20 ms, one frame
+        assert_eq!(opus_packet_duration(&[31 << 3]), 960);
+        // SILK 60 ms, two frames
+        assert_eq!(opus_packet_duration(&[3 << 3 | 1]), 5760);
+        // hybrid 10 ms, three frames in a code 3 packet
+        assert_eq!(opus_packet_duration(&[12 << 3 | 3, 3]), 1440);
+        assert_eq!(opus_packet_duration(&[]), 0);
+    }
+}
@@ -0,0 +1,173 @@
+use std::ops::Range;
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::params;
+use stream_desk::protocol::{Packet, MAX_BOOKMARK_NAME_LENGTH};
+
+use crate::{format_recording_time, mp4::Chapter, structs::Session};
+
+/// A named moment of a recording.
+pub struct Bookmark {
+    /// The time of the bookmark in the recording, in milliseconds.
+    pub time_ms: u64,
+    pub name: String,
+    /// The username of the user who added the bookmark.
+    pub username: String,
+}
+
+impl Bookmark {
+    /// Creates the packet that sends the bookmark to a client.
+    pub fn packet(&self) -> Packet {
+        Packet::Bookmark {
+            time_ms: self.time_ms,
+            name: self.name.clone(),
+            username: self.username.clone(),
+        }
+    }
+
+    /// Creates the chapter of the recording's video that starts at the bookmark.
+    pub fn chapter(&self) -> Chapter {
+        Chapter {
+            start_ms: self.time_ms,
+            title: self.name.clone(),
+        }
+    }
+}
+
+/// Bookmarks the current moment of a session's recording and tells everyone in the session.
+///
+/// The bookmark is written to the database right away, so it isn't lost if the server
+/// stops before the session ends.
+///
+/// # Arguments
+///
+/// * `session` - The session.
+/// * `db_pool` - The pool of the database connections.
+/// * `username` - The username of the user adding the bookmark.
+/// * `name` - The name of the bookmark, or empty to name it by its time.
+///
+/// # Returns
+///
+/// An `std::io::Result<()>` that signifies if the bookmark was sent successfully.
+pub fn add_bookmark(
+    session: &mut Session,
+    db_pool: &Pool<SqliteConnectionManager>,
+    username: &str,
+    name: &str,
+) -> std::io::Result<()> {
+    // nothing is recorded while the recording is paused, so there is nothing to bookmark
+    if session.events.is_paused() {
+        if let Some(connection) = session.connections.get_mut(username) {
+            let message = "#rBookmarks can't be added while the recording is paused.";
+            connection.channel.send(Packet::Chat {
+                message: message.to_owned(),
+            })?;
+        }
+        return Ok(());
+    }
+
+    let time_ms = session.events.elapsed_ms();
+
+    let name = match name.trim() {
+        "" => format!("Bookmark at {}", format_recording_time(time_ms)),
+        name => name.chars().take(MAX_BOOKMARK_NAME_LENGTH).collect(),
+    };
+
+    let bookmark = Bookmark {
+        time_ms,
+        name,
+        username: username.to_string(),
+    };
+    if let Some(recording_id) = session.recording_id {
+        insert_bookmark(db_pool, recording_id, &bookmark);
+    }
+    session.broadcast_all(bookmark.packet())?;
+
+    Ok(())
+}
+
+/// Inserts a bookmark of a recording to the database.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `recording_id` - The ID of the recording.
+/// * `bookmark` - The bookmark.
+fn insert_bookmark(
+    db_pool: &Pool<SqliteConnectionManager>,
+    recording_id: i32,
+    bookmark: &Bookmark,
+) {
+    let _ = db_pool.get().unwrap().execute(
+        "INSERT INTO recording_bookmarks (recording_id, time_ms, name, username) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            recording_id,
+            bookmark.time_ms as i64,
+            bookmark.name,
+            bookmark.username
+        ],
+    );
+}
+
+/// Retrieves the bookmarks of a recording from the database.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `recording_id` - The ID of the recording.
+///
+/// # Returns
+///
+/// The bookmarks of the recording in time order.
+pub fn query_bookmarks(
+    db_pool: &Pool<SqliteConnectionManager>,
+    recording_id: i32,
+) -> Vec<Bookmark> {
+    let connection = db_pool.get().unwrap();
+
+    let mut query = connection
+        .prepare(
+            "SELECT time_ms, name, username FROM recording_bookmarks WHERE recording_id = ?1 ORDER BY time_ms",
+        )
+        .unwrap();
+
+    query
+        .query_map([recording_id], |row| {
+            Ok(Bookmark {
+                time_ms: row.get::<_, i64>(0)? as u64,
+                name: row.get(1)?,
+                username: row.get(2)?,
+            })
+        })
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Copies the bookmarks of a part of a recording to a clip of it.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `parent_id` - The ID of the recording the clip was cut from.
+/// * `clip_id` - The ID of the clip.
+/// * `range_ms` - The part of the recording the clip was cut from, in milliseconds.
+pub fn copy_bookmarks(
+    db_pool: &Pool<SqliteConnectionManager>,
+    parent_id: i32,
+    clip_id: i32,
+    range_ms: Range<u64>,
+) {
+    let _ = db_pool.get().unwrap().execute(
+        "INSERT INTO recording_bookmarks (recording_id, time_ms, name, username)
+            SELECT ?1, time_ms - ?3, name, username FROM recording_bookmarks
+            WHERE recording_id = ?2 AND time_ms >= ?3 AND time_ms < ?4",
+        params![
+            clip_id,
+            parent_id,
+            range_ms.start as i64,
+            range_ms.end as i64
+        ],
+    );
+}
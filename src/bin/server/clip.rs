@@ -0,0 +1,236 @@
+use std::{
+    ops::Range,
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+};
+
+use chrono::{DateTime, TimeDelta};
+use log::info;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::params;
+use stream_desk::{
+    protocol::{Packet, MAX_RECORDING_TITLE_LENGTH},
+    secure_channel::SecureChannel,
+    LOG_TARGET,
+};
+
+use crate::{
+    bookmarks::{copy_bookmarks, query_bookmarks, Bookmark},
+    events::copy_events,
+    format_recording_time, get_duration_ms, get_video_path, has_ffmpeg,
+    mp4::{add_chapters, cut},
+    storage::has_storage_left,
+    structs::Recording,
+    thumbnails::generate_thumbnails,
+};
+
+/// The shortest clip that can be created, in milliseconds.
+const MIN_CLIP_MS: u64 = 1000;
+
+/// Cuts a part of a recording into a new video file.
+///
+/// Without `accurate`, the frames are copied as they are, so the clip is lossless
+/// and fast to create but starts at the keyframe before the part. Otherwise FFmpeg
+/// reencodes the clip to start and end at the exact frames.
+///
+/// # Arguments
+///
+/// * `source` - The filename of the recording (without the extension).
+/// * `clip` - The filename of the clip (without the extension).
+/// * `range_ms` - The part of the recording to cut, in milliseconds.
+/// * `accurate` - Whether the clip must be frame accurate.
+///
+/// # Returns
+///
+/// The time of the recording the clip starts at in milliseconds, or `None` if the
+/// clip couldn't be cut.
+fn cut_clip(source: &str, clip: &str, range_ms: Range<u64>, accurate: bool) -> Option<u64> {
+    if !accurate {
+        return cut(source, clip, range_ms);
+    }
+
+    // clips are played as they are stored, so they are encoded like the live stream
+    Command::new("ffmpeg")
+        .arg("-y")
+        .args(["-ss", &format!("{:.3}", range_ms.start as f64 / 1000.0)])
+        .arg("-i")
+        .arg(get_video_path(source))
+        .args([
+            "-t",
+            &format!("{:.3}", (range_ms.end - range_ms.start) as f64 / 1000.0),
+        ])
+        .args([
+            "-c:v",
+            "libx264",
+            "-preset",
+            "veryfast",
+            "-profile:v",
+            "baseline",
+            "-crf",
+            "18",
+            "-c:a",
+            "libopus",
+            "-movflags",
+            "+faststart",
+        ])
+        .arg(get_video_path(clip))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+        .then_some(range_ms.start)
+}
+
+/// Inserts a clip to the database as a recording of its own, linked to the recording
+/// it was cut from. A clip of the user's own recording is kept as long as the recording.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `filename` - The filename of the clip.
+/// * `time` - The time the clip's part of the recording happened.
+/// * `title` - The title of the clip.
+/// * `user_id` - The ID of the user the clip belongs to.
+/// * `parent_id` - The ID of the recording the clip was cut from.
+///
+/// # Returns
+///
+/// The ID of the new recording, or `None` if it couldn't be inserted.
+fn insert_clip_to_database(
+    db_pool: &Pool<SqliteConnectionManager>,
+    filename: &str,
+    time: &str,
+    title: &str,
+    user_id: i32,
+    parent_id: i32,
+) -> Option<i32> {
+    let db_pool = db_pool.get().unwrap();
+
+    db_pool
+        .execute(
+            "INSERT INTO recordings (filename, time, user_id, title, parent_id, retention_days)
+                VALUES (?1, ?2, ?3, ?4, ?5,
+                    (SELECT retention_days FROM recordings WHERE recording_id = ?5 AND user_id = ?3))",
+            params![filename, time, user_id, title, parent_id],
+        )
+        .ok()?;
+
+    Some(db_pool.last_insert_rowid() as i32)
+}
+
+/// Starts a thread that cuts a clip out of a recording and saves it as a new recording
+/// of the user, then tells the client how it went.
+///
+/// # Arguments
+///
+/// * `channel` - The `SecureChannel` connected to the client.
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user creating the clip, who owns it.
+/// * `id` - The ID of the recording.
+/// * `recording` - The recording to cut the clip from.
+/// * `range_ms` - The part of the recording to cut, in milliseconds.
+/// * `accurate` - Whether the clip must be frame accurate.
+///
+/// # Returns
+///
+/// The thread's `JoinHandle`, or `None` if the clip was refused right away.
+pub fn thread_create_clip(
+    mut channel: SecureChannel,
+    db_pool: Pool<SqliteConnectionManager>,
+    user_id: i32,
+    id: i32,
+    recording: &Recording,
+    range_ms: Range<u64>,
+    accurate: bool,
+) -> Option<JoinHandle<()>> {
+    let start_ms = range_ms.start;
+    let end_ms = range_ms.end.min(get_duration_ms(&recording.filename));
+    if end_ms < start_ms + MIN_CLIP_MS {
+        let _ = channel.send(Packet::ClipFailed {
+            message: "A clip must be at least a second long.".to_owned(),
+        });
+        return None;
+    }
+
+    if !has_storage_left(&db_pool, user_id) {
+        let _ = channel.send(Packet::ClipFailed {
+            message: "You have no storage left for the clip.".to_owned(),
+        });
+        return None;
+    }
+
+    if accurate && !has_ffmpeg() {
+        let _ = channel.send(Packet::ClipFailed {
+            message: "Frame accurate clips need FFmpeg, which the server doesn't have.".to_owned(),
+        });
+        return None;
+    }
+
+    let source = recording.filename.clone();
+    let recording_time = recording.time.clone();
+    let name = if recording.title.is_empty() {
+        "Clip".to_owned()
+    } else {
+        recording.title.clone()
+    };
+
+    let thread_clip = thread::spawn(move || {
+        let filename = uuid::Uuid::new_v4().to_string();
+
+        let Some(start_ms) = cut_clip(&source, &filename, start_ms..end_ms, accurate) else {
+            let _ = std::fs::remove_file(get_video_path(&filename));
+            let _ = channel.send(Packet::ClipFailed {
+                message: "The clip couldn't be cut from the recording.".to_owned(),
+            });
+            return;
+        };
+
+        // the clip is dated when its part of the recording happened
+        let time = DateTime::parse_from_rfc3339(&recording_time)
+            .map(|time| time + TimeDelta::milliseconds(start_ms as i64))
+            .map_or(recording_time, |time| time.to_rfc3339());
+
+        let range = format!(
+            " ({}–{})",
+            format_recording_time(start_ms),
+            format_recording_time(end_ms)
+        );
+        let title: String = name
+            .chars()
+            .take(MAX_RECORDING_TITLE_LENGTH - range.chars().count())
+            .chain(range.chars())
+            .collect();
+
+        match insert_clip_to_database(&db_pool, &filename, &time, &title, user_id, id) {
+            Some(clip_id) => {
+                copy_events(&source, &filename, start_ms..end_ms);
+                copy_bookmarks(&db_pool, id, clip_id, start_ms..end_ms);
+
+                // the bookmarks in the clip are its chapters
+                let bookmarks = query_bookmarks(&db_pool, clip_id);
+                if !bookmarks.is_empty() {
+                    let chapters: Vec<_> = bookmarks.iter().map(Bookmark::chapter).collect();
+                    add_chapters(&filename, &chapters);
+                }
+                generate_thumbnails(&filename);
+
+                info!(
+                    target: LOG_TARGET,
+                    "Created clip {} of recording {} as {}.mp4.", clip_id, id, filename
+                );
+
+                let _ = channel.send(Packet::ClipCreated { title });
+            }
+
+            None => {
+                let _ = std::fs::remove_file(get_video_path(&filename));
+                let _ = channel.send(Packet::ClipFailed {
+                    message: "The clip couldn't be saved.".to_owned(),
+                });
+            }
+        }
+    });
+
+    Some(thread_clip)
+}
@@ -0,0 +1,136 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use chrono::Local;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::params;
+use stream_desk::{protocol::Packet, secure_channel::SecureChannel};
+
+use crate::get_video_path;
+
+/// The size of a single chunk of a downloaded recording.
+const CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// The maximum number of bytes a user may download in a day, so recordings can't be
+/// downloaded in bulk.
+const DAILY_DOWNLOAD_QUOTA: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Gets the number of bytes a user downloaded today.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user.
+/// * `day` - Today's date.
+fn downloaded_today(db_pool: &Pool<SqliteConnectionManager>, user_id: i32, day: &str) -> u64 {
+    db_pool
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT bytes FROM download_usage WHERE user_id = ?1 AND day = ?2",
+            params![user_id, day],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_or(0, |bytes| bytes as u64)
+}
+
+/// Adds downloaded bytes to a user's usage of today.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user.
+/// * `day` - Today's date.
+/// * `bytes` - The number of bytes downloaded.
+fn add_downloaded(db_pool: &Pool<SqliteConnectionManager>, user_id: i32, day: &str, bytes: u64) {
+    let _ = db_pool.get().unwrap().execute(
+        "INSERT INTO download_usage (user_id, day, bytes) VALUES (?1, ?2, ?3)
+            ON CONFLICT (user_id, day) DO UPDATE SET bytes = bytes + ?3",
+        params![user_id, day, bytes as i64],
+    );
+}
+
+/// Computes the MD5 checksum of a file.
+///
+/// # Arguments
+///
+/// * `file` - The file, which is read from the start.
+///
+/// # Returns
+///
+/// The checksum as a hex string.
+fn file_checksum(file: &mut File) -> io::Result<String> {
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut hasher = md5::Context::new();
+    io::copy(file, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.compute()))
+}
+
+/// Sends a chunk of a recording's video file, or its checksum once the whole file was sent.
+///
+/// The client requests the chunks one by one, so a download can be paused, cancelled
+/// or resumed from any offset.
+///
+/// # Arguments
+///
+/// * `channel` - The `SecureChannel` connected to the client.
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the downloading user, who must have access to the recording.
+/// * `id` - The ID of the recording.
+/// * `filename` - The filename of the recording (without the extension).
+/// * `offset` - The offset of the requested chunk.
+///
+/// # Returns
+///
+/// An `std::io::Result<()>` that signifies if something went wrong.
+pub fn send_recording_chunk(
+    channel: &mut SecureChannel,
+    db_pool: &Pool<SqliteConnectionManager>,
+    user_id: i32,
+    id: i32,
+    filename: &str,
+    offset: u64,
+) -> std::io::Result<()> {
+    let failure = |message: &str| Packet::DownloadFailed {
+        id,
+        message: message.to_string(),
+    };
+
+    let Ok(mut file) = File::open(get_video_path(filename)) else {
+        return channel.send(failure("Video file does not exist on the server."));
+    };
+    let size = file.metadata()?.len();
+
+    if offset >= size {
+        let checksum = file_checksum(&mut file)?;
+        return channel.send(Packet::RecordingEnd { id, checksum });
+    }
+
+    let day = Local::now().format("%Y-%m-%d").to_string();
+    let length = CHUNK_SIZE.min(size - offset);
+    if downloaded_today(db_pool, user_id, &day) + length > DAILY_DOWNLOAD_QUOTA {
+        return channel.send(failure(
+            "You reached the daily download limit. Try again tomorrow.",
+        ));
+    }
+
+    let mut bytes = vec![0u8; length as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut bytes)?;
+
+    channel.send(Packet::RecordingChunk {
+        id,
+        offset,
+        size,
+        bytes,
+    })?;
+
+    add_downloaded(db_pool, user_id, &day, length);
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    ops::Range,
+    path::PathBuf,
+};
+
+use stream_desk::protocol::{Packet, SessionEventKind};
+
+use crate::{recording::RecordingClock, recordings_folder};
+
+/// The events of a recorded session, written to a log next to the recording as they happen.
+///
+/// Every line of the log is an event: its time in milliseconds since the recording
+/// started, its kind and its text, separated by tabs.
+pub struct EventLog {
+    /// The log file, once the host started the recording.
+    file: Option<File>,
+    /// The time into the recording, which stands still while the host paused it.
+    /// The events are skipped until it's resumed.
+    clock: RecordingClock,
+}
+
+/// Gets the path of the event log of a recording.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+pub fn get_events_path(filename: &str) -> PathBuf {
+    recordings_folder().join(format!("{filename}.events"))
+}
+
+/// Escapes the text of an event so it fits on one line of the log.
+///
+/// # Arguments
+///
+/// * `text` - The text of the event.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+/// Reverses `escape`.
+///
+/// # Arguments
+///
+/// * `text` - The escaped text of an event.
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(c) => result.push(c),
+            None => (),
+        }
+    }
+
+    result
+}
+
+impl EventLog {
+    /// Creates the event log of a new session, which records nothing yet.
+    pub fn new() -> Self {
+        Self {
+            file: None,
+            clock: RecordingClock::start(),
+        }
+    }
+
+    /// Starts writing the events of the session along with the recording.
+    ///
+    /// The host is logged as joining first, so the recording's owner can be found
+    /// from the log if the server stops before the recording is listed.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The filename of the recording.
+    /// * `host` - The username of the host.
+    pub fn start_recording(&mut self, filename: &str, host: &str) {
+        self.file = File::create(get_events_path(filename)).ok();
+        self.clock = RecordingClock::start();
+        self.log(SessionEventKind::Join, host);
+    }
+
+    /// Gets the time into the recording, without the time it was paused for.
+    ///
+    /// # Returns
+    ///
+    /// The current time into the recording in milliseconds.
+    pub fn elapsed_ms(&self) -> u64 {
+        self.clock.elapsed().as_millis() as u64
+    }
+
+    /// Checks whether the host paused the recording.
+    pub fn is_paused(&self) -> bool {
+        self.clock.is_paused()
+    }
+
+    /// Pauses or resumes the log, noting when it was paused and resumed.
+    ///
+    /// Both are noted at the same time, as the time the recording was paused for
+    /// is left out of it, which marks where the gap is.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_paused` - Whether the recording was paused or resumed.
+    /// * `host` - The username of the host, who paused or resumed it.
+    pub fn set_paused(&mut self, is_paused: bool, host: &str) {
+        if is_paused {
+            self.log(SessionEventKind::RecordingPaused, host);
+            self.clock.pause();
+        } else {
+            self.clock.resume();
+            self.log(SessionEventKind::RecordingResumed, host);
+        }
+    }
+
+    /// Writes an event to the log, timed now.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of the event.
+    /// * `text` - The text of the event: the chat message, or the username of the event's user.
+    pub fn log(&mut self, kind: SessionEventKind, text: &str) {
+        let time_ms = self.elapsed_ms();
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        if self.clock.is_paused() {
+            return;
+        }
+
+        let _ = writeln!(file, "{}\t{}\t{}", time_ms, kind as u8, escape(text));
+    }
+}
+
+/// Reads the event log of a recording.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+///
+/// # Returns
+///
+/// The events as `Packet::SessionEvent`s in order, which is empty if the recording
+/// has no event log.
+pub fn read_events(filename: &str) -> Vec<Packet> {
+    let Ok(log) = fs::read_to_string(get_events_path(filename)) else {
+        return Vec::new();
+    };
+
+    log.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+
+            let time_ms = fields.next()?.parse().ok()?;
+            let kind = SessionEventKind::from_byte(fields.next()?.parse().ok()?)?;
+            let text = unescape(fields.next()?);
+
+            Some(Packet::SessionEvent {
+                time_ms,
+                kind,
+                text,
+            })
+        })
+        .collect()
+}
+
+/// Writes the events of a part of a recording as the event log of a clip of it.
+///
+/// # Arguments
+///
+/// * `source` - The filename of the recording (without the extension).
+/// * `clip` - The filename of the clip (without the extension).
+/// * `range_ms` - The part of the recording the clip was cut from, in milliseconds.
+pub fn copy_events(source: &str, clip: &str, range_ms: Range<u64>) {
+    let lines: String = read_events(source)
+        .into_iter()
+        .filter_map(|event| match event {
+            Packet::SessionEvent {
+                time_ms,
+                kind,
+                text,
+            } if range_ms.contains(&time_ms) => Some(format!(
+                "{}\t{}\t{}\n",
+                time_ms - range_ms.start,
+                kind as u8,
+                escape(&text)
+            )),
+            _ => None,
+        })
+        .collect();
+
+    if !lines.is_empty() {
+        let _ = fs::write(get_events_path(clip), lines);
+    }
+}
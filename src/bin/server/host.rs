@@ -1,69 +1,29 @@
-use crate::{get_video_path, SessionHashMap, SharedSession};
+use crate::{
+    access::grant_access,
+    audio::{forward_audio, mix_into_recording},
+    bookmarks::{add_bookmark, query_bookmarks, Bookmark},
+    mp4::Mp4Writer,
+    recording::{finish_recording_in_database, insert_recording_to_database},
+    simulcast::{forward_screen, is_keyframe_start},
+    storage::storage_left,
+    thumbnails::generate_thumbnails,
+    transfer::forward_transfer_packet,
+    SessionHashMap, SharedSession,
+};
 use chrono::Local;
 
 use log::info;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::params;
-use std::{
-    io::Write,
-    process::{Child, Command, Stdio},
-};
+use std::thread;
 use stream_desk::{
-    protocol::{Packet, ResultPacket},
+    protocol::{
+        is_clipboard_within_limits, Packet, RecordingState, ResultPacket, SessionEventKind,
+    },
     secure_channel::SecureChannel,
     UserType, LOG_TARGET,
 };
 
-/// Starts an `ffmpeg` process that saves the recording to file at the end of the session.
-///
-/// # Arguments
-///
-/// * `filename` - The filename to save the video to.
-///
-/// # Returns
-///
-/// The subprocess `Child` object.
-fn ffmpeg_save_recording(filename: &str) -> Child {
-    let output_path = get_video_path(filename);
-
-    let ffmpeg = Command::new("ffmpeg")
-        .args([
-            "-f",
-            "h264",
-            "-i",
-            "-",
-            "-c",
-            "copy",
-            output_path.to_str().unwrap(),
-        ])
-        .stdin(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()
-        .expect("Failed to spawn ffmpeg");
-
-    ffmpeg
-}
-
-/// Inserts the recording data to the database.
-///
-/// # Arguments
-/// * `db_pool` - The pool of the database connections.
-/// * `filename` - The filename of the video.
-/// * `time` - The timestamp of the meeting.
-/// * `user_id` - The ID of the user this recording belongs to.
-fn insert_recording_to_database(
-    db_pool: &Pool<SqliteConnectionManager>,
-    filename: &str,
-    time: &str,
-    user_id: i32,
-) {
-    let _ = db_pool.get().unwrap().execute(
-        "INSERT INTO recordings (filename, time, user_id) VALUES (?1, ?2, ?3)",
-        params![filename, time, user_id],
-    );
-}
-
 /// Handles packets from the client.
 ///
 /// # Arguments
@@ -91,157 +51,422 @@ pub fn handle_host(
     let time = Local::now().to_rfc3339();
     let filename = uuid::Uuid::new_v4().to_string();
 
-    let mut ffmpeg = ffmpeg_save_recording(&filename);
-    let mut stdin = ffmpeg.stdin.take().unwrap();
-
-    loop {
-        let packet = channel.receive().unwrap_or_default();
+    // the session starts as recording if the host chose to record it, but hosts who
+    // used up their storage quota aren't recorded
+    let record = session.lock().unwrap().recording_state == RecordingState::Recording;
+    let storage_left = storage_left(db_pool, user_id);
+    let is_recorded = record && storage_left > 0;
+    if !record {
+        info!(target: LOG_TARGET, "Host chose not to record session {}.", code);
+    } else if !is_recorded {
+        info!(
+            target: LOG_TARGET,
+            "Session {} isn't recorded, as {} has no storage left.", code, username
+        );
+    }
 
-        match packet {
-            Packet::Join { username, .. } => {
-                let mut session = session.lock().unwrap();
+    // listed as in progress from the start, so it can be recovered if the server stops
+    let recording_id = match is_recorded {
+        true => insert_recording_to_database(db_pool, &filename, &time, user_id),
+        false => None,
+    };
+
+    let mut video = is_recorded.then(|| Mp4Writer::create(&filename));
+    {
+        let mut session = session.lock().unwrap();
+        if is_recorded {
+            session.audio.start_recording(&filename);
+            session.events.start_recording(&filename, &username);
+        }
+        session.recording_id = recording_id;
+        session.recording_state = match is_recorded {
+            true => RecordingState::Recording,
+            false => RecordingState::Off,
+        };
+    }
 
-                if let Some((mut connection, join_sender)) = session.pending_join.remove(&username)
-                {
-                    // notify user they were allowed
-                    let success = ResultPacket::Success("Joining".to_string());
-                    connection.channel.send(success)?;
+    // the video resumes at a keyframe after a pause, so it can be decoded
+    let mut is_paused = false;
+    let mut awaits_keyframe = false;
+
+    // the host is warned once the video takes most of the storage that was left
+    let mut is_storage_warned = false;
+
+    // the session is wrapped up even if it ends with an error, like a participant
+    // whose connection was lost, so the recording is listed and the session removed
+    let result = (|| -> std::io::Result<()> {
+        // the host is told too, as the storage quota may not allow recording
+        {
+            let mut session = session.lock().unwrap();
+            let state = session.recording_state;
+            session.broadcast_all(Packet::RecordingState { state })?;
+        }
 
-                    // notify user thread
-                    let _ = join_sender.send(true);
+        loop {
+            let packet = channel.receive().unwrap_or_default();
+
+            match packet {
+                Packet::Join { username, .. } => {
+                    let mut session = session.lock().unwrap();
+
+                    if let Some((mut connection, join_sender)) =
+                        session.pending_join.remove(&username)
+                    {
+                        // notify user they were allowed
+                        let success = ResultPacket::Success("Joining".to_string());
+                        connection.channel.send(success)?;
+
+                        // notify user thread
+                        let _ = join_sender.send(true);
+
+                        // send all usernames
+                        for (username, user_connection) in &session.connections {
+                            let username_packet = Packet::UserUpdate {
+                                user_type: user_connection.user_type,
+                                joined_before: true,
+                                username: username.clone(),
+                            };
+                            connection.channel.send(username_packet)?;
+                        }
+
+                        // send the clipboard synchronization state
+                        let clipboard_packet = Packet::ClipboardSync {
+                            enabled: session.clipboard_sync,
+                        };
+                        connection.channel.send(clipboard_packet)?;
 
-                    // send all usernames
-                    for (username, user_connection) in &session.connections {
-                        let username_packet = Packet::UserUpdate {
-                            user_type: user_connection.user_type,
-                            joined_before: true,
+                        // let the user know whether they're recorded
+                        let recording_packet = Packet::RecordingState {
+                            state: session.recording_state,
+                        };
+                        connection.channel.send(recording_packet)?;
+
+                        // send the size of the shared screen
+                        if let Some((width, height)) = session.screen_size {
+                            let screen_packet = Packet::ScreenSource {
+                                width,
+                                height,
+                                layers: session.screen_layers,
+                            };
+                            connection.channel.send(screen_packet)?;
+                        }
+
+                        // send the headers of the audio streams in progress
+                        for audio_packet in session.audio.header_packets() {
+                            connection.channel.send(audio_packet)?;
+                        }
+
+                        session.connections.insert(username.clone(), connection);
+                        session.attendees.insert(username.clone());
+                        session.events.log(SessionEventKind::Join, &username);
+
+                        // send new username to all participants
+                        let packet = Packet::UserUpdate {
+                            user_type: UserType::Participant,
+                            joined_before: false,
                             username: username.clone(),
                         };
-                        connection.channel.send(username_packet)?;
+                        session.broadcast_all(packet)?;
                     }
+                }
 
-                    session.connections.insert(username.clone(), connection);
+                Packet::DenyJoin { username } => {
+                    let mut session = session.lock().unwrap();
 
-                    // send new username to all participants
-                    let packet = Packet::UserUpdate {
-                        user_type: UserType::Participant,
-                        joined_before: false,
-                        username: username.clone(),
-                    };
-                    session.broadcast_all(packet)?;
-                }
-            }
+                    if let Some((connection, join_sender)) = session.pending_join.get_mut(&username)
+                    {
+                        // notify user they were denied
+                        let failure =
+                            ResultPacket::Failure("You were denied by the host.".to_string());
+                        connection.channel.send(failure)?;
 
-            Packet::DenyJoin { username } => {
-                let mut session = session.lock().unwrap();
+                        // notify user thread
+                        let _ = join_sender.send(false);
+                    }
 
-                if let Some((connection, join_sender)) = session.pending_join.get_mut(&username) {
-                    // notify user they were denied
-                    let failure = ResultPacket::Failure("You were denied by the host.".to_string());
-                    connection.channel.send(failure)?;
+                    // remove from pending
+                    session.pending_join.remove(&username);
 
-                    // notify user thread
-                    let _ = join_sender.send(false);
+                    info!(target: LOG_TARGET, "User {} was denied from session {}.", username, code);
                 }
 
-                // remove from pending
-                session.pending_join.remove(&username);
+                Packet::Screen { layer, bytes } => {
+                    // only the full quality layer is recorded
+                    if let (0, Some(video)) = (layer, &mut video) {
+                        awaits_keyframe &= !is_keyframe_start(&bytes);
+                        if !is_paused && !awaits_keyframe {
+                            video.push_nal(&bytes);
+
+                            let written_bytes = video.written_bytes();
+                            if written_bytes >= storage_left {
+                                // the recording stops for good, as resuming would exceed the quota
+                                is_paused = true;
+                                video.pause();
+
+                                let mut session = session.lock().unwrap();
+                                session.audio.pause_recording();
+                                session.events.set_paused(true, &username);
+                                session.recording_state = RecordingState::Off;
+                                session.broadcast_all(Packet::RecordingState {
+                                    state: RecordingState::Off,
+                                })?;
+
+                                let message =
+                                    "#rYour storage is full, so the recording was stopped.";
+                                channel.send(Packet::Chat {
+                                    message: message.to_owned(),
+                                })?;
+
+                                info!(
+                                    target: LOG_TARGET,
+                                    "Stopped recording session {}, as {} has no storage left.",
+                                    code,
+                                    username
+                                );
+                            } else if !is_storage_warned && written_bytes >= storage_left / 10 * 9 {
+                                is_storage_warned = true;
+
+                                let message = "#rYour storage is almost full. \
+                                    The recording stops when it runs out.";
+                                channel.send(Packet::Chat {
+                                    message: message.to_owned(),
+                                })?;
+                            }
+                        }
+                    }
 
-                info!(target: LOG_TARGET, "User {} was denied from session {}.", username, code);
-            }
+                    let mut session = session.lock().unwrap();
+                    forward_screen(&mut session, layer, bytes)?;
+                }
 
-            Packet::Screen { ref bytes } => {
-                stdin.write_all(&bytes).unwrap();
-                let mut session = session.lock().unwrap();
-                session.broadcast_participants(packet)?;
-            }
+                Packet::ScreenSource {
+                    width,
+                    height,
+                    layers,
+                } => {
+                    let mut session = session.lock().unwrap();
+                    session.screen_size = Some((width, height));
+                    session.screen_layers = layers.max(1);
+                    for connection in session.connections.values_mut() {
+                        connection.layer.limit_layers(layers.max(1));
+                    }
+                    session.broadcast_participants(packet)?;
+
+                    info!(
+                        target: LOG_TARGET,
+                        "Host of session {} is now sharing a {}x{} screen.", code, width, height
+                    );
+                }
 
-            Packet::SessionExit | Packet::None => {
-                let mut session = session.lock().unwrap();
+                Packet::RecordingState { state } => {
+                    let mut session = session.lock().unwrap();
 
-                let packet = Packet::SessionEnd;
-                session.broadcast_all(packet)?;
+                    // a session that isn't recorded can't be paused or resumed
+                    let is_change = matches!(
+                        (session.recording_state, state),
+                        (RecordingState::Recording, RecordingState::Paused)
+                            | (RecordingState::Paused, RecordingState::Recording)
+                    );
+                    if is_change {
+                        is_paused = state == RecordingState::Paused;
+                        awaits_keyframe = !is_paused;
+
+                        if is_paused {
+                            session.audio.pause_recording();
+                        } else {
+                            session.audio.resume_recording();
+                        }
+                        if let Some(video) = &mut video {
+                            match is_paused {
+                                true => video.pause(),
+                                false => video.resume(),
+                            }
+                        }
+                        session.events.set_paused(is_paused, &username);
+
+                        session.recording_state = state;
+                        session.broadcast_all(packet)?;
+
+                        info!(
+                            target: LOG_TARGET,
+                            "Host {} the recording of session {}.",
+                            if is_paused { "paused" } else { "resumed" },
+                            code
+                        );
+                    }
+                }
 
-                let mut sessions = sessions.lock().unwrap();
-                sessions.remove(&code);
+                Packet::CursorState { .. } => {
+                    let mut session = session.lock().unwrap();
+                    session.broadcast_participants(packet)?;
+                }
 
-                info!(target: LOG_TARGET, "Host ended session {}.", code);
+                Packet::Audio { .. } | Packet::AudioEnd { .. } => {
+                    let mut session = session.lock().unwrap();
+                    forward_audio(&mut session, packet, &username)?;
+                }
 
-                break;
-            }
+                Packet::SessionExit | Packet::None => {
+                    let mut session = session.lock().unwrap();
 
-            Packet::RequestControl { username } => {
-                let mut session = session.lock().unwrap();
-                if let Some(user_connection) = session.connections.get_mut(&username) {
-                    user_connection.user_type = UserType::Controller;
+                    let packet = Packet::SessionEnd;
+                    session.broadcast_all(packet)?;
 
-                    let packet = Packet::RequestControl {
-                        username: username.clone(),
-                    };
-                    user_connection.channel.send(packet)?;
+                    let mut sessions = sessions.lock().unwrap();
+                    sessions.remove(&code);
 
-                    // notify all users
-                    let user_update = Packet::UserUpdate {
-                        user_type: UserType::Controller,
-                        joined_before: true,
-                        username: username.to_string(),
-                    };
-                    session.broadcast_all(user_update)?;
+                    info!(target: LOG_TARGET, "Host ended session {}.", code);
 
-                    info!(
-                        target: LOG_TARGET,
-                        "User {} is now the Controller of session {}.",
-                        username, code
-                    );
+                    return Ok(());
                 }
-            }
-
-            Packet::DenyControl { username } => {
-                let mut session = session.lock().unwrap();
-                if let Some(user_connection) = session.connections.get_mut(&username) {
-                    let was_controller = user_connection.user_type == UserType::Controller;
 
-                    user_connection.user_type = UserType::Participant;
+                Packet::RequestControl { username } => {
+                    let mut session = session.lock().unwrap();
+                    if let Some(user_connection) = session.connections.get_mut(&username) {
+                        user_connection.user_type = UserType::Controller;
 
-                    let packet = Packet::DenyControl {
-                        username: username.clone(),
-                    };
-                    user_connection.channel.send(packet)?;
+                        let packet = Packet::RequestControl {
+                            username: username.clone(),
+                        };
+                        user_connection.channel.send(packet)?;
 
-                    // if the user is a controller notify all users
-                    if was_controller {
+                        // notify all users
                         let user_update = Packet::UserUpdate {
-                            user_type: UserType::Participant,
+                            user_type: UserType::Controller,
                             joined_before: true,
                             username: username.to_string(),
                         };
                         session.broadcast_all(user_update)?;
+                        session
+                            .events
+                            .log(SessionEventKind::ControlGranted, &username);
 
                         info!(
                             target: LOG_TARGET,
-                            "User {} is no longer the Controller of session {}.",
+                            "User {} is now the Controller of session {}.",
                             username, code
                         );
                     }
                 }
-            }
 
-            Packet::Chat { message } => {
-                let message = username.to_string() + ": " + &message;
-                let packet = Packet::Chat { message };
+                Packet::DenyControl { username } => {
+                    let mut session = session.lock().unwrap();
+                    if let Some(user_connection) = session.connections.get_mut(&username) {
+                        let was_controller = user_connection.user_type == UserType::Controller;
 
-                let mut session = session.lock().unwrap();
-                session.broadcast_all(packet)?;
-            }
+                        user_connection.user_type = UserType::Participant;
+
+                        let packet = Packet::DenyControl {
+                            username: username.clone(),
+                        };
+                        user_connection.channel.send(packet)?;
+
+                        // if the user is a controller notify all users
+                        if was_controller {
+                            let user_update = Packet::UserUpdate {
+                                user_type: UserType::Participant,
+                                joined_before: true,
+                                username: username.to_string(),
+                            };
+                            session.broadcast_all(user_update)?;
+                            session
+                                .events
+                                .log(SessionEventKind::ControlRevoked, &username);
+
+                            info!(
+                                target: LOG_TARGET,
+                                "User {} is no longer the Controller of session {}.",
+                                username, code
+                            );
+                        } else {
+                            session
+                                .events
+                                .log(SessionEventKind::ControlDenied, &username);
+                        }
+                    }
+                }
+
+                Packet::ClipboardSync { enabled } => {
+                    let mut session = session.lock().unwrap();
+                    session.clipboard_sync = enabled;
+                    session.broadcast_participants(packet)?;
+
+                    info!(
+                        target: LOG_TARGET,
+                        "Clipboard synchronization was {} in session {}.",
+                        if enabled { "enabled" } else { "disabled" },
+                        code
+                    );
+                }
+
+                Packet::ClipboardText { .. } | Packet::ClipboardImage { .. } => {
+                    let session = session.lock().unwrap();
+
+                    // the host's clipboard only goes to the controller
+                    if session.clipboard_sync && is_clipboard_within_limits(&packet) {
+                        if let Some(mut controller) = session.controller() {
+                            controller.send(packet)?;
+                        }
+                    }
+                }
+
+                Packet::FileOffer { .. }
+                | Packet::FileAccept { .. }
+                | Packet::FileChunk { .. }
+                | Packet::FileEnd { .. }
+                | Packet::FileCancel { .. } => {
+                    let mut session = session.lock().unwrap();
+                    forward_transfer_packet(&mut session, packet, &username, channel)?;
+                }
+
+                Packet::Chat { message } => {
+                    let message = username.to_string() + ": " + &message;
 
-            _ => (),
+                    let mut session = session.lock().unwrap();
+                    session.events.log(SessionEventKind::Chat, &message);
+                    session.broadcast_all(Packet::Chat { message })?;
+                }
+
+                Packet::AddBookmark { name } => {
+                    let mut session = session.lock().unwrap();
+                    add_bookmark(&mut session, db_pool, &username, &name)?;
+                }
+
+                _ => (),
+            }
         }
+    })();
+
+    if result.is_err() {
+        let _ = session.lock().unwrap().broadcast_all(Packet::SessionEnd);
+        sessions.lock().unwrap().remove(&code);
+
+        info!(target: LOG_TARGET, "Session {} ended with an error.", code);
     }
 
-    drop(stdin);
-    let _ = ffmpeg.wait();
+    let audio_tracks = session.lock().unwrap().audio.finish();
 
-    insert_recording_to_database(db_pool, &filename, &time, user_id);
+    let Some(mut video) = video else {
+        return result;
+    };
+    video.finish();
+
+    // the bookmarks are the chapters of the downloaded video
+    let bookmarks = recording_id.map_or_else(Vec::new, |id| query_bookmarks(db_pool, id));
+    let chapters: Vec<_> = bookmarks.iter().map(Bookmark::chapter).collect();
+    mix_into_recording(&filename, audio_tracks, &chapters);
+
+    // the thumbnails take a while for long recordings, so don't keep the host waiting
+    let thumbnails_filename = filename.clone();
+    thread::spawn(move || generate_thumbnails(&thumbnails_filename));
+
+    // everyone who was in the session may watch it again
+    if let Some(recording_id) = recording_id {
+        finish_recording_in_database(db_pool, recording_id);
+
+        let attendees = std::mem::take(&mut session.lock().unwrap().attendees);
+        grant_access(db_pool, recording_id, &attendees);
+    }
 
-    Ok(())
+    result
 }
@@ -1,34 +1,60 @@
+use access::{has_access, query_shared_recordings, share_recording};
+use download::send_recording_chunk;
+use events::get_events_path;
 use host::handle_host;
 use log::info;
 use login_register::login_or_register;
+use mp4::VideoIndex;
 use participant::handle_participant;
+use party::{handle_party_leader, handle_party_viewer, join_watch_party, WatchParty};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use rand::Rng;
+use recording::recover_recordings;
+use rusqlite::params;
+use simulcast::LayerState;
 use std::{
     collections::HashMap,
     net::TcpListener,
     path::PathBuf,
-    process::Command,
+    process::{Command, Stdio},
     sync::{
         mpsc::{self},
-        Arc, Mutex,
+        Arc, Mutex, OnceLock,
     },
     thread::{self},
 };
+use storage::{set_retention, storage_packet, thread_delete_expired};
 use stream_desk::{
     initialize_logger,
-    protocol::{Packet, ResultPacket},
+    protocol::{
+        Packet, RecordingState, ResultPacket, MAX_RECORDING_DESCRIPTION_LENGTH,
+        MAX_RECORDING_TITLE_LENGTH,
+    },
     secure_channel::SecureChannel,
     UserType, LOG_DIR, LOG_TARGET, SERVER_LOG_FILE,
 };
 use structs::*;
-use watch::handle_watching;
-
+use thumbnails::{get_poster_path, get_sprite_path, poster_packet};
+use watch::{handle_watching, RecordingRequests};
+
+mod access;
+mod audio;
+mod bookmarks;
+mod clip;
+mod download;
+mod events;
 mod host;
 mod login_register;
+mod mp4;
 mod participant;
+mod party;
+mod recording;
+mod simulcast;
+mod storage;
 mod structs;
+mod thumbnails;
+mod transfer;
 mod watch;
 
 const RECORDINGS_FOLDER: &'static str = "recordings";
@@ -37,6 +63,20 @@ const DATABASE_FILE: &'static str = "database.sqlite";
 type SharedSession = Arc<Mutex<Session>>;
 type SessionHashMap = Arc<Mutex<HashMap<u32, SharedSession>>>;
 
+/// Gets the folder the recordings are kept in.
+///
+/// # Returns
+///
+/// The `recordings` folder, or a folder in the temporary directory for the tests, so
+/// they never leave files among the server's recordings.
+fn recordings_folder() -> PathBuf {
+    if cfg!(test) {
+        std::env::temp_dir().join("stream-desk-test-recordings")
+    } else {
+        PathBuf::from(RECORDINGS_FOLDER)
+    }
+}
+
 /// Constructs the full file path for a video recording.
 ///
 /// This function creates a `PathBuf` pointing to a video file within the
@@ -51,7 +91,91 @@ type SessionHashMap = Arc<Mutex<HashMap<u32, SharedSession>>>;
 /// A `PathBuf` containing the complete path to the video file in the format
 /// `recordings/{filename}.mp4`.
 fn get_video_path(filename: &str) -> PathBuf {
-    PathBuf::from(RECORDINGS_FOLDER).join(format!("{filename}.mp4"))
+    recordings_folder().join(format!("{filename}.mp4"))
+}
+
+/// Checks whether `ffmpeg` can be run, which transcoding recordings, generating their
+/// thumbnails, cutting frame accurate clips and mixing the audio of several speakers need.
+///
+/// Everything else, like muxing the recordings and cutting clips at keyframes, is done
+/// by the server itself.
+///
+/// # Returns
+///
+/// `true` if `ffmpeg` is installed. It's only checked the first time.
+fn has_ffmpeg() -> bool {
+    static HAS_FFMPEG: OnceLock<bool> = OnceLock::new();
+
+    *HAS_FFMPEG.get_or_init(|| {
+        Command::new("ffmpeg")
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    })
+}
+
+/// Formats a time in a recording as minutes and seconds, like the player shows it.
+///
+/// # Arguments
+///
+/// * `time_ms` - The time in milliseconds.
+fn format_recording_time(time_ms: u64) -> String {
+    let seconds = time_ms / 1000;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Removes a recording from the database and its files from the disk.
+///
+/// # Arguments
+///
+/// * `db_pool` - A `&Pool<SqliteConnectionManager>` for database connection management.
+/// * `id` - The ID of the recording.
+/// * `filename` - The filename of the recording (without the extension).
+fn delete_recording(db_pool: &Pool<SqliteConnectionManager>, id: i32, filename: &str) {
+    let _ = db_pool
+        .get()
+        .unwrap()
+        .execute("DELETE FROM recordings WHERE recording_id = ?1", [id]);
+    let _ = db_pool
+        .get()
+        .unwrap()
+        .execute("DELETE FROM recording_access WHERE recording_id = ?1", [id]);
+    let _ = db_pool.get().unwrap().execute(
+        "DELETE FROM recording_bookmarks WHERE recording_id = ?1",
+        [id],
+    );
+    // the clips of the recording are kept as recordings of their own
+    let _ = db_pool.get().unwrap().execute(
+        "UPDATE recordings SET parent_id = NULL WHERE parent_id = ?1",
+        [id],
+    );
+
+    let _ = std::fs::remove_file(get_video_path(filename));
+    let _ = std::fs::remove_file(get_sprite_path(filename));
+    let _ = std::fs::remove_file(get_poster_path(filename));
+    let _ = std::fs::remove_file(get_events_path(filename));
+}
+
+/// Updates a text column of a recording in the database.
+///
+/// # Arguments
+///
+/// * `db_pool` - A `&Pool<SqliteConnectionManager>` for database connection management.
+/// * `id` - The ID of the recording.
+/// * `column` - The column to update, `title` or `description`.
+/// * `value` - The new value of the column.
+fn update_recording_text(
+    db_pool: &Pool<SqliteConnectionManager>,
+    id: i32,
+    column: &str,
+    value: &str,
+) {
+    let _ = db_pool.get().unwrap().execute(
+        &format!("UPDATE recordings SET {column} = ?1 WHERE recording_id = ?2"),
+        params![value, id],
+    );
 }
 
 /// Generates a unique 6-digit session code for new remote desktop sessions.
@@ -80,10 +204,7 @@ fn generate_session_code(sessions: &HashMap<u32, SharedSession>) -> u32 {
     }
 }
 
-/// Determines the duration of a video file in frames using FFprobe.
-///
-/// This function executes FFprobe to extract the duration of a video file
-/// and converts it to frame count assuming a 30 FPS frame rate.
+/// Determines the duration of a video file in milliseconds from the index of its frames.
 ///
 /// # Arguments
 ///
@@ -91,44 +212,55 @@ fn generate_session_code(sessions: &HashMap<u32, SharedSession>) -> u32 {
 ///
 /// # Returns
 ///
-/// An `i32` representing the total number of frames in the video, calculated
-/// by multiplying the duration in seconds by 30 and rounding up.
+/// A `u64` representing the duration of the video in milliseconds, which is 0 if the
+/// video can't be read.
+fn get_duration_ms(filename: &str) -> u64 {
+    VideoIndex::read(filename).map_or(0, |index| index.duration_ms())
+}
+
+/// Creates the response to a request only the owner of a recording may make.
+fn not_owner_failure() -> ResultPacket {
+    ResultPacket::Failure("Only the owner of a recording can change it.".to_owned())
+}
+
+/// Finds a recording the user may watch, whose video is on the server.
 ///
-/// # Panics
+/// # Arguments
+///
+/// * `recordings` - The recordings that were listed for the user.
+/// * `db_pool` - A `&Pool<SqliteConnectionManager>` for database connection management.
+/// * `user_id` - The ID of the user.
+/// * `id` - The ID of the recording.
 ///
-/// Panics if FFprobe cannot be executed or if the output cannot be parsed as
-/// a valid floating-point duration value.
-fn get_duration_frames(filename: &str) -> i32 {
-    let input_path = get_video_path(filename);
-
-    let output = Command::new("ffprobe")
-        .args([
-            "-v",
-            "error",
-            "-select_streams",
-            "v:0",
-            "-show_entries",
-            "format=duration",
-            "-of",
-            "default=noprint_wrappers=1:nokey=1",
-            input_path.to_str().unwrap(),
-        ])
-        .output()
-        .expect("Error launching ffprobe.");
-
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let seconds = output_str
-        .trim()
-        .parse::<f64>()
-        .expect("should be valid f64");
-
-    (seconds * 30.0).ceil() as i32
+/// # Returns
+///
+/// The recording, or the `ResultPacket::Failure` to answer with if it can't be watched.
+fn find_playable_recording<'a>(
+    recordings: &'a HashMap<i32, Recording>,
+    db_pool: &Pool<SqliteConnectionManager>,
+    user_id: i32,
+    id: i32,
+) -> Result<&'a Recording, ResultPacket> {
+    // the access could have been lost since the list was sent
+    let recording = recordings
+        .get(&id)
+        .filter(|_| has_access(db_pool, user_id, id))
+        .ok_or_else(|| ResultPacket::Failure("No recording found.".to_owned()))?;
+
+    if !get_video_path(&recording.filename).exists() {
+        return Err(ResultPacket::Failure(
+            "Video file does not exist on the server.".to_owned(),
+        ));
+    }
+
+    Ok(recording)
 }
 
-/// Retrieves all recordings associated with a specific user from the database.
+/// Retrieves all recordings a specific user may watch from the database.
 ///
 /// This function queries the SQLite database to fetch all recording metadata
-/// for a given user, including recording IDs, filenames, and timestamps.
+/// for a given user, including recording IDs, filenames, and timestamps. These are
+/// the user's own recordings and the recordings shared with them.
 ///
 /// # Arguments
 ///
@@ -138,7 +270,8 @@ fn get_duration_frames(filename: &str) -> i32 {
 /// # Returns
 ///
 /// A `HashMap<i32, Recording>` where keys are recording IDs and values are
-/// `Recording` structs containing filename and timestamp information.
+/// `Recording` structs containing filename, timestamp, title, description and
+/// retention.
 ///
 /// # Panics
 ///
@@ -148,19 +281,26 @@ fn query_recordings(
     db_pool: &Pool<SqliteConnectionManager>,
     user_id: i32,
 ) -> HashMap<i32, Recording> {
-    let db_pool = db_pool.get().unwrap();
+    let connection = db_pool.get().unwrap();
 
-    let mut query = db_pool
-        .prepare("SELECT recording_id, filename, time FROM recordings WHERE user_id = ?1")
+    let mut query = connection
+        .prepare(
+            "SELECT recording_id, filename, time, title, description, retention_days FROM recordings WHERE user_id = ?1 AND in_progress = 0",
+        )
         .unwrap();
 
-    // get a vector of (filename, time)
-    let recordings = query
+    // get a vector of (id, recording)
+    let mut recordings = query
         .query_map([user_id], |row| {
             let id: i32 = row.get(0)?;
             let recording = Recording {
                 filename: row.get(1)?,
                 time: row.get(2)?,
+                title: row.get(3)?,
+                description: row.get(4)?,
+                owner: None,
+                // kept forever unless the owner chose otherwise
+                retention_days: row.get::<_, Option<u32>>(5)?.unwrap_or(0),
             };
             Ok((id, recording))
         })
@@ -168,6 +308,8 @@ fn query_recordings(
         .collect::<Result<HashMap<_, _>, _>>()
         .unwrap();
 
+    recordings.extend(query_shared_recordings(db_pool, user_id));
+
     recordings
 }
 
@@ -196,7 +338,7 @@ fn query_recordings(
 /// - Manages menu scene with recording listings
 /// - Processes host requests and creates new sessions
 /// - Handles join requests with session validation
-/// - Manages recording playback requests
+/// - Manages recording playback requests and watch parties
 /// - Maintains proper cleanup on client disconnection
 fn handle_client(
     mut channel: SecureChannel,
@@ -226,14 +368,40 @@ fn handle_client(
 
         'menu_scene: loop {
             // send all recordings
-            let recordings = query_recordings(&db_pool, user_id);
+            let mut recordings = query_recordings(&db_pool, user_id);
             for (id, recording) in &recordings {
                 let packet = Packet::RecordingName {
                     id: *id,
                     name: recording.time.clone(),
                 };
                 channel.send(packet)?;
+
+                let details = Packet::RecordingDetails {
+                    id: *id,
+                    title: recording.title.clone(),
+                    description: recording.description.clone(),
+                };
+                channel.send(details)?;
+
+                if let Some(owner) = &recording.owner {
+                    let owner = Packet::RecordingOwner {
+                        id: *id,
+                        owner: owner.clone(),
+                    };
+                    channel.send(owner)?;
+                }
+
+                let retention = Packet::RecordingRetention {
+                    id: *id,
+                    days: recording.retention_days,
+                };
+                channel.send(retention)?;
+
+                if let Some(poster) = poster_packet(*id, &recording.filename) {
+                    channel.send(poster)?;
+                }
             }
+            channel.send(storage_packet(&db_pool, user_id))?;
             channel.send(Packet::None)?;
 
             loop {
@@ -246,17 +414,23 @@ fn handle_client(
                         break 'menu_scene;
                     }
 
-                    Packet::Host => {
+                    Packet::Host { record } => {
                         let mut sessions_guard = sessions.lock().unwrap();
                         let code = generate_session_code(&sessions_guard);
 
                         let host_connection = Connection {
                             channel: channel.clone(),
                             user_type: UserType::Host,
+                            layer: LayerState::new(),
                         };
 
-                        let session =
-                            Arc::new(Mutex::new(Session::new(username.clone(), host_connection)));
+                        let mut session = Session::new(username.clone(), host_connection);
+                        // the host's choice, which the storage quota may still overrule
+                        if record {
+                            session.recording_state = RecordingState::Recording;
+                        }
+
+                        let session = Arc::new(Mutex::new(session));
                         sessions_guard.insert(code, session.clone());
 
                         // release the lock
@@ -304,6 +478,53 @@ fn handle_client(
                                 continue;
                             }
 
+                            // watch parties let in everyone who may watch the recording,
+                            // without asking the leader
+                            let party_recording_id = session_guard
+                                .watch_party
+                                .as_ref()
+                                .map(|party| party.recording_id);
+                            if let Some(recording_id) = party_recording_id {
+                                drop(session_guard);
+
+                                let recording = match find_playable_recording(
+                                    &recordings,
+                                    &db_pool,
+                                    user_id,
+                                    recording_id,
+                                ) {
+                                    Ok(recording) => recording,
+                                    Err(_) => {
+                                        let failure = ResultPacket::Failure(
+                                            "You don't have access to the recording of this watch party."
+                                                .to_string(),
+                                        );
+                                        channel.send(failure)?;
+                                        continue;
+                                    }
+                                };
+
+                                // the second result has the duration, which tells the client
+                                // it joined a watch party
+                                let success = ResultPacket::Success("Joining".to_owned());
+                                channel.send(success)?;
+                                let duration_ms = get_duration_ms(&recording.filename);
+                                channel.send(ResultPacket::Success(duration_ms.to_string()))?;
+
+                                join_watch_party(&channel, &session, &username)?;
+
+                                info!(target: LOG_TARGET, "User {} joined watch party {}.", username, code);
+
+                                let requests = RecordingRequests::new(
+                                    &db_pool,
+                                    user_id,
+                                    recording_id,
+                                    recording,
+                                );
+                                handle_party_viewer(&mut channel, session, username, requests)?;
+                                break;
+                            }
+
                             let success = ResultPacket::Success("Joining".to_owned());
                             channel.send(success)?;
 
@@ -319,6 +540,7 @@ fn handle_client(
                             let connection = Connection {
                                 channel: channel.clone(),
                                 user_type: UserType::Participant,
+                                layer: LayerState::new(),
                             };
                             session_guard
                                 .pending_join
@@ -335,6 +557,7 @@ fn handle_client(
                                     &mut channel,
                                     session.clone(),
                                     username.clone(),
+                                    &db_pool,
                                 )?;
                                 break;
                             }
@@ -349,32 +572,138 @@ fn handle_client(
                     }
 
                     Packet::WatchRecording { id } => {
-                        let recording = recordings.get(&id);
-                        match recording {
+                        match find_playable_recording(&recordings, &db_pool, user_id, id) {
+                            Ok(recording) => {
+                                let duration_ms = get_duration_ms(&recording.filename);
+                                let success = ResultPacket::Success(duration_ms.to_string());
+                                channel.send(success)?;
+
+                                info!(
+                                    target: LOG_TARGET,
+                                    "User {} is watching recording {}.mp4.",
+                                    username, recording.filename
+                                );
+
+                                let requests =
+                                    RecordingRequests::new(&db_pool, user_id, id, recording);
+                                handle_watching(&mut channel, requests)?;
+                                break;
+                            }
+                            Err(failure) => {
+                                channel.send(failure)?;
+                            }
+                        }
+                    }
+
+                    Packet::WatchParty { id } => {
+                        match find_playable_recording(&recordings, &db_pool, user_id, id) {
+                            Ok(recording) => {
+                                let mut sessions_guard = sessions.lock().unwrap();
+                                let code = generate_session_code(&sessions_guard);
+
+                                let leader_connection = Connection {
+                                    channel: channel.clone(),
+                                    user_type: UserType::Host,
+                                    layer: LayerState::new(),
+                                };
+
+                                let mut session = Session::new(username.clone(), leader_connection);
+                                session.watch_party = Some(WatchParty::new(id));
+
+                                let session = Arc::new(Mutex::new(session));
+                                sessions_guard.insert(code, session.clone());
+
+                                // release the lock
+                                drop(sessions_guard);
+
+                                // send back the session code, then the duration
+                                channel.send(ResultPacket::Success(format!("{}", code)))?;
+                                let duration_ms = get_duration_ms(&recording.filename);
+                                channel.send(ResultPacket::Success(duration_ms.to_string()))?;
+
+                                info!(
+                                    target: LOG_TARGET,
+                                    "User {} started watch party {} for recording {}.mp4.",
+                                    username, code, recording.filename
+                                );
+
+                                let requests =
+                                    RecordingRequests::new(&db_pool, user_id, id, recording);
+                                handle_party_leader(
+                                    &mut channel,
+                                    session,
+                                    sessions.clone(),
+                                    code,
+                                    username.clone(),
+                                    requests,
+                                )?;
+                                break;
+                            }
+                            Err(failure) => {
+                                channel.send(failure)?;
+                            }
+                        }
+                    }
+
+                    Packet::RenameRecording { id, title } => {
+                        let result = match recordings.get_mut(&id) {
+                            Some(recording) if recording.owner.is_some() => not_owner_failure(),
+
+                            Some(_) if title.chars().count() > MAX_RECORDING_TITLE_LENGTH => {
+                                ResultPacket::Failure("The title is too long.".to_owned())
+                            }
+
                             Some(recording) => {
-                                let does_video_exists =
-                                    get_video_path(&recording.filename).exists();
-
-                                if does_video_exists {
-                                    let num_of_frames = get_duration_frames(&recording.filename);
-                                    let success = ResultPacket::Success(num_of_frames.to_string());
-                                    channel.send(success)?;
-
-                                    info!(
-                                        target: LOG_TARGET,
-                                        "User {} is watching recording {}.mp4.",
-                                        username, recording.filename
-                                    );
-
-                                    handle_watching(&mut channel, &recording.filename)?;
-                                    break;
-                                } else {
-                                    let failure = ResultPacket::Failure(
-                                        "Video file does not exist on the server.".to_owned(),
-                                    );
-                                    channel.send(failure)?;
-                                }
+                                update_recording_text(&db_pool, id, "title", &title);
+                                recording.title = title;
+
+                                ResultPacket::Success(recording.title.clone())
                             }
+
+                            None => ResultPacket::Failure("No recording found.".to_owned()),
+                        };
+                        channel.send(result)?;
+                    }
+
+                    Packet::DescribeRecording { id, description } => {
+                        let result = match recordings.get_mut(&id) {
+                            Some(recording) if recording.owner.is_some() => not_owner_failure(),
+
+                            Some(_)
+                                if description.chars().count()
+                                    > MAX_RECORDING_DESCRIPTION_LENGTH =>
+                            {
+                                ResultPacket::Failure("The description is too long.".to_owned())
+                            }
+
+                            Some(recording) => {
+                                update_recording_text(&db_pool, id, "description", &description);
+                                recording.description = description;
+
+                                ResultPacket::Success(recording.description.clone())
+                            }
+
+                            None => ResultPacket::Failure("No recording found.".to_owned()),
+                        };
+                        channel.send(result)?;
+                    }
+
+                    Packet::DeleteRecording { id } => {
+                        let is_owner = recordings.get(&id).map(|r| r.owner.is_none());
+                        match is_owner {
+                            Some(true) => {
+                                let recording = recordings.remove(&id).unwrap();
+                                delete_recording(&db_pool, id, &recording.filename);
+                                channel.send(ResultPacket::Success(String::new()))?;
+
+                                info!(
+                                    target: LOG_TARGET,
+                                    "User {} deleted recording {}.mp4.", username, recording.filename
+                                );
+                            }
+
+                            Some(false) => channel.send(not_owner_failure())?,
+
                             None => {
                                 let failure =
                                     ResultPacket::Failure("No recording found.".to_owned());
@@ -383,6 +712,88 @@ fn handle_client(
                         }
                     }
 
+                    Packet::RecordingRetention { id, days } => {
+                        let result = match recordings.get_mut(&id) {
+                            Some(recording) if recording.owner.is_some() => not_owner_failure(),
+
+                            Some(recording) => {
+                                let result = set_retention(&db_pool, id, &recording.time, days);
+                                if let ResultPacket::Success(_) = result {
+                                    recording.retention_days = days;
+                                }
+
+                                result
+                            }
+
+                            None => ResultPacket::Failure("No recording found.".to_owned()),
+                        };
+                        channel.send(result)?;
+                    }
+
+                    Packet::GetStorageUsage => {
+                        channel.send(storage_packet(&db_pool, user_id))?;
+                    }
+
+                    Packet::DownloadRecording { id, offset } => match recordings.get(&id) {
+                        Some(recording) if has_access(&db_pool, user_id, id) => {
+                            send_recording_chunk(
+                                &mut channel,
+                                &db_pool,
+                                user_id,
+                                id,
+                                &recording.filename,
+                                offset,
+                            )?;
+
+                            if offset == 0 {
+                                info!(
+                                    target: LOG_TARGET,
+                                    "User {} is downloading recording {}.mp4.",
+                                    username,
+                                    recording.filename
+                                );
+                            }
+                        }
+
+                        _ => {
+                            let failure = Packet::DownloadFailed {
+                                id,
+                                message: "No recording found.".to_owned(),
+                            };
+                            channel.send(failure)?;
+                        }
+                    },
+
+                    Packet::ShareRecording {
+                        id,
+                        username: share_with,
+                    } => {
+                        let result = match recordings.get(&id) {
+                            Some(recording) if recording.owner.is_some() => not_owner_failure(),
+
+                            Some(recording) => {
+                                match share_recording(&db_pool, id, user_id, &share_with) {
+                                    Ok(()) => {
+                                        info!(
+                                            target: LOG_TARGET,
+                                            "User {} shared recording {}.mp4 with {}.",
+                                            username, recording.filename, share_with
+                                        );
+
+                                        ResultPacket::Success(format!(
+                                            "Shared the recording with {}.",
+                                            share_with
+                                        ))
+                                    }
+                                    Err(msg) => ResultPacket::Failure(msg),
+                                }
+                            }
+
+                            None => ResultPacket::Failure("No recording found.".to_owned()),
+                        };
+                        channel.send(result)?;
+                    }
+
                     _ => (),
                 }
             }
@@ -400,14 +811,15 @@ fn handle_client(
 ///
 /// - Creates the recordings directory if it doesn't exist
 /// - Initializes SQLite database connection pool
-/// - Creates necessary database tables (users and recordings)
+/// - Creates necessary database tables (users, recordings, recording access, bookmarks and download usage)
+/// - Repairs the recordings of sessions that were cut off when the server stopped
 /// - Binds TCP listener to port 7643 on all interfaces
 /// - Spawns secure channels and client handler threads for each connection
 /// - Maintains a shared session map for active remote desktop sessions
 /// - Handles connection errors gracefully without terminating the server
 fn main() {
     let _ = std::fs::create_dir(LOG_DIR);
-    let _ = std::fs::create_dir(RECORDINGS_FOLDER);
+    let _ = std::fs::create_dir(recordings_folder());
 
     initialize_logger(SERVER_LOG_FILE);
 
@@ -421,7 +833,8 @@ fn main() {
             "CREATE TABLE IF NOT EXISTS users(
                 user_id INTEGER PRIMARY KEY,
                 username TEXT NOT NULL UNIQUE,
-                password TEXT NOT NULL
+                password TEXT NOT NULL,
+                storage_quota INTEGER
             )",
             [],
         )
@@ -436,12 +849,99 @@ fn main() {
                 filename TEXT NOT NULL,
                 time TEXT NOT NULL,
                 user_id INTEGER,
+                title TEXT NOT NULL DEFAULT '',
+                description TEXT NOT NULL DEFAULT '',
+                parent_id INTEGER,
+                in_progress INTEGER NOT NULL DEFAULT 0,
+                retention_days INTEGER,
+                FOREIGN KEY (user_id) REFERENCES users(user_id),
+                FOREIGN KEY (parent_id) REFERENCES recordings(recording_id)
+            )",
+            [],
+        )
+        .unwrap();
+
+    db_pool
+        .get()
+        .unwrap()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS recording_access(
+                recording_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                PRIMARY KEY (recording_id, user_id),
+                FOREIGN KEY (recording_id) REFERENCES recordings(recording_id),
                 FOREIGN KEY (user_id) REFERENCES users(user_id)
             )",
             [],
         )
         .unwrap();
 
+    db_pool
+        .get()
+        .unwrap()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS recording_bookmarks(
+                bookmark_id INTEGER PRIMARY KEY,
+                recording_id INTEGER NOT NULL,
+                time_ms INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                username TEXT NOT NULL,
+                FOREIGN KEY (recording_id) REFERENCES recordings(recording_id)
+            )",
+            [],
+        )
+        .unwrap();
+
+    db_pool
+        .get()
+        .unwrap()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS download_usage(
+                user_id INTEGER NOT NULL,
+                day TEXT NOT NULL,
+                bytes INTEGER NOT NULL,
+                PRIMARY KEY (user_id, day),
+                FOREIGN KEY (user_id) REFERENCES users(user_id)
+            )",
+            [],
+        )
+        .unwrap();
+
+    // databases from before recordings had titles and descriptions (fails if they exist)
+    for column in ["title", "description"] {
+        let _ = db_pool.get().unwrap().execute(
+            &format!("ALTER TABLE recordings ADD COLUMN {column} TEXT NOT NULL DEFAULT ''"),
+            [],
+        );
+    }
+
+    // databases from before clips were linked to their recordings (fails if it exists)
+    let _ = db_pool.get().unwrap().execute(
+        "ALTER TABLE recordings ADD COLUMN parent_id INTEGER REFERENCES recordings(recording_id)",
+        [],
+    );
+
+    // databases from before recordings were listed while in progress (fails if it exists)
+    let _ = db_pool.get().unwrap().execute(
+        "ALTER TABLE recordings ADD COLUMN in_progress INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // databases from before recordings expired (fails if it exists)
+    let _ = db_pool.get().unwrap().execute(
+        "ALTER TABLE recordings ADD COLUMN retention_days INTEGER",
+        [],
+    );
+
+    // databases from before users had storage quotas (fails if it exists)
+    let _ = db_pool
+        .get()
+        .unwrap()
+        .execute("ALTER TABLE users ADD COLUMN storage_quota INTEGER", []);
+
+    recover_recordings(&db_pool);
+    thread_delete_expired(db_pool.clone());
+
     let listener = TcpListener::bind("0.0.0.0:7643").expect("Could not bind listener");
 
     let sessions: SessionHashMap = Arc::new(Mutex::new(HashMap::new()));
@@ -0,0 +1,2363 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use crate::{get_video_path, recording::RecordingClock};
+
+/// The timescale of the recorded video track, in ticks per second.
+const TIMESCALE: u32 = 1000;
+
+/// The ID of the video track in the recordings written by the server.
+const TRACK_ID: u32 = 1;
+
+/// The content of the `ftyp` box of the files written by the server.
+const FILE_TYPE: &[u8] = b"isom\x00\x00\x02\x00isomiso6avc1mp41";
+
+/// The sample rate Opus audio is timed in, and the ticks of it in a millisecond.
+const OPUS_SAMPLE_RATE: u32 = 48_000;
+const OPUS_TICKS_PER_MS: u64 = 48;
+
+/// How much audio before a clip is decoded with it, so the decoder has settled on the
+/// sound by the time the clip starts.
+const OPUS_PRE_ROLL_MS: u64 = 80;
+
+/// How long the last frame of a recording is shown, as no frame follows it to end it.
+const LAST_FRAME_MS: u32 = 33;
+
+/// The flags of a keyframe and of a frame that depends on others in a track fragment.
+const KEYFRAME_FLAGS: u32 = 0x0200_0000;
+const NON_KEYFRAME_FLAGS: u32 = 0x0101_0000;
+
+/// The NAL unit types the muxer looks at.
+pub const NAL_IDR: u8 = 5;
+const NAL_SEI: u8 = 6;
+pub const NAL_SPS: u8 = 7;
+pub const NAL_PPS: u8 = 8;
+pub const NAL_AUD: u8 = 9;
+
+/// The access unit delimiter sent before every frame, which tells the decoder a frame starts.
+pub const ACCESS_UNIT_DELIMITER: [u8; 5] = [0x00, 0x00, 0x01, NAL_AUD, 0xF0];
+
+/// The profiles whose SPS has the chroma format and the scaling matrices.
+const HIGH_PROFILES: [u8; 12] = [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134];
+
+/// The identity matrix of the movie and track headers.
+const MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// Gets the type of a NAL unit.
+///
+/// # Arguments
+///
+/// * `nal` - The NAL unit, without the start code.
+pub fn nal_type(nal: &[u8]) -> u8 {
+    nal.first().map_or(0, |header| header & 0x1F)
+}
+
+/// Checks whether a NAL unit is a slice of a frame.
+///
+/// # Arguments
+///
+/// * `nal` - The NAL unit, without the start code.
+fn is_slice(nal: &[u8]) -> bool {
+    (1..=NAL_IDR).contains(&nal_type(nal))
+}
+
+/// Removes the Annex B start code from the front of a NAL unit.
+///
+/// # Arguments
+///
+/// * `bytes` - The NAL unit, with or without its start code.
+pub fn strip_start_code(bytes: &[u8]) -> &[u8] {
+    let zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+    if zeros >= 2 && bytes.get(zeros) == Some(&1) {
+        &bytes[zeros + 1..]
+    } else {
+        bytes
+    }
+}
+
+/// Reads the bits of a NAL unit's payload, with the emulation prevention bytes removed.
+struct BitReader {
+    bytes: Vec<u8>,
+    position: usize,
+}
+
+impl BitReader {
+    /// Creates a reader of the payload of a NAL unit, after its header.
+    ///
+    /// # Arguments
+    ///
+    /// * `nal` - The NAL unit, without the start code.
+    fn new(nal: &[u8]) -> Self {
+        let mut bytes = Vec::with_capacity(nal.len());
+        let mut zeros = 0;
+
+        for &byte in nal.iter().skip(1) {
+            // 00 00 03 is written wherever the payload has 00 00 0x
+            if zeros >= 2 && byte == 3 {
+                zeros = 0;
+                continue;
+            }
+
+            zeros = if byte == 0 { zeros + 1 } else { 0 };
+            bytes.push(byte);
+        }
+
+        Self { bytes, position: 0 }
+    }
+
+    fn bit(&mut self) -> Option<u32> {
+        let byte = self.bytes.get(self.position / 8)?;
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+
+        Some(bit as u32)
+    }
+
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        (0..count).try_fold(0, |value, _| Some(value << 1 | self.bit()?))
+    }
+
+    /// Reads an unsigned Exp-Golomb code.
+    fn unsigned(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+        while self.bit()? == 0 {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return None;
+            }
+        }
+
+        Some((1 << leading_zeros) - 1 + self.bits(leading_zeros)?)
+    }
+
+    /// Reads a signed Exp-Golomb code.
+    fn signed(&mut self) -> Option<i32> {
+        let code = self.unsigned()? as i64;
+        let value = if code % 2 == 0 {
+            -(code / 2)
+        } else {
+            (code + 1) / 2
+        };
+
+        Some(value as i32)
+    }
+}
+
+/// Reads the size of the frames from a sequence parameter set.
+///
+/// # Arguments
+///
+/// * `sps` - The SPS NAL unit, without the start code.
+///
+/// # Returns
+///
+/// The width and height of the frames, or `None` if the SPS is malformed.
+fn sps_dimensions(sps: &[u8]) -> Option<(u32, u32)> {
+    let mut reader = BitReader::new(sps);
+
+    let profile = reader.bits(8)? as u8;
+    reader.bits(16)?; // constraint flags and level
+    reader.unsigned()?; // seq_parameter_set_id
+
+    let mut chroma_format = 1;
+    if HIGH_PROFILES.contains(&profile) {
+        chroma_format = reader.unsigned()?;
+        if chroma_format == 3 {
+            reader.bit()?; // separate_colour_plane_flag
+        }
+        reader.unsigned()?; // bit_depth_luma_minus8
+        reader.unsigned()?; // bit_depth_chroma_minus8
+        reader.bit()?; // qpprime_y_zero_transform_bypass_flag
+
+        if reader.bit()? == 1 {
+            let lists = if chroma_format == 3 { 12 } else { 8 };
+            for list in 0..lists {
+                if reader.bit()? == 0 {
+                    continue;
+                }
+
+                let size = if list < 6 { 16 } else { 64 };
+                let mut last_scale = 8;
+                let mut next_scale = 8;
+                for _ in 0..size {
+                    if next_scale != 0 {
+                        next_scale = (last_scale + reader.signed()? + 256) % 256;
+                    }
+                    if next_scale != 0 {
+                        last_scale = next_scale;
+                    }
+                }
+            }
+        }
+    }
+
+    reader.unsigned()?; // log2_max_frame_num_minus4
+    match reader.unsigned()? {
+        0 => {
+            reader.unsigned()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            reader.bit()?; // delta_pic_order_always_zero_flag
+            reader.signed()?; // offset_for_non_ref_pic
+            reader.signed()?; // offset_for_top_to_bottom_field
+            for _ in 0..reader.unsigned()? {
+                reader.signed()?; // offset_for_ref_frame
+            }
+        }
+        _ => (),
+    }
+    reader.unsigned()?; // max_num_ref_frames
+    reader.bit()?; // gaps_in_frame_num_value_allowed_flag
+
+    let width_in_macroblocks = reader.unsigned()? + 1;
+    let height_in_map_units = reader.unsigned()? + 1;
+    let frame_mbs_only = reader.bit()?;
+    if frame_mbs_only == 0 {
+        reader.bit()?; // mb_adaptive_frame_field_flag
+    }
+    reader.bit()?; // direct_8x8_inference_flag
+
+    let mut width = width_in_macroblocks * 16;
+    let mut height = (2 - frame_mbs_only) * height_in_map_units * 16;
+
+    if reader.bit()? == 1 {
+        let (crop_x, crop_y) = match chroma_format {
+            0 => (1, 2 - frame_mbs_only),
+            1 => (2, 2 * (2 - frame_mbs_only)),
+            2 => (2, 2 - frame_mbs_only),
+            _ => (1, 2 - frame_mbs_only),
+        };
+
+        let left = reader.unsigned()?;
+        let right = reader.unsigned()?;
+        let top = reader.unsigned()?;
+        let bottom = reader.unsigned()?;
+
+        width = width.checked_sub(crop_x * (left + right))?;
+        height = height.checked_sub(crop_y * (top + bottom))?;
+    }
+
+    Some((width, height))
+}
+
+/// Writes numbers as big-endian 32 bit fields.
+///
+/// # Arguments
+///
+/// * `values` - The numbers to write.
+fn u32_fields(values: &[u32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect()
+}
+
+/// Builds an MP4 box from its type and its content.
+///
+/// # Arguments
+///
+/// * `kind` - The four character type of the box.
+/// * `content` - The content of the box.
+fn mp4_box(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(8 + content.len());
+    bytes.extend_from_slice(&(8 + content.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(kind);
+    bytes.extend_from_slice(content);
+    bytes
+}
+
+/// Builds an MP4 box that starts with a version and flags.
+///
+/// # Arguments
+///
+/// * `kind` - The four character type of the box.
+/// * `version` - The version of the box.
+/// * `flags` - The 24 bit flags of the box.
+/// * `content` - The content of the box after the version and flags.
+fn full_box(kind: &[u8; 4], version: u8, flags: u32, content: &[u8]) -> Vec<u8> {
+    let mut bytes = ((version as u32) << 24 | flags).to_be_bytes().to_vec();
+    bytes.extend_from_slice(content);
+    mp4_box(kind, &bytes)
+}
+
+/// Builds the sample entry of an H.264 video track, with its decoder configuration.
+///
+/// The entry is an `avc3` one, as the encoder is restarted when the quality or the
+/// shared screen changes, and the parameter sets it starts with are only in the
+/// samples. The configuration has the first ones, and the size of the video is the
+/// size of the first SPS, which players scale the video to.
+///
+/// # Arguments
+///
+/// * `parameter_sets` - The SPS and PPS units of the video.
+/// * `length_size` - The size of the length before every NAL unit in a sample.
+fn avc_sample_entry(parameter_sets: &[Vec<u8>], length_size: usize) -> Vec<u8> {
+    let sps: Vec<&Vec<u8>> = parameter_sets
+        .iter()
+        .filter(|nal| nal_type(nal) == NAL_SPS && nal.len() >= 4)
+        .collect();
+    let pps: Vec<&Vec<u8>> = parameter_sets
+        .iter()
+        .filter(|nal| nal_type(nal) == NAL_PPS)
+        .collect();
+    let first_sps = sps.first().map_or(&[0; 4][..], |sps| sps.as_slice());
+    let (width, height) = sps_dimensions(first_sps).unwrap_or((0, 0));
+
+    let mut avcc = vec![
+        1,
+        first_sps[1],
+        first_sps[2],
+        first_sps[3],
+        0xFC | (length_size as u8 - 1),
+        0xE0 | sps.len() as u8,
+    ];
+    for sps in &sps {
+        avcc.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(sps);
+    }
+    avcc.push(pps.len() as u8);
+    for pps in &pps {
+        avcc.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(pps);
+    }
+
+    let mut avc3 = vec![0; 6];
+    avc3.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+    avc3.extend_from_slice(&[0; 16]);
+    avc3.extend_from_slice(&(width as u16).to_be_bytes());
+    avc3.extend_from_slice(&(height as u16).to_be_bytes());
+    avc3.extend(u32_fields(&[0x0048_0000, 0x0048_0000, 0])); // 72 dpi
+    avc3.extend_from_slice(&1u16.to_be_bytes()); // frame count
+    avc3.extend_from_slice(&[0; 32]); // compressor name
+    avc3.extend_from_slice(&[0x00, 0x18, 0xFF, 0xFF]); // depth, pre defined
+    avc3.extend(mp4_box(b"avcC", &avcc));
+
+    mp4_box(b"avc3", &avc3)
+}
+
+/// What a track of a movie is made of, to build its `trak` box from.
+struct TrackBoxes {
+    id: u32,
+    /// `vide` or `soun`.
+    handler: [u8; 4],
+    /// The width and height of the video, or 0 for audio.
+    width: u32,
+    height: u32,
+    /// The length of the track in the movie, in milliseconds.
+    duration_ms: u64,
+    /// The ticks per second of the media.
+    timescale: u32,
+    /// The length of the media in its own ticks.
+    media_duration: u64,
+    /// The sample entry that describes the media.
+    sample_entry: Vec<u8>,
+    /// The boxes of the sample table after the `stsd` box.
+    sample_table: Vec<u8>,
+    /// The `edts` box, or nothing if the media starts with the movie.
+    edits: Vec<u8>,
+}
+
+impl TrackBoxes {
+    /// Builds the `trak` box of the track.
+    fn build(&self) -> Vec<u8> {
+        let is_video = &self.handler == b"vide";
+
+        let mut tkhd = u32_fields(&[0, 0, self.id, 0, self.duration_ms as u32, 0, 0]);
+        tkhd.extend_from_slice(&[0; 4]); // layer, alternate group
+        tkhd.extend_from_slice(&if is_video { [0; 4] } else { [1, 0, 0, 0] }); // volume
+        tkhd.extend(u32_fields(&MATRIX));
+        tkhd.extend(u32_fields(&[self.width << 16, self.height << 16]));
+
+        let mut mdhd = u32_fields(&[0, 0, self.timescale, self.media_duration as u32]);
+        mdhd.extend_from_slice(&[0x55, 0xC4, 0, 0]); // "und" language
+
+        let mut hdlr = u32_fields(&[0]);
+        hdlr.extend_from_slice(&self.handler);
+        hdlr.extend(u32_fields(&[0, 0, 0]));
+        hdlr.extend_from_slice(if is_video {
+            b"VideoHandler\0"
+        } else {
+            b"SoundHandler\0"
+        });
+
+        let stsd = [u32_fields(&[1]), self.sample_entry.clone()].concat();
+        let stbl = [full_box(b"stsd", 0, 0, &stsd), self.sample_table.clone()].concat();
+
+        let dref = full_box(
+            b"dref",
+            0,
+            0,
+            &[u32_fields(&[1]), full_box(b"url ", 0, 1, &[])].concat(),
+        );
+        let media_header = if is_video {
+            full_box(b"vmhd", 0, 1, &[0; 8])
+        } else {
+            full_box(b"smhd", 0, 0, &[0; 4])
+        };
+        let minf = [
+            media_header,
+            mp4_box(b"dinf", &dref),
+            mp4_box(b"stbl", &stbl),
+        ]
+        .concat();
+
+        let mdia = [
+            full_box(b"mdhd", 0, 0, &mdhd),
+            full_box(b"hdlr", 0, 0, &hdlr),
+            mp4_box(b"minf", &minf),
+        ]
+        .concat();
+
+        let trak = [
+            full_box(b"tkhd", 0, 3, &tkhd),
+            self.edits.clone(),
+            mp4_box(b"mdia", &mdia),
+        ]
+        .concat();
+        mp4_box(b"trak", &trak)
+    }
+}
+
+/// Builds the `mvhd` box of a movie.
+///
+/// # Arguments
+///
+/// * `duration_ms` - The length of the movie in milliseconds.
+/// * `track_count` - The number of tracks in the movie.
+fn movie_header(duration_ms: u64, track_count: u32) -> Vec<u8> {
+    let mut mvhd = u32_fields(&[0, 0, TIMESCALE, duration_ms as u32, 0x0001_0000]);
+    mvhd.extend_from_slice(&0x0100u16.to_be_bytes()); // volume
+    mvhd.extend_from_slice(&[0; 10]);
+    mvhd.extend(u32_fields(&MATRIX));
+    mvhd.extend_from_slice(&[0; 24]);
+    mvhd.extend(u32_fields(&[track_count + 1]));
+
+    full_box(b"mvhd", 0, 0, &mvhd)
+}
+
+/// Builds the `ftyp` and `moov` boxes that start a fragmented recording.
+///
+/// The movie has no samples of its own: every frame is in a fragment after it. The
+/// frames are timed from the start of the recording, so the time before the first
+/// one is an empty edit.
+///
+/// # Arguments
+///
+/// * `sps` - The sequence parameter set of the video.
+/// * `pps` - The picture parameter set of the video.
+/// * `first_ms` - The time of the first frame in the recording, in milliseconds.
+fn file_header(sps: &[u8], pps: &[u8], first_ms: u64) -> Vec<u8> {
+    let (width, height) = sps_dimensions(sps).unwrap_or((0, 0));
+
+    let ftyp = mp4_box(b"ftyp", FILE_TYPE);
+
+    let track = TrackBoxes {
+        id: TRACK_ID,
+        handler: *b"vide",
+        width,
+        height,
+        duration_ms: 0,
+        timescale: TIMESCALE,
+        media_duration: 0,
+        sample_entry: avc_sample_entry(&[sps.to_vec(), pps.to_vec()], 4),
+        sample_table: [
+            full_box(b"stts", 0, 0, &u32_fields(&[0])),
+            full_box(b"stsc", 0, 0, &u32_fields(&[0])),
+            full_box(b"stsz", 0, 0, &u32_fields(&[0, 0])),
+            full_box(b"stco", 0, 0, &u32_fields(&[0])),
+        ]
+        .concat(),
+        // the length of a fragmented movie isn't known, so its edit lasts to the end
+        edits: if first_ms > 0 {
+            edit_list(first_ms, 0, first_ms as u32)
+        } else {
+            Vec::new()
+        },
+    };
+    let trex = full_box(b"trex", 0, 0, &u32_fields(&[TRACK_ID, 1, 0, 0, 0]));
+
+    let moov = [movie_header(0, 1), track.build(), mp4_box(b"mvex", &trex)].concat();
+
+    [ftyp, mp4_box(b"moov", &moov)].concat()
+}
+
+/// A frame of the recording: the NAL units of one access unit.
+struct RecordedFrame {
+    /// The NAL units, without start codes.
+    nals: Vec<Vec<u8>>,
+    /// When the frame arrived, in milliseconds since the recording started.
+    time_ms: u64,
+    keyframe: bool,
+}
+
+/// Builds the fragment of a single frame: its `moof` and `mdat` boxes.
+///
+/// # Arguments
+///
+/// * `sequence` - The number of the fragment, starting from 1.
+/// * `decode_time` - The time of the frame in the recording, in milliseconds.
+/// * `duration` - How long the frame is shown, in milliseconds.
+/// * `frame` - The frame.
+fn fragment(sequence: u32, decode_time: u64, duration: u32, frame: &RecordedFrame) -> Vec<u8> {
+    // the NAL units are stored with their lengths instead of start codes
+    let data: Vec<u8> = frame
+        .nals
+        .iter()
+        .flat_map(|nal| [&(nal.len() as u32).to_be_bytes()[..], nal].concat())
+        .collect();
+
+    let flags = if frame.keyframe {
+        KEYFRAME_FLAGS
+    } else {
+        NON_KEYFRAME_FLAGS
+    };
+
+    let moof = |data_offset: u32| {
+        let traf = [
+            full_box(b"tfhd", 0, 0x02_0000, &u32_fields(&[TRACK_ID])),
+            full_box(b"tfdt", 1, 0, &decode_time.to_be_bytes()),
+            full_box(
+                b"trun",
+                0,
+                0x0701,
+                &u32_fields(&[1, data_offset, duration, data.len() as u32, flags]),
+            ),
+        ]
+        .concat();
+
+        let content = [
+            full_box(b"mfhd", 0, 0, &u32_fields(&[sequence])),
+            mp4_box(b"traf", &traf),
+        ]
+        .concat();
+        mp4_box(b"moof", &content)
+    };
+
+    // the data starts after the moof and the header of the mdat
+    let moof_size = moof(0).len() as u32;
+
+    [moof(moof_size + 8), mp4_box(b"mdat", &data)].concat()
+}
+
+/// Writes the H.264 stream of a session into a fragmented MP4 as it arrives.
+///
+/// Every frame is written in a fragment of its own once the next frame arrives, so
+/// the file is playable up to the last complete fragment if the server stops. The
+/// frames are timestamped as they arrive, by a clock started along with the ones of
+/// the audio and the events, so the time before the first keyframe stays in the
+/// recording. The time the recording was paused for is left out.
+pub struct Mp4Writer {
+    /// The video file, unless it couldn't be created or written.
+    file: Option<File>,
+    /// The time into the recording, which the frames are timestamped with.
+    clock: RecordingClock,
+    /// The first parameter sets of the stream, which describe the video in the header.
+    /// Every parameter set stays in its frame too, as the encoder may change them.
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+    /// The frame whose NAL units are arriving.
+    current: Option<RecordedFrame>,
+    /// The last complete frame, written once the next one gives its duration.
+    previous: Option<RecordedFrame>,
+    /// Whether the header was written, along with the first frame.
+    is_started: bool,
+    /// The number of fragments written.
+    sequence: u32,
+    /// The size of the file written so far.
+    written_bytes: u64,
+}
+
+impl Mp4Writer {
+    /// Creates the video file of a recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The filename of the recording (without the extension).
+    pub fn create(filename: &str) -> Self {
+        Self {
+            file: File::create(get_video_path(filename)).ok(),
+            clock: RecordingClock::start(),
+            sps: None,
+            pps: None,
+            current: None,
+            previous: None,
+            is_started: false,
+            sequence: 0,
+            written_bytes: 0,
+        }
+    }
+
+    /// Gets the size of the video written so far.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes written to the file.
+    pub fn written_bytes(&self) -> u64 {
+        self.written_bytes
+    }
+
+    /// Pauses the recording, so the frames that arrive after it's resumed follow right
+    /// after the ones before the pause.
+    pub fn pause(&mut self) {
+        self.clock.pause();
+    }
+
+    /// Resumes the recording.
+    pub fn resume(&mut self) {
+        self.clock.resume();
+    }
+
+    /// Adds a NAL unit of the stream to the recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The NAL unit, with its Annex B start code.
+    pub fn push_nal(&mut self, bytes: &[u8]) {
+        let nal = strip_start_code(bytes);
+        if nal.is_empty() {
+            return;
+        }
+
+        // a frame ends where the units of the next one start, or at its first slice
+        let starts_frame = match nal_type(nal) {
+            NAL_SEI | NAL_SPS | NAL_PPS | NAL_AUD => true,
+            // first_mb_in_slice is 0
+            _ if is_slice(nal) => nal.get(1).is_some_and(|byte| byte & 0x80 != 0),
+            _ => false,
+        };
+        let has_slices = self
+            .current
+            .as_ref()
+            .is_some_and(|frame| frame.nals.iter().any(|nal| is_slice(nal)));
+        if starts_frame && has_slices {
+            self.end_frame();
+        }
+
+        match nal_type(nal) {
+            // the frames are separated by the fragments
+            NAL_AUD => return,
+            NAL_SPS if nal.len() >= 4 => {
+                self.sps.get_or_insert_with(|| nal.to_vec());
+            }
+            NAL_PPS => {
+                self.pps.get_or_insert_with(|| nal.to_vec());
+            }
+            _ => (),
+        }
+
+        let time_ms = self.clock.elapsed().as_millis() as u64;
+        let frame = self.current.get_or_insert_with(|| RecordedFrame {
+            nals: Vec::new(),
+            time_ms,
+            keyframe: false,
+        });
+        frame.keyframe |= nal_type(nal) == NAL_IDR;
+        frame.nals.push(nal.to_vec());
+    }
+
+    /// Ends the frame whose NAL units arrived, and writes the frame before it.
+    fn end_frame(&mut self) {
+        let Some(frame) = self.current.take() else {
+            return;
+        };
+
+        // the frames before the first keyframe can't be decoded
+        if self.previous.is_none() && !self.is_started && !frame.keyframe {
+            return;
+        }
+
+        if let Some(previous) = self.previous.take() {
+            let duration = frame.time_ms.saturating_sub(previous.time_ms).max(1);
+            self.write_fragment(&previous, duration as u32);
+        }
+
+        self.previous = Some(frame);
+    }
+
+    /// Writes a frame to the file, after the header if it's the first frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The frame.
+    /// * `duration` - How long the frame is shown, in milliseconds.
+    fn write_fragment(&mut self, frame: &RecordedFrame, duration: u32) {
+        let mut bytes = Vec::new();
+
+        if !self.is_started {
+            let (Some(sps), Some(pps)) = (&self.sps, &self.pps) else {
+                return;
+            };
+            bytes.extend(file_header(sps, pps, frame.time_ms));
+            self.is_started = true;
+        }
+
+        self.sequence += 1;
+        bytes.extend(fragment(self.sequence, frame.time_ms, duration, frame));
+
+        // a recording with a gap in it would be corrupted, so stop at the first error
+        let failed = self
+            .file
+            .as_mut()
+            .is_some_and(|file| file.write_all(&bytes).is_err());
+        if failed {
+            self.file = None;
+        } else if self.file.is_some() {
+            self.written_bytes += bytes.len() as u64;
+        }
+    }
+
+    /// Writes the frames that are left, at the end of the session.
+    pub fn finish(&mut self) {
+        self.end_frame();
+
+        if let Some(last) = self.previous.take() {
+            self.write_fragment(&last, LAST_FRAME_MS);
+        }
+
+        self.file = None;
+    }
+}
+
+/// Reads the fields of an MP4 box in order.
+struct FieldReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> FieldReader<'a> {
+    fn bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.data.len() < count {
+            return None;
+        }
+
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+}
+
+/// Splits the content of an MP4 box into its child boxes.
+///
+/// # Arguments
+///
+/// * `data` - The content of the box.
+///
+/// # Returns
+///
+/// The type and the content of every complete child box, in order.
+fn child_boxes(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut boxes = Vec::new();
+    let mut rest = data;
+
+    while rest.len() >= 8 {
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let (header_size, size) = match u32::from_be_bytes(rest[..4].try_into().unwrap()) {
+            0 => (8, rest.len()),
+            1 if rest.len() >= 16 => (
+                16,
+                u64::from_be_bytes(rest[8..16].try_into().unwrap()) as usize,
+            ),
+            size => (8, size as usize),
+        };
+
+        if size < header_size || size > rest.len() {
+            break;
+        }
+
+        boxes.push((kind, &rest[header_size..size]));
+        rest = &rest[size..];
+    }
+
+    boxes
+}
+
+/// Finds the first child box of a type.
+///
+/// # Arguments
+///
+/// * `data` - The content of the parent box.
+/// * `kind` - The type of the child box.
+fn find_box<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    child_boxes(data)
+        .into_iter()
+        .find(|(child_kind, _)| child_kind == kind)
+        .map(|(_, content)| content)
+}
+
+/// Reads a box that starts with a version and flags.
+///
+/// # Arguments
+///
+/// * `data` - The content of the box.
+///
+/// # Returns
+///
+/// The version, the flags and a reader of the fields after them.
+fn full_box_fields(data: &[u8]) -> Option<(u8, u32, FieldReader<'_>)> {
+    let mut reader = FieldReader { data };
+    let version_and_flags = reader.u32()?;
+
+    Some((
+        (version_and_flags >> 24) as u8,
+        version_and_flags & 0xFF_FFFF,
+        reader,
+    ))
+}
+
+/// A frame of the video track of a recording.
+pub struct Sample {
+    /// The presentation time of the frame in milliseconds.
+    pub time_ms: u64,
+    /// How long the frame is shown in milliseconds.
+    pub duration_ms: u64,
+    /// Where the frame is in the file.
+    offset: u64,
+    size: u32,
+    pub keyframe: bool,
+}
+
+/// A sample in the timescale of its track, before its time is known in milliseconds.
+struct TrackSample {
+    decode_time: u64,
+    duration: u32,
+    composition_offset: i32,
+    offset: u64,
+    size: u32,
+    keyframe: bool,
+}
+
+/// The defaults of the samples in the fragments of a track.
+#[derive(Default, Clone, Copy)]
+struct SampleDefaults {
+    duration: u32,
+    size: u32,
+    flags: u32,
+}
+
+/// The video track of an MP4 file, as read from its `moov` box.
+struct VideoTrack {
+    id: u32,
+    timescale: u32,
+    parameter_sets: Vec<Vec<u8>>,
+    length_size: usize,
+    defaults: SampleDefaults,
+    /// The samples in the movie itself, for files that aren't fragmented.
+    samples: Vec<TrackSample>,
+    /// The ticks to add to the decode times of the samples, from the edit list.
+    edit_shift: i64,
+}
+
+/// Reads the decoder configuration of an `avc1` or `avc3` sample entry.
+///
+/// # Arguments
+///
+/// * `entry` - The content of the sample entry.
+///
+/// # Returns
+///
+/// The SPS and PPS units, and the size of the length before every NAL unit.
+fn read_avc_config(entry: &[u8]) -> Option<(Vec<Vec<u8>>, usize)> {
+    // the avcC box follows the fields of the visual sample entry
+    let avcc = find_box(entry.get(78..)?, b"avcC")?;
+    let mut reader = FieldReader { data: avcc };
+
+    reader.bytes(4)?; // version, profile, compatibility, level
+    let length_size = (reader.u8()? & 0x03) as usize + 1;
+
+    let mut parameter_sets = Vec::new();
+    let sps_count = reader.u8()? & 0x1F;
+    for _ in 0..sps_count {
+        let size = reader.u16()? as usize;
+        parameter_sets.push(reader.bytes(size)?.to_vec());
+    }
+    let pps_count = reader.u8()?;
+    for _ in 0..pps_count {
+        let size = reader.u16()? as usize;
+        parameter_sets.push(reader.bytes(size)?.to_vec());
+    }
+
+    Some((parameter_sets, length_size))
+}
+
+/// Reads the samples of a track that aren't in fragments, from its sample table.
+///
+/// # Arguments
+///
+/// * `stbl` - The content of the `stbl` box of the track.
+fn read_sample_table(stbl: &[u8]) -> Option<Vec<TrackSample>> {
+    let (_, _, mut stsz) = full_box_fields(find_box(stbl, b"stsz")?)?;
+    let uniform_size = stsz.u32()?;
+    let count = stsz.u32()? as usize;
+    let sizes = (0..count)
+        .map(|_| match uniform_size {
+            0 => stsz.u32(),
+            size => Some(size),
+        })
+        .collect::<Option<Vec<u32>>>()?;
+
+    let mut durations = Vec::with_capacity(count);
+    let (_, _, mut stts) = full_box_fields(find_box(stbl, b"stts")?)?;
+    for _ in 0..stts.u32()? {
+        let (run, duration) = (stts.u32()?, stts.u32()?);
+        durations.extend(std::iter::repeat_n(duration, run as usize));
+    }
+
+    let mut composition_offsets = Vec::with_capacity(count);
+    if let Some((_, _, mut ctts)) = find_box(stbl, b"ctts").and_then(full_box_fields) {
+        for _ in 0..ctts.u32()? {
+            let (run, offset) = (ctts.u32()?, ctts.u32()? as i32);
+            composition_offsets.extend(std::iter::repeat_n(offset, run as usize));
+        }
+    }
+
+    // without a sync sample table every sample is a keyframe
+    let keyframes: Option<HashSet<u32>> = match find_box(stbl, b"stss") {
+        Some(stss) => {
+            let (_, _, mut stss) = full_box_fields(stss)?;
+            Some(
+                (0..stss.u32()?)
+                    .map(|_| stss.u32())
+                    .collect::<Option<_>>()?,
+            )
+        }
+        None => None,
+    };
+
+    let chunk_offsets = match (find_box(stbl, b"stco"), find_box(stbl, b"co64")) {
+        (Some(stco), _) => {
+            let (_, _, mut stco) = full_box_fields(stco)?;
+            (0..stco.u32()?)
+                .map(|_| stco.u32().map(u64::from))
+                .collect::<Option<Vec<u64>>>()?
+        }
+        (None, Some(co64)) => {
+            let (_, _, mut co64) = full_box_fields(co64)?;
+            (0..co64.u32()?)
+                .map(|_| co64.u64())
+                .collect::<Option<Vec<u64>>>()?
+        }
+        (None, None) => return None,
+    };
+
+    let (_, _, mut stsc) = full_box_fields(find_box(stbl, b"stsc")?)?;
+    let chunk_runs = (0..stsc.u32()?)
+        .map(|_| {
+            let first_chunk = stsc.u32()?;
+            let samples_per_chunk = stsc.u32()?;
+            stsc.u32()?; // sample description index
+            Some((first_chunk, samples_per_chunk))
+        })
+        .collect::<Option<Vec<(u32, u32)>>>()?;
+
+    let mut samples = Vec::with_capacity(count);
+    let mut decode_time = 0;
+
+    for (chunk, &chunk_offset) in chunk_offsets.iter().enumerate() {
+        let chunk_number = chunk as u32 + 1;
+        let samples_per_chunk = chunk_runs
+            .iter()
+            .take_while(|(first_chunk, _)| *first_chunk <= chunk_number)
+            .last()
+            .map_or(0, |(_, samples_per_chunk)| *samples_per_chunk);
+
+        let mut offset = chunk_offset;
+        for _ in 0..samples_per_chunk {
+            let index = samples.len();
+            let Some(&size) = sizes.get(index) else {
+                break;
+            };
+            let duration = durations.get(index).copied().unwrap_or(0);
+
+            samples.push(TrackSample {
+                decode_time,
+                duration,
+                composition_offset: composition_offsets.get(index).copied().unwrap_or(0),
+                offset,
+                size,
+                keyframe: keyframes
+                    .as_ref()
+                    .is_none_or(|keyframes| keyframes.contains(&(index as u32 + 1))),
+            });
+
+            decode_time += duration as u64;
+            offset += size as u64;
+        }
+    }
+
+    Some(samples)
+}
+
+/// Reads the timescale of a movie, which its edit lists are timed in.
+///
+/// # Arguments
+///
+/// * `moov` - The content of the `moov` box.
+fn movie_timescale(moov: &[u8]) -> Option<u32> {
+    let (version, _, mut mvhd) = full_box_fields(find_box(moov, b"mvhd")?)?;
+    mvhd.bytes(if version == 1 { 16 } else { 8 })?;
+    Some(mvhd.u32()?.max(1))
+}
+
+/// Reads the timescale of the media of a track.
+///
+/// # Arguments
+///
+/// * `trak` - The content of the `trak` box.
+fn media_timescale(trak: &[u8]) -> Option<u32> {
+    let (version, _, mut mdhd) = full_box_fields(find_box(find_box(trak, b"mdia")?, b"mdhd")?)?;
+    mdhd.bytes(if version == 1 { 16 } else { 8 })?;
+    Some(mdhd.u32()?.max(1))
+}
+
+/// Reads where the media of a track is shown in the movie, from its edit list.
+///
+/// Empty edits at the start delay the media, and the first edit of the media skips
+/// its start, like the pre-skip of an Opus track. The edits after it are ignored.
+///
+/// # Arguments
+///
+/// * `trak` - The content of the `trak` box.
+/// * `movie_timescale` - The timescale of the movie.
+/// * `timescale` - The timescale of the media of the track.
+///
+/// # Returns
+///
+/// The ticks of the media to add to the decode times of its samples, or `None` if
+/// the track has no edit list.
+fn read_edit_shift(trak: &[u8], movie_timescale: u32, timescale: u32) -> Option<i64> {
+    let elst = find_box(find_box(trak, b"edts")?, b"elst")?;
+    let (version, _, mut elst) = full_box_fields(elst)?;
+
+    let mut shift = 0;
+    for _ in 0..elst.u32()? {
+        let (segment_duration, media_time) = if version == 1 {
+            (elst.u64()?, elst.u64()? as i64)
+        } else {
+            (elst.u32()? as u64, elst.u32()? as i32 as i64)
+        };
+        elst.u32()?; // rate
+
+        if media_time != -1 {
+            return Some(shift - media_time);
+        }
+        shift += (segment_duration * timescale as u64 / movie_timescale as u64) as i64;
+    }
+
+    Some(shift)
+}
+
+/// Reads the first video track of a `moov` box.
+///
+/// # Arguments
+///
+/// * `moov` - The content of the `moov` box.
+fn read_video_track(moov: &[u8]) -> Option<VideoTrack> {
+    let trak = child_boxes(moov)
+        .into_iter()
+        .filter(|(kind, _)| kind == b"trak")
+        .map(|(_, trak)| trak)
+        .find(|trak| {
+            find_box(trak, b"mdia")
+                .and_then(|mdia| find_box(mdia, b"hdlr"))
+                .is_some_and(|hdlr| hdlr.get(8..12) == Some(b"vide"))
+        })?;
+
+    let (version, _, mut tkhd) = full_box_fields(find_box(trak, b"tkhd")?)?;
+    tkhd.bytes(if version == 1 { 16 } else { 8 })?;
+    let id = tkhd.u32()?;
+
+    let mdia = find_box(trak, b"mdia")?;
+    let timescale = media_timescale(trak)?;
+    let movie_timescale = movie_timescale(moov).unwrap_or(TIMESCALE);
+
+    let stbl = find_box(find_box(mdia, b"minf")?, b"stbl")?;
+    let (_, _, stsd) = full_box_fields(find_box(stbl, b"stsd")?)?;
+    let (kind, entry) = *child_boxes(stsd.data.get(4..)?).first()?;
+    if kind != *b"avc1" && kind != *b"avc3" {
+        return None;
+    }
+    let (parameter_sets, length_size) = read_avc_config(entry)?;
+
+    let defaults = find_box(moov, b"mvex")
+        .map(|mvex| child_boxes(mvex))
+        .into_iter()
+        .flatten()
+        .filter(|(kind, _)| kind == b"trex")
+        .find_map(|(_, trex)| {
+            let (_, _, mut trex) = full_box_fields(trex)?;
+            if trex.u32()? != id {
+                return None;
+            }
+            trex.u32()?; // sample description index
+
+            Some(SampleDefaults {
+                duration: trex.u32()?,
+                size: trex.u32()?,
+                flags: trex.u32()?,
+            })
+        })
+        .unwrap_or_default();
+
+    Some(VideoTrack {
+        id,
+        timescale,
+        parameter_sets,
+        length_size,
+        defaults,
+        samples: read_sample_table(stbl).unwrap_or_default(),
+        edit_shift: read_edit_shift(trak, movie_timescale, timescale).unwrap_or(0),
+    })
+}
+
+/// Reads the samples of the video track in a `moof` box.
+///
+/// # Arguments
+///
+/// * `moof` - The content of the `moof` box.
+/// * `moof_offset` - Where the `moof` box starts in the file.
+/// * `track` - The video track.
+/// * `next_decode_time` - The decode time after the last sample so far, used if the
+///   fragment doesn't have its own.
+///
+/// # Returns
+///
+/// The samples, or `None` if the fragment is malformed.
+fn read_fragment(
+    moof: &[u8],
+    moof_offset: u64,
+    track: &VideoTrack,
+    next_decode_time: u64,
+) -> Option<Vec<TrackSample>> {
+    let mut samples = Vec::new();
+
+    for (_, traf) in child_boxes(moof)
+        .into_iter()
+        .filter(|(kind, _)| kind == b"traf")
+    {
+        let (_, tfhd_flags, mut tfhd) = full_box_fields(find_box(traf, b"tfhd")?)?;
+        if tfhd.u32()? != track.id {
+            continue;
+        }
+
+        let mut defaults = track.defaults;
+        let base_offset = if tfhd_flags & 0x01 != 0 {
+            tfhd.u64()?
+        } else {
+            moof_offset
+        };
+        if tfhd_flags & 0x02 != 0 {
+            tfhd.u32()?; // sample description index
+        }
+        if tfhd_flags & 0x08 != 0 {
+            defaults.duration = tfhd.u32()?;
+        }
+        if tfhd_flags & 0x10 != 0 {
+            defaults.size = tfhd.u32()?;
+        }
+        if tfhd_flags & 0x20 != 0 {
+            defaults.flags = tfhd.u32()?;
+        }
+
+        let mut decode_time = match find_box(traf, b"tfdt").and_then(full_box_fields) {
+            Some((1, _, mut tfdt)) => tfdt.u64()?,
+            Some((_, _, mut tfdt)) => tfdt.u32()? as u64,
+            None => samples
+                .last()
+                .map_or(next_decode_time, |sample: &TrackSample| {
+                    sample.decode_time + sample.duration as u64
+                }),
+        };
+        let mut offset = base_offset;
+
+        for (_, trun) in child_boxes(traf)
+            .into_iter()
+            .filter(|(kind, _)| kind == b"trun")
+        {
+            let (_, flags, mut trun) = full_box_fields(trun)?;
+            let count = trun.u32()?;
+            if flags & 0x01 != 0 {
+                offset = base_offset.checked_add_signed(trun.u32()? as i32 as i64)?;
+            }
+            let first_flags = if flags & 0x04 != 0 {
+                Some(trun.u32()?)
+            } else {
+                None
+            };
+
+            for index in 0..count {
+                let duration = if flags & 0x100 != 0 {
+                    trun.u32()?
+                } else {
+                    defaults.duration
+                };
+                let size = if flags & 0x200 != 0 {
+                    trun.u32()?
+                } else {
+                    defaults.size
+                };
+                let sample_flags = if flags & 0x400 != 0 {
+                    trun.u32()?
+                } else {
+                    first_flags.filter(|_| index == 0).unwrap_or(defaults.flags)
+                };
+                let composition_offset = if flags & 0x800 != 0 {
+                    trun.u32()? as i32
+                } else {
+                    0
+                };
+
+                samples.push(TrackSample {
+                    decode_time,
+                    duration,
+                    composition_offset,
+                    offset,
+                    size,
+                    // sample_is_non_sync_sample
+                    keyframe: sample_flags & 0x0001_0000 == 0,
+                });
+
+                decode_time += duration as u64;
+                offset += size as u64;
+            }
+        }
+    }
+
+    Some(samples)
+}
+
+/// The boxes of an MP4 file that describe its media, read without the media data.
+struct MovieBoxes {
+    /// The content of the `moov` box.
+    moov: Vec<u8>,
+    /// The content of every `moof` box, with where the box starts in the file.
+    fragments: Vec<(u64, Vec<u8>)>,
+    /// The size of the file up to the end of its last complete box.
+    complete_len: u64,
+}
+
+impl MovieBoxes {
+    /// Reads the boxes of an MP4 file, up to its last complete box.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The MP4 file.
+    ///
+    /// # Returns
+    ///
+    /// The boxes, or `None` if the file can't be read or has no `moov` box.
+    fn read(file: &mut File) -> Option<Self> {
+        let file_len = file.metadata().ok()?.len();
+
+        let mut moov = None;
+        let mut fragments = Vec::new();
+        let mut position = 0;
+
+        // only the moov and moof boxes are read, the media data is skipped
+        while position + 8 <= file_len {
+            let mut header = [0; 16];
+            file.seek(SeekFrom::Start(position)).ok()?;
+            file.read_exact(&mut header[..8]).ok()?;
+
+            let kind: [u8; 4] = header[4..8].try_into().unwrap();
+            let (header_size, size) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+                0 => (8, file_len - position),
+                1 => {
+                    file.read_exact(&mut header[8..]).ok()?;
+                    (16, u64::from_be_bytes(header[8..].try_into().unwrap()))
+                }
+                size => (8, size as u64),
+            };
+
+            if size < header_size || position + size > file_len {
+                break;
+            }
+
+            if &kind == b"moov" || &kind == b"moof" {
+                let mut content = vec![0; (size - header_size) as usize];
+                file.read_exact(&mut content).ok()?;
+
+                if &kind == b"moov" {
+                    moov = Some(content);
+                } else {
+                    fragments.push((position, content));
+                }
+            }
+
+            position += size;
+        }
+
+        Some(Self {
+            moov: moov?,
+            fragments,
+            complete_len: position,
+        })
+    }
+}
+
+/// The frames of the video track of a recording, read from the boxes of its file.
+///
+/// Both regular and fragmented MP4 files are read, so recordings processed by other
+/// tools play too. A file that was cut off is read up to its last complete box.
+#[derive(Default)]
+pub struct VideoIndex {
+    /// The frames in decoding order.
+    pub samples: Vec<Sample>,
+    /// The SPS and PPS of the track, which the decoder needs before the first frame.
+    parameter_sets: Vec<Vec<u8>>,
+    /// The size of the length before every NAL unit in a sample.
+    length_size: usize,
+    /// The size of the file up to the end of its last complete box.
+    complete_len: u64,
+}
+
+impl VideoIndex {
+    /// Reads the index of a recording's video.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The filename of the recording (without the extension).
+    ///
+    /// # Returns
+    ///
+    /// The index, or `None` if the file can't be read or has no H.264 video track.
+    pub fn read(filename: &str) -> Option<Self> {
+        let mut file = File::open(get_video_path(filename)).ok()?;
+        let MovieBoxes {
+            moov,
+            fragments,
+            complete_len,
+        } = MovieBoxes::read(&mut file)?;
+
+        let mut track = read_video_track(&moov)?;
+
+        for (moof_offset, moof) in fragments {
+            let next_decode_time = track
+                .samples
+                .last()
+                .map_or(0, |sample| sample.decode_time + sample.duration as u64);
+
+            match read_fragment(&moof, moof_offset, &track, next_decode_time) {
+                Some(samples) => track.samples.extend(samples),
+                None => break,
+            }
+        }
+
+        let to_ms = |ticks: i64| (ticks.max(0) as u64) * 1000 / track.timescale as u64;
+        let samples = track
+            .samples
+            .iter()
+            .filter(|sample| sample.offset + sample.size as u64 <= complete_len)
+            .map(|sample| Sample {
+                time_ms: to_ms(
+                    sample.decode_time as i64 + sample.composition_offset as i64 + track.edit_shift,
+                ),
+                duration_ms: to_ms(sample.duration as i64),
+                offset: sample.offset,
+                size: sample.size,
+                keyframe: sample.keyframe,
+            })
+            .collect();
+
+        Some(Self {
+            samples,
+            parameter_sets: track.parameter_sets,
+            length_size: track.length_size,
+            complete_len,
+        })
+    }
+
+    /// Gets the duration of the video.
+    ///
+    /// # Returns
+    ///
+    /// The end of the last frame in milliseconds, or 0 if there are no frames.
+    pub fn duration_ms(&self) -> u64 {
+        self.samples
+            .iter()
+            .map(|sample| sample.time_ms + sample.duration_ms)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Gets the size of the file up to the end of its last complete box, where a file
+    /// that was cut off can be truncated to.
+    pub fn complete_len(&self) -> u64 {
+        self.complete_len
+    }
+
+    /// Finds the frame decoding has to start from to show a given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_ms` - The wanted time in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// The index of the last keyframe at or before `time_ms`, or 0 if there is none.
+    pub fn keyframe_before(&self, time_ms: u64) -> usize {
+        self.samples
+            .iter()
+            .rposition(|sample| sample.keyframe && sample.time_ms <= time_ms)
+            .unwrap_or(0)
+    }
+
+    /// Creates the NAL units the decoder needs before the first frame.
+    ///
+    /// # Returns
+    ///
+    /// The SPS and PPS units of the video, with Annex B start codes.
+    pub fn parameter_set_nals(&self) -> Vec<Vec<u8>> {
+        self.parameter_sets
+            .iter()
+            .map(|nal| [&[0, 0, 1], nal.as_slice()].concat())
+            .collect()
+    }
+
+    /// Reads the NAL units of a frame from the video file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The video file.
+    /// * `sample` - The frame, from this index.
+    ///
+    /// # Returns
+    ///
+    /// The NAL units of the frame with Annex B start codes, without access unit delimiters.
+    pub fn read_nals(&self, file: &mut File, sample: &Sample) -> io::Result<Vec<Vec<u8>>> {
+        let mut data = vec![0; sample.size as usize];
+        file.seek(SeekFrom::Start(sample.offset))?;
+        file.read_exact(&mut data)?;
+
+        let mut nals = Vec::new();
+        let mut rest = data.as_slice();
+
+        while rest.len() >= self.length_size {
+            let (length, after) = rest.split_at(self.length_size);
+            let length = length
+                .iter()
+                .fold(0, |length, &byte| length << 8 | byte as usize);
+            if length > after.len() {
+                break;
+            }
+
+            let (nal, after) = after.split_at(length);
+            if nal_type(nal) != NAL_AUD {
+                nals.push([&[0, 0, 1], nal].concat());
+            }
+            rest = after;
+        }
+
+        Ok(nals)
+    }
+}
+
+/// A chapter of a movie, which players list to jump to.
+pub struct Chapter {
+    /// When the chapter starts, in milliseconds.
+    pub start_ms: u64,
+    pub title: String,
+}
+
+/// The decoder configuration of an Opus stream, as in its `OpusHead` packet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpusConfig {
+    pub channels: u8,
+    /// The number of samples to drop from the start of the decoded audio.
+    pub pre_skip: u16,
+    /// The sample rate of the audio before it was encoded, only for information.
+    pub input_sample_rate: u32,
+    /// The gain to apply to the decoded audio, in 1/256 dB.
+    pub output_gain: i16,
+    /// The channel mapping family, followed by its mapping table unless it's 0.
+    pub channel_mapping: Vec<u8>,
+}
+
+impl OpusConfig {
+    /// Reads the configuration from the `OpusHead` packet of an Ogg Opus stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `head` - The `OpusHead` packet.
+    ///
+    /// # Returns
+    ///
+    /// The configuration, or `None` if the packet isn't an `OpusHead`.
+    pub fn from_opus_head(head: &[u8]) -> Option<Self> {
+        if !head.starts_with(b"OpusHead") || head.len() < 19 {
+            return None;
+        }
+
+        let channels = head[9];
+        let channel_mapping = match head[18] {
+            0 => vec![0],
+            // the stream counts and a mapping for every channel follow the family
+            _ => head.get(18..21 + channels as usize)?.to_vec(),
+        };
+
+        Some(Self {
+            channels,
+            pre_skip: u16::from_le_bytes([head[10], head[11]]),
+            input_sample_rate: u32::from_le_bytes(head[12..16].try_into().unwrap()),
+            output_gain: i16::from_le_bytes([head[16], head[17]]),
+            channel_mapping,
+        })
+    }
+
+    /// Reads the configuration from the `dOps` box of an Opus track.
+    ///
+    /// # Arguments
+    ///
+    /// * `dops` - The content of the `dOps` box.
+    fn from_dops(dops: &[u8]) -> Option<Self> {
+        let mut reader = FieldReader { data: dops };
+        reader.u8()?; // version
+
+        Some(Self {
+            channels: reader.u8()?,
+            pre_skip: reader.u16()?,
+            input_sample_rate: reader.u32()?,
+            output_gain: reader.u16()? as i16,
+            channel_mapping: reader.data.to_vec(),
+        })
+    }
+
+    /// Builds the sample entry of an Opus track with this configuration.
+    fn sample_entry(&self) -> Vec<u8> {
+        let mut dops = vec![0, self.channels];
+        dops.extend_from_slice(&self.pre_skip.to_be_bytes());
+        dops.extend_from_slice(&self.input_sample_rate.to_be_bytes());
+        dops.extend_from_slice(&self.output_gain.to_be_bytes());
+        dops.extend_from_slice(&self.channel_mapping);
+
+        let mut entry = vec![0; 6];
+        entry.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+        entry.extend_from_slice(&[0; 8]);
+        entry.extend_from_slice(&(self.channels as u16).to_be_bytes());
+        entry.extend_from_slice(&16u16.to_be_bytes()); // sample size
+        entry.extend_from_slice(&[0; 4]);
+        entry.extend(u32_fields(&[OPUS_SAMPLE_RATE << 16]));
+        entry.extend(mp4_box(b"dOps", &dops));
+
+        mp4_box(b"Opus", &entry)
+    }
+}
+
+/// An Opus packet of an audio stream.
+pub struct OpusPacket {
+    pub data: Vec<u8>,
+    /// The number of samples the packet decodes to, at 48 kHz.
+    pub duration: u32,
+}
+
+/// An Opus audio stream to mux into a movie as a track of its own.
+pub struct OpusStream {
+    pub config: OpusConfig,
+    pub packets: Vec<OpusPacket>,
+    /// When the stream starts in the movie, in milliseconds.
+    pub start_ms: u64,
+}
+
+impl OpusStream {
+    /// Gets how long the stream plays, without the samples its pre-skip drops.
+    ///
+    /// # Returns
+    ///
+    /// The duration in milliseconds.
+    fn duration_ms(&self) -> u64 {
+        let samples: u64 = self
+            .packets
+            .iter()
+            .map(|packet| packet.duration as u64)
+            .sum();
+
+        samples.saturating_sub(self.config.pre_skip as u64) / OPUS_TICKS_PER_MS
+    }
+}
+
+/// An Opus track of an MP4 file, as read from its `moov` box.
+struct OpusTrack {
+    config: OpusConfig,
+    /// The samples, timed in 48 kHz ticks.
+    samples: Vec<TrackSample>,
+    /// The ticks to add to the decode times of the samples, from the edit list.
+    edit_shift: i64,
+}
+
+/// Reads the Opus tracks of a `moov` box.
+///
+/// # Arguments
+///
+/// * `moov` - The content of the `moov` box.
+///
+/// # Returns
+///
+/// The tracks timed at 48 kHz, as Opus tracks should be. Other tracks are skipped.
+fn read_opus_tracks(moov: &[u8]) -> Vec<OpusTrack> {
+    let movie_timescale = movie_timescale(moov).unwrap_or(TIMESCALE);
+
+    child_boxes(moov)
+        .into_iter()
+        .filter(|(kind, _)| kind == b"trak")
+        .filter_map(|(_, trak)| {
+            if media_timescale(trak)? != OPUS_SAMPLE_RATE {
+                return None;
+            }
+
+            let stbl = find_box(find_box(find_box(trak, b"mdia")?, b"minf")?, b"stbl")?;
+            let (_, _, stsd) = full_box_fields(find_box(stbl, b"stsd")?)?;
+            let (kind, entry) = *child_boxes(stsd.data.get(4..)?).first()?;
+            if kind != *b"Opus" {
+                return None;
+            }
+
+            // the dOps box follows the fields of the audio sample entry
+            let config = OpusConfig::from_dops(find_box(entry.get(28..)?, b"dOps")?)?;
+
+            Some(OpusTrack {
+                config,
+                samples: read_sample_table(stbl)?,
+                edit_shift: read_edit_shift(trak, movie_timescale, OPUS_SAMPLE_RATE).unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// Reads the part of the Opus tracks of a recording that's played along a part of
+/// its video.
+///
+/// Every stream starts a little before the part, so the decoder settles on the sound
+/// by the time it's heard, and its pre-skip drops the audio before the part.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+/// * `range_ms` - The part of the recording, in milliseconds.
+///
+/// # Returns
+///
+/// The streams timed from the start of the part, or `None` if the file can't be read.
+fn read_audio(filename: &str, range_ms: Range<u64>) -> Option<Vec<OpusStream>> {
+    let mut file = File::open(get_video_path(filename)).ok()?;
+    let movie = MovieBoxes::read(&mut file)?;
+
+    let start = (range_ms.start * OPUS_TICKS_PER_MS) as i64;
+    let end = (range_ms.end * OPUS_TICKS_PER_MS) as i64;
+    let mut streams = Vec::new();
+
+    for track in read_opus_tracks(&movie.moov) {
+        // where the decoded audio of a sample is in the recording, before any pre-skip
+        let decoded_at = |sample: &TrackSample| sample.decode_time as i64 + track.edit_shift;
+
+        let (Some(first_sample), Some(last_sample)) = (track.samples.first(), track.samples.last())
+        else {
+            continue;
+        };
+        if decoded_at(last_sample) + last_sample.duration as i64 <= start {
+            continue;
+        }
+
+        let pre_roll = (OPUS_PRE_ROLL_MS * OPUS_TICKS_PER_MS) as i64;
+        let first = track
+            .samples
+            .iter()
+            .rposition(|sample| decoded_at(sample) <= start - pre_roll)
+            .unwrap_or(0);
+        let track_start = decoded_at(first_sample) + track.config.pre_skip as i64;
+        let shown_start = start.max(track_start);
+
+        let mut packets = Vec::new();
+        for sample in track.samples[first..]
+            .iter()
+            .take_while(|sample| decoded_at(sample) < end)
+        {
+            let mut data = vec![0; sample.size as usize];
+            file.seek(SeekFrom::Start(sample.offset)).ok()?;
+            file.read_exact(&mut data).ok()?;
+
+            packets.push(OpusPacket {
+                data,
+                duration: sample.duration,
+            });
+        }
+        if packets.is_empty() {
+            continue;
+        }
+
+        let pre_skip = shown_start - decoded_at(&track.samples[first]);
+        streams.push(OpusStream {
+            config: OpusConfig {
+                pre_skip: pre_skip.clamp(0, u16::MAX as i64) as u16,
+                ..track.config
+            },
+            packets,
+            start_ms: ((shown_start - start) / OPUS_TICKS_PER_MS as i64) as u64,
+        });
+    }
+
+    Some(streams)
+}
+
+/// Builds the sample table of a track whose samples are all in a single chunk.
+///
+/// # Arguments
+///
+/// * `durations` - How long every sample lasts, in the ticks of the track.
+/// * `sizes` - The size of every sample.
+/// * `keyframes` - The numbers of the keyframes from 1, or `None` if every sample is one.
+/// * `chunk_offset` - Where the chunk starts in the file.
+fn sample_table(
+    durations: &[u32],
+    sizes: &[u32],
+    keyframes: Option<Vec<u32>>,
+    chunk_offset: u64,
+) -> Vec<u8> {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &duration in durations {
+        match runs.last_mut() {
+            Some((count, run_duration)) if *run_duration == duration => *count += 1,
+            _ => runs.push((1, duration)),
+        }
+    }
+
+    let mut stts = u32_fields(&[runs.len() as u32]);
+    for (count, duration) in runs {
+        stts.extend(u32_fields(&[count, duration]));
+    }
+
+    let stss = keyframes.map(|keyframes| {
+        let stss = [
+            u32_fields(&[keyframes.len() as u32]),
+            u32_fields(&keyframes),
+        ]
+        .concat();
+        full_box(b"stss", 0, 0, &stss)
+    });
+
+    let stsz = [u32_fields(&[0, sizes.len() as u32]), u32_fields(sizes)].concat();
+    let co64 = [u32_fields(&[1]), chunk_offset.to_be_bytes().to_vec()].concat();
+
+    [
+        full_box(b"stts", 0, 0, &stts),
+        stss.unwrap_or_default(),
+        full_box(b"stsc", 0, 0, &u32_fields(&[1, 1, sizes.len() as u32, 1])),
+        full_box(b"stsz", 0, 0, &stsz),
+        full_box(b"co64", 0, 0, &co64),
+    ]
+    .concat()
+}
+
+/// Builds the edit list of a track that's shown from some time into the movie.
+///
+/// # Arguments
+///
+/// * `start_ms` - When the track starts in the movie, in milliseconds.
+/// * `duration_ms` - How long the track is shown, in milliseconds, or 0 for the rest of a
+///   fragmented track.
+/// * `media_time` - Where the shown part starts in the media, in the ticks of the track.
+fn edit_list(start_ms: u64, duration_ms: u64, media_time: u32) -> Vec<u8> {
+    let mut entries = Vec::new();
+    if start_ms > 0 {
+        // an empty edit, which shows nothing of the track
+        entries.push([start_ms as u32, u32::MAX, 0x0001_0000]);
+    }
+    entries.push([duration_ms as u32, media_time, 0x0001_0000]);
+
+    let elst = [
+        u32_fields(&[entries.len() as u32]),
+        u32_fields(&entries.concat()),
+    ]
+    .concat();
+    mp4_box(b"edts", &full_box(b"elst", 0, 0, &elst))
+}
+
+/// Builds the `udta` box with the chapters of a movie, in the Nero chapter list that
+/// most players read.
+///
+/// # Arguments
+///
+/// * `chapters` - The chapters, at most 255 of them.
+fn chapter_list(chapters: &[&Chapter]) -> Vec<u8> {
+    let mut chpl = u32_fields(&[0]);
+    chpl.push(chapters.len() as u8);
+
+    for chapter in chapters {
+        // in units of 100 nanoseconds
+        chpl.extend_from_slice(&(chapter.start_ms * 10_000).to_be_bytes());
+
+        let mut title_len = chapter.title.len().min(u8::MAX as usize);
+        while !chapter.title.is_char_boundary(title_len) {
+            title_len -= 1;
+        }
+        chpl.push(title_len as u8);
+        chpl.extend_from_slice(&chapter.title.as_bytes()[..title_len]);
+    }
+
+    mp4_box(b"udta", &full_box(b"chpl", 1, 0, &chpl))
+}
+
+/// Writes a part of a recording's video, with audio and chapters, into a regular MP4
+/// file with its `moov` box first.
+///
+/// Every track is a single chunk in the `mdat` box: the video first, then every
+/// audio stream. The audio is cut off where the video ends. The movie is written next
+/// to the target and replaces it once it's complete, so the target may be the source.
+///
+/// # Arguments
+///
+/// * `target` - The filename of the movie (without the extension).
+/// * `source` - The filename of the recording (without the extension).
+/// * `index` - The index of the recording's video.
+/// * `samples` - The frames of the video to write, from `index`.
+/// * `start_ms` - The time of the recording the movie starts at.
+/// * `audio` - The audio streams, timed from the start of the movie.
+/// * `chapters` - The chapters, timed from the start of the movie.
+///
+/// # Returns
+///
+/// `true` if the movie was written.
+fn write_movie(
+    target: &str,
+    source: &str,
+    index: &VideoIndex,
+    samples: &[Sample],
+    start_ms: u64,
+    audio: &[OpusStream],
+    chapters: &[Chapter],
+) -> bool {
+    let Some(first_sample) = samples.first() else {
+        return false;
+    };
+
+    let video_start_ms = first_sample.time_ms.saturating_sub(start_ms);
+    let durations: Vec<u32> = samples
+        .iter()
+        .map(|sample| sample.duration_ms as u32)
+        .collect();
+    let sizes: Vec<u32> = samples.iter().map(|sample| sample.size).collect();
+    let media_duration: u64 = durations.iter().map(|&duration| duration as u64).sum();
+    let duration_ms = video_start_ms + media_duration;
+    let (width, height) = index
+        .parameter_sets
+        .iter()
+        .find(|nal| nal_type(nal) == NAL_SPS)
+        .and_then(|sps| sps_dimensions(sps))
+        .unwrap_or((0, 0));
+
+    let audio: Vec<(&OpusStream, u64)> = audio
+        .iter()
+        .map(|stream| {
+            let shown_ms = stream
+                .duration_ms()
+                .min(duration_ms.saturating_sub(stream.start_ms));
+            (stream, shown_ms)
+        })
+        .filter(|(_, shown_ms)| *shown_ms > 0)
+        .collect();
+    let chapters: Vec<&Chapter> = chapters
+        .iter()
+        .filter(|chapter| chapter.start_ms < duration_ms)
+        .take(u8::MAX as usize)
+        .collect();
+
+    let moov = |data_offset: u64| {
+        let video = TrackBoxes {
+            id: TRACK_ID,
+            handler: *b"vide",
+            width,
+            height,
+            duration_ms,
+            timescale: TIMESCALE,
+            media_duration,
+            sample_entry: avc_sample_entry(&index.parameter_sets, index.length_size),
+            sample_table: sample_table(
+                &durations,
+                &sizes,
+                Some(
+                    (1..)
+                        .zip(samples)
+                        .filter(|(_, sample)| sample.keyframe)
+                        .map(|(number, _)| number)
+                        .collect(),
+                ),
+                data_offset,
+            ),
+            edits: if video_start_ms > 0 {
+                edit_list(video_start_ms, media_duration, 0)
+            } else {
+                Vec::new()
+            },
+        };
+
+        let mut boxes = vec![
+            movie_header(duration_ms, 1 + audio.len() as u32),
+            video.build(),
+        ];
+        let mut offset = data_offset + sizes.iter().map(|&size| size as u64).sum::<u64>();
+
+        for (id, (stream, shown_ms)) in (TRACK_ID + 1..).zip(&audio) {
+            let durations: Vec<u32> = stream
+                .packets
+                .iter()
+                .map(|packet| packet.duration)
+                .collect();
+            let sizes: Vec<u32> = stream
+                .packets
+                .iter()
+                .map(|packet| packet.data.len() as u32)
+                .collect();
+
+            let track = TrackBoxes {
+                id,
+                handler: *b"soun",
+                width: 0,
+                height: 0,
+                duration_ms: stream.start_ms + shown_ms,
+                timescale: OPUS_SAMPLE_RATE,
+                media_duration: durations.iter().map(|&duration| duration as u64).sum(),
+                sample_entry: stream.config.sample_entry(),
+                sample_table: sample_table(&durations, &sizes, None, offset),
+                edits: edit_list(stream.start_ms, *shown_ms, stream.config.pre_skip as u32),
+            };
+            boxes.push(track.build());
+            offset += sizes.iter().map(|&size| size as u64).sum::<u64>();
+        }
+
+        if !chapters.is_empty() {
+            boxes.push(chapter_list(&chapters));
+        }
+
+        (mp4_box(b"moov", &boxes.concat()), offset - data_offset)
+    };
+
+    let ftyp = mp4_box(b"ftyp", FILE_TYPE);
+    // the chunk offsets are 64 bit, so the size of the moov box doesn't depend on them
+    let (moov_size, data_size) = moov(0);
+    let data_offset = (ftyp.len() + moov_size.len()) as u64 + 16;
+    let (moov, _) = moov(data_offset);
+
+    let partial_path = get_video_path(&format!("{target}.partial"));
+    let written = File::open(get_video_path(source)).and_then(|mut source| {
+        let mut file = BufWriter::new(File::create(&partial_path)?);
+        file.write_all(&ftyp)?;
+        file.write_all(&moov)?;
+
+        // a 64 bit mdat, as long recordings are bigger than 4 GB
+        file.write_all(&1u32.to_be_bytes())?;
+        file.write_all(b"mdat")?;
+        file.write_all(&(16 + data_size).to_be_bytes())?;
+
+        let mut data = Vec::new();
+        for sample in samples {
+            data.resize(sample.size as usize, 0);
+            source.seek(SeekFrom::Start(sample.offset))?;
+            source.read_exact(&mut data)?;
+            file.write_all(&data)?;
+        }
+        for (stream, _) in &audio {
+            for packet in &stream.packets {
+                file.write_all(&packet.data)?;
+            }
+        }
+
+        file.flush()
+    });
+
+    // the source is closed by now, so it can be replaced
+    if written.is_ok() && fs::rename(&partial_path, get_video_path(target)).is_ok() {
+        true
+    } else {
+        let _ = fs::remove_file(&partial_path);
+        false
+    }
+}
+
+/// Rewrites a finished recording as a regular MP4 file with audio and chapters.
+///
+/// The video is copied as it is, and any audio the file had is replaced.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+/// * `audio` - The audio streams, timed from the start of the recording.
+/// * `chapters` - The chapters, timed from the start of the recording.
+///
+/// # Returns
+///
+/// `true` if the recording was rewritten, `false` if it was left as it was.
+pub fn remux(filename: &str, audio: &[OpusStream], chapters: &[Chapter]) -> bool {
+    let Some(index) = VideoIndex::read(filename) else {
+        return false;
+    };
+
+    write_movie(
+        filename,
+        filename,
+        &index,
+        &index.samples,
+        0,
+        audio,
+        chapters,
+    )
+}
+
+/// Rewrites a recording with new chapters, keeping its video and its Opus audio.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+/// * `chapters` - The chapters, timed from the start of the recording.
+///
+/// # Returns
+///
+/// `true` if the recording was rewritten, `false` if it was left as it was.
+pub fn add_chapters(filename: &str, chapters: &[Chapter]) -> bool {
+    let Some(index) = VideoIndex::read(filename) else {
+        return false;
+    };
+    let Some(audio) = read_audio(filename, 0..index.duration_ms()) else {
+        return false;
+    };
+
+    write_movie(
+        filename,
+        filename,
+        &index,
+        &index.samples,
+        0,
+        &audio,
+        chapters,
+    )
+}
+
+/// Cuts a part of a recording into a new video file, without reencoding it.
+///
+/// The clip starts at the keyframe before the part, as the frames after a keyframe
+/// can't be decoded without it, and its audio starts along with the video.
+///
+/// # Arguments
+///
+/// * `source` - The filename of the recording (without the extension).
+/// * `clip` - The filename of the clip (without the extension).
+/// * `range_ms` - The part of the recording, in milliseconds.
+///
+/// # Returns
+///
+/// The time of the recording the clip starts at in milliseconds, or `None` if the
+/// clip couldn't be cut.
+pub fn cut(source: &str, clip: &str, range_ms: Range<u64>) -> Option<u64> {
+    let index = VideoIndex::read(source)?;
+
+    let first = index.keyframe_before(range_ms.start);
+    let start_ms = index.samples.get(first)?.time_ms;
+    let count = index.samples[first..]
+        .iter()
+        .take_while(|sample| sample.time_ms < range_ms.end)
+        .count();
+
+    let audio = read_audio(source, start_ms..range_ms.end)?;
+    let samples = &index.samples[first..first + count];
+
+    write_movie(clip, source, &index, samples, start_ms, &audio, &[]).then_some(start_ms)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::recordings_folder;
+
+    /// The SPS and PPS of a 320x240 baseline stream.
+    const SPS: [u8; 11] = [
+        0x67, 0x42, 0xC0, 0x0D, 0xDA, 0x05, 0x07, 0xEC, 0x04, 0x40, 0x00,
+    ];
+    const PPS: [u8; 4] = [0x68, 0xCE, 0x3C, 0x80];
+
+    /// Writes a fragmented recording like `Mp4Writer` does, with a frame every 40 ms
+    /// and a keyframe every second.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_ms` - The time of the first frame.
+    /// * `count` - The number of frames.
+    ///
+    /// # Returns
+    ///
+    /// The filename of the recording.
+    pub fn write_recording(first_ms: u64, count: u64) -> String {
+        let filename = uuid::Uuid::new_v4().to_string();
+        fs::create_dir_all(recordings_folder()).unwrap();
+
+        let mut bytes = file_header(&SPS, &PPS, first_ms);
+        for index in 0..count {
+            let frame = RecordedFrame {
+                nals: vec![vec![0x65, index as u8, 0xAA], vec![0x06, 0x05]],
+                time_ms: first_ms + index * 40,
+                keyframe: index % 25 == 0,
+            };
+            bytes.extend(fragment(index as u32 + 1, frame.time_ms, 40, &frame));
+        }
+        fs::write(get_video_path(&filename), bytes).unwrap();
+
+        filename
+    }
+
+    fn opus_config() -> OpusConfig {
+        OpusConfig {
+            channels: 2,
+            pre_skip: 312,
+            input_sample_rate: 48_000,
+            output_gain: -256,
+            channel_mapping: vec![0],
+        }
+    }
+
+    /// Creates a stream of 20 ms packets whose contents tell them apart.
+    fn opus_stream(count: usize, start_ms: u64) -> OpusStream {
+        OpusStream {
+            config: opus_config(),
+            packets: (0..count)
+                .map(|index| OpusPacket {
+                    data: vec![index as u8; 3 + index % 5],
+                    duration: 960,
+                })
+                .collect(),
+            start_ms,
+        }
+    }
+
+    fn read_chapters(filename: &str) -> Vec<(u64, String)> {
+        let mut file = File::open(get_video_path(filename)).unwrap();
+        let moov = MovieBoxes::read(&mut file).unwrap().moov;
+        let (_, _, mut chpl) =
+            full_box_fields(find_box(find_box(&moov, b"udta").unwrap(), b"chpl").unwrap()).unwrap();
+        chpl.u32().unwrap();
+
+        (0..chpl.u8().unwrap())
+            .map(|_| {
+                let start = chpl.u64().unwrap() / 10_000;
+                let size = chpl.u8().unwrap() as usize;
+                let title = String::from_utf8(chpl.bytes(size).unwrap().to_vec()).unwrap();
+                (start, title)
+            })
+            .collect()
+    }
+
+    pub fn remove_recording(filename: &str) {
+        let _ = fs::remove_file(get_video_path(filename));
+    }
+
+    #[test]
+    fn fragmented_recording_is_indexed() {
+        let filename = write_recording(0, 60);
+        let index = VideoIndex::read(&filename).unwrap();
+        let mut file = File::open(get_video_path(&filename)).unwrap();
+
+        assert_eq!(index.samples.len(), 60);
+        assert_eq!(index.duration_ms(), 2400);
+        assert_eq!(index.keyframe_before(1500), 25);
+        assert_eq!(
+            index.parameter_set_nals(),
+            vec![
+                [&[0, 0, 1], &SPS[..]].concat(),
+                [&[0, 0, 1], &PPS[..]].concat()
+            ]
+        );
+        assert_eq!(
+            index.read_nals(&mut file, &index.samples[7]).unwrap(),
+            vec![vec![0, 0, 1, 0x65, 7, 0xAA], vec![0, 0, 1, 0x06, 0x05]]
+        );
+
+        remove_recording(&filename);
+    }
+
+    /// Reads the type of the sample entry of a recording's video.
+    fn sample_entry_kind(filename: &str) -> [u8; 4] {
+        let mut file = File::open(get_video_path(filename)).unwrap();
+        let moov = MovieBoxes::read(&mut file).unwrap().moov;
+        let mdia = find_box(find_box(&moov, b"trak").unwrap(), b"mdia").unwrap();
+        let stbl = find_box(find_box(mdia, b"minf").unwrap(), b"stbl").unwrap();
+        let (_, _, stsd) = full_box_fields(find_box(stbl, b"stsd").unwrap()).unwrap();
+
+        child_boxes(&stsd.data[4..])[0].0
+    }
+
+    #[test]
+    fn changed_parameter_sets_stay_in_band() {
+        let filename = uuid::Uuid::new_v4().to_string();
+        fs::create_dir_all(recordings_folder()).unwrap();
+
+        // the encoder restarts with other parameter sets before the second keyframe
+        let other_sps = [0x67, 0x42, 0xC0, 0x1E, 0xAB, 0xCD];
+        let mut writer = Mp4Writer::create(&filename);
+        for (index, sps) in [&SPS[..], &other_sps, &other_sps].into_iter().enumerate() {
+            writer.push_nal(&[&[0, 0, 0, 1], sps].concat());
+            writer.push_nal(&[&[0, 0, 0, 1], &PPS[..]].concat());
+            writer.push_nal(&[0, 0, 0, 1, 0x65, 0x88, index as u8]);
+        }
+        writer.finish();
+
+        for remuxed in [false, true] {
+            if remuxed {
+                assert!(remux(&filename, &[], &[]));
+            }
+
+            assert_eq!(&sample_entry_kind(&filename), b"avc3");
+            let index = VideoIndex::read(&filename).unwrap();
+            let mut file = File::open(get_video_path(&filename)).unwrap();
+            assert_eq!(index.samples.len(), 3);
+            assert_eq!(
+                index.read_nals(&mut file, &index.samples[1]).unwrap()[0],
+                [&[0, 0, 1], &other_sps[..]].concat()
+            );
+        }
+
+        remove_recording(&filename);
+    }
+
+    #[test]
+    fn time_before_the_first_keyframe_stays_in_the_recording() {
+        let filename = write_recording(700, 10);
+        let index = VideoIndex::read(&filename).unwrap();
+
+        assert_eq!(index.samples[0].time_ms, 700);
+        assert_eq!(index.samples[9].time_ms, 1060);
+        assert_eq!(index.duration_ms(), 1100);
+        assert_eq!(index.keyframe_before(0), 0);
+
+        remove_recording(&filename);
+    }
+
+    #[test]
+    fn cut_off_recording_is_read_up_to_its_last_fragment() {
+        let filename = write_recording(0, 10);
+        let path = get_video_path(&filename);
+        let len = fs::metadata(&path).unwrap().len();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 5)
+            .unwrap();
+
+        let index = VideoIndex::read(&filename).unwrap();
+        assert_eq!(index.samples.len(), 9);
+        assert!(index.complete_len() < len - 5);
+
+        remove_recording(&filename);
+    }
+
+    #[test]
+    fn opus_config_round_trips_through_dops() {
+        let mut head = b"OpusHead".to_vec();
+        head.extend_from_slice(&[1, 2]);
+        head.extend_from_slice(&312u16.to_le_bytes());
+        head.extend_from_slice(&48_000u32.to_le_bytes());
+        head.extend_from_slice(&(-256i16).to_le_bytes());
+        head.push(0);
+
+        let config = OpusConfig::from_opus_head(&head).unwrap();
+        assert_eq!(config, opus_config());
+
+        let entry = config.sample_entry();
+        let (kind, content) = child_boxes(&entry)[0];
+        assert_eq!(&kind, b"Opus");
+        let dops = find_box(&content[28..], b"dOps").unwrap();
+        assert_eq!(OpusConfig::from_dops(dops).unwrap(), config);
+    }
+
+    #[test]
+    fn remux_keeps_video_and_adds_audio_and_chapters() {
+        let filename = write_recording(500, 60);
+        let before = VideoIndex::read(&filename).unwrap();
+        let chapters = [
+            Chapter {
+                start_ms: 600,
+                title: "Start".to_owned(),
+            },
+            Chapter {
+                start_ms: 2000,
+                title: "Later".to_owned(),
+            },
+        ];
+
+        assert!(remux(&filename, &[opus_stream(100, 100)], &chapters));
+
+        let after = VideoIndex::read(&filename).unwrap();
+        let mut before_file = File::open(get_video_path(&filename)).unwrap();
+        assert_eq!(after.samples.len(), before.samples.len());
+        for (before_sample, after_sample) in before.samples.iter().zip(&after.samples) {
+            assert_eq!(after_sample.time_ms, before_sample.time_ms);
+            assert_eq!(after_sample.duration_ms, before_sample.duration_ms);
+            assert_eq!(after_sample.keyframe, before_sample.keyframe);
+        }
+        assert_eq!(
+            after
+                .read_nals(&mut before_file, &after.samples[30])
+                .unwrap(),
+            vec![vec![0, 0, 1, 0x65, 30, 0xAA], vec![0, 0, 1, 0x06, 0x05]]
+        );
+
+        let audio = read_audio(&filename, 0..after.duration_ms()).unwrap();
+        assert_eq!(audio.len(), 1);
+        assert_eq!(audio[0].config, opus_config());
+        assert_eq!(audio[0].start_ms, 100);
+        let expected = opus_stream(100, 100);
+        assert_eq!(audio[0].packets.len(), expected.packets.len());
+        for (read, written) in audio[0].packets.iter().zip(&expected.packets) {
+            assert_eq!(read.data, written.data);
+            assert_eq!(read.duration, written.duration);
+        }
+
+        assert_eq!(
+            read_chapters(&filename),
+            vec![(600, "Start".to_owned()), (2000, "Later".to_owned())]
+        );
+
+        remove_recording(&filename);
+    }
+
+    #[test]
+    fn cut_starts_at_the_keyframe_before_the_part() {
+        let source = write_recording(0, 100);
+        assert!(remux(&source, &[opus_stream(200, 0)], &[]));
+        let clip = uuid::Uuid::new_v4().to_string();
+
+        assert_eq!(cut(&source, &clip, 1200..2000), Some(1000));
+
+        let index = VideoIndex::read(&clip).unwrap();
+        assert_eq!(index.samples.len(), 25);
+        assert_eq!(index.samples[0].time_ms, 0);
+        assert!(index.samples[0].keyframe);
+
+        // the audio is decoded from a little before the clip and skipped up to its start
+        let audio = read_audio(&clip, 0..index.duration_ms()).unwrap();
+        assert_eq!(audio.len(), 1);
+        assert_eq!(audio[0].start_ms, 0);
+        let skipped_packets = audio[0].config.pre_skip as usize / 960;
+        assert_eq!(
+            audio[0].packets[skipped_packets].data,
+            opus_stream(200, 0).packets[50].data
+        );
+
+        remove_recording(&source);
+        remove_recording(&clip);
+    }
+}
@@ -1,6 +1,14 @@
-use stream_desk::{protocol::Packet, secure_channel::SecureChannel, UserType};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use stream_desk::{
+    protocol::{is_clipboard_within_limits, Packet, SessionEventKind},
+    secure_channel::SecureChannel,
+    UserType,
+};
 
-use crate::SharedSession;
+use crate::{
+    audio::forward_audio, bookmarks::add_bookmark, transfer::forward_transfer_packet, SharedSession,
+};
 
 /// Handles packets from the client.
 ///
@@ -9,6 +17,7 @@ use crate::SharedSession;
 /// * `channel` - A `SecureChannel` connected to the client.
 /// * `session` - The `Session` object that the user is connected to.
 /// * `username` - The username of the client.
+/// * `db_pool` - The pool of the database connections.
 ///
 /// # Returns
 ///
@@ -17,6 +26,7 @@ pub fn handle_participant(
     channel: &mut SecureChannel,
     session: SharedSession,
     username: String,
+    db_pool: &Pool<SqliteConnectionManager>,
 ) -> std::io::Result<()> {
     loop {
         let packet = channel.receive().unwrap_or_default();
@@ -30,6 +40,59 @@ pub fn handle_participant(
                 }
             }
 
+            Packet::ClipboardText { .. } | Packet::ClipboardImage { .. } => {
+                let session = session.lock().unwrap();
+
+                // only the controller can sync the clipboard, and only if the host opted in
+                if session.clipboard_sync
+                    && session.connections.get(&username).unwrap().user_type == UserType::Controller
+                    && is_clipboard_within_limits(&packet)
+                {
+                    session.host().send(packet)?;
+                }
+            }
+
+            Packet::FileOffer { .. }
+            | Packet::FileAccept { .. }
+            | Packet::FileChunk { .. }
+            | Packet::FileEnd { .. }
+            | Packet::FileCancel { .. } => {
+                let mut session = session.lock().unwrap();
+                forward_transfer_packet(&mut session, packet, &username, channel)?;
+            }
+
+            Packet::Audio { .. } | Packet::AudioEnd { .. } => {
+                let mut session = session.lock().unwrap();
+                forward_audio(&mut session, packet, &username)?;
+            }
+
+            Packet::StreamFeedback {
+                received_kbps,
+                queue_depth,
+                dropped_frames,
+                ..
+            } => {
+                let mut session = session.lock().unwrap();
+                let layers = session.screen_layers;
+
+                let connection = session.connections.get_mut(&username).unwrap();
+                connection
+                    .layer
+                    .report(layers, received_kbps, queue_depth, dropped_frames);
+
+                // participants on lower layers are taken care of here, so they don't
+                // drag the host's full quality stream down
+                if connection.layer.layer == 0 {
+                    let packet = Packet::StreamFeedback {
+                        username: username.clone(),
+                        received_kbps,
+                        queue_depth,
+                        dropped_frames,
+                    };
+                    session.host().send(packet)?;
+                }
+            }
+
             Packet::RequestControl { .. } => {
                 let session = session.lock().unwrap();
 
@@ -48,6 +111,8 @@ pub fn handle_participant(
             Packet::SessionExit | Packet::None => {
                 let mut session = session.lock().unwrap();
                 session.connections.remove(&username);
+                session.audio.end_stream(&username);
+                session.events.log(SessionEventKind::Leave, &username);
 
                 let user_update_packet = Packet::UserUpdate {
                     user_type: UserType::Leaving,
@@ -63,10 +128,15 @@ pub fn handle_participant(
 
             Packet::Chat { message } => {
                 let message = username.to_string() + ": " + &message;
-                let packet = Packet::Chat { message };
 
                 let mut session = session.lock().unwrap();
-                session.broadcast_all(packet)?;
+                session.events.log(SessionEventKind::Chat, &message);
+                session.broadcast_all(Packet::Chat { message })?;
+            }
+
+            Packet::AddBookmark { name } => {
+                let mut session = session.lock().unwrap();
+                add_bookmark(&mut session, db_pool, &username, &name)?;
             }
 
             Packet::SessionEnd => break,
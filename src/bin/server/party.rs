@@ -0,0 +1,416 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use log::info;
+use stream_desk::{protocol::Packet, secure_channel::SecureChannel, UserType, LOG_TARGET};
+
+use crate::{
+    mp4::{nal_type, VideoIndex, NAL_IDR, NAL_PPS, NAL_SPS},
+    simulcast::LayerState,
+    structs::{Connection, Session},
+    watch::{
+        choose_quality, frame_packets, start_playback, PlaybackControl, PlaybackTarget,
+        RecordingRequests,
+    },
+    SessionHashMap, SharedSession,
+};
+
+/// The state of a watch party, kept in its session. The leader is the session's host,
+/// and the viewers who joined are its participants.
+pub struct WatchParty {
+    /// The ID of the watched recording.
+    pub recording_id: i32,
+    /// The playback speed and pause state the leader chose.
+    pub control: PlaybackControl,
+    /// The viewers who start watching at the next keyframe, as they joined or the
+    /// leader sought since.
+    waiting: HashSet<String>,
+    /// The last parameter sets of the stream, which the decoders of the waiting
+    /// viewers need before the keyframe.
+    parameter_sets: Vec<Vec<u8>>,
+}
+
+impl WatchParty {
+    /// Creates the state of a watch party that starts at the beginning of a recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `recording_id` - The ID of the recording.
+    pub fn new(recording_id: i32) -> Self {
+        Self {
+            recording_id,
+            control: PlaybackControl::new(),
+            waiting: HashSet::new(),
+            parameter_sets: Vec::new(),
+        }
+    }
+}
+
+/// Sends a frame of the recording to everyone in a watch party.
+///
+/// The viewers who wait for a keyframe are told where the leader is and get the
+/// parameter sets before it, so their decoders can start from it.
+///
+/// # Arguments
+///
+/// * `session` - The session of the watch party.
+/// * `start_ms` - The time the playback was started from, in milliseconds.
+/// * `pts_ms` - The presentation time of the frame, in milliseconds.
+/// * `nals` - The NAL units of the frame.
+pub fn send_party_frame(session: &mut Session, start_ms: u64, pts_ms: u64, nals: Vec<Vec<u8>>) {
+    let Session {
+        connections,
+        watch_party: Some(party),
+        ..
+    } = session
+    else {
+        return;
+    };
+
+    let parameter_sets: Vec<Vec<u8>> = nals
+        .iter()
+        .filter(|nal| matches!(nal_type(&nal[3..]), NAL_SPS | NAL_PPS))
+        .cloned()
+        .collect();
+    if !parameter_sets.is_empty() {
+        party.parameter_sets = parameter_sets;
+    }
+
+    let is_keyframe = nals.iter().any(|nal| nal_type(&nal[3..]) == NAL_IDR);
+    let starting: HashSet<String> = if is_keyframe {
+        party.waiting.drain().collect()
+    } else {
+        HashSet::new()
+    };
+
+    let first_frame = match starting.is_empty() {
+        true => Vec::new(),
+        false => frame_packets(
+            pts_ms,
+            [party.parameter_sets.clone(), nals.clone()].concat(),
+        ),
+    };
+    let frame = frame_packets(pts_ms, nals);
+
+    // a viewer who left meanwhile is taken care of by their own thread
+    for (username, connection) in connections.iter_mut() {
+        if party.waiting.contains(username) {
+            continue;
+        }
+
+        let packets = if starting.contains(username) {
+            let seek = Packet::SeekTo {
+                time_ms: pts_ms.max(start_ms),
+            };
+            let _ = connection.channel.send(seek);
+            &first_frame
+        } else {
+            &frame
+        };
+
+        for packet in packets {
+            let _ = connection.channel.send(packet.clone());
+        }
+    }
+}
+
+/// Sends a packet of the stream to everyone in a watch party who is watching it.
+///
+/// # Arguments
+///
+/// * `session` - The session of the watch party.
+/// * `packet` - The packet to send.
+pub fn send_party_packet(session: &mut Session, packet: Packet) {
+    let Session {
+        connections,
+        watch_party: Some(party),
+        ..
+    } = session
+    else {
+        return;
+    };
+
+    for (username, connection) in connections.iter_mut() {
+        if !party.waiting.contains(username) {
+            let _ = connection.channel.send(packet.clone());
+        }
+    }
+}
+
+/// Adds a viewer to a watch party, who starts watching at the next keyframe.
+///
+/// # Arguments
+///
+/// * `channel` - The `SecureChannel` connected to the viewer.
+/// * `session` - The session of the watch party.
+/// * `username` - The username of the viewer.
+///
+/// # Returns
+///
+/// An `std::io::Result<()>` that signifies if the viewer was told about the party.
+pub fn join_watch_party(
+    channel: &SecureChannel,
+    session: &SharedSession,
+    username: &str,
+) -> std::io::Result<()> {
+    let mut session = session.lock().unwrap();
+
+    let mut connection = Connection {
+        channel: channel.clone(),
+        user_type: UserType::Participant,
+        layer: LayerState::new(),
+    };
+
+    // send all usernames
+    for (username, user_connection) in &session.connections {
+        let username_packet = Packet::UserUpdate {
+            user_type: user_connection.user_type,
+            joined_before: true,
+            username: username.clone(),
+        };
+        connection.channel.send(username_packet)?;
+    }
+
+    if let Some(party) = &mut session.watch_party {
+        let speed_percent = party.control.speed_percent.load(Ordering::Relaxed);
+        connection
+            .channel
+            .send(Packet::PlaybackSpeed { speed_percent })?;
+
+        let paused = party.control.paused.load(Ordering::Relaxed);
+        connection.channel.send(Packet::PlaybackPause { paused })?;
+
+        party.waiting.insert(username.to_string());
+    }
+
+    session.connections.insert(username.to_string(), connection);
+
+    // send new username to everyone
+    let packet = Packet::UserUpdate {
+        user_type: UserType::Participant,
+        joined_before: false,
+        username: username.to_string(),
+    };
+    let _ = session.broadcast_all(packet);
+
+    Ok(())
+}
+
+/// Sends a chat message to everyone in a watch party.
+///
+/// # Arguments
+///
+/// * `session` - The session of the watch party.
+/// * `username` - The username of the sender.
+/// * `message` - The message.
+fn send_party_chat(session: &SharedSession, username: &str, message: &str) {
+    let message = username.to_string() + ": " + message;
+
+    let mut session = session.lock().unwrap();
+    let _ = session.broadcast_all(Packet::Chat { message });
+}
+
+/// Handles packets from the leader of a watch party, who controls its playback.
+///
+/// # Arguments
+///
+/// * `channel` - A `SecureChannel` connected to the leader.
+/// * `session` - The session of the watch party.
+/// * `sessions` - The `HashMap` of all the sessions.
+/// * `code` - The session code.
+/// * `username` - The username of the leader.
+/// * `requests` - The handler of the requests about the watched recording.
+///
+/// # Returns
+///
+/// An `std::io::Result<()>` that signifies if something went wrong.
+pub fn handle_party_leader(
+    channel: &mut SecureChannel,
+    session: SharedSession,
+    sessions: SessionHashMap,
+    code: u32,
+    username: String,
+    mut requests: RecordingRequests,
+) -> std::io::Result<()> {
+    let filename = requests.recording().filename.clone();
+    let index = Arc::new(VideoIndex::read(&filename).unwrap_or_default());
+    let control = match &session.lock().unwrap().watch_party {
+        Some(party) => party.control.clone(),
+        None => PlaybackControl::new(),
+    };
+    let mut quality = None;
+
+    let mut stop_flag = Arc::new(AtomicBool::new(false));
+
+    let mut thread_send = Some(start_playback(
+        PlaybackTarget::Party(session.clone()),
+        &filename,
+        &index,
+        0,
+        quality,
+        &control,
+        &stop_flag,
+    ));
+
+    loop {
+        let packet = channel.receive().unwrap_or_default();
+
+        match packet {
+            Packet::SeekInit => {
+                // the session isn't locked, as the thread locks it for every frame
+                stop_flag.store(true, Ordering::Relaxed);
+                let _ = thread_send.take().unwrap().join();
+
+                channel.send(Packet::SeekInit)?;
+            }
+
+            Packet::SeekTo { time_ms } => {
+                // the viewers follow from the first keyframe of the new stream
+                {
+                    let mut session = session.lock().unwrap();
+                    let viewers: HashSet<String> = session
+                        .connections
+                        .keys()
+                        .filter(|viewer| **viewer != username)
+                        .cloned()
+                        .collect();
+                    if let Some(party) = &mut session.watch_party {
+                        party.waiting = viewers;
+                    }
+                }
+
+                stop_flag = Arc::new(AtomicBool::new(false));
+                thread_send = Some(start_playback(
+                    PlaybackTarget::Party(session.clone()),
+                    &filename,
+                    &index,
+                    time_ms,
+                    quality,
+                    &control,
+                    &stop_flag,
+                ));
+            }
+
+            Packet::PlaybackSpeed { speed_percent } => {
+                let speed_percent = control.set_speed(speed_percent);
+
+                let mut session = session.lock().unwrap();
+                let _ = session.broadcast_participants(Packet::PlaybackSpeed { speed_percent });
+            }
+
+            Packet::PlaybackPause { paused } => {
+                control.paused.store(paused, Ordering::Relaxed);
+
+                let mut session = session.lock().unwrap();
+                let _ = session.broadcast_participants(Packet::PlaybackPause { paused });
+            }
+
+            Packet::PlaybackQuality { height, bitrate } => match choose_quality(height, bitrate) {
+                Ok(chosen) => quality = chosen,
+                Err(failure) => {
+                    quality = None;
+                    channel.send(failure)?;
+                }
+            },
+
+            Packet::Chat { message } => {
+                send_party_chat(&session, &username, &message);
+            }
+
+            Packet::SessionExit | Packet::None => {
+                stop_flag.store(true, Ordering::Relaxed);
+                if let Some(thread_send) = thread_send.take() {
+                    let _ = thread_send.join();
+                }
+
+                {
+                    let mut session = session.lock().unwrap();
+                    let _ = session.broadcast_participants(Packet::SessionEnd);
+
+                    let mut sessions = sessions.lock().unwrap();
+                    sessions.remove(&code);
+                }
+
+                info!(target: LOG_TARGET, "Leader ended watch party {}.", code);
+
+                requests.finish();
+
+                channel.send(Packet::SessionExit)?;
+
+                break;
+            }
+
+            packet => requests.handle(channel, packet)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles packets from a viewer of a watch party, who watches along with the leader.
+///
+/// # Arguments
+///
+/// * `channel` - A `SecureChannel` connected to the viewer.
+/// * `session` - The session of the watch party.
+/// * `username` - The username of the viewer.
+/// * `requests` - The handler of the requests about the watched recording.
+///
+/// # Returns
+///
+/// An `std::io::Result<()>` that signifies if something went wrong.
+pub fn handle_party_viewer(
+    channel: &mut SecureChannel,
+    session: SharedSession,
+    username: String,
+    mut requests: RecordingRequests,
+) -> std::io::Result<()> {
+    loop {
+        let packet = channel.receive().unwrap_or_default();
+
+        match packet {
+            Packet::Chat { message } => {
+                send_party_chat(&session, &username, &message);
+            }
+
+            Packet::SessionExit | Packet::None => {
+                {
+                    let mut session = session.lock().unwrap();
+                    session.connections.remove(&username);
+                    if let Some(party) = &mut session.watch_party {
+                        party.waiting.remove(&username);
+                    }
+
+                    let user_update_packet = Packet::UserUpdate {
+                        user_type: UserType::Leaving,
+                        joined_before: false,
+                        username: username.clone(),
+                    };
+                    let _ = session.broadcast_all(user_update_packet);
+                }
+
+                requests.finish();
+
+                channel.send(Packet::SessionExit)?;
+
+                break;
+            }
+
+            // the leader ended the party
+            Packet::SessionEnd => {
+                requests.finish();
+
+                break;
+            }
+
+            packet => requests.handle(channel, packet)?,
+        }
+    }
+
+    Ok(())
+}
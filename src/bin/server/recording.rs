@@ -0,0 +1,411 @@
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local, TimeDelta};
+use log::info;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::params;
+use stream_desk::{
+    protocol::{Packet, SessionEventKind},
+    LOG_TARGET,
+};
+
+use crate::{
+    access::grant_access,
+    audio::{mix_into_recording, move_tracks, recover_tracks, remove_tracks},
+    bookmarks::{query_bookmarks, Bookmark},
+    delete_recording,
+    events::{get_events_path, read_events},
+    get_duration_ms, get_video_path,
+    mp4::VideoIndex,
+    recordings_folder,
+    thumbnails::generate_thumbnails,
+};
+
+/// The endings of the files written while a recording is processed, which are only
+/// left behind if the server stopped meanwhile.
+const INTERMEDIATE_ENDINGS: [&str; 2] = [".mixed.ogg", ".partial.mp4"];
+
+/// The folder in the recordings folder that videos without an owner are moved to,
+/// for the server's operator to look at.
+const QUARANTINE_FOLDER: &str = "quarantine";
+
+/// The time into a recording, which stands still while the recording is paused.
+///
+/// The video, the audio tracks, the events and the bookmarks are all timed by it, so
+/// what happens after a pause follows right after what happened before it.
+#[derive(Clone, Copy)]
+pub struct RecordingClock {
+    /// When the recording started.
+    started: Instant,
+    /// How long the recording was paused for, not counting the current pause.
+    paused: Duration,
+    /// When the current pause started, if the recording is paused.
+    paused_at: Option<Instant>,
+}
+
+impl RecordingClock {
+    /// Starts the clock at 0.
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            paused: Duration::ZERO,
+            paused_at: None,
+        }
+    }
+
+    /// Stops the clock until it's resumed.
+    pub fn pause(&mut self) {
+        self.paused_at.get_or_insert_with(Instant::now);
+    }
+
+    /// Resumes the clock from where it was paused.
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused += paused_at.elapsed();
+        }
+    }
+
+    /// Checks whether the clock is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Gets the time into the recording.
+    ///
+    /// # Returns
+    ///
+    /// The time since the recording started, without the time it was paused for.
+    pub fn elapsed(&self) -> Duration {
+        let now = self.paused_at.unwrap_or_else(Instant::now);
+        now.duration_since(self.started).saturating_sub(self.paused)
+    }
+}
+
+/// Repairs the video of a recording that was cut off, by truncating it after its last
+/// complete fragment.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+///
+/// # Returns
+///
+/// `true` if the recording has frames to play, `false` if it has no usable video.
+pub fn repair_video(filename: &str) -> bool {
+    let Some(index) = VideoIndex::read(filename) else {
+        return false;
+    };
+    if index.samples.is_empty() {
+        return false;
+    }
+
+    OpenOptions::new()
+        .write(true)
+        .open(get_video_path(filename))
+        .and_then(|file| file.set_len(index.complete_len()))
+        .is_ok()
+}
+
+/// Inserts a recording that has just started to the database, marked as in progress.
+///
+/// # Arguments
+/// * `db_pool` - The pool of the database connections.
+/// * `filename` - The filename of the video.
+/// * `time` - The timestamp of the meeting.
+/// * `user_id` - The ID of the user this recording belongs to.
+///
+/// # Returns
+///
+/// The ID of the new recording, or `None` if it couldn't be inserted.
+pub fn insert_recording_to_database(
+    db_pool: &Pool<SqliteConnectionManager>,
+    filename: &str,
+    time: &str,
+    user_id: i32,
+) -> Option<i32> {
+    let db_pool = db_pool.get().unwrap();
+
+    db_pool
+        .execute(
+            "INSERT INTO recordings (filename, time, user_id, in_progress) VALUES (?1, ?2, ?3, 1)",
+            params![filename, time, user_id],
+        )
+        .ok()?;
+
+    Some(db_pool.last_insert_rowid() as i32)
+}
+
+/// Marks a recording as finished, which lists it for its owner.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `recording_id` - The ID of the recording.
+pub fn finish_recording_in_database(db_pool: &Pool<SqliteConnectionManager>, recording_id: i32) {
+    let _ = db_pool.get().unwrap().execute(
+        "UPDATE recordings SET in_progress = 0 WHERE recording_id = ?1",
+        [recording_id],
+    );
+}
+
+/// Finds who was in a recorded session from its event log.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+///
+/// # Returns
+///
+/// The usernames of the users who joined, starting with the host, who joins first.
+fn find_attendees(filename: &str) -> Vec<String> {
+    let mut attendees: Vec<String> = Vec::new();
+
+    for event in read_events(filename) {
+        if let Packet::SessionEvent {
+            kind: SessionEventKind::Join,
+            text,
+            ..
+        } = event
+        {
+            if !attendees.contains(&text) {
+                attendees.push(text);
+            }
+        }
+    }
+
+    attendees
+}
+
+/// Lists a video that doesn't belong to any recording for the host of its session.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `filename` - The filename of the video (without the extension).
+///
+/// # Returns
+///
+/// The ID of the new recording, or `None` if the video has no usable frames or
+/// its host can't be found.
+fn adopt_orphan(db_pool: &Pool<SqliteConnectionManager>, filename: &str) -> Option<i32> {
+    let attendees = find_attendees(filename);
+    let host = attendees.first()?;
+    let user_id = db_pool
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT user_id FROM users WHERE username = ?1",
+            [host],
+            |row| row.get(0),
+        )
+        .ok()?;
+
+    if !repair_video(filename) {
+        return None;
+    }
+
+    // the video was last written when the session ended
+    let modified = fs::metadata(get_video_path(filename))
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    let time = DateTime::<Local>::from(modified)
+        - TimeDelta::milliseconds(get_duration_ms(filename) as i64);
+
+    let id = insert_recording_to_database(db_pool, filename, &time.to_rfc3339(), user_id)?;
+    mix_into_recording(filename, recover_tracks(filename), &[]);
+    finish_recording_in_database(db_pool, id);
+    grant_access(db_pool, id, &attendees[1..]);
+
+    let thumbnails_filename = filename.to_string();
+    thread::spawn(move || generate_thumbnails(&thumbnails_filename));
+
+    Some(id)
+}
+
+/// Moves a video that can't be given an owner out of the way, along with its audio
+/// tracks and event log.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the video (without the extension).
+fn quarantine(filename: &str) {
+    let folder = recordings_folder().join(QUARANTINE_FOLDER);
+    let _ = fs::create_dir_all(&folder);
+
+    for path in [get_video_path(filename), get_events_path(filename)] {
+        if let Some(name) = path.file_name() {
+            let _ = fs::rename(&path, folder.join(name));
+        }
+    }
+    move_tracks(recover_tracks(filename), &folder);
+}
+
+/// Repairs the recordings whose sessions were cut off when the server stopped, and
+/// cleans up after the processing that was cut off.
+///
+/// The recordings that are still playable are repaired, mixed with their audio and listed,
+/// the others are deleted. Videos in the recordings folder that don't belong to any
+/// recording are listed for the host found in their event log, or moved to the
+/// quarantine folder if there is none.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+pub fn recover_recordings(db_pool: &Pool<SqliteConnectionManager>) {
+    let Ok(entries) = fs::read_dir(recordings_folder()) else {
+        return;
+    };
+    let names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect();
+
+    for name in &names {
+        if INTERMEDIATE_ENDINGS
+            .iter()
+            .any(|ending| name.ends_with(ending))
+        {
+            let _ = fs::remove_file(recordings_folder().join(name));
+        }
+    }
+
+    let connection = db_pool.get().unwrap();
+
+    let mut query = connection
+        .prepare("SELECT recording_id, filename FROM recordings WHERE in_progress = 1")
+        .unwrap();
+    let in_progress: Vec<(i32, String)> = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    for (id, filename) in in_progress {
+        let tracks = recover_tracks(&filename);
+
+        if repair_video(&filename) {
+            let bookmarks = query_bookmarks(db_pool, id);
+            let chapters: Vec<_> = bookmarks.iter().map(Bookmark::chapter).collect();
+            mix_into_recording(&filename, tracks, &chapters);
+            finish_recording_in_database(db_pool, id);
+
+            // everyone who was in the session may watch it again, as after a clean exit
+            let attendees = find_attendees(&filename);
+            grant_access(db_pool, id, attendees.iter().skip(1));
+
+            let thumbnails_filename = filename.clone();
+            thread::spawn(move || generate_thumbnails(&thumbnails_filename));
+
+            info!(target: LOG_TARGET, "Recovered recording {} ({}.mp4).", id, filename);
+        } else {
+            remove_tracks(tracks);
+            delete_recording(db_pool, id, &filename);
+
+            info!(
+                target: LOG_TARGET,
+                "Deleted recording {} ({}.mp4), which had no usable video.", id, filename
+            );
+        }
+    }
+
+    let mut query = connection
+        .prepare("SELECT filename FROM recordings")
+        .unwrap();
+    let known: HashSet<String> = query
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+
+    for name in &names {
+        let Some(filename) = name.strip_suffix(".mp4") else {
+            continue;
+        };
+
+        let is_orphan = !filename.contains('.') && !known.contains(filename);
+        if !is_orphan || !get_video_path(filename).exists() {
+            continue;
+        }
+
+        match adopt_orphan(db_pool, filename) {
+            Some(id) => info!(
+                target: LOG_TARGET,
+                "Found {} without a recording, listed it as recording {}.", name, id
+            ),
+            None => {
+                quarantine(filename);
+                info!(
+                    target: LOG_TARGET,
+                    "Found {} without a recording or a known host, moved it to {}.",
+                    name,
+                    recordings_folder().join(QUARANTINE_FOLDER).display()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4::tests::{remove_recording, write_recording};
+
+    #[test]
+    fn cut_off_video_is_truncated_after_its_last_fragment() {
+        let filename = write_recording(0, 5);
+        let path = get_video_path(&filename);
+        let complete = fs::read(&path).unwrap();
+
+        // a fragment that was being written when the server stopped
+        let mut cut_off = complete.clone();
+        cut_off.extend([0x00, 0x00, 0x01, 0x00, b'm', b'o', b'o', b'f', 0x00, 0x00]);
+        fs::write(&path, cut_off).unwrap();
+
+        assert!(repair_video(&filename));
+        assert_eq!(fs::read(&path).unwrap(), complete);
+        assert_eq!(VideoIndex::read(&filename).unwrap().samples.len(), 5);
+
+        remove_recording(&filename);
+    }
+
+    #[test]
+    fn video_without_frames_is_not_repaired() {
+        let filename = write_recording(0, 0);
+        assert!(!repair_video(&filename));
+        remove_recording(&filename);
+
+        assert!(!repair_video(&uuid::Uuid::new_v4().to_string()));
+    }
+
+    #[test]
+    fn clock_leaves_out_the_time_it_was_paused() {
+        let mut clock = RecordingClock {
+            started: Instant::now() - Duration::from_secs(2),
+            paused: Duration::from_millis(500),
+            paused_at: None,
+        };
+        assert!(!clock.is_paused());
+        assert!(clock.elapsed() >= Duration::from_millis(1_500));
+        assert!(clock.elapsed() < Duration::from_millis(1_600));
+
+        clock.pause();
+        assert!(clock.is_paused());
+        let paused = clock.elapsed();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(clock.elapsed(), paused);
+
+        // pausing again doesn't move the start of the pause
+        clock.pause();
+        assert_eq!(clock.elapsed(), paused);
+
+        clock.resume();
+        assert!(!clock.is_paused());
+        assert!(clock.elapsed() >= paused);
+        assert!(clock.elapsed() < paused + Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,260 @@
+use std::time::{Duration, Instant};
+
+use stream_desk::{protocol::Packet, UserType};
+
+use crate::{
+    mp4::{nal_type, strip_start_code, NAL_SPS},
+    structs::Session,
+};
+
+/// A connection is congested if it receives less than this share of what is forwarded to it.
+const MIN_THROUGHPUT_RATIO: f32 = 0.75;
+/// Below this rate (in kilobits per second) the throughput is too noisy to compare.
+const MIN_MEASURED_KBPS: f32 = 100.0;
+/// A connection is congested if more decoded frames than this are waiting to be shown.
+const MAX_QUEUE_DEPTH: u32 = 2;
+/// A connection is congested if sending to it blocks for more than this share of the time.
+const MAX_BLOCKED_RATIO: f32 = 0.5;
+/// The number of healthy reports in a row before a connection moves to a better layer.
+const HEALTHY_REPORTS_TO_RAISE: u32 = 3;
+
+/// The simulcast state of the screen stream sent to a single connection.
+pub struct LayerState {
+    /// The layer being forwarded.
+    pub layer: u8,
+    /// The layer to switch to at its next keyframe.
+    pending_layer: Option<u8>,
+    /// The number of screen bytes forwarded since the last report.
+    forwarded_bytes: u64,
+    /// The time spent blocked on sending screen packets since the last report.
+    blocked: Duration,
+    /// When the last report arrived.
+    last_report: Instant,
+    /// The number of reports in a row without congestion.
+    healthy_reports: u32,
+}
+
+impl LayerState {
+    /// Creates the state of a new connection, which starts at the full quality layer.
+    pub fn new() -> Self {
+        Self {
+            layer: 0,
+            pending_layer: None,
+            forwarded_bytes: 0,
+            blocked: Duration::ZERO,
+            last_report: Instant::now(),
+            healthy_reports: 0,
+        }
+    }
+
+    /// Returns the layer the connection is on, or is about to switch to.
+    fn target_layer(&self) -> u8 {
+        self.pending_layer.unwrap_or(self.layer)
+    }
+
+    /// Updates the layer after a stream report from the participant.
+    ///
+    /// A congested connection moves to the next lower quality layer, and a connection
+    /// that stays healthy for a while moves back up. The switch itself happens at the
+    /// next keyframe of the new layer, so the participant's decoder isn't fed a partial stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `layers` - The number of layers the host encodes.
+    /// * `received_kbps` - The rate the participant received screen data at.
+    /// * `queue_depth` - The number of decoded frames waiting to be shown.
+    /// * `dropped_frames` - The number of frames dropped since the last report.
+    pub fn report(
+        &mut self,
+        layers: u8,
+        received_kbps: u32,
+        queue_depth: u32,
+        dropped_frames: u32,
+    ) {
+        let elapsed = self.last_report.elapsed().as_secs_f32().max(0.001);
+        let forwarded_kbps = (self.forwarded_bytes * 8 / 1000) as f32 / elapsed;
+        let blocked_ratio = self.blocked.as_secs_f32() / elapsed;
+
+        self.last_report = Instant::now();
+        self.forwarded_bytes = 0;
+        self.blocked = Duration::ZERO;
+
+        let congested = dropped_frames > 0
+            || queue_depth > MAX_QUEUE_DEPTH
+            || blocked_ratio > MAX_BLOCKED_RATIO
+            || (forwarded_kbps >= MIN_MEASURED_KBPS
+                && (received_kbps as f32) < forwarded_kbps * MIN_THROUGHPUT_RATIO);
+
+        let target = self.target_layer();
+        if congested {
+            self.healthy_reports = 0;
+            if target + 1 < layers {
+                self.pending_layer = Some(target + 1);
+            }
+        } else {
+            self.healthy_reports += 1;
+            if self.healthy_reports >= HEALTHY_REPORTS_TO_RAISE && target > 0 {
+                self.healthy_reports = 0;
+                self.pending_layer = Some(target - 1);
+            }
+        }
+
+        if self.pending_layer == Some(self.layer) {
+            self.pending_layer = None;
+        }
+    }
+
+    /// Makes sure the connection is on a layer the host still encodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `layers` - The number of layers the host encodes.
+    pub fn limit_layers(&mut self, layers: u8) {
+        if self.layer >= layers {
+            // the host restarted its encoders, so the next packets start with a keyframe
+            self.layer = 0;
+            self.pending_layer = None;
+        } else if self.pending_layer.is_some_and(|pending| pending >= layers) {
+            self.pending_layer = None;
+        }
+    }
+
+    /// Decides if a screen packet should be forwarded to the connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The layer of the packet.
+    /// * `keyframe_start` - Whether the packet starts a keyframe.
+    ///
+    /// # Returns
+    ///
+    /// Whether the packet belongs to the connection's layer.
+    fn wants(&mut self, layer: u8, keyframe_start: bool) -> bool {
+        if self.pending_layer == Some(layer) && keyframe_start {
+            self.layer = layer;
+            self.pending_layer = None;
+        }
+
+        layer == self.layer
+    }
+}
+
+/// Checks if a NAL unit starts a keyframe, which the encoder starts with an SPS.
+///
+/// # Arguments
+///
+/// * `bytes` - The NAL unit, with its start code.
+pub fn is_keyframe_start(bytes: &[u8]) -> bool {
+    nal_type(strip_start_code(bytes)) == NAL_SPS
+}
+
+/// Forwards a screen packet to every participant that is on its layer.
+///
+/// # Arguments
+///
+/// * `session` - The session the packet belongs to.
+/// * `layer` - The simulcast layer of the packet.
+/// * `bytes` - The NAL unit in the packet.
+///
+/// # Returns
+///
+/// An `std::io::Result<()>` that signifies if something went wrong.
+pub fn forward_screen(session: &mut Session, layer: u8, bytes: Vec<u8>) -> std::io::Result<()> {
+    let keyframe_start = is_keyframe_start(&bytes);
+    let length = bytes.len() as u64;
+    let packet = Packet::Screen { layer, bytes };
+
+    for connection in session.connections.values_mut() {
+        if connection.user_type != UserType::Participant
+            && connection.user_type != UserType::Controller
+        {
+            continue;
+        }
+
+        if !connection.layer.wants(layer, keyframe_start) {
+            continue;
+        }
+
+        let start = Instant::now();
+        connection.channel.send(packet.clone())?;
+        connection.layer.blocked += start.elapsed();
+        connection.layer.forwarded_bytes += length;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn congestion_lowers_the_layer_at_its_next_keyframe() {
+        let mut state = LayerState::new();
+
+        state.report(3, 0, 0, 2);
+        assert_eq!(state.target_layer(), 1);
+
+        // the current layer is kept until the new one starts a keyframe
+        assert!(state.wants(0, true));
+        assert!(!state.wants(1, false));
+        assert_eq!(state.layer, 0);
+
+        assert!(state.wants(1, true));
+        assert!(!state.wants(0, true));
+        assert_eq!(state.layer, 1);
+    }
+
+    #[test]
+    fn congestion_stops_at_the_lowest_layer() {
+        let mut state = LayerState::new();
+
+        for _ in 0..5 {
+            state.report(2, 0, MAX_QUEUE_DEPTH + 1, 0);
+        }
+
+        assert_eq!(state.target_layer(), 1);
+    }
+
+    #[test]
+    fn healthy_reports_raise_the_layer() {
+        let mut state = LayerState::new();
+        state.report(3, 0, 0, 1);
+        state.wants(1, true);
+
+        for _ in 0..HEALTHY_REPORTS_TO_RAISE - 1 {
+            state.report(3, 0, 0, 0);
+            assert_eq!(state.target_layer(), 1);
+        }
+        state.report(3, 0, 0, 0);
+        assert_eq!(state.target_layer(), 0);
+
+        // a congested report in between starts the count over
+        let mut state = LayerState::new();
+        state.report(3, 0, 0, 1);
+        state.wants(1, true);
+        state.report(3, 0, 0, 0);
+        state.report(3, 0, 0, 1);
+        state.report(3, 0, 0, 0);
+        assert_eq!(state.target_layer(), 2);
+    }
+
+    #[test]
+    fn fewer_layers_move_the_connection_to_full_quality() {
+        let mut state = LayerState::new();
+        state.report(3, 0, 0, 1);
+        state.wants(1, true);
+
+        state.limit_layers(1);
+
+        assert_eq!(state.layer, 0);
+        assert_eq!(state.target_layer(), 0);
+    }
+
+    #[test]
+    fn keyframes_start_with_an_sps() {
+        assert!(is_keyframe_start(&[0, 0, 1, 0x67, 0x42]));
+        assert!(!is_keyframe_start(&[0, 0, 1, 0x41]));
+        assert!(!is_keyframe_start(&[0, 0, 1]));
+    }
+}
@@ -0,0 +1,333 @@
+use std::{fs, sync::Arc, thread, time::Duration};
+
+use chrono::{DateTime, Local, TimeDelta};
+use log::info;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::params;
+use stream_desk::{
+    protocol::{Packet, ResultPacket},
+    LOG_TARGET,
+};
+
+use crate::{
+    delete_recording,
+    events::get_events_path,
+    get_video_path,
+    thumbnails::{get_poster_path, get_sprite_path},
+};
+
+/// The storage the recordings of a user may take, unless the user was given a quota
+/// of their own in `users.storage_quota`.
+const DEFAULT_STORAGE_QUOTA: u64 = 5 * 1024 * 1024 * 1024;
+
+/// The longest a recording can be kept for, other than forever.
+const MAX_RETENTION_DAYS: u32 = 10 * 365;
+
+/// How often the expired recordings are deleted.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Gets the size of a recording's files on the disk.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+///
+/// # Returns
+///
+/// The size of the video, the thumbnails and the event log in bytes.
+fn recording_size(filename: &str) -> u64 {
+    [
+        get_video_path(filename),
+        get_sprite_path(filename),
+        get_poster_path(filename),
+        get_events_path(filename),
+    ]
+    .iter()
+    .filter_map(|path| fs::metadata(path).ok())
+    .map(|metadata| metadata.len())
+    .sum()
+}
+
+/// Gets the storage a user's recordings take, including the ones in progress.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user.
+///
+/// # Returns
+///
+/// The size of the user's recordings in bytes.
+pub fn storage_usage(db_pool: &Pool<SqliteConnectionManager>, user_id: i32) -> u64 {
+    let connection = db_pool.get().unwrap();
+
+    let mut query = connection
+        .prepare("SELECT filename FROM recordings WHERE user_id = ?1")
+        .unwrap();
+
+    query
+        .query_map([user_id], |row| row.get::<_, String>(0))
+        .unwrap()
+        .filter_map(Result::ok)
+        .map(|filename| recording_size(&filename))
+        .sum()
+}
+
+/// Gets the storage a user's recordings may take.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user.
+///
+/// # Returns
+///
+/// The user's quota in bytes.
+pub fn storage_quota(db_pool: &Pool<SqliteConnectionManager>, user_id: i32) -> u64 {
+    db_pool
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT storage_quota FROM users WHERE user_id = ?1",
+            [user_id],
+            |row| row.get::<_, Option<i64>>(0),
+        )
+        .ok()
+        .flatten()
+        .map_or(DEFAULT_STORAGE_QUOTA, |quota| quota.max(0) as u64)
+}
+
+/// Gets how much more a user's recordings may take.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user.
+///
+/// # Returns
+///
+/// The part of the user's quota that isn't used, in bytes.
+pub fn storage_left(db_pool: &Pool<SqliteConnectionManager>, user_id: i32) -> u64 {
+    storage_quota(db_pool, user_id).saturating_sub(storage_usage(db_pool, user_id))
+}
+
+/// Checks whether a user's recordings leave room for a new one.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user.
+///
+/// # Returns
+///
+/// `true` if the user's recordings take less than their quota.
+pub fn has_storage_left(db_pool: &Pool<SqliteConnectionManager>, user_id: i32) -> bool {
+    storage_left(db_pool, user_id) > 0
+}
+
+/// Creates the packet that tells a user how much of their storage quota they use.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `user_id` - The ID of the user.
+pub fn storage_packet(db_pool: &Pool<SqliteConnectionManager>, user_id: i32) -> Packet {
+    Packet::StorageUsage {
+        used_bytes: storage_usage(db_pool, user_id),
+        quota_bytes: storage_quota(db_pool, user_id),
+    }
+}
+
+/// Gets when a recording expires.
+///
+/// # Arguments
+///
+/// * `time` - The time of the recording, in RFC 3339.
+/// * `retention_days` - How many days the recording is kept for, 0 if it's kept forever.
+///
+/// # Returns
+///
+/// The time the recording expires at, or `None` if it's kept forever or its time
+/// can't be read.
+fn expiry(time: &str, retention_days: u32) -> Option<DateTime<Local>> {
+    if retention_days == 0 {
+        return None;
+    }
+
+    let time = DateTime::parse_from_rfc3339(time).ok()?;
+    Some(time.with_timezone(&Local) + TimeDelta::days(retention_days as i64))
+}
+
+/// Changes how long a recording is kept, on behalf of its owner.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+/// * `id` - The ID of the recording.
+/// * `time` - The time of the recording, in RFC 3339.
+/// * `retention_days` - How many days to keep the recording for, 0 to keep it forever.
+///
+/// # Returns
+///
+/// The `ResultPacket` to answer the owner with.
+pub fn set_retention(
+    db_pool: &Pool<SqliteConnectionManager>,
+    id: i32,
+    time: &str,
+    retention_days: u32,
+) -> ResultPacket {
+    if retention_days > MAX_RETENTION_DAYS {
+        return ResultPacket::Failure("Recordings can't be kept that long.".to_owned());
+    }
+
+    // the sweeper would delete the recording right away
+    if expiry(time, retention_days).is_some_and(|expiry| expiry <= Local::now()) {
+        return ResultPacket::Failure("The recording is already older than that.".to_owned());
+    }
+
+    let updated = db_pool.get().unwrap().execute(
+        "UPDATE recordings SET retention_days = ?1 WHERE recording_id = ?2",
+        params![retention_days, id],
+    );
+
+    match updated {
+        Ok(_) => ResultPacket::Success(retention_days.to_string()),
+        Err(_) => {
+            ResultPacket::Failure("Error changing how long the recording is kept.".to_owned())
+        }
+    }
+}
+
+/// Deletes the recordings that were kept for as long as their retention period.
+///
+/// Only the recordings whose owner chose a retention period expire, the others
+/// (with no `retention_days`, like the ones from before recordings expired) are
+/// kept forever.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+fn delete_expired_recordings(db_pool: &Pool<SqliteConnectionManager>) {
+    let expired: Vec<(i32, String)> = {
+        let connection = db_pool.get().unwrap();
+
+        let mut query = connection
+            .prepare(
+                "SELECT recording_id, filename, time, retention_days FROM recordings
+                    WHERE in_progress = 0 AND retention_days > 0",
+            )
+            .unwrap();
+
+        let now = Local::now();
+        query
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, u32>(3)?,
+                ))
+            })
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|(_, _, time, retention_days)| {
+                expiry(time, *retention_days).is_some_and(|expiry| expiry <= now)
+            })
+            .map(|(id, filename, ..)| (id, filename))
+            .collect()
+    };
+
+    for (id, filename) in expired {
+        delete_recording(db_pool, id, &filename);
+
+        info!(
+            target: LOG_TARGET,
+            "Deleted recording {} ({}.mp4), which expired.", id, filename
+        );
+    }
+}
+
+/// Starts a thread that deletes the expired recordings, now and then every
+/// `SWEEP_INTERVAL`.
+///
+/// # Arguments
+///
+/// * `db_pool` - The pool of the database connections.
+pub fn thread_delete_expired(db_pool: Arc<Pool<SqliteConnectionManager>>) {
+    thread::spawn(move || loop {
+        delete_expired_recordings(&db_pool);
+        thread::sleep(SWEEP_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_expire_after_their_retention_period() {
+        let time = "2025-03-01T12:00:00+00:00";
+        let recorded = DateTime::parse_from_rfc3339(time).unwrap();
+
+        assert_eq!(expiry(time, 30).unwrap(), recorded + TimeDelta::days(30));
+        assert_eq!(expiry(time, 0), None);
+        assert_eq!(expiry("not a time", 30), None);
+    }
+
+    #[test]
+    fn only_expired_recordings_are_deleted() {
+        let db_pool = Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::memory())
+            .unwrap();
+        db_pool
+            .get()
+            .unwrap()
+            .execute(
+                "CREATE TABLE recordings (
+                    recording_id INTEGER PRIMARY KEY,
+                    filename TEXT NOT NULL,
+                    time TEXT NOT NULL,
+                    in_progress INTEGER NOT NULL DEFAULT 0,
+                    retention_days INTEGER,
+                    parent_id INTEGER
+                )",
+                [],
+            )
+            .unwrap();
+
+        let long_ago = (Local::now() - TimeDelta::days(40)).to_rfc3339();
+        let recently = (Local::now() - TimeDelta::days(2)).to_rfc3339();
+        for (id, time, in_progress, retention_days) in [
+            (1, &long_ago, 0, Some(30)),
+            (2, &long_ago, 0, None),
+            (3, &long_ago, 0, Some(0)),
+            (4, &long_ago, 1, Some(30)),
+            (5, &recently, 0, Some(30)),
+        ] {
+            db_pool
+                .get()
+                .unwrap()
+                .execute(
+                    "INSERT INTO recordings (recording_id, filename, time, in_progress, retention_days)
+                        VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![id, format!("expiry-test-{id}"), time, in_progress, retention_days],
+                )
+                .unwrap();
+        }
+
+        delete_expired_recordings(&db_pool);
+
+        let connection = db_pool.get().unwrap();
+        let mut query = connection
+            .prepare("SELECT recording_id FROM recordings ORDER BY recording_id")
+            .unwrap();
+        let kept: Vec<i32> = query
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .filter_map(Result::ok)
+            .collect();
+        assert_eq!(kept, [2, 3, 4, 5]);
+    }
+}
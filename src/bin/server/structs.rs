@@ -1,23 +1,64 @@
-use std::{collections::HashMap, sync::mpsc::Sender};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::mpsc::Sender,
+};
 
-use stream_desk::{protocol::Packet, secure_channel::SecureChannel, UserType};
+use stream_desk::{
+    protocol::{Packet, RecordingState},
+    secure_channel::SecureChannel,
+    UserType,
+};
 
-/// Represents a recording, with a filename and a timestamp.
+use crate::{audio::SessionAudio, events::EventLog, party::WatchParty, simulcast::LayerState};
+
+/// Represents a recording, with a filename, a timestamp, and the title and
+/// description its owner gave it.
 pub struct Recording {
     pub filename: String,
     pub time: String,
+    pub title: String,
+    pub description: String,
+    /// The username of the owner if the recording was shared with the user, `None` if it's theirs.
+    pub owner: Option<String>,
+    /// How many days the recording is kept before it's deleted, 0 if it's kept forever.
+    pub retention_days: u32,
 }
 
 /// Represents a connection to a client with a `SecureChannel` and the user type.
 pub struct Connection {
     pub channel: SecureChannel,
     pub user_type: UserType,
+    /// The simulcast layer of the screen stream sent to the client.
+    pub layer: LayerState,
 }
 
 /// Represents a session with all of the connections and the pending requests.
 pub struct Session {
     pub connections: HashMap<String, Connection>,
     pub pending_join: HashMap<String, (Connection, Sender<bool>)>,
+    /// Whether the host opted in to clipboard synchronization with the controller.
+    pub clipboard_sync: bool,
+    /// The file transfers that were offered, by the username of the user who offered the
+    /// file and the transfer ID they chose, with the offered file size.
+    pub transfers: HashMap<(String, u32), u64>,
+    /// The total number of file bytes transferred in the session.
+    pub transferred_bytes: u64,
+    /// The size of the area the host shares, once the host announced it.
+    pub screen_size: Option<(u32, u32)>,
+    /// The number of simulcast layers the host encodes.
+    pub screen_layers: u8,
+    /// The audio streams of the host and the participants.
+    pub audio: SessionAudio,
+    /// Whether the session is recorded, which the participants are told.
+    pub recording_state: RecordingState,
+    /// The chat, joins and leaves and control changes, written along with the recording.
+    pub events: EventLog,
+    /// The ID of the session's recording in the database, if the session is recorded.
+    pub recording_id: Option<i32>,
+    /// The usernames of everyone who was let into the session, who get access to its recording.
+    pub attendees: HashSet<String>,
+    /// The state of the watch party, if the session is one instead of a shared screen.
+    pub watch_party: Option<WatchParty>,
 }
 
 impl Session {
@@ -38,6 +79,17 @@ impl Session {
         Self {
             connections,
             pending_join: HashMap::new(),
+            clipboard_sync: false,
+            transfers: HashMap::new(),
+            transferred_bytes: 0,
+            screen_size: None,
+            screen_layers: 1,
+            audio: SessionAudio::new(),
+            recording_state: RecordingState::Off,
+            events: EventLog::new(),
+            recording_id: None,
+            attendees: HashSet::new(),
+            watch_party: None,
         }
     }
 
@@ -93,4 +145,30 @@ impl Session {
             .channel
             .clone()
     }
+
+    /// Finds the username of the host
+    ///
+    /// # Returns
+    ///
+    /// The username of the host.
+    pub fn host_username(&self) -> String {
+        self.connections
+            .iter()
+            .find(|(_, conn)| conn.user_type == UserType::Host)
+            .unwrap()
+            .0
+            .clone()
+    }
+
+    /// Finds the connection of the controller, if there is one.
+    ///
+    /// # Returns
+    ///
+    /// The connection of the controller, or `None` if no user is controlling.
+    pub fn controller(&self) -> Option<SecureChannel> {
+        self.connections
+            .iter()
+            .find(|(_, conn)| conn.user_type == UserType::Controller)
+            .map(|(_, conn)| conn.channel.clone())
+    }
 }
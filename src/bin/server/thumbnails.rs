@@ -0,0 +1,184 @@
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use log::info;
+use stream_desk::{protocol::Packet, LOG_TARGET};
+
+use crate::{get_duration_ms, get_video_path, has_ffmpeg, recordings_folder};
+
+/// The size of a thumbnail in the sprite sheet.
+const THUMBNAIL_WIDTH: u32 = 160;
+const THUMBNAIL_HEIGHT: u32 = 90;
+
+/// The width of the poster thumbnail shown in the menu.
+const POSTER_WIDTH: u32 = 320;
+
+/// The shortest time between two thumbnails.
+const MIN_INTERVAL_MS: u32 = 10_000;
+
+/// The most thumbnails in a sprite sheet, so long recordings get sparser thumbnails
+/// instead of a huge image.
+const MAX_THUMBNAILS: u32 = 400;
+
+/// The number of thumbnails in every row of the sprite sheet.
+const SHEET_COLUMNS: u32 = 10;
+
+/// How a recording's thumbnails are laid out in its sprite sheet.
+struct SheetLayout {
+    /// The time between two thumbnails in milliseconds.
+    interval_ms: u32,
+    /// The number of rows of thumbnails.
+    rows: u32,
+}
+
+impl SheetLayout {
+    /// Computes the layout of the sprite sheet of a recording.
+    ///
+    /// The same duration always gives the same layout, so it doesn't have to be stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration_ms` - The duration of the recording in milliseconds.
+    fn for_duration(duration_ms: u64) -> Self {
+        let interval_ms = (duration_ms.div_ceil(MAX_THUMBNAILS as u64) as u32).max(MIN_INTERVAL_MS);
+
+        // a thumbnail at the start and one every interval after it
+        let count = (duration_ms / interval_ms as u64) as u32 + 1;
+
+        Self {
+            interval_ms,
+            rows: count.div_ceil(SHEET_COLUMNS),
+        }
+    }
+}
+
+/// Gets the path of the thumbnail sprite sheet of a recording.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+pub fn get_sprite_path(filename: &str) -> PathBuf {
+    recordings_folder().join(format!("{filename}.sprite.jpg"))
+}
+
+/// Gets the path of the poster thumbnail of a recording.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+pub fn get_poster_path(filename: &str) -> PathBuf {
+    recordings_folder().join(format!("{filename}.poster.jpg"))
+}
+
+/// Generates the thumbnail sprite sheet and the poster of a finished recording,
+/// next to its video, with FFmpeg.
+///
+/// Without FFmpeg, the recording has no thumbnails and the client shows it without them.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+pub fn generate_thumbnails(filename: &str) {
+    if !has_ffmpeg() {
+        return;
+    }
+
+    let video_path = get_video_path(filename);
+    let duration_ms = get_duration_ms(filename);
+    let layout = SheetLayout::for_duration(duration_ms);
+
+    let sprite_filter = format!(
+        "fps=1000/{},scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,tile={}x{}",
+        layout.interval_ms,
+        SHEET_COLUMNS,
+        layout.rows,
+        w = THUMBNAIL_WIDTH,
+        h = THUMBNAIL_HEIGHT,
+    );
+
+    let sprite_status = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-i")
+        .arg(&video_path)
+        .args(["-vf", &sprite_filter, "-frames:v", "1", "-q:v", "5"])
+        .arg(get_sprite_path(filename))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    // a frame a little into the recording, after the first frames settle
+    let poster_time_ms = (duration_ms / 10).min(5000);
+    let poster_status = Command::new("ffmpeg")
+        .arg("-y")
+        .args(["-ss", &format!("{:.3}", poster_time_ms as f64 / 1000.0)])
+        .arg("-i")
+        .arg(&video_path)
+        .args([
+            "-vf",
+            &format!("scale={}:-2", POSTER_WIDTH),
+            "-frames:v",
+            "1",
+            "-q:v",
+            "4",
+        ])
+        .arg(get_poster_path(filename))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    if !sprite_status.is_ok_and(|status| status.success())
+        || !poster_status.is_ok_and(|status| status.success())
+    {
+        info!(
+            target: LOG_TARGET,
+            "Couldn't generate the thumbnails of recording {}.", filename
+        );
+    }
+}
+
+/// Reads the poster thumbnail of a recording.
+///
+/// # Arguments
+///
+/// * `id` - The ID of the recording.
+/// * `filename` - The filename of the recording (without the extension).
+///
+/// # Returns
+///
+/// A `Packet::RecordingPoster`, or `None` if the recording has no poster.
+pub fn poster_packet(id: i32, filename: &str) -> Option<Packet> {
+    let bytes = std::fs::read(get_poster_path(filename)).ok()?;
+
+    Some(Packet::RecordingPoster { id, bytes })
+}
+
+/// Reads the thumbnail sprite sheet of a recording.
+///
+/// # Arguments
+///
+/// * `filename` - The filename of the recording (without the extension).
+///
+/// # Returns
+///
+/// A `Packet::Thumbnails`, whose bytes are empty if the recording has no thumbnails.
+pub fn thumbnails_packet(filename: &str) -> Packet {
+    let Ok(bytes) = std::fs::read(get_sprite_path(filename)) else {
+        return Packet::Thumbnails {
+            interval_ms: 0,
+            columns: 0,
+            rows: 0,
+            bytes: Vec::new(),
+        };
+    };
+
+    let layout = SheetLayout::for_duration(get_duration_ms(filename));
+
+    Packet::Thumbnails {
+        interval_ms: layout.interval_ms,
+        columns: SHEET_COLUMNS,
+        rows: layout.rows,
+        bytes,
+    }
+}
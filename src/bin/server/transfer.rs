@@ -0,0 +1,173 @@
+use log::info;
+use stream_desk::{protocol::Packet, secure_channel::SecureChannel, UserType, LOG_TARGET};
+
+use crate::structs::Session;
+
+/// The maximum size of a single transferred file.
+const MAX_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+/// The maximum number of file bytes that can be transferred in a single session.
+const MAX_SESSION_TRANSFER_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Rejects a file transfer by sending a `FileCancel` back to the sender.
+///
+/// # Arguments
+///
+/// * `channel` - The `SecureChannel` of the sender.
+/// * `id` - The ID of the transfer.
+/// * `target` - The username of the other side of the transfer.
+///
+/// # Returns
+///
+/// An `std::io::Result<()>` that signifies if the message was sent successfully.
+fn reject_transfer(channel: &mut SecureChannel, id: u32, target: &str) -> std::io::Result<()> {
+    let cancel = Packet::FileCancel {
+        id,
+        username: target.to_string(),
+    };
+    channel.send(cancel)
+}
+
+/// Checks a file transfer packet against the session limits and forwards it.
+///
+/// File transfers are only allowed between the host and a participant. Packets from
+/// a participant always go to the host, and packets from the host go to the user
+/// named in the packet. The `username` field is rewritten to the sender before forwarding,
+/// so the receiver knows who the other side of the transfer is. Transfers are told apart
+/// by the user who offered the file along with the ID, as every user chooses their own IDs.
+///
+/// # Arguments
+///
+/// * `session` - The session the sender is in.
+/// * `packet` - One of the `File*` packets.
+/// * `sender` - The username of the sender.
+/// * `channel` - The `SecureChannel` of the sender.
+///
+/// # Returns
+///
+/// An `std::io::Result<()>` that signifies if something went wrong.
+pub fn forward_transfer_packet(
+    session: &mut Session,
+    packet: Packet,
+    sender: &str,
+    channel: &mut SecureChannel,
+) -> std::io::Result<()> {
+    let (id, username) = match &packet {
+        Packet::FileOffer { id, username, .. }
+        | Packet::FileAccept { id, username, .. }
+        | Packet::FileChunk { id, username, .. }
+        | Packet::FileEnd { id, username, .. }
+        | Packet::FileCancel { id, username } => (*id, username.clone()),
+        _ => return Ok(()),
+    };
+
+    let sender_type = session.connections.get(sender).unwrap().user_type;
+    let target = if sender_type == UserType::Host {
+        username
+    } else {
+        session.host_username()
+    };
+
+    if target == sender || !session.connections.contains_key(&target) {
+        return reject_transfer(channel, id, &target);
+    }
+
+    // the offerer sends the chunks and the end, the other side accepts
+    let offered_key = (sender.to_string(), id);
+    let accepted_key = (target.clone(), id);
+
+    // check the limits and rewrite the username to the sender
+    let packet = match packet {
+        Packet::FileOffer { filename, size, .. } => {
+            if size > MAX_FILE_SIZE || session.transferred_bytes + size > MAX_SESSION_TRANSFER_SIZE
+            {
+                info!(
+                    target: LOG_TARGET,
+                    "Rejected file {} from {}: over the size limit.", filename, sender
+                );
+                return reject_transfer(channel, id, &target);
+            }
+
+            session.transfers.insert(offered_key, size);
+
+            Packet::FileOffer {
+                id,
+                username: sender.to_string(),
+                filename,
+                size,
+            }
+        }
+
+        Packet::FileAccept { offset, .. } => {
+            if !session.transfers.contains_key(&accepted_key) {
+                return reject_transfer(channel, id, &target);
+            }
+
+            Packet::FileAccept {
+                id,
+                username: sender.to_string(),
+                offset,
+            }
+        }
+
+        Packet::FileChunk { offset, bytes, .. } => {
+            let chunk_size = bytes.len() as u64;
+            // the offset comes from the client, so it may overflow
+            let within_file = session
+                .transfers
+                .get(&offered_key)
+                .zip(offset.checked_add(chunk_size))
+                .is_some_and(|(size, end)| end <= *size);
+            let within_session =
+                session.transferred_bytes + chunk_size <= MAX_SESSION_TRANSFER_SIZE;
+
+            if !within_file || !within_session {
+                // stop both sides of the transfer
+                session.transfers.remove(&offered_key);
+                if let Some(connection) = session.connections.get_mut(&target) {
+                    reject_transfer(&mut connection.channel, id, sender)?;
+                }
+                return reject_transfer(channel, id, &target);
+            }
+
+            session.transferred_bytes += chunk_size;
+
+            Packet::FileChunk {
+                id,
+                username: sender.to_string(),
+                offset,
+                bytes,
+            }
+        }
+
+        Packet::FileEnd { checksum, .. } => {
+            session.transfers.remove(&offered_key);
+
+            Packet::FileEnd {
+                id,
+                username: sender.to_string(),
+                checksum,
+            }
+        }
+
+        Packet::FileCancel { .. } => {
+            // either side may cancel
+            if session.transfers.remove(&offered_key).is_none() {
+                session.transfers.remove(&accepted_key);
+            }
+
+            Packet::FileCancel {
+                id,
+                username: sender.to_string(),
+            }
+        }
+
+        _ => return Ok(()),
+    };
+
+    session
+        .connections
+        .get_mut(&target)
+        .unwrap()
+        .channel
+        .send(packet)
+}
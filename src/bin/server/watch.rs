@@ -1,139 +1,746 @@
-use crate::get_video_path;
-use h264_reader::{
-    annexb::AnnexBReader,
-    nal::{Nal, RefNal},
-    push::NalInterest,
+use crate::{
+    bookmarks::query_bookmarks,
+    clip::thread_create_clip,
+    events::read_events,
+    get_video_path, has_ffmpeg,
+    mp4::{nal_type, VideoIndex, ACCESS_UNIT_DELIMITER, NAL_AUD},
+    party::{send_party_frame, send_party_packet},
+    structs::Recording,
+    thumbnails::thumbnails_packet,
+    SharedSession,
 };
 
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
 use stream_desk::{protocol::Packet, secure_channel::SecureChannel};
 
 use std::{
+    fs::File,
     io::Read,
     process::{Child, ChildStdout, Command, Stdio},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
         Arc,
     },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
+    vec,
 };
 
-/// Starts an `ffmpeg` process that encodes the video file with H.264.
+/// The slowest and fastest playback speeds, in percent of real time.
+const MIN_SPEED_PERCENT: u32 = 25;
+const MAX_SPEED_PERCENT: u32 = 400;
+
+/// How far ahead of the playback clock the frames are sent, so the client has a buffer.
+const PACING_LEAD_MS: u64 = 1000;
+
+/// The longest the sending thread sleeps before checking whether it should stop.
+const PACING_SLEEP: Duration = Duration::from_millis(50);
+
+/// A frame to send: its presentation time in milliseconds and its NAL units, with
+/// start codes.
+type VideoFrame = (u64, Vec<Vec<u8>>);
+
+/// The speed and the pause state of a playback, which the sending thread follows.
+#[derive(Clone)]
+pub struct PlaybackControl {
+    /// The playback speed, in percent of real time.
+    pub speed_percent: Arc<AtomicU32>,
+    /// Whether the frames are held back, which only watch parties do.
+    pub paused: Arc<AtomicBool>,
+}
+
+impl PlaybackControl {
+    /// Creates the control of a playback at real time.
+    pub fn new() -> Self {
+        Self {
+            speed_percent: Arc::new(AtomicU32::new(100)),
+            paused: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Changes the playback speed, within the supported speeds.
+    ///
+    /// # Arguments
+    ///
+    /// * `speed_percent` - The requested speed, in percent of real time.
+    ///
+    /// # Returns
+    ///
+    /// The speed the playback continues at.
+    pub fn set_speed(&self, speed_percent: u32) -> u32 {
+        let speed_percent = speed_percent.clamp(MIN_SPEED_PERCENT, MAX_SPEED_PERCENT);
+        self.speed_percent.store(speed_percent, Ordering::Relaxed);
+
+        speed_percent
+    }
+
+    /// Gets the speed the clock should run at, which is 0 while paused.
+    fn clock_speed(&self) -> u32 {
+        if self.paused.load(Ordering::Relaxed) {
+            0
+        } else {
+            self.speed_percent.load(Ordering::Relaxed)
+        }
+    }
+}
+
+/// Where the frames of a playback are sent.
+pub enum PlaybackTarget {
+    /// A user watching on their own.
+    Viewer(Box<SecureChannel>),
+    /// Everyone in a watch party.
+    Party(SharedSession),
+}
+
+impl PlaybackTarget {
+    /// Sends a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_ms` - The time the playback was started from, in milliseconds.
+    /// * `pts_ms` - The presentation time of the frame, in milliseconds.
+    /// * `nals` - The NAL units of the frame.
+    ///
+    /// # Returns
+    ///
+    /// An `std::io::Result<()>` that signifies if the frame was sent successfully.
+    fn send_frame(
+        &mut self,
+        start_ms: u64,
+        pts_ms: u64,
+        nals: Vec<Vec<u8>>,
+    ) -> std::io::Result<()> {
+        match self {
+            Self::Viewer(channel) => {
+                for packet in frame_packets(pts_ms, nals) {
+                    channel.send(packet)?;
+                }
+            }
+            Self::Party(session) => {
+                let mut session = session.lock().unwrap();
+                send_party_frame(&mut session, start_ms, pts_ms, nals);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends a packet that isn't part of a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - The packet.
+    ///
+    /// # Returns
+    ///
+    /// An `std::io::Result<()>` that signifies if the packet was sent successfully.
+    fn send_packet(&mut self, packet: Packet) -> std::io::Result<()> {
+        match self {
+            Self::Viewer(channel) => channel.send(packet),
+            Self::Party(session) => {
+                let mut session = session.lock().unwrap();
+                send_party_packet(&mut session, packet);
+                Ok(())
+            }
+        }
+    }
+
+    /// Tells the viewers the stream ended, so their decoders hand out the last frames.
+    ///
+    /// # Returns
+    ///
+    /// An `std::io::Result<()>` that signifies if the packet was sent successfully.
+    fn end(&mut self) -> std::io::Result<()> {
+        self.send_packet(Packet::None)
+    }
+}
+
+/// Creates the packets that send a frame: its timestamp, then an access unit delimiter,
+/// which starts every frame, and then its NAL units.
 ///
 /// # Arguments
 ///
-/// * `filename` - The filename of the video (without the extension).
-/// * `time_seconds` - The wanted starting time of the video.
+/// * `pts_ms` - The presentation time of the frame, in milliseconds.
+/// * `nals` - The NAL units of the frame.
 ///
 /// # Returns
-/// The subprocess `Child` object.
-fn ffmpeg_send_recording(filename: &str, time_seconds: i32) -> Child {
-    let input_path = get_video_path(filename);
-
-    let ffmpeg = Command::new("ffmpeg")
-        .args(&[
-            "-ss",
-            &time_seconds.to_string(),
-            "-i",
-            input_path.to_str().unwrap(),
-            "-vcodec",
-            "libx264",
-            "-preset",
-            "ultrafast",
-            "-tune",
-            "zerolatency",
-            "-force_key_frames",
-            "expr:gte(t,0)", // Force keyframe at the beginning
-            "-f",
-            "h264",
-            "-",
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()
-        .expect("Failed to start FFmpeg");
-
-    ffmpeg
-}
-
-/// Starts a thread to send the H.264 NAL units.
+///
+/// The packets to send, in order.
+pub fn frame_packets(pts_ms: u64, nals: Vec<Vec<u8>>) -> Vec<Packet> {
+    let mut packets = vec![
+        Packet::FrameTime { pts_ms },
+        Packet::Screen {
+            layer: 0,
+            bytes: ACCESS_UNIT_DELIMITER.to_vec(),
+        },
+    ];
+    packets.extend(nals.into_iter().map(|nal| Packet::Screen {
+        layer: 0,
+        bytes: nal,
+    }));
+
+    packets
+}
+
+/// The playback position the frames are sent by, which runs at the playback speed.
+struct PlaybackClock {
+    /// The instant and the position the clock was last set at.
+    anchor: Instant,
+    anchor_ms: u64,
+    speed_percent: u32,
+}
+
+impl PlaybackClock {
+    /// Starts the clock at a position.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_ms` - The position in milliseconds.
+    /// * `speed_percent` - The playback speed, in percent of real time.
+    fn new(start_ms: u64, speed_percent: u32) -> Self {
+        Self {
+            anchor: Instant::now(),
+            anchor_ms: start_ms,
+            speed_percent,
+        }
+    }
+
+    /// Gets the current position in milliseconds.
+    fn now_ms(&self) -> u64 {
+        self.anchor_ms + self.anchor.elapsed().as_millis() as u64 * self.speed_percent as u64 / 100
+    }
+
+    /// Changes the speed of the clock, continuing from the current position.
+    ///
+    /// # Arguments
+    ///
+    /// * `speed_percent` - The playback speed, in percent of real time.
+    fn set_speed(&mut self, speed_percent: u32) {
+        if speed_percent != self.speed_percent {
+            self.anchor_ms = self.now_ms();
+            self.anchor = Instant::now();
+            self.speed_percent = speed_percent;
+        }
+    }
+}
+
+/// Reads the frames of a recording from its file, as they were recorded.
+struct StoredFrames {
+    file: File,
+    index: Arc<VideoIndex>,
+    /// The index of the next frame to read.
+    next: usize,
+    /// The parameter sets to send before the first frame, until they are sent.
+    parameter_sets: Option<Vec<Vec<u8>>>,
+}
+
+impl StoredFrames {
+    /// Opens a recording to read it from the keyframe before a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The filename of the video (without the extension).
+    /// * `index` - The index of the frames of the video.
+    /// * `start_ms` - The wanted starting time of the video, in milliseconds.
+    fn open(filename: &str, index: Arc<VideoIndex>, start_ms: u64) -> Option<Self> {
+        Some(Self {
+            file: File::open(get_video_path(filename)).ok()?,
+            next: index.keyframe_before(start_ms),
+            parameter_sets: Some(index.parameter_set_nals()),
+            index,
+        })
+    }
+}
+
+impl Iterator for StoredFrames {
+    type Item = VideoFrame;
+
+    fn next(&mut self) -> Option<VideoFrame> {
+        let sample = self.index.samples.get(self.next)?;
+        let nals = self.index.read_nals(&mut self.file, sample).ok()?;
+        self.next += 1;
+
+        // the decoder needs the parameter sets before the first frame
+        let nals = match self.parameter_sets.take() {
+            Some(parameter_sets) => [parameter_sets, nals].concat(),
+            None => nals,
+        };
+
+        Some((sample.time_ms, nals))
+    }
+}
+
+/// Reads the frames of a recording transcoded to a lower quality by `ffmpeg`.
+struct TranscodedFrames {
+    ffmpeg: Child,
+    stdout: ChildStdout,
+    /// The presentation times of the frames that are left, in order, from the index of
+    /// the recording.
+    frame_times: vec::IntoIter<u64>,
+    /// The stream read from `ffmpeg` that wasn't split into NAL units yet.
+    buffer: Vec<u8>,
+    /// Whether `ffmpeg` finished writing the stream.
+    ended: bool,
+    /// The NAL units of the frame being read.
+    nals: Vec<Vec<u8>>,
+}
+
+/// Finds the next Annex B start code in a stream.
 ///
 /// # Arguments
 ///
-/// * `channel` - The `SecureChannel` to send to.
-/// * `stdout` - The `ffmpeg` stdout to read the H.264 stream from.
+/// * `bytes` - The stream.
+/// * `from` - Where to start looking.
+///
+/// # Returns
+///
+/// Where the `00 00 01` of the start code begins.
+fn find_start_code(bytes: &[u8], from: usize) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(3)
+        .position(|window| window == [0, 0, 1])
+        .map(|position| from + position)
+}
+
+impl TranscodedFrames {
+    /// Starts an `ffmpeg` process that transcodes the video file with H.264.
+    ///
+    /// Every frame starts with an access unit delimiter. Decoding starts at a keyframe
+    /// so the first frames are decoded cleanly, and the frames between the keyframe and
+    /// the wanted starting time are dropped. Every other frame is kept, so the frames
+    /// take the presentation times of the recorded frames from the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The filename of the video (without the extension).
+    /// * `index` - The index of the frames of the video.
+    /// * `start_ms` - The wanted starting time of the video, in milliseconds.
+    /// * `height` - The height to scale the video down to.
+    /// * `bitrate` - The bitrate to encode the video with, in kilobits per second.
+    ///
+    /// # Returns
+    ///
+    /// The transcoded frames, or `None` if `ffmpeg` couldn't be started.
+    fn start(
+        filename: &str,
+        index: &VideoIndex,
+        start_ms: u64,
+        height: u32,
+        bitrate: u32,
+    ) -> Option<Self> {
+        let input_path = get_video_path(filename);
+
+        let keyframe = index.keyframe_before(start_ms);
+        let keyframe_ms = index.samples.get(keyframe)?.time_ms;
+        let dropped = index.samples[keyframe..]
+            .iter()
+            .take_while(|sample| sample.time_ms < start_ms)
+            .count();
+        let frame_times: Vec<u64> = index.samples[keyframe + dropped..]
+            .iter()
+            .map(|sample| sample.time_ms)
+            .collect();
+
+        // drop the frames up to the wanted one, counted from the keyframe, and scale
+        let filter = format!("select=gte(n\\,{}),scale=-2:'min({},ih)'", dropped, height);
+
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args([
+                "-nostats",
+                "-ss",
+                &format!("{:.3}", keyframe_ms as f64 / 1000.0),
+                "-noaccurate_seek", // Keep the frames from the keyframe, the filter drops them
+                "-i",
+                input_path.to_str().unwrap(),
+                "-vf",
+                &filter,
+                "-fps_mode",
+                "passthrough", // Keep the frames as they were recorded
+                "-vcodec",
+                "libx264",
+                "-preset",
+                "ultrafast",
+                "-tune",
+                "zerolatency",
+                "-b:v",
+                &format!("{}k", bitrate),
+                "-maxrate",
+                &format!("{}k", bitrate),
+                "-bufsize",
+                &format!("{}k", bitrate / 2),
+                "-force_key_frames",
+                "expr:gte(t,0)", // Force keyframe at the beginning
+                "-x264-params",
+                "aud=1", // Mark the start of every frame
+                "-f",
+                "h264",
+                "-",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+
+        Some(Self {
+            stdout: ffmpeg.stdout.take()?,
+            frame_times: frame_times.into_iter(),
+            ffmpeg,
+            buffer: Vec::new(),
+            ended: false,
+            nals: Vec::new(),
+        })
+    }
+
+    /// Reads the next NAL unit of the stream.
+    ///
+    /// # Returns
+    ///
+    /// The NAL unit with its start code, or `None` at the end of the stream.
+    fn next_nal(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let start = find_start_code(&self.buffer, 0);
+
+            // a NAL unit is complete once the next one starts
+            if let Some(start) = start {
+                if let Some(next) = find_start_code(&self.buffer, start + 3) {
+                    let mut nal: Vec<u8> = self.buffer.drain(..next).skip(start).collect();
+
+                    // the leading zero of a four byte start code
+                    while nal.last() == Some(&0) {
+                        nal.pop();
+                    }
+                    return Some(nal);
+                }
+            }
+
+            if self.ended {
+                let nal: Vec<u8> = self.buffer.drain(..).skip(start?).collect();
+                return (nal.len() > 3).then_some(nal);
+            }
+
+            let mut chunk = [0u8; 4096];
+            match self.stdout.read(&mut chunk) {
+                Ok(0) | Err(_) => self.ended = true,
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+            }
+        }
+    }
+}
+
+impl Iterator for TranscodedFrames {
+    type Item = VideoFrame;
+
+    fn next(&mut self) -> Option<VideoFrame> {
+        loop {
+            let nal = self.next_nal();
+
+            // an access unit delimiter starts every frame, it ends the one before
+            let frame_ended = nal
+                .as_ref()
+                .is_none_or(|nal| nal_type(&nal[3..]) == NAL_AUD);
+            if frame_ended && !self.nals.is_empty() {
+                // the transcoded frames are the recorded ones, so they can't outnumber them
+                let time_ms = self.frame_times.next()?;
+
+                let nals = std::mem::take(&mut self.nals);
+                if let Some(nal) = nal.filter(|nal| nal_type(&nal[3..]) != NAL_AUD) {
+                    self.nals.push(nal);
+                }
+                return Some((time_ms, nals));
+            }
+
+            match nal {
+                Some(nal) if nal_type(&nal[3..]) != NAL_AUD => self.nals.push(nal),
+                Some(_) => (),
+                None => return None,
+            }
+        }
+    }
+}
+
+impl Drop for TranscodedFrames {
+    fn drop(&mut self) {
+        let _ = self.ffmpeg.kill();
+        let _ = self.ffmpeg.wait();
+    }
+}
+
+/// Starts a thread to send the H.264 NAL units of the recording.
+///
+/// The frames are paced by their presentation times at the playback speed, a little
+/// ahead of the playback clock. The client skips the frames before the wanted time,
+/// as they are already late when they arrive.
+///
+/// # Arguments
+///
+/// * `target` - Where to send the frames.
+/// * `frames` - The frames to send, from the keyframe before the wanted time.
+/// * `start_ms` - The wanted starting time of the video, in milliseconds.
+/// * `control` - The playback speed and pause state to follow.
 /// * `stop_flag` - The flag that tells the thread to stop.
 ///
 /// # Returns
 ///
 /// The thread's `JoinHandle`.
 fn thread_send_screen(
-    mut channel: SecureChannel,
-    mut stdout: ChildStdout,
+    mut target: PlaybackTarget,
+    frames: Box<dyn Iterator<Item = VideoFrame> + Send>,
+    start_ms: u64,
+    control: PlaybackControl,
     stop_flag: Arc<AtomicBool>,
 ) -> JoinHandle<std::io::Result<()>> {
     thread::spawn(move || -> std::io::Result<()> {
-        let mut reader = AnnexBReader::accumulate(|nal: RefNal<'_>| {
-            if !nal.is_complete() {
-                return NalInterest::Buffer;
+        let mut clock = PlaybackClock::new(start_ms, control.clock_speed());
+
+        'frames: for (pts_ms, nals) in frames {
+            // wait until the frame is due
+            loop {
+                if stop_flag.load(Ordering::Relaxed) {
+                    break 'frames;
+                }
+
+                clock.set_speed(control.clock_speed());
+                let send_ms = pts_ms.saturating_sub(PACING_LEAD_MS);
+                let now_ms = clock.now_ms();
+                if send_ms <= now_ms {
+                    break;
+                }
+
+                // a paused clock doesn't move, so only the stop flag is waited for
+                if clock.speed_percent == 0 {
+                    thread::sleep(PACING_SLEEP);
+                    continue;
+                }
+
+                let wait =
+                    Duration::from_millis((send_ms - now_ms) * 100 / clock.speed_percent as u64);
+                thread::sleep(wait.min(PACING_SLEEP));
             }
 
-            // getting nal unit type
-            match nal.header() {
-                Ok(_) => (),
-                Err(_) => return NalInterest::Ignore,
-            };
+            target.send_frame(start_ms, pts_ms, nals)?;
+        }
+
+        target.end()?;
+
+        Ok(())
+    })
+}
+
+/// Starts sending the recording from a time, as it was recorded or transcoded.
+///
+/// If the recording can't be transcoded, the viewers are told and it's sent as recorded.
+///
+/// # Arguments
+///
+/// * `target` - Where to send the frames.
+/// * `filename` - The filename of the video (without the extension).
+/// * `index` - The index of the frames of the video.
+/// * `start_ms` - The wanted starting time of the video, in milliseconds.
+/// * `quality` - The height and bitrate to transcode the video to, or `None` to send
+///   it as it was recorded.
+/// * `control` - The playback speed and pause state to follow.
+/// * `stop_flag` - The flag that tells the sending to stop.
+///
+/// # Returns
+///
+/// The sending thread's `JoinHandle`.
+pub fn start_playback(
+    mut target: PlaybackTarget,
+    filename: &str,
+    index: &Arc<VideoIndex>,
+    start_ms: u64,
+    quality: Option<(u32, u32)>,
+    control: &PlaybackControl,
+    stop_flag: &Arc<AtomicBool>,
+) -> JoinHandle<std::io::Result<()>> {
+    // transcoding is only worth a CPU core when the client asks for less
+    let transcoded = quality.and_then(|(height, bitrate)| {
+        let transcoded = TranscodedFrames::start(filename, index, start_ms, height, bitrate);
+        if transcoded.is_none() {
+            let _ = target.send_packet(Packet::PlaybackQualityFailed {
+                message: "The recording couldn't be transcoded, it's shown as recorded.".to_owned(),
+            });
+        }
+        transcoded
+    });
 
-            // sending the NAL (with the start)
-            let mut nal_bytes: Vec<u8> = vec![0x00, 0x00, 0x01];
-            nal.reader()
-                .read_to_end(&mut nal_bytes)
-                .expect("should be able to read NAL");
+    let frames: Box<dyn Iterator<Item = VideoFrame> + Send> = match transcoded {
+        Some(transcoded) => Box::new(transcoded),
+        None => match StoredFrames::open(filename, index.clone(), start_ms) {
+            Some(stored) => Box::new(stored),
+            None => Box::new(std::iter::empty()),
+        },
+    };
 
-            let _ = channel.send(Packet::Screen { bytes: nal_bytes });
+    thread_send_screen(target, frames, start_ms, control.clone(), stop_flag.clone())
+}
 
-            NalInterest::Ignore
-        });
+/// Gets the quality a client asked to watch in.
+///
+/// # Arguments
+///
+/// * `height` - The height to scale the recording down to, or 0.
+/// * `bitrate` - The bitrate in kilobits per second, or 0.
+///
+/// # Returns
+///
+/// The height and the bitrate to transcode to, or `None` to send the recording as it
+/// was recorded.
+pub fn requested_quality(height: u32, bitrate: u32) -> Option<(u32, u32)> {
+    (height > 0 && bitrate > 0).then_some((height, bitrate))
+}
 
-        let mut buffer = [0u8; 4096];
+/// Chooses the quality a client asked to watch in, if the server can transcode to it.
+///
+/// # Arguments
+///
+/// * `height` - The height to scale the recording down to, or 0.
+/// * `bitrate` - The bitrate in kilobits per second, or 0.
+///
+/// # Returns
+///
+/// The quality to watch in as `requested_quality` gives it, or the
+/// `Packet::PlaybackQualityFailed` to answer with if `ffmpeg` isn't installed.
+pub fn choose_quality(height: u32, bitrate: u32) -> Result<Option<(u32, u32)>, Packet> {
+    match requested_quality(height, bitrate) {
+        Some(_) if !has_ffmpeg() => Err(Packet::PlaybackQualityFailed {
+            message: "The server can't transcode recordings, they're only shown as recorded."
+                .to_owned(),
+        }),
+        quality => Ok(quality),
+    }
+}
 
-        while !stop_flag.load(Ordering::Relaxed) {
-            match stdout.read(&mut buffer) {
-                Ok(0) => break,
-                Ok(n) => {
-                    reader.push(&buffer[..n]);
+/// Answers the requests about a recording that don't change its playback: its
+/// thumbnails, bookmarks and events, and the clips cut out of it.
+pub struct RecordingRequests<'a> {
+    db_pool: &'a Pool<SqliteConnectionManager>,
+    /// The ID of the watching user, who owns the clips.
+    user_id: i32,
+    /// The ID of the watched recording.
+    id: i32,
+    recording: &'a Recording,
+    /// The clips being cut, which report to the client before it leaves.
+    thread_clips: Vec<JoinHandle<()>>,
+}
+
+impl<'a> RecordingRequests<'a> {
+    /// Creates the handler of the requests about a recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_pool` - The pool of the database connections, for saving clips.
+    /// * `user_id` - The ID of the watching user.
+    /// * `id` - The ID of the watched recording.
+    /// * `recording` - The watched recording.
+    pub fn new(
+        db_pool: &'a Pool<SqliteConnectionManager>,
+        user_id: i32,
+        id: i32,
+        recording: &'a Recording,
+    ) -> Self {
+        Self {
+            db_pool,
+            user_id,
+            id,
+            recording,
+            thread_clips: Vec::new(),
+        }
+    }
+
+    /// Gets the watched recording.
+    pub fn recording(&self) -> &Recording {
+        self.recording
+    }
+
+    /// Answers a packet if it's a request about the recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The `SecureChannel` connected to the client.
+    /// * `packet` - The packet the client sent.
+    ///
+    /// # Returns
+    ///
+    /// An `std::io::Result<()>` that signifies if the answer was sent successfully.
+    pub fn handle(&mut self, channel: &mut SecureChannel, packet: Packet) -> std::io::Result<()> {
+        match packet {
+            Packet::GetThumbnails => {
+                channel.send(thumbnails_packet(&self.recording.filename))?;
+            }
+
+            Packet::GetBookmarks => {
+                for bookmark in query_bookmarks(self.db_pool, self.id) {
+                    channel.send(bookmark.packet())?;
                 }
-                Err(e) => {
-                    eprintln!("ffmpeg read error: {}", e);
-                    break;
+            }
+
+            Packet::GetEvents => {
+                for event in read_events(&self.recording.filename) {
+                    channel.send(event)?;
                 }
             }
-        }
 
-        channel.send(Packet::None)?;
+            Packet::CreateClip {
+                start_ms,
+                end_ms,
+                accurate,
+            } => {
+                self.thread_clips.extend(thread_create_clip(
+                    channel.clone(),
+                    self.db_pool.clone(),
+                    self.user_id,
+                    self.id,
+                    self.recording,
+                    start_ms..end_ms,
+                    accurate,
+                ));
+            }
+
+            _ => (),
+        }
 
         Ok(())
-    })
+    }
+
+    /// Waits for the clips being cut, so they report before the client leaves.
+    pub fn finish(&mut self) {
+        for thread_clip in self.thread_clips.drain(..) {
+            let _ = thread_clip.join();
+        }
+    }
 }
 
 /// Handles packets from the client.
 ///
 /// # Arguments
 /// * `channel` - The `SecureChannel` connected to the client.
-/// * `filename` - The filename of the played video.
+/// * `requests` - The handler of the requests about the played recording.
 ///
 /// # Returns
 ///
 /// An `std::io::Result<()>` that signifies if something went wrong.
-pub fn handle_watching(channel: &mut SecureChannel, filename: &str) -> std::io::Result<()> {
-    let mut ffmpeg = ffmpeg_send_recording(filename, 0);
-    let mut stdout = ffmpeg.stdout.take().unwrap();
+pub fn handle_watching(
+    channel: &mut SecureChannel,
+    mut requests: RecordingRequests,
+) -> std::io::Result<()> {
+    let filename = requests.recording().filename.clone();
+    let index = Arc::new(VideoIndex::read(&filename).unwrap_or_default());
+    let control = PlaybackControl::new();
+    let mut quality = None;
 
     let mut stop_flag = Arc::new(AtomicBool::new(false));
 
-    let mut thread_send = Some(thread_send_screen(
-        channel.clone(),
-        stdout,
-        stop_flag.clone(),
+    let mut thread_send = Some(start_playback(
+        PlaybackTarget::Viewer(Box::new(channel.clone())),
+        &filename,
+        &index,
+        0,
+        quality,
+        &control,
+        &stop_flag,
     ));
 
     loop {
@@ -141,41 +748,103 @@ pub fn handle_watching(channel: &mut SecureChannel, filename: &str) -> std::io::
 
         match packet {
             Packet::SeekInit => {
-                // Kill current ffmpeg and thread
+                // Stop the current thread
                 stop_flag.store(true, Ordering::Relaxed);
-                let _ = ffmpeg.kill();
                 let _ = thread_send.take().unwrap().join();
 
                 // send session exit
                 channel.send(Packet::SeekInit)?;
             }
 
-            Packet::SeekTo { time_seconds } => {
-                // Restart with seek
-                ffmpeg = ffmpeg_send_recording(filename, time_seconds);
-                stdout = ffmpeg.stdout.take().unwrap();
+            Packet::SeekTo { time_ms } => {
+                // Restart from the wanted time
                 stop_flag = Arc::new(AtomicBool::new(false));
-                thread_send = Some(thread_send_screen(
-                    channel.clone(),
-                    stdout,
-                    stop_flag.clone(),
+                thread_send = Some(start_playback(
+                    PlaybackTarget::Viewer(Box::new(channel.clone())),
+                    &filename,
+                    &index,
+                    time_ms,
+                    quality,
+                    &control,
+                    &stop_flag,
                 ));
             }
 
+            Packet::PlaybackSpeed { speed_percent } => {
+                control.set_speed(speed_percent);
+            }
+
+            Packet::PlaybackQuality { height, bitrate } => match choose_quality(height, bitrate) {
+                Ok(chosen) => quality = chosen,
+                Err(failure) => {
+                    quality = None;
+                    channel.send(failure)?;
+                }
+            },
+
             Packet::SessionExit | Packet::None => {
                 stop_flag.store(true, Ordering::Relaxed);
 
-                let _ = ffmpeg.kill();
                 let _ = thread_send.take().unwrap().join();
 
+                requests.finish();
+
                 channel.send(Packet::SessionExit)?;
 
                 break;
             }
 
-            _ => (),
+            packet => requests.handle(channel, packet)?,
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clock that was started the given time ago.
+    fn clock_started_ago(elapsed: Duration, start_ms: u64, speed_percent: u32) -> PlaybackClock {
+        PlaybackClock {
+            anchor: Instant::now() - elapsed,
+            anchor_ms: start_ms,
+            speed_percent,
+        }
+    }
+
+    #[test]
+    fn clock_runs_at_the_playback_speed() {
+        let clock = clock_started_ago(Duration::from_secs(2), 1_000, 100);
+        assert!((3_000..3_100).contains(&clock.now_ms()));
+
+        let clock = clock_started_ago(Duration::from_secs(2), 1_000, 25);
+        assert!((1_500..1_600).contains(&clock.now_ms()));
+
+        let clock = clock_started_ago(Duration::from_secs(2), 1_000, 400);
+        assert!((9_000..9_400).contains(&clock.now_ms()));
+    }
+
+    #[test]
+    fn changing_speed_continues_from_the_current_position() {
+        let mut clock = clock_started_ago(Duration::from_secs(2), 0, 200);
+        clock.set_speed(50);
+
+        assert!((4_000..4_100).contains(&clock.anchor_ms));
+        assert!(clock.now_ms() >= clock.anchor_ms && clock.now_ms() < clock.anchor_ms + 100);
+
+        // setting the same speed leaves the anchor where it was
+        let anchor = clock.anchor;
+        clock.set_speed(50);
+        assert_eq!(clock.anchor, anchor);
+    }
+
+    #[test]
+    fn quality_is_requested_only_with_a_height_and_a_bitrate() {
+        assert_eq!(requested_quality(720, 2_500), Some((720, 2_500)));
+        assert_eq!(requested_quality(0, 2_500), None);
+        assert_eq!(requested_quality(720, 0), None);
+        assert_eq!(requested_quality(0, 0), None);
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use eframe::egui::{self, DragValue, Ui};
+use winapi::shared::minwindef::{BOOL, LPARAM, TRUE};
+use winapi::shared::windef::{HDC, HMONITOR, HWND, LPRECT, POINT, RECT};
+use winapi::um::winuser::{
+    self, ClientToScreen, EnumDisplayMonitors, EnumWindows, FindWindowW, GetClientRect,
+    GetMonitorInfoW, GetSystemMetrics, GetWindowTextLengthW, GetWindowTextW, IsWindowVisible,
+    MONITORINFO,
+};
+
+/// A rectangle of the virtual desktop, in pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CaptureRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The part of the host's desktop that is shared with the participants.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CaptureSource {
+    /// A single display, by its index in `list_displays`.
+    Display(usize),
+    /// A rectangular region of the virtual desktop.
+    Region(CaptureRect),
+    /// A single window, by its title.
+    Window(String),
+}
+
+impl Default for CaptureSource {
+    /// Shares the primary display.
+    fn default() -> Self {
+        Self::Display(0)
+    }
+}
+
+impl fmt::Display for CaptureSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureSource::Display(index) => write!(f, "Display {}", index + 1),
+            CaptureSource::Region(_) => write!(f, "Region"),
+            CaptureSource::Window(title) => write!(f, "Window: {}", title),
+        }
+    }
+}
+
+/// A capture source and its rectangle.
+///
+/// The rectangle is found once, when the source is chosen, rather than for every
+/// mouse event. Windows can move, so their rectangle is refreshed by the cursor thread.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CaptureArea {
+    pub source: CaptureSource,
+    pub rect: Option<CaptureRect>,
+}
+
+impl CaptureArea {
+    /// Finds the current rectangle of a capture source.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The capture source.
+    pub fn new(source: CaptureSource) -> Self {
+        let rect = capture_rect(&source);
+        Self { source, rect }
+    }
+}
+
+/// The capture area shared between the UI and the background threads.
+pub type SharedCaptureSource = Arc<Mutex<CaptureArea>>;
+
+/// Converts a Windows `RECT` into a `CaptureRect`.
+fn from_rect(rect: RECT) -> CaptureRect {
+    CaptureRect {
+        x: rect.left,
+        y: rect.top,
+        width: (rect.right - rect.left).max(0) as u32,
+        height: (rect.bottom - rect.top).max(0) as u32,
+    }
+}
+
+/// Lists the host's displays, the primary display first.
+///
+/// # Returns
+///
+/// The rectangles of the displays on the virtual desktop.
+pub fn list_displays() -> Vec<CaptureRect> {
+    unsafe extern "system" fn callback(
+        monitor: HMONITOR,
+        _hdc: HDC,
+        _rect: LPRECT,
+        data: LPARAM,
+    ) -> BOOL {
+        let displays = &mut *(data as *mut Vec<(bool, CaptureRect)>);
+
+        let mut info: MONITORINFO = std::mem::zeroed();
+        info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+        if GetMonitorInfoW(monitor, &mut info) != 0 {
+            let primary = info.dwFlags & winuser::MONITORINFOF_PRIMARY != 0;
+            displays.push((primary, from_rect(info.rcMonitor)));
+        }
+
+        TRUE
+    }
+
+    let mut displays: Vec<(bool, CaptureRect)> = Vec::new();
+    unsafe {
+        EnumDisplayMonitors(
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            Some(callback),
+            &mut displays as *mut _ as LPARAM,
+        );
+    }
+
+    // primary first, then from left to right
+    displays.sort_by_key(|(primary, rect)| (!primary, rect.x, rect.y));
+    displays.into_iter().map(|(_, rect)| rect).collect()
+}
+
+/// Lists the titles of the visible top-level windows.
+///
+/// # Returns
+///
+/// The window titles, sorted and without duplicates.
+pub fn list_windows() -> Vec<String> {
+    unsafe extern "system" fn callback(window: HWND, data: LPARAM) -> BOOL {
+        let titles = &mut *(data as *mut Vec<String>);
+
+        let length = GetWindowTextLengthW(window);
+        if IsWindowVisible(window) != 0 && length > 0 {
+            let mut buffer = vec![0u16; length as usize + 1];
+            let copied = GetWindowTextW(window, buffer.as_mut_ptr(), buffer.len() as i32);
+            titles.push(String::from_utf16_lossy(&buffer[..copied as usize]));
+        }
+
+        TRUE
+    }
+
+    let mut titles: Vec<String> = Vec::new();
+    unsafe {
+        EnumWindows(Some(callback), &mut titles as *mut _ as LPARAM);
+    }
+
+    titles.sort();
+    titles.dedup();
+    titles
+}
+
+/// Returns the rectangle covering all of the host's displays.
+pub fn virtual_desktop() -> CaptureRect {
+    unsafe {
+        CaptureRect {
+            x: GetSystemMetrics(winuser::SM_XVIRTUALSCREEN),
+            y: GetSystemMetrics(winuser::SM_YVIRTUALSCREEN),
+            width: GetSystemMetrics(winuser::SM_CXVIRTUALSCREEN).max(1) as u32,
+            height: GetSystemMetrics(winuser::SM_CYVIRTUALSCREEN).max(1) as u32,
+        }
+    }
+}
+
+/// Finds the current rectangle of a capture source.
+///
+/// Windows are looked up by title, and only their client area is captured.
+/// The size is rounded down to even numbers, as required by the H.264 encoder,
+/// so the mouse is mapped onto exactly the pixels that are streamed.
+///
+/// # Arguments
+///
+/// * `source` - The capture source.
+///
+/// # Returns
+///
+/// The rectangle on the virtual desktop, or `None` if the display or window doesn't exist.
+pub fn capture_rect(source: &CaptureSource) -> Option<CaptureRect> {
+    let rect = match source {
+        CaptureSource::Display(index) => list_displays().get(*index).copied(),
+
+        CaptureSource::Region(rect) => Some(*rect),
+
+        CaptureSource::Window(title) => unsafe {
+            let wide_title: Vec<u16> = title.encode_utf16().chain(Some(0)).collect();
+            let window = FindWindowW(std::ptr::null(), wide_title.as_ptr());
+            if window.is_null() {
+                return None;
+            }
+
+            let mut rect: RECT = std::mem::zeroed();
+            if GetClientRect(window, &mut rect) == 0 {
+                return None;
+            }
+
+            let mut origin = POINT { x: 0, y: 0 };
+            ClientToScreen(window, &mut origin);
+
+            Some(CaptureRect {
+                x: origin.x,
+                y: origin.y,
+                width: rect.right.max(0) as u32,
+                height: rect.bottom.max(0) as u32,
+            })
+        },
+    }?;
+
+    Some(CaptureRect {
+        width: rect.width & !1,
+        height: rect.height & !1,
+        ..rect
+    })
+}
+
+/// Builds the `gdigrab` input arguments for a capture area.
+///
+/// # Arguments
+///
+/// * `area` - The capture source and its rectangle.
+///
+/// # Returns
+///
+/// The `ffmpeg` arguments that go right before the encoder settings.
+pub fn ffmpeg_input_args(area: &CaptureArea) -> Vec<String> {
+    let mut args = Vec::new();
+
+    match &area.source {
+        CaptureSource::Window(title) => {
+            args.push("-i".to_string());
+            args.push(format!("title={}", title));
+        }
+
+        _ => {
+            let rect = area.rect.unwrap_or_else(virtual_desktop);
+
+            args.extend([
+                "-offset_x".to_string(),
+                rect.x.to_string(),
+                "-offset_y".to_string(),
+                rect.y.to_string(),
+                "-video_size".to_string(),
+                format!("{}x{}", rect.width, rect.height),
+                "-i".to_string(),
+                "desktop".to_string(),
+            ]);
+        }
+    }
+
+    args
+}
+
+/// Maps a normalized position on the shared area to the virtual desktop.
+///
+/// # Arguments
+///
+/// * `rect` - The rectangle of the shared area.
+/// * `mouse_x` - The normalized X coordinate on the shared area (0 to 65,535).
+/// * `mouse_y` - The normalized Y coordinate on the shared area (0 to 65,535).
+///
+/// # Returns
+///
+/// The position normalized to the whole virtual desktop, as expected by
+/// `MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK`.
+pub fn to_virtual_desktop(rect: CaptureRect, mouse_x: u32, mouse_y: u32) -> (u32, u32) {
+    let desktop = virtual_desktop();
+
+    // the pixel on the virtual desktop
+    let x = rect.x as i64 + mouse_x as i64 * (rect.width as i64 - 1).max(0) / 65535;
+    let y = rect.y as i64 + mouse_y as i64 * (rect.height as i64 - 1).max(0) / 65535;
+
+    let x = (x - desktop.x as i64) * 65535 / (desktop.width as i64 - 1).max(1);
+    let y = (y - desktop.y as i64) * 65535 / (desktop.height as i64 - 1).max(1);
+
+    (x.clamp(0, 65535) as u32, y.clamp(0, 65535) as u32)
+}
+
+/// Maps a pixel on the virtual desktop to a normalized position on the shared area.
+///
+/// # Arguments
+///
+/// * `rect` - The rectangle of the shared area.
+/// * `x` - The X coordinate on the virtual desktop.
+/// * `y` - The Y coordinate on the virtual desktop.
+///
+/// # Returns
+///
+/// The normalized position (0 to 65,535), or `None` if the pixel is outside the shared area.
+pub fn from_virtual_desktop(rect: CaptureRect, x: i32, y: i32) -> Option<(u32, u32)> {
+    let x = x as i64 - rect.x as i64;
+    let y = y as i64 - rect.y as i64;
+    if x < 0 || y < 0 || x >= rect.width as i64 || y >= rect.height as i64 {
+        return None;
+    }
+
+    let x = x * 65535 / (rect.width as i64 - 1).max(1);
+    let y = y * 65535 / (rect.height as i64 - 1).max(1);
+
+    Some((x as u32, y as u32))
+}
+
+/// Renders the controls for choosing a capture source.
+///
+/// # Arguments
+///
+/// * `ui` - The `Ui` to draw on.
+/// * `source` - The capture source being edited.
+///
+/// # Returns
+///
+/// Whether the source was changed.
+pub fn source_picker(ui: &mut Ui, source: &mut CaptureSource) -> bool {
+    let before = source.clone();
+
+    let displays = list_displays();
+    let desktop = virtual_desktop();
+
+    egui::ComboBox::from_label("Share")
+        .selected_text(source.to_string())
+        .width(200.0)
+        .show_ui(ui, |ui| {
+            for (index, display) in displays.iter().enumerate() {
+                let label = format!(
+                    "Display {} ({}x{})",
+                    index + 1,
+                    display.width,
+                    display.height
+                );
+                ui.selectable_value(source, CaptureSource::Display(index), label);
+            }
+
+            let region = match source {
+                CaptureSource::Region(rect) => *rect,
+                _ => displays.first().copied().unwrap_or(desktop),
+            };
+            ui.selectable_value(source, CaptureSource::Region(region), "Region");
+
+            ui.separator();
+            for title in list_windows() {
+                ui.selectable_value(source, CaptureSource::Window(title.clone()), title);
+            }
+        });
+
+    if let CaptureSource::Region(rect) = source {
+        let right = desktop.x + desktop.width as i32;
+        let bottom = desktop.y + desktop.height as i32;
+
+        ui.horizontal(|ui| {
+            ui.label("X");
+            ui.add(DragValue::new(&mut rect.x).range(desktop.x..=right - 2));
+            ui.label("Y");
+            ui.add(DragValue::new(&mut rect.y).range(desktop.y..=bottom - 2));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Width");
+            ui.add(DragValue::new(&mut rect.width).range(2..=(right - rect.x) as u32));
+            ui.label("Height");
+            ui.add(DragValue::new(&mut rect.height).range(2..=(bottom - rect.y) as u32));
+        });
+    }
+
+    *source != before
+}
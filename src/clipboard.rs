@@ -0,0 +1,91 @@
+use std::borrow::Cow;
+
+use arboard::{Clipboard, ImageData};
+use stream_desk::protocol::{
+    Packet, ProtocolMessage, MAX_CLIPBOARD_IMAGE_SIZE, MAX_CLIPBOARD_TEXT_LENGTH,
+};
+
+/// Reads the local clipboard and turns its content into a clipboard packet.
+///
+/// Text is preferred over images. Content that exceeds the protocol size limits
+/// is ignored.
+///
+/// # Returns
+///
+/// An `Option<Packet>` which is:
+/// - `Some(Packet::ClipboardText)` or `Some(Packet::ClipboardImage)` with the clipboard content.
+/// - `None` if the clipboard is empty, unavailable, or its content is too large.
+pub fn read_clipboard() -> Option<Packet> {
+    let mut clipboard = Clipboard::new().ok()?;
+
+    if let Ok(text) = clipboard.get_text() {
+        if text.is_empty() || text.len() > MAX_CLIPBOARD_TEXT_LENGTH {
+            return None;
+        }
+
+        return Some(Packet::ClipboardText { text });
+    }
+
+    let image = clipboard.get_image().ok()?;
+    if image.bytes.len() > MAX_CLIPBOARD_IMAGE_SIZE {
+        return None;
+    }
+
+    Some(Packet::ClipboardImage {
+        width: image.width as u32,
+        height: image.height as u32,
+        bytes: image.bytes.into_owned(),
+    })
+}
+
+/// Writes the content of a clipboard packet to the local clipboard.
+///
+/// Packets that are not clipboard packets, or that exceed the protocol size limits,
+/// are ignored.
+///
+/// # Arguments
+///
+/// * `packet` - A `Packet::ClipboardText` or `Packet::ClipboardImage` to apply.
+pub fn write_clipboard(packet: &Packet) {
+    let Ok(mut clipboard) = Clipboard::new() else {
+        return;
+    };
+
+    match packet {
+        Packet::ClipboardText { text } if text.len() <= MAX_CLIPBOARD_TEXT_LENGTH => {
+            let _ = clipboard.set_text(text.as_str());
+        }
+
+        Packet::ClipboardImage {
+            width,
+            height,
+            bytes,
+        } if bytes.len() <= MAX_CLIPBOARD_IMAGE_SIZE
+            && bytes.len() == *width as usize * *height as usize * 4 =>
+        {
+            let image = ImageData {
+                width: *width as usize,
+                height: *height as usize,
+                bytes: Cow::Borrowed(bytes),
+            };
+            let _ = clipboard.set_image(image);
+        }
+
+        _ => (),
+    }
+}
+
+/// Computes a digest of a clipboard packet's content.
+///
+/// Used to detect clipboard changes without keeping a copy of the content around.
+///
+/// # Arguments
+///
+/// * `packet` - The clipboard packet to digest.
+///
+/// # Returns
+///
+/// The MD5 digest of the packet's bytes.
+pub fn clipboard_digest(packet: &Packet) -> md5::Digest {
+    md5::compute(packet.to_bytes())
+}
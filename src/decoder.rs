@@ -0,0 +1,220 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use eframe::egui::{Color32, ColorImage};
+use log::info;
+use openh264::formats::YUVSource;
+use stream_desk::LOG_TARGET;
+
+/// The size the `ffmpeg` fallback scales frames to, since it doesn't report the stream size.
+const FALLBACK_FRAME_SIZE: [usize; 2] = [1920, 1080];
+
+/// A decoded frame.
+pub struct Frame {
+    /// The pixels, ready to be uploaded as a texture.
+    pub image: ColorImage,
+    /// When the frame should be shown, relative to the start of the stream, if the
+    /// stream carries presentation times.
+    pub pts: Option<Duration>,
+}
+
+/// Receives every decoded frame.
+pub type FrameSink = Box<dyn FnMut(Frame) + Send>;
+
+/// An H.264 decoder that hands the decoded frames to a `FrameSink`.
+///
+/// The stream is expected to have no reordered frames (as encoded with `zerolatency`),
+/// so frames come out in the order their presentation times were given.
+pub trait Decoder {
+    /// Feeds a NAL unit, with its start code, to the decoder.
+    ///
+    /// Corrupted data is skipped rather than stopping the decoder.
+    fn push(&mut self, nal: &[u8]);
+
+    /// Sets the presentation time of the frame whose NAL units are pushed next.
+    fn start_frame(&mut self, pts: Duration);
+
+    /// Signals the end of the stream, so any frames still in the decoder are handed out.
+    fn finish(&mut self) {}
+}
+
+/// Creates a black frame whose pixels are written as RGBA bytes.
+///
+/// Decoded frames are opaque, so straight and premultiplied alpha are the same and
+/// the bytes can be written right into the `ColorImage` without converting them.
+///
+/// # Arguments
+///
+/// * `size` - The width and height of the frame.
+fn blank_frame(size: [usize; 2]) -> ColorImage {
+    ColorImage::new(size, Color32::BLACK)
+}
+
+/// Decodes in-process with the openh264 software decoder.
+struct SoftwareDecoder {
+    decoder: openh264::decoder::Decoder,
+    sink: FrameSink,
+    /// The presentation time of the frame whose NAL units are pushed, which is decoded
+    /// as soon as they arrive. A frame that fails to decode takes its time with it.
+    frame_pts: Option<Duration>,
+}
+
+impl Decoder for SoftwareDecoder {
+    fn push(&mut self, nal: &[u8]) {
+        let Ok(Some(yuv)) = self.decoder.decode(nal) else {
+            return;
+        };
+
+        let (width, height) = yuv.dimensions();
+        let mut image = blank_frame([width, height]);
+        yuv.write_rgba8(bytemuck::cast_slice_mut(&mut image.pixels));
+
+        let pts = self.frame_pts.take();
+        (self.sink)(Frame { image, pts });
+    }
+
+    fn start_frame(&mut self, pts: Duration) {
+        self.frame_pts = Some(pts);
+    }
+}
+
+/// Decodes by piping the stream through an `ffmpeg` child process.
+struct FfmpegDecoder {
+    ffmpeg: Child,
+    stdin: Option<ChildStdin>,
+    /// The presentation times of the frames that weren't decoded yet.
+    pending_pts: Arc<Mutex<VecDeque<Duration>>>,
+    thread_read_decoded: Option<JoinHandle<()>>,
+}
+
+impl FfmpegDecoder {
+    /// Starts `ffmpeg` and a thread that reads the decoded frames from its stdout.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - Receives every decoded frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ffmpeg` fails to spawn.
+    fn start(mut sink: FrameSink) -> Self {
+        let [width, height] = FALLBACK_FRAME_SIZE;
+
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args([
+                "-flags",
+                "low_delay", // Prioritize low latency decoding
+                "-fflags",
+                "discardcorrupt", // Discard corrupted frames instead of stopping
+                "-f",
+                "h264", // Input format is H.264
+                "-i",
+                "-", // Read input from stdin
+                "-vf",
+                &format!("scale={}:{}", width, height), // Frames of a known size
+                "-fps_mode",
+                "passthrough", // Exactly one output frame per decoded frame
+                "-f",
+                "rawvideo", // Output raw video
+                "-pix_fmt",
+                "rgba", // Output pixel format is RGBA
+                "-",    // Write output to stdout
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to spawn ffmpeg");
+
+        let stdin = ffmpeg.stdin.take();
+        let mut stdout = ffmpeg.stdout.take().unwrap();
+
+        let pending_pts = Arc::new(Mutex::new(VecDeque::new()));
+        let thread_pending_pts = pending_pts.clone();
+
+        let thread_read_decoded = thread::spawn(move || loop {
+            // read exactly one decoded frame, straight into the image
+            let mut image = blank_frame(FALLBACK_FRAME_SIZE);
+            if stdout
+                .read_exact(bytemuck::cast_slice_mut(&mut image.pixels))
+                .is_err()
+            {
+                break;
+            }
+
+            let pts = thread_pending_pts.lock().unwrap().pop_front();
+            sink(Frame { image, pts });
+        });
+
+        Self {
+            ffmpeg,
+            stdin,
+            pending_pts,
+            thread_read_decoded: Some(thread_read_decoded),
+        }
+    }
+}
+
+impl Decoder for FfmpegDecoder {
+    fn push(&mut self, nal: &[u8]) {
+        if let Some(stdin) = &mut self.stdin {
+            let _ = stdin.write_all(nal);
+        }
+    }
+
+    fn start_frame(&mut self, pts: Duration) {
+        self.pending_pts.lock().unwrap().push_back(pts);
+    }
+
+    fn finish(&mut self) {
+        // closing stdin makes ffmpeg decode what's left and exit
+        self.stdin = None;
+    }
+}
+
+impl Drop for FfmpegDecoder {
+    fn drop(&mut self) {
+        self.stdin = None;
+        let _ = self.ffmpeg.kill();
+        let _ = self.ffmpeg.wait();
+
+        if let Some(handle) = self.thread_read_decoded.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Creates an H.264 decoder, preferring the in-process software decoder.
+///
+/// Falls back to an `ffmpeg` child process if the software decoder can't be created.
+///
+/// # Arguments
+///
+/// * `sink` - Receives every decoded frame.
+///
+/// # Returns
+///
+/// The decoder, which should be fed from a single thread.
+pub fn start_decoder(sink: FrameSink) -> Box<dyn Decoder> {
+    match openh264::decoder::Decoder::new() {
+        Ok(decoder) => Box::new(SoftwareDecoder {
+            decoder,
+            sink,
+            frame_pts: None,
+        }),
+
+        Err(e) => {
+            info!(
+                target: LOG_TARGET,
+                "Couldn't create the software decoder ({}), decoding with ffmpeg.", e
+            );
+            Box::new(FfmpegDecoder::start(sink))
+        }
+    }
+}
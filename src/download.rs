@@ -0,0 +1,306 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use eframe::egui::{self, Color32, RichText, Ui};
+use stream_desk::{protocol::Packet, secure_channel::SecureChannel};
+
+use crate::file_transfer::{format_size, PARTIAL_EXTENSION};
+
+/// Represents the state of a recording download.
+enum DownloadState {
+    /// The recording is being downloaded.
+    InProgress,
+    /// The recording was downloaded and its checksum was verified.
+    Completed,
+    /// The download was cancelled, and can be resumed by downloading to the same path.
+    Cancelled,
+    /// The download failed, with the reason.
+    Failed(String),
+}
+
+/// The progress of a download, shared with the downloading thread.
+struct DownloadProgress {
+    /// The number of bytes downloaded so far.
+    received: u64,
+    /// The size of the recording in bytes, once the server sent it.
+    size: Option<u64>,
+    /// The current state of the download.
+    state: DownloadState,
+}
+
+/// A recording being downloaded to a file in the background.
+///
+/// The downloading thread uses the channel until the download stops, so nothing
+/// else should be sent or received meanwhile.
+pub struct Download {
+    /// The path the recording is saved to.
+    path: PathBuf,
+    /// The progress of the download.
+    progress: Arc<Mutex<DownloadProgress>>,
+    /// Flag that tells the downloading thread to stop.
+    cancel_flag: Arc<AtomicBool>,
+    /// Handle of the downloading thread.
+    thread_download: Option<JoinHandle<()>>,
+}
+
+/// Gets the path of the partial file of a download.
+///
+/// # Arguments
+///
+/// * `path` - The path the recording is saved to.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(format!(".{}", PARTIAL_EXTENSION));
+    PathBuf::from(partial)
+}
+
+/// Downloads a recording chunk by chunk, resuming a partial file if there is one.
+///
+/// # Arguments
+///
+/// * `channel` - The `SecureChannel` to request the chunks through.
+/// * `id` - The ID of the recording.
+/// * `path` - The path to save the recording to.
+/// * `progress` - The progress of the download, updated after every chunk.
+/// * `cancel_flag` - The flag that tells the download to stop.
+///
+/// # Returns
+///
+/// `Err` with a message for the user if the download failed.
+fn download_recording(
+    channel: &mut SecureChannel,
+    id: i32,
+    path: &Path,
+    progress: &Mutex<DownloadProgress>,
+    cancel_flag: &AtomicBool,
+) -> Result<(), String> {
+    let partial = partial_path(path);
+
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&partial)
+        .map_err(|_| "Could not create the file".to_string())?;
+
+    // the checksum covers the whole file, including the part downloaded before
+    let mut hasher = md5::Context::new();
+    io::copy(
+        &mut File::open(&partial).map_err(|_| "Could not read the file".to_string())?,
+        &mut hasher,
+    )
+    .map_err(|_| "Could not read the file".to_string())?;
+
+    let mut offset = file.metadata().map_or(0, |metadata| metadata.len());
+    progress.lock().unwrap().received = offset;
+
+    loop {
+        if cancel_flag.load(Ordering::Relaxed) {
+            progress.lock().unwrap().state = DownloadState::Cancelled;
+            return Ok(());
+        }
+
+        channel
+            .send(Packet::DownloadRecording { id, offset })
+            .map_err(|_| "Lost connection to the server".to_string())?;
+
+        let packet = channel
+            .receive()
+            .map_err(|_| "Lost connection to the server".to_string())?;
+
+        match packet {
+            Packet::RecordingChunk {
+                offset: chunk_offset,
+                size,
+                bytes,
+                ..
+            } if chunk_offset == offset => {
+                file.write_all(&bytes)
+                    .map_err(|_| "Could not write the file".to_string())?;
+                hasher.consume(&bytes);
+
+                offset += bytes.len() as u64;
+
+                let mut progress = progress.lock().unwrap();
+                progress.received = offset;
+                progress.size = Some(size);
+            }
+
+            Packet::RecordingEnd { checksum, .. } => {
+                drop(file);
+
+                if format!("{:x}", hasher.compute()) != checksum {
+                    let _ = fs::remove_file(&partial);
+                    return Err("The download was corrupted, try again".to_string());
+                }
+
+                fs::rename(&partial, path).map_err(|_| "Could not save the file".to_string())?;
+
+                progress.lock().unwrap().state = DownloadState::Completed;
+                return Ok(());
+            }
+
+            Packet::DownloadFailed { message, .. } => return Err(message),
+
+            _ => return Err("Unexpected response from the server".to_string()),
+        }
+    }
+}
+
+impl Download {
+    /// Starts downloading a recording in the background.
+    ///
+    /// If a partial file of an earlier download to the same path exists, the download
+    /// continues from where it stopped.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The `SecureChannel` to download through.
+    /// * `id` - The ID of the recording.
+    /// * `path` - The path to save the recording to.
+    ///
+    /// # Returns
+    ///
+    /// The started `Download`.
+    pub fn start(channel: &SecureChannel, id: i32, path: PathBuf) -> Self {
+        let progress = Arc::new(Mutex::new(DownloadProgress {
+            received: 0,
+            size: None,
+            state: DownloadState::InProgress,
+        }));
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+
+        let mut channel = channel.clone();
+        let thread_progress = progress.clone();
+        let thread_cancel_flag = cancel_flag.clone();
+        let thread_path = path.clone();
+
+        let thread_download = thread::spawn(move || {
+            if let Err(msg) = download_recording(
+                &mut channel,
+                id,
+                &thread_path,
+                &thread_progress,
+                &thread_cancel_flag,
+            ) {
+                thread_progress.lock().unwrap().state = DownloadState::Failed(msg);
+            }
+        });
+
+        Self {
+            path,
+            progress,
+            cancel_flag,
+            thread_download: Some(thread_download),
+        }
+    }
+
+    /// Checks whether the download is still using the channel.
+    ///
+    /// # Returns
+    ///
+    /// `true` until the downloading thread stopped.
+    pub fn is_active(&self) -> bool {
+        self.thread_download
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Stops the download after the current chunk, keeping the partial file for resuming.
+    pub fn cancel(&mut self) {
+        self.cancel_flag.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.thread_download.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Displays the progress of the download.
+    ///
+    /// # Arguments
+    ///
+    /// * `ui` - The `egui::Ui` to draw on.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the user dismissed a finished download.
+    pub fn ui(&mut self, ui: &mut Ui) -> bool {
+        let mut dismissed = false;
+
+        ui.group(|ui| {
+            let filename = self.path.file_name().unwrap_or_default().to_string_lossy();
+            ui.label(RichText::new(filename).strong());
+
+            let progress = self.progress.lock().unwrap();
+            match &progress.state {
+                DownloadState::InProgress => {
+                    let fraction = progress
+                        .size
+                        .filter(|size| *size > 0)
+                        .map_or(0.0, |size| progress.received as f32 / size as f32);
+                    let text = match progress.size {
+                        Some(size) => {
+                            format!("{} / {}", format_size(progress.received), format_size(size))
+                        }
+                        None => format_size(progress.received),
+                    };
+                    drop(progress);
+
+                    // keep the progress moving while no input arrives
+                    ui.ctx().request_repaint_after(Duration::from_millis(100));
+
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::ProgressBar::new(fraction)
+                                .desired_width(160.0)
+                                .text(text),
+                        );
+                        if ui.button("Cancel").clicked() {
+                            self.cancel();
+                        }
+                    });
+                }
+
+                state => {
+                    let (message, color) = match state {
+                        DownloadState::Completed => {
+                            (format!("Saved to {}", self.path.display()), Color32::GREEN)
+                        }
+                        DownloadState::Cancelled => (
+                            "Cancelled, save to the same file to resume".to_string(),
+                            Color32::GRAY,
+                        ),
+                        DownloadState::Failed(reason) => (reason.clone(), Color32::RED),
+                        DownloadState::InProgress => unreachable!(),
+                    };
+                    drop(progress);
+
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(message).color(color));
+                        dismissed = ui.button("Dismiss").clicked();
+                    });
+                }
+            }
+        });
+
+        dismissed
+    }
+}
+
+impl Drop for Download {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -0,0 +1,665 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use eframe::egui::{self, Color32, RichText, Ui};
+use stream_desk::{protocol::Packet, secure_channel::SecureChannel};
+
+/// The folder received files are saved to.
+pub const DOWNLOADS_FOLDER: &'static str = "downloads";
+/// The extension of files that are still being received.
+pub const PARTIAL_EXTENSION: &'static str = "part";
+/// The size of a single file chunk.
+const CHUNK_SIZE: usize = 32 * 1024;
+/// The maximum rate (in bytes per second) a file is sent at, so screen packets aren't starved.
+const MAX_TRANSFER_RATE: f64 = 4.0 * 1024.0 * 1024.0;
+
+/// The list of file transfers shared between the UI and the background threads.
+pub type SharedTransfers = Arc<Mutex<Vec<Transfer>>>;
+
+/// Represents the state of a file transfer.
+#[derive(PartialEq)]
+pub enum TransferState {
+    /// The file was offered and is waiting for the receiver to accept it.
+    Offered,
+    /// The file is being transferred.
+    InProgress,
+    /// The file was transferred and its checksum was verified.
+    Completed,
+    /// The transfer was cancelled or failed, with the reason.
+    Failed(String),
+}
+
+/// Represents a single file transfer, either incoming or outgoing.
+pub struct Transfer {
+    /// The ID of the transfer, chosen by the sender.
+    id: u32,
+    /// The username of the other side of the transfer.
+    peer: String,
+    /// The name of the file.
+    filename: String,
+    /// The size of the file in bytes.
+    size: u64,
+    /// The number of bytes transferred so far.
+    transferred: u64,
+    /// Whether this client is the sender.
+    outgoing: bool,
+    /// The current state of the transfer.
+    state: TransferState,
+
+    /// The source file for outgoing transfers, or the partial file for incoming ones.
+    path: PathBuf,
+    /// The open partial file of an incoming transfer.
+    file: Option<File>,
+    /// The running checksum of an incoming transfer.
+    hasher: Option<md5::Context>,
+    /// Flag that tells the sending thread to stop.
+    cancel_flag: Arc<AtomicBool>,
+    /// Handle of the thread sending an outgoing transfer.
+    thread_send_file: Option<JoinHandle<()>>,
+}
+
+impl Transfer {
+    /// Checks whether the transfer has not finished yet.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the transfer is offered or in progress, `false` otherwise.
+    fn is_active(&self) -> bool {
+        self.state == TransferState::Offered || self.state == TransferState::InProgress
+    }
+
+    /// Marks the transfer as failed and stops it.
+    ///
+    /// # Arguments
+    ///
+    /// * `reason` - The reason the transfer failed, displayed to the user.
+    fn fail(&mut self, reason: &str) {
+        self.state = TransferState::Failed(reason.to_string());
+        self.cancel_flag.store(true, Ordering::Relaxed);
+        self.file = None;
+        self.hasher = None;
+    }
+}
+
+/// Finds a transfer by its ID.
+///
+/// # Arguments
+///
+/// * `transfers` - The list of transfers.
+/// * `id` - The ID of the transfer.
+///
+/// # Returns
+///
+/// A mutable reference to the transfer, or `None` if there is no such transfer.
+fn find_transfer(transfers: &mut [Transfer], id: u32) -> Option<&mut Transfer> {
+    transfers.iter_mut().find(|transfer| transfer.id == id)
+}
+
+/// Finds a path in the downloads folder that doesn't overwrite an existing file.
+///
+/// # Arguments
+///
+/// * `filename` - The wanted filename.
+///
+/// # Returns
+///
+/// The first of `filename`, `filename (1)`, `filename (2)`, ... that doesn't exist yet.
+fn unique_download_path(filename: &str) -> PathBuf {
+    let path = PathBuf::from(DOWNLOADS_FOLDER).join(filename);
+    if !path.exists() {
+        return path;
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|i| PathBuf::from(DOWNLOADS_FOLDER).join(format!("{stem} ({i}){extension}")))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Offers a file to another user in the session.
+///
+/// # Arguments
+///
+/// * `transfers` - The shared list of transfers.
+/// * `path` - The path of the file to send.
+/// * `peer` - The username of the receiver.
+/// * `channel` - The `SecureChannel` to send the offer through.
+///
+/// # Returns
+///
+/// `Err` with a message if the file can't be read.
+pub fn offer_file(
+    transfers: &SharedTransfers,
+    path: &Path,
+    peer: &str,
+    channel: &mut SecureChannel,
+) -> Result<(), String> {
+    let metadata = fs::metadata(path).map_err(|_| "Could not read the file.".to_string())?;
+    if !metadata.is_file() {
+        return Err("Only files can be sent.".to_string());
+    }
+
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or("Invalid filename.".to_string())?;
+
+    let id = rand::random::<u32>();
+
+    transfers.lock().unwrap().push(Transfer {
+        id,
+        peer: peer.to_string(),
+        filename: filename.clone(),
+        size: metadata.len(),
+        transferred: 0,
+        outgoing: true,
+        state: TransferState::Offered,
+
+        path: path.to_path_buf(),
+        file: None,
+        hasher: None,
+        cancel_flag: Arc::new(AtomicBool::new(false)),
+        thread_send_file: None,
+    });
+
+    let offer = Packet::FileOffer {
+        id,
+        username: peer.to_string(),
+        filename,
+        size: metadata.len(),
+    };
+    channel.send(offer).unwrap();
+
+    Ok(())
+}
+
+/// Accepts an incoming file offer.
+///
+/// If a partial file with the same name from an interrupted transfer exists,
+/// the transfer resumes from where it stopped.
+///
+/// # Arguments
+///
+/// * `transfer` - The offered transfer.
+/// * `channel` - The `SecureChannel` to send the acceptance through.
+///
+/// # Returns
+///
+/// An `io::Result<()>` that signifies if the partial file could be prepared.
+fn accept_transfer(transfer: &mut Transfer, channel: &mut SecureChannel) -> io::Result<()> {
+    fs::create_dir_all(DOWNLOADS_FOLDER)?;
+
+    let partial_path = PathBuf::from(DOWNLOADS_FOLDER)
+        .join(format!("{}.{}", transfer.filename, PARTIAL_EXTENSION));
+
+    // resume only if the partial file can belong to this file
+    let partial_size = fs::metadata(&partial_path).map_or(0, |metadata| metadata.len());
+    let offset = if partial_size <= transfer.size {
+        partial_size
+    } else {
+        0
+    };
+
+    let mut hasher = md5::Context::new();
+    if offset > 0 {
+        io::copy(&mut File::open(&partial_path)?.take(offset), &mut hasher)?;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(offset > 0)
+        .write(true)
+        .truncate(offset == 0)
+        .open(&partial_path)?;
+
+    transfer.path = partial_path;
+    transfer.file = Some(file);
+    transfer.hasher = Some(hasher);
+    transfer.transferred = offset;
+    transfer.state = TransferState::InProgress;
+
+    let accept = Packet::FileAccept {
+        id: transfer.id,
+        username: transfer.peer.clone(),
+        offset,
+    };
+    channel.send(accept).unwrap();
+
+    Ok(())
+}
+
+/// Cancels a transfer and notifies the other side.
+///
+/// # Arguments
+///
+/// * `transfer` - The transfer to cancel.
+/// * `channel` - The `SecureChannel` to send the cancellation through.
+fn cancel_transfer(transfer: &mut Transfer, channel: &mut SecureChannel) {
+    transfer.fail("Cancelled");
+
+    let cancel = Packet::FileCancel {
+        id: transfer.id,
+        username: transfer.peer.clone(),
+    };
+    channel.send(cancel).unwrap();
+}
+
+/// Sends a file in chunks, starting from `offset`, followed by its checksum.
+///
+/// # Arguments
+///
+/// * `channel` - The `SecureChannel` to send the file through.
+/// * `transfers` - The shared list of transfers, used to report progress.
+/// * `id` - The ID of the transfer.
+/// * `peer` - The username of the receiver.
+/// * `path` - The path of the file.
+/// * `offset` - The number of bytes the receiver already has.
+/// * `cancel_flag` - The flag that tells the transfer to stop.
+///
+/// # Returns
+///
+/// An `io::Result<()>` that signifies if something went wrong.
+fn send_file(
+    channel: &mut SecureChannel,
+    transfers: &SharedTransfers,
+    id: u32,
+    peer: &str,
+    path: &Path,
+    offset: u64,
+    cancel_flag: &AtomicBool,
+) -> io::Result<()> {
+    let mut file = File::open(path)?;
+
+    // the checksum covers the whole file, including the part the receiver already has
+    let mut hasher = md5::Context::new();
+    io::copy(&mut (&mut file).take(offset), &mut hasher)?;
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut position = offset;
+    let started = Instant::now();
+
+    loop {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+
+        hasher.consume(&buffer[..n]);
+
+        let chunk = Packet::FileChunk {
+            id,
+            username: peer.to_string(),
+            offset: position,
+            bytes: buffer[..n].to_vec(),
+        };
+        channel.send(chunk)?;
+
+        position += n as u64;
+
+        if let Some(transfer) = find_transfer(&mut transfers.lock().unwrap(), id) {
+            transfer.transferred = position;
+        }
+
+        // pace the transfer so it doesn't starve the screen packets
+        let target = Duration::from_secs_f64((position - offset) as f64 / MAX_TRANSFER_RATE);
+        if let Some(wait) = target.checked_sub(started.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+
+    let end = Packet::FileEnd {
+        id,
+        username: peer.to_string(),
+        checksum: format!("{:x}", hasher.compute()),
+    };
+    channel.send(end)?;
+
+    if let Some(transfer) = find_transfer(&mut transfers.lock().unwrap(), id) {
+        if transfer.state == TransferState::InProgress {
+            transfer.state = TransferState::Completed;
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts a thread that sends an accepted file.
+///
+/// # Arguments
+///
+/// * `channel` - The `SecureChannel` to send the file through.
+/// * `transfers` - The shared list of transfers.
+/// * `transfer` - The accepted transfer.
+/// * `offset` - The number of bytes the receiver already has.
+///
+/// # Returns
+///
+/// The thread's `JoinHandle`.
+fn thread_send_file(
+    mut channel: SecureChannel,
+    transfers: SharedTransfers,
+    transfer: &Transfer,
+    offset: u64,
+) -> JoinHandle<()> {
+    let id = transfer.id;
+    let peer = transfer.peer.clone();
+    let path = transfer.path.clone();
+    let cancel_flag = transfer.cancel_flag.clone();
+
+    thread::spawn(move || {
+        let result = send_file(
+            &mut channel,
+            &transfers,
+            id,
+            &peer,
+            &path,
+            offset,
+            &cancel_flag,
+        );
+
+        if result.is_err() {
+            if let Some(transfer) = find_transfer(&mut transfers.lock().unwrap(), id) {
+                transfer.fail("Could not read the file");
+            }
+
+            let _ = channel.send(Packet::FileCancel { id, username: peer });
+        }
+    })
+}
+
+/// Handles a file transfer packet received from the server.
+///
+/// # Arguments
+///
+/// * `transfers` - The shared list of transfers.
+/// * `packet` - One of the `File*` packets.
+/// * `channel` - The `SecureChannel` to respond through.
+pub fn handle_transfer_packet(
+    transfers: &SharedTransfers,
+    packet: Packet,
+    channel: &mut SecureChannel,
+) {
+    let mut transfers_guard = transfers.lock().unwrap();
+
+    match packet {
+        Packet::FileOffer {
+            id,
+            username,
+            filename,
+            size,
+        } => {
+            // never trust the sender's path
+            let Some(filename) = Path::new(&filename).file_name() else {
+                return;
+            };
+
+            transfers_guard.push(Transfer {
+                id,
+                peer: username,
+                filename: filename.to_string_lossy().to_string(),
+                size,
+                transferred: 0,
+                outgoing: false,
+                state: TransferState::Offered,
+
+                path: PathBuf::new(),
+                file: None,
+                hasher: None,
+                cancel_flag: Arc::new(AtomicBool::new(false)),
+                thread_send_file: None,
+            });
+        }
+
+        Packet::FileAccept { id, offset, .. } => {
+            if let Some(transfer) = find_transfer(&mut transfers_guard, id) {
+                if transfer.outgoing && transfer.state == TransferState::Offered {
+                    transfer.state = TransferState::InProgress;
+                    transfer.transferred = offset;
+                    transfer.thread_send_file = Some(thread_send_file(
+                        channel.clone(),
+                        transfers.clone(),
+                        transfer,
+                        offset,
+                    ));
+                }
+            }
+        }
+
+        Packet::FileChunk {
+            id, offset, bytes, ..
+        } => {
+            let Some(transfer) = find_transfer(&mut transfers_guard, id) else {
+                return;
+            };
+
+            if transfer.outgoing || transfer.state != TransferState::InProgress {
+                return;
+            }
+
+            let written = match (&mut transfer.file, &mut transfer.hasher) {
+                (Some(file), Some(hasher)) if offset == transfer.transferred => {
+                    hasher.consume(&bytes);
+                    file.write_all(&bytes).is_ok()
+                }
+                _ => false,
+            };
+
+            if written {
+                transfer.transferred += bytes.len() as u64;
+            } else {
+                cancel_transfer(transfer, channel);
+                transfer.state = TransferState::Failed("Could not write the file".to_string());
+            }
+        }
+
+        Packet::FileEnd { id, checksum, .. } => {
+            let Some(transfer) = find_transfer(&mut transfers_guard, id) else {
+                return;
+            };
+
+            if transfer.outgoing || transfer.state != TransferState::InProgress {
+                return;
+            }
+
+            transfer.file = None;
+            let digest = transfer.hasher.take().map(|hasher| hasher.compute());
+
+            if digest.map(|digest| format!("{:x}", digest)) == Some(checksum) {
+                let _ = fs::rename(&transfer.path, unique_download_path(&transfer.filename));
+                transfer.state = TransferState::Completed;
+            } else {
+                // a corrupted partial file must not be resumed
+                let _ = fs::remove_file(&transfer.path);
+                cancel_transfer(transfer, channel);
+                transfer.state = TransferState::Failed("Checksum mismatch".to_string());
+            }
+        }
+
+        Packet::FileCancel { id, username } => {
+            if let Some(transfer) = find_transfer(&mut transfers_guard, id) {
+                if transfer.is_active() || transfer.outgoing {
+                    transfer.fail(&format!("Cancelled by {}", username));
+                }
+            }
+        }
+
+        _ => (),
+    }
+}
+
+/// Stops all the transfers with a user, keeping partial files so they can be resumed.
+///
+/// # Arguments
+///
+/// * `transfers` - The shared list of transfers.
+/// * `peer` - The username of the user that left.
+pub fn interrupt_transfers(transfers: &SharedTransfers, peer: &str) {
+    let mut transfers = transfers.lock().unwrap();
+
+    for transfer in transfers.iter_mut() {
+        if transfer.peer == peer && transfer.is_active() {
+            transfer.fail("Interrupted, send the file again to resume");
+        }
+    }
+}
+
+/// Stops all the transfers and waits for the sending threads to finish.
+///
+/// Partial files are kept so the transfers can be resumed after reconnecting.
+///
+/// # Arguments
+///
+/// * `transfers` - The shared list of transfers.
+pub fn stop_transfers(transfers: &SharedTransfers) {
+    let threads: Vec<JoinHandle<()>> = {
+        let mut transfers = transfers.lock().unwrap();
+
+        transfers
+            .iter_mut()
+            .filter_map(|transfer| {
+                transfer.cancel_flag.store(true, Ordering::Relaxed);
+                transfer.thread_send_file.take()
+            })
+            .collect()
+    };
+
+    // joining without the lock, since the threads lock it to report progress
+    for thread in threads {
+        let _ = thread.join();
+    }
+}
+
+/// Formats a number of bytes in a human readable way.
+///
+/// # Arguments
+///
+/// * `bytes` - The number of bytes.
+///
+/// # Returns
+///
+/// The formatted size, e.g. `"1.5 MB"`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Displays the list of file transfers with their progress.
+///
+/// Incoming offers can be accepted or declined, and active transfers can be cancelled.
+///
+/// # Arguments
+///
+/// * `ui` - The `egui::Ui` to draw on.
+/// * `transfers` - The shared list of transfers.
+/// * `channel` - The `SecureChannel` to send responses through.
+pub fn transfers_ui(ui: &mut Ui, transfers: &SharedTransfers, channel: &mut SecureChannel) {
+    let mut transfers = transfers.lock().unwrap();
+
+    if transfers.is_empty() {
+        ui.label("Drop files on the window to send them.");
+        return;
+    }
+
+    for transfer in transfers.iter_mut().rev() {
+        ui.group(|ui| {
+            let direction = if transfer.outgoing { "To" } else { "From" };
+            ui.label(RichText::new(&transfer.filename).strong());
+            ui.label(format!(
+                "{} {} | {}",
+                direction,
+                transfer.peer,
+                format_size(transfer.size)
+            ));
+
+            match &transfer.state {
+                TransferState::Offered if transfer.outgoing => {
+                    ui.horizontal(|ui| {
+                        ui.label("Waiting for response...");
+                        if ui.button("Cancel").clicked() {
+                            cancel_transfer(transfer, channel);
+                        }
+                    });
+                }
+
+                TransferState::Offered => {
+                    ui.horizontal(|ui| {
+                        if ui.button("Accept").clicked()
+                            && accept_transfer(transfer, channel).is_err()
+                        {
+                            cancel_transfer(transfer, channel);
+                            transfer.state =
+                                TransferState::Failed("Could not create the file".to_string());
+                        }
+
+                        if ui.button("Decline").clicked() {
+                            cancel_transfer(transfer, channel);
+                        }
+                    });
+                }
+
+                TransferState::InProgress => {
+                    let progress = if transfer.size == 0 {
+                        1.0
+                    } else {
+                        transfer.transferred as f32 / transfer.size as f32
+                    };
+
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::ProgressBar::new(progress)
+                                .desired_width(120.0)
+                                .show_percentage(),
+                        );
+                        if ui.button("Cancel").clicked() {
+                            cancel_transfer(transfer, channel);
+                        }
+                    });
+                }
+
+                TransferState::Completed => {
+                    let message = if transfer.outgoing {
+                        "Sent".to_string()
+                    } else {
+                        format!("Saved to the {} folder", DOWNLOADS_FOLDER)
+                    };
+                    ui.label(RichText::new(message).color(Color32::GREEN));
+                }
+
+                TransferState::Failed(reason) => {
+                    ui.label(RichText::new(reason).color(Color32::RED));
+                }
+            }
+        });
+    }
+}
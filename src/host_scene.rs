@@ -6,8 +6,11 @@ use h264_reader::{
 };
 use log::info;
 use stream_desk::{
-    chat_ui, protocol::ControlPayload, secure_channel::SecureChannel, users_list, Scene,
-    SceneChange, UserType, LOG_TARGET,
+    bookmark_message, bookmark_ui, chat_ui,
+    protocol::{ControlPayload, RecordingState},
+    recording_indicator, recording_message,
+    secure_channel::SecureChannel,
+    users_list, Scene, SceneChange, UserType, LOG_TARGET,
 };
 
 use eframe::egui::PointerButton;
@@ -16,25 +19,56 @@ use std::{
     io::Read,
     process::{Child, ChildStdout, Command, Stdio},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, Mutex,
     },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
-use stream_desk::protocol::Packet;
+use stream_desk::protocol::{CursorShape, Packet};
+use winapi::shared::windef::HCURSOR;
 use winapi::um::winuser::{
-    self, SendInput, INPUT, INPUT_KEYBOARD, INPUT_MOUSE, KEYBDINPUT, MOUSEINPUT, WHEEL_DELTA,
+    self, GetCursorInfo, LoadCursorW, SendInput, CURSORINFO, INPUT, INPUT_KEYBOARD, INPUT_MOUSE,
+    KEYBDINPUT, MOUSEINPUT,
 };
 
-use crate::menu_scene::MenuScene;
+use crate::{
+    audio::{
+        default_system_device, device_picker, list_audio_devices, AudioCapture, AudioKind,
+        AudioPlayer, SharedAudioPlayer,
+    },
+    capture::{
+        capture_rect, ffmpeg_input_args, from_virtual_desktop, source_picker, to_virtual_desktop,
+        CaptureArea, CaptureRect, CaptureSource, SharedCaptureSource,
+    },
+    clipboard::{clipboard_digest, read_clipboard, write_clipboard},
+    file_transfer::{
+        handle_transfer_packet, interrupt_transfers, offer_file, stop_transfers, transfers_ui,
+        SharedTransfers,
+    },
+    menu_scene::MenuScene,
+    stream_quality::{Feedback, QualityController, SharedFeedback, StreamQuality},
+};
+
+/// How often the host's local clipboard is checked for changes.
+const CLIPBOARD_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How often the host's cursor is checked for changes.
+const CURSOR_POLL_INTERVAL: Duration = Duration::from_millis(16);
+/// How often the cursor is sent even if it didn't change, so new participants get it.
+const CURSOR_RESEND_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Starts FFmpeg process to capture desktop screen as H.264 stream
 ///
 /// This function launches FFmpeg with optimized settings for real-time screen sharing:
-/// - Uses gdigrab for Windows desktop capture
-/// - 30 FPS framerate for smooth playback
+/// - Uses gdigrab for Windows desktop capture, limited to the chosen display, region or window
+/// - The framerate, bitrate and resolution of the current stream quality
 /// - Ultrafast preset with zero latency tuning for minimal delay
-/// - H.264 encoding with no scene cut detection for consistent streaming
+/// - H.264 encoding with a keyframe every 2 seconds and no scene cut detection for consistent streaming
+///
+/// # Arguments
+///
+/// * `area` - The part of the desktop to capture
+/// * `quality` - The encoder settings
 ///
 /// # Returns
 ///
@@ -43,25 +77,32 @@ use crate::menu_scene::MenuScene;
 /// # Panics
 ///
 /// Panics if FFmpeg cannot be started (e.g., FFmpeg not installed or not in PATH)
-fn start_ffmpeg() -> Child {
+fn start_ffmpeg(area: &CaptureArea, quality: StreamQuality) -> Child {
+    let framerate = quality.framerate.to_string();
+    let keyframe_interval = (quality.framerate * 2).to_string();
+
     let ffmpeg = Command::new("ffmpeg")
-        .args(&[
+        .args([
             "-f",
             "gdigrab",
             "-framerate",
-            "30",
+            &framerate,
             "-draw_mouse",
             "0",
-            "-i",
-            "desktop",
+        ])
+        .args(ffmpeg_input_args(area))
+        .args([
             "-vcodec",
             "libx264",
             "-preset",
             "ultrafast",
             "-tune",
             "zerolatency",
+        ])
+        .args(quality.ffmpeg_args())
+        .args([
             "-g",
-            "60",
+            &keyframe_interval,
             "-x264opts",
             "no-scenecut",
             "-sc_threshold",
@@ -90,7 +131,9 @@ fn start_ffmpeg() -> Child {
 ///
 /// * `channel` - Secure communication channel for sending packets
 /// * `stdout` - FFmpeg process stdout handle for reading video data
+/// * `layer` - The simulcast layer the screen packets are tagged with
 /// * `stop_flag` - Atomic boolean to signal thread termination
+/// * `sent_bytes` - Counter of the sent screen bytes of layer 0, for measuring the bitrate
 ///
 /// # Returns
 ///
@@ -98,7 +141,9 @@ fn start_ffmpeg() -> Child {
 fn thread_send_screen(
     mut channel: SecureChannel,
     mut stdout: ChildStdout,
+    layer: u8,
     stop_flag: Arc<AtomicBool>,
+    sent_bytes: Arc<AtomicU64>,
 ) -> JoinHandle<()> {
     thread::spawn(move || {
         let mut reader = AnnexBReader::accumulate(|nal: RefNal<'_>| {
@@ -116,7 +161,14 @@ fn thread_send_screen(
                 .read_to_end(&mut nal_bytes)
                 .expect("should be able to read NAL");
 
-            channel.send(Packet::Screen { bytes: nal_bytes }).unwrap();
+            if layer == 0 {
+                sent_bytes.fetch_add(nal_bytes.len() as u64, Ordering::Relaxed);
+            }
+            let packet = Packet::Screen {
+                layer,
+                bytes: nal_bytes,
+            };
+            channel.send(packet).unwrap();
 
             NalInterest::Ignore
         });
@@ -146,6 +198,11 @@ fn thread_send_screen(
 /// - Control packets (mouse/keyboard input from controllers)
 /// - Control requests from viewers
 /// - Chat messages
+/// - Clipboard content from the controller
+/// - File transfers with participants
+/// - Stream feedback from participants
+/// - Microphone audio from participants
+/// - Recording state changes
 /// - Session end signals
 ///
 /// # Arguments
@@ -155,6 +212,12 @@ fn thread_send_screen(
 /// * `requesting_control` - Set of users requesting control permissions
 /// * `requesting_join` - Set of users requesting to join the session
 /// * `chat_log` - Shared chat message history
+/// * `last_clipboard` - Digest of the last synchronized clipboard content
+/// * `file_transfers` - Shared list of file transfers with participants
+/// * `capture_source` - The shared part of the desktop, which control positions are relative to
+/// * `stream_feedback` - The latest stream report of every participant
+/// * `audio_player` - Player for the participants' microphones
+/// * `recording_state` - Whether the session is recorded, as the server reports it
 ///
 /// # Returns
 ///
@@ -165,6 +228,12 @@ fn thread_read_socket(
     requesting_control: Arc<Mutex<HashSet<String>>>,
     requesting_join: Arc<Mutex<HashSet<String>>>,
     chat_log: Arc<Mutex<Vec<String>>>,
+    last_clipboard: Arc<Mutex<Option<md5::Digest>>>,
+    file_transfers: SharedTransfers,
+    capture_source: SharedCaptureSource,
+    stream_feedback: SharedFeedback,
+    audio_player: SharedAudioPlayer,
+    recording_state: Arc<Mutex<RecordingState>>,
 ) -> JoinHandle<()> {
     thread::spawn(move || loop {
         let packet = channel.receive().unwrap_or_default();
@@ -183,6 +252,9 @@ fn thread_read_socket(
                 let mut usernames = usernames.lock().unwrap();
                 if user_type == UserType::Leaving {
                     usernames.remove(&username);
+                    interrupt_transfers(&file_transfers, &username);
+                    stream_feedback.lock().unwrap().remove(&username);
+                    audio_player.lock().unwrap().stop(&username);
 
                     let mut chat_log = chat_log.lock().unwrap();
                     chat_log.push(format!("#r{} has disconnected.", username));
@@ -200,20 +272,53 @@ fn thread_read_socket(
             }
 
             Packet::Control { payload } => match payload {
-                ControlPayload::MouseMove { mouse_x, mouse_y } => send_mouse_move(mouse_x, mouse_y),
+                ControlPayload::MouseMove { mouse_x, mouse_y } => {
+                    if let Some(rect) = capture_source.lock().unwrap().rect {
+                        let (x, y) = to_virtual_desktop(rect, mouse_x, mouse_y);
+                        send_mouse_move(x, y);
+                    }
+                }
 
                 ControlPayload::MouseClick {
                     mouse_x,
                     mouse_y,
                     pressed,
                     button,
-                } => send_mouse_click(mouse_x, mouse_y, button, pressed),
+                } => {
+                    if let Some(rect) = capture_source.lock().unwrap().rect {
+                        let (x, y) = to_virtual_desktop(rect, mouse_x, mouse_y);
+                        send_mouse_click(x, y, button, pressed);
+                    }
+                }
 
                 ControlPayload::Keyboard { pressed, key } => send_key(key, pressed),
 
-                ControlPayload::Scroll { delta } => send_scroll(delta),
+                ControlPayload::Scroll { delta_x, delta_y } => send_scroll(delta_x, delta_y),
             },
 
+            Packet::StreamFeedback {
+                username,
+                received_kbps,
+                queue_depth,
+                dropped_frames,
+            } => {
+                let feedback = Feedback {
+                    received_kbps,
+                    queue_depth,
+                    dropped_frames,
+                    received: Instant::now(),
+                };
+                stream_feedback.lock().unwrap().insert(username, feedback);
+            }
+
+            Packet::Audio { username, bytes } => {
+                audio_player.lock().unwrap().play(&username, bytes);
+            }
+
+            Packet::AudioEnd { username } => {
+                audio_player.lock().unwrap().stop(&username);
+            }
+
             Packet::RequestControl { username } => {
                 let mut requesting_control = requesting_control.lock().unwrap();
                 requesting_control.insert(username);
@@ -224,6 +329,38 @@ fn thread_read_socket(
                 chat_log.push(message);
             }
 
+            Packet::Bookmark {
+                time_ms,
+                name,
+                username,
+            } => {
+                let mut chat_log = chat_log.lock().unwrap();
+                chat_log.push(bookmark_message(time_ms, &name, &username));
+            }
+
+            Packet::RecordingState { state } => {
+                *recording_state.lock().unwrap() = state;
+
+                let mut chat_log = chat_log.lock().unwrap();
+                chat_log.push(recording_message(state));
+            }
+
+            Packet::ClipboardText { .. } | Packet::ClipboardImage { .. } => {
+                // remember the content so the clipboard watcher doesn't send it back
+                let mut last_clipboard = last_clipboard.lock().unwrap();
+                *last_clipboard = Some(clipboard_digest(&packet));
+
+                write_clipboard(&packet);
+            }
+
+            Packet::FileOffer { .. }
+            | Packet::FileAccept { .. }
+            | Packet::FileChunk { .. }
+            | Packet::FileEnd { .. }
+            | Packet::FileCancel { .. } => {
+                handle_transfer_packet(&file_transfers, packet, &mut channel);
+            }
+
             Packet::SessionEnd => break,
 
             _ => (),
@@ -231,10 +368,243 @@ fn thread_read_socket(
     })
 }
 
+/// Background thread for watching the host's local clipboard
+///
+/// Polls the clipboard every `CLIPBOARD_POLL_INTERVAL` and, while clipboard
+/// synchronization is enabled, sends any new content to the server, which
+/// forwards it to the current controller.
+///
+/// # Arguments
+///
+/// * `channel` - Secure communication channel for sending packets
+/// * `clipboard_sync` - Whether the host opted in to clipboard synchronization
+/// * `last_clipboard` - Digest of the last synchronized clipboard content
+/// * `stop_flag` - Atomic boolean to signal thread termination
+///
+/// # Returns
+///
+/// A `JoinHandle` for the spawned thread
+fn thread_watch_clipboard(
+    mut channel: SecureChannel,
+    clipboard_sync: Arc<AtomicBool>,
+    last_clipboard: Arc<Mutex<Option<md5::Digest>>>,
+    stop_flag: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        while !stop_flag.load(Ordering::Relaxed) {
+            thread::sleep(CLIPBOARD_POLL_INTERVAL);
+
+            if !clipboard_sync.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            let Some(packet) = read_clipboard() else {
+                continue;
+            };
+
+            let digest = clipboard_digest(&packet);
+            let mut last_clipboard = last_clipboard.lock().unwrap();
+
+            if *last_clipboard != Some(digest) {
+                *last_clipboard = Some(digest);
+                let _ = channel.send(packet);
+            }
+        }
+    })
+}
+
+/// Background thread for streaming the host's cursor to participants
+///
+/// The screen capture doesn't draw the cursor, so its position, shape and visibility
+/// are polled every `CURSOR_POLL_INTERVAL` and sent separately from the video as
+/// `Packet::CursorState` whenever they change. The position is normalized to the
+/// shared area, in the same 0 to 65,535 range used by the control packets.
+///
+/// # Arguments
+///
+/// * `channel` - Secure communication channel for sending packets
+/// * `capture_source` - The shared part of the desktop
+/// * `stop_flag` - Atomic boolean to signal thread termination
+///
+/// # Returns
+///
+/// A `JoinHandle` for the spawned thread
+fn thread_send_cursor(
+    mut channel: SecureChannel,
+    capture_source: SharedCaptureSource,
+    stop_flag: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        // the handles of the shared system cursors, to tell the shape apart
+        let shapes = unsafe {
+            [
+                (winuser::IDC_ARROW, CursorShape::Arrow),
+                (winuser::IDC_IBEAM, CursorShape::Text),
+                (winuser::IDC_HAND, CursorShape::Hand),
+                (winuser::IDC_WAIT, CursorShape::Wait),
+                (winuser::IDC_APPSTARTING, CursorShape::Progress),
+                (winuser::IDC_CROSS, CursorShape::Crosshair),
+                (winuser::IDC_SIZEWE, CursorShape::ResizeHorizontal),
+                (winuser::IDC_SIZENS, CursorShape::ResizeVertical),
+                (winuser::IDC_SIZENWSE, CursorShape::ResizeNwSe),
+                (winuser::IDC_SIZENESW, CursorShape::ResizeNeSw),
+                (winuser::IDC_SIZEALL, CursorShape::Move),
+                (winuser::IDC_NO, CursorShape::NotAllowed),
+                (winuser::IDC_HELP, CursorShape::Help),
+            ]
+            .map(|(name, shape)| (LoadCursorW(std::ptr::null_mut(), name), shape))
+        };
+
+        let mut last_cursor = Packet::None;
+        let mut last_sent = Instant::now();
+
+        while !stop_flag.load(Ordering::Relaxed) {
+            thread::sleep(CURSOR_POLL_INTERVAL);
+
+            let mut area = capture_source.lock().unwrap().clone();
+
+            // windows can be moved or resized while they're shared
+            if let CaptureSource::Window(_) = area.source {
+                area.rect = capture_rect(&area.source);
+
+                let mut shared = capture_source.lock().unwrap();
+                if shared.source == area.source {
+                    shared.rect = area.rect;
+                }
+            }
+
+            let Some(cursor) = area.rect.and_then(|rect| read_cursor(&shapes, rect)) else {
+                continue;
+            };
+
+            if cursor != last_cursor || last_sent.elapsed() >= CURSOR_RESEND_INTERVAL {
+                last_cursor = cursor.clone();
+                last_sent = Instant::now();
+                let _ = channel.send(cursor);
+            }
+        }
+    })
+}
+
+/// Reads the current state of the host's cursor
+///
+/// # Arguments
+///
+/// * `shapes` - The handles of the system cursors and the shapes they represent.
+///   Custom cursors that aren't in the list are reported as `CursorShape::Arrow`.
+/// * `rect` - The shared area; the cursor is hidden while it's outside of it.
+///
+/// # Returns
+///
+/// A `Packet::CursorState`, or `None` if the cursor couldn't be read
+///
+/// # Safety
+///
+/// This function uses unsafe Windows API calls to query the cursor
+fn read_cursor(shapes: &[(HCURSOR, CursorShape)], rect: CaptureRect) -> Option<Packet> {
+    unsafe {
+        let mut info: CURSORINFO = std::mem::zeroed();
+        info.cbSize = std::mem::size_of::<CURSORINFO>() as u32;
+        if GetCursorInfo(&mut info) == 0 {
+            return None;
+        }
+
+        let position = from_virtual_desktop(rect, info.ptScreenPos.x, info.ptScreenPos.y);
+        let (x, y) = position.unwrap_or_default();
+
+        let shape = shapes
+            .iter()
+            .find(|(handle, _)| *handle == info.hCursor)
+            .map_or(CursorShape::Arrow, |(_, shape)| *shape);
+
+        Some(Packet::CursorState {
+            x,
+            y,
+            shape,
+            visible: position.is_some() && info.flags & winuser::CURSOR_SHOWING != 0,
+        })
+    }
+}
+
+/// Announces the size of the shared area and the number of layers to the participants
+///
+/// # Arguments
+///
+/// * `channel` - Secure communication channel for sending packets
+/// * `area` - The part of the desktop being shared
+/// * `layers` - The number of simulcast layers being encoded
+fn send_screen_source(channel: &mut SecureChannel, area: &CaptureArea, layers: u8) {
+    if let Some(rect) = area.rect {
+        let packet = Packet::ScreenSource {
+            width: rect.width,
+            height: rect.height,
+            layers,
+        };
+        let _ = channel.send(packet);
+    }
+}
+
+/// Starts an FFmpeg encoder and a screen streaming thread for every simulcast layer
+///
+/// # Arguments
+///
+/// * `channel` - Secure communication channel for sending packets
+/// * `area` - The part of the desktop to capture
+/// * `quality_controller` - The controller with the full quality settings and the number of layers
+/// * `stop_flag` - Atomic boolean to signal thread termination
+/// * `sent_bytes` - Counter of the sent screen bytes of layer 0
+///
+/// # Returns
+///
+/// The FFmpeg process and the streaming thread of every layer, starting with layer 0
+fn start_encoders(
+    channel: &mut SecureChannel,
+    area: &CaptureArea,
+    quality_controller: &QualityController,
+    stop_flag: &Arc<AtomicBool>,
+    sent_bytes: &Arc<AtomicU64>,
+) -> Vec<(Child, JoinHandle<()>)> {
+    let layers = quality_controller.layers();
+    let quality = quality_controller.quality();
+    let source_height = area.rect.map_or(1080, |rect| rect.height);
+
+    send_screen_source(channel, area, layers);
+
+    (0..layers)
+        .map(|layer| {
+            let mut command = start_ffmpeg(area, quality.for_layer(layer, source_height));
+            let stdout = command.stdout.take().unwrap();
+            let thread = thread_send_screen(
+                channel.clone(),
+                stdout,
+                layer,
+                stop_flag.clone(),
+                sent_bytes.clone(),
+            );
+
+            (command, thread)
+        })
+        .collect()
+}
+
+/// Stops all of the encoders and waits for their streaming threads to finish
+///
+/// # Arguments
+///
+/// * `encoders` - The FFmpeg processes and streaming threads of the layers
+fn stop_encoders(encoders: &mut Vec<(Child, JoinHandle<()>)>) {
+    for (mut command, thread) in encoders.drain(..) {
+        // killing FFmpeg ends the stream, which stops the screen thread
+        let _ = command.kill();
+        let _ = command.wait();
+        let _ = thread.join();
+    }
+}
+
 /// Sends mouse movement input to the host system
 ///
 /// Uses Windows API to simulate mouse cursor movement at absolute coordinates.
-/// The coordinates are treated as absolute positions on the screen.
+/// The coordinates are normalized to the whole virtual desktop (0 to 65,535).
 ///
 /// # Arguments
 ///
@@ -252,7 +622,9 @@ fn send_mouse_move(mouse_x: u32, mouse_y: u32) {
             dx: mouse_x as i32,
             dy: mouse_y as i32,
             mouseData: 0,
-            dwFlags: winuser::MOUSEEVENTF_ABSOLUTE | winuser::MOUSEEVENTF_MOVE,
+            dwFlags: winuser::MOUSEEVENTF_ABSOLUTE
+                | winuser::MOUSEEVENTF_VIRTUALDESK
+                | winuser::MOUSEEVENTF_MOVE,
             time: 0,
             dwExtraInfo: 0,
         };
@@ -269,13 +641,14 @@ fn send_mouse_move(mouse_x: u32, mouse_y: u32) {
 /// Sends mouse click input to the host system
 ///
 /// Simulates mouse button press/release events at specified coordinates.
-/// Supports primary (left), secondary (right), and middle mouse buttons.
+/// Supports primary (left), secondary (right), middle, and the two extra (back/forward)
+/// mouse buttons.
 ///
 /// # Arguments
 ///
 /// * `mouse_x` - Absolute X coordinate for click position
 /// * `mouse_y` - Absolute Y coordinate for click position  
-/// * `button` - Which mouse button to simulate (Primary/Secondary/Middle)
+/// * `button` - Which mouse button to simulate (Primary/Secondary/Middle/Extra1/Extra2)
 /// * `pressed` - Whether this is a button press (`true`) or release (`false`)
 ///
 /// # Safety
@@ -283,7 +656,10 @@ fn send_mouse_move(mouse_x: u32, mouse_y: u32) {
 /// This function uses unsafe Windows API calls to inject input events
 fn send_mouse_click(mouse_x: u32, mouse_y: u32, button: PointerButton, pressed: bool) {
     unsafe {
-        let mut flags: u32 = winuser::MOUSEEVENTF_ABSOLUTE | winuser::MOUSEEVENTF_MOVE;
+        let mut flags: u32 = winuser::MOUSEEVENTF_ABSOLUTE
+            | winuser::MOUSEEVENTF_VIRTUALDESK
+            | winuser::MOUSEEVENTF_MOVE;
+        let mut mouse_data: u32 = 0;
         if button == PointerButton::Primary {
             if pressed {
                 flags |= winuser::MOUSEEVENTF_LEFTDOWN;
@@ -302,6 +678,17 @@ fn send_mouse_click(mouse_x: u32, mouse_y: u32, button: PointerButton, pressed:
             } else {
                 flags |= winuser::MOUSEEVENTF_MIDDLEUP;
             }
+        } else {
+            if pressed {
+                flags |= winuser::MOUSEEVENTF_XDOWN;
+            } else {
+                flags |= winuser::MOUSEEVENTF_XUP;
+            }
+            mouse_data = if button == PointerButton::Extra1 {
+                winuser::XBUTTON1 as u32
+            } else {
+                winuser::XBUTTON2 as u32
+            };
         }
 
         let mut click_up_input: INPUT = std::mem::zeroed();
@@ -310,7 +697,7 @@ fn send_mouse_click(mouse_x: u32, mouse_y: u32, button: PointerButton, pressed:
         *click_up_input.u.mi_mut() = MOUSEINPUT {
             dx: mouse_x as i32,
             dy: mouse_y as i32,
-            mouseData: 0,
+            mouseData: mouse_data,
             dwFlags: flags,
             time: 0,
             dwExtraInfo: 0,
@@ -327,30 +714,44 @@ fn send_mouse_click(mouse_x: u32, mouse_y: u32, button: PointerButton, pressed:
 
 /// Sends mouse scroll wheel input to the host system
 ///
-/// Simulates vertical scrolling with the specified delta value.
-/// Positive delta scrolls up, negative delta scrolls down.
+/// Simulates vertical and horizontal scrolling with the specified delta values.
+/// The deltas are in `SCROLL_UNITS_PER_NOTCH` units, which match Windows' `WHEEL_DELTA`,
+/// so smooth scrolling is passed through without rounding to whole notches.
 ///
 /// # Arguments
 ///
-/// * `delta` - Scroll amount and direction (positive = up, negative = down)
+/// * `delta_x` - Horizontal scroll amount and direction (positive = right, negative = left)
+/// * `delta_y` - Vertical scroll amount and direction (positive = up, negative = down)
 ///
 /// # Safety
 ///
 /// This function uses unsafe Windows API calls to inject input events
-fn send_scroll(delta: i32) {
+fn send_scroll(delta_x: i32, delta_y: i32) {
+    let axes = [
+        (delta_y, winuser::MOUSEEVENTF_WHEEL),
+        (delta_x, winuser::MOUSEEVENTF_HWHEEL),
+    ];
+
     unsafe {
-        let mut scroll_input: INPUT = std::mem::zeroed();
-        scroll_input.type_ = INPUT_MOUSE;
-        *scroll_input.u.mi_mut() = MOUSEINPUT {
-            dx: 0,
-            dy: 0,
-            mouseData: (delta * WHEEL_DELTA as i32) as u32,
-            dwFlags: winuser::MOUSEEVENTF_WHEEL,
-            time: 0,
-            dwExtraInfo: 0,
-        };
+        let mut inputs = Vec::new();
+        for (delta, flag) in axes {
+            if delta == 0 {
+                continue;
+            }
+
+            let mut scroll_input: INPUT = std::mem::zeroed();
+            scroll_input.type_ = INPUT_MOUSE;
+            *scroll_input.u.mi_mut() = MOUSEINPUT {
+                dx: 0,
+                dy: 0,
+                mouseData: delta as u32,
+                dwFlags: flag,
+                time: 0,
+                dwExtraInfo: 0,
+            };
+            inputs.push(scroll_input);
+        }
 
-        let mut inputs = [scroll_input];
         SendInput(
             inputs.len() as u32,
             inputs.as_mut_ptr(),
@@ -399,6 +800,9 @@ fn send_key(key: u16, pressed: bool) {
 /// - User management and permissions
 /// - Control request handling
 /// - Chat functionality
+/// - Clipboard synchronization with the controller
+/// - File transfers with participants
+/// - Pausing and resuming the recording of the session
 /// - Session lifecycle management
 ///
 /// This scene runs background threads for screen capture and network communication
@@ -422,21 +826,60 @@ pub struct HostScene {
     chat_log: Arc<Mutex<Vec<String>>>,
     /// Current chat message being composed
     chat_message: String,
-
-    /// FFmpeg process handle for screen capture
-    ffmpeg_command: Child,
-    /// Background thread handle for screen streaming
-    thread_send_screen: Option<JoinHandle<()>>,
+    /// Name of the next bookmark
+    bookmark_name: String,
+
+    /// Whether the host opted in to clipboard synchronization with the controller
+    clipboard_sync: Arc<AtomicBool>,
+    /// Digest of the last synchronized clipboard content
+    last_clipboard: Arc<Mutex<Option<md5::Digest>>>,
+
+    /// Shared list of file transfers with participants
+    file_transfers: SharedTransfers,
+    /// The participant dropped files are sent to, or `None` to send to everyone
+    send_files_to: Option<String>,
+
+    /// The part of the desktop being shared
+    capture_source: SharedCaptureSource,
+    /// The capture source being edited in the UI, applied with the "Switch" button
+    pending_source: CaptureSource,
+
+    /// Adjusts the encoder settings to the participants' feedback
+    quality_controller: QualityController,
+    /// The latest stream report of every participant
+    stream_feedback: SharedFeedback,
+    /// Number of screen bytes sent since the quality was last evaluated
+    sent_bytes: Arc<AtomicU64>,
+
+    /// The audio input devices of the host
+    audio_devices: Vec<String>,
+    /// The device whose audio is shared, `None` if no audio is shared
+    audio_device: Option<String>,
+    /// FFmpeg process and streaming thread of the shared audio
+    audio_capture: Option<AudioCapture>,
+    /// Player for the participants' microphones
+    audio_player: SharedAudioPlayer,
+
+    /// Whether the session is recorded, which the host can pause and resume
+    recording_state: Arc<Mutex<RecordingState>>,
+
+    /// FFmpeg process and screen streaming thread handles of every simulcast layer
+    encoders: Vec<(Child, JoinHandle<()>)>,
     /// Background thread handle for network communication
     thread_read_socket: Option<JoinHandle<()>>,
+    /// Background thread handle for the clipboard watcher
+    thread_watch_clipboard: Option<JoinHandle<()>>,
+    /// Background thread handle for cursor streaming
+    thread_send_cursor: Option<JoinHandle<()>>,
 }
 
 impl HostScene {
     /// Creates a new host scene and starts screen sharing
     ///
     /// This constructor:
-    /// 1. Starts FFmpeg for screen capture
-    /// 2. Spawns background threads for streaming and network handling
+    /// 1. Starts FFmpeg for screen capture, once per simulcast layer, and for system audio
+    ///    if a loopback device is found
+    /// 2. Spawns background threads for streaming (screen and cursor) and network handling
     /// 3. Initializes user management structures
     /// 4. Sets up chat functionality
     ///
@@ -445,17 +888,34 @@ impl HostScene {
     /// * `session_code` - Unique code for this session
     /// * `channel` - Secure communication channel to clients
     /// * `username` - Host's username
+    /// * `source` - The part of the desktop to share
     ///
     /// # Returns
     ///
     /// A new `HostScene` instance ready for use
-    pub fn new(session_code: String, channel: &mut SecureChannel, username: String) -> Self {
-        let mut command = start_ffmpeg();
-        let stdout = command.stdout.take().unwrap();
-
+    pub fn new(
+        session_code: String,
+        channel: &mut SecureChannel,
+        username: String,
+        source: CaptureSource,
+    ) -> Self {
+        let quality_controller = QualityController::new();
         let stop_flag = Arc::new(AtomicBool::new(false));
+        let sent_bytes = Arc::new(AtomicU64::new(0));
 
-        let thread_send_screen = thread_send_screen(channel.clone(), stdout, stop_flag.clone());
+        let area = CaptureArea::new(source);
+        let encoders = start_encoders(channel, &area, &quality_controller, &stop_flag, &sent_bytes);
+
+        let pending_source = area.source.clone();
+        let capture_source = Arc::new(Mutex::new(area));
+
+        let audio_devices = list_audio_devices();
+        let audio_device = default_system_device(&audio_devices);
+        let audio_capture = audio_device
+            .as_ref()
+            .map(|device| AudioCapture::start(channel, device, AudioKind::System));
+        let audio_player = Arc::new(Mutex::new(AudioPlayer::new()));
+        let recording_state = Arc::new(Mutex::new(RecordingState::Off));
 
         let mut usernames_types = HashMap::new();
         usernames_types.insert(username.clone(), UserType::Host);
@@ -466,14 +926,35 @@ impl HostScene {
 
         let chat_log = Arc::new(Mutex::new(Vec::new()));
 
+        let clipboard_sync = Arc::new(AtomicBool::new(false));
+        let last_clipboard = Arc::new(Mutex::new(None));
+        let file_transfers = Arc::new(Mutex::new(Vec::new()));
+        let stream_feedback = Arc::new(Mutex::new(HashMap::new()));
+
         let thread_read_socket = thread_read_socket(
             channel.clone(),
             usernames.clone(),
             requesting_control.clone(),
             requesting_join.clone(),
             chat_log.clone(),
+            last_clipboard.clone(),
+            file_transfers.clone(),
+            capture_source.clone(),
+            stream_feedback.clone(),
+            audio_player.clone(),
+            recording_state.clone(),
         );
 
+        let thread_watch_clipboard = thread_watch_clipboard(
+            channel.clone(),
+            clipboard_sync.clone(),
+            last_clipboard.clone(),
+            stop_flag.clone(),
+        );
+
+        let thread_send_cursor =
+            thread_send_cursor(channel.clone(), capture_source.clone(), stop_flag.clone());
+
         Self {
             session_code,
             stop_flag,
@@ -485,19 +966,98 @@ impl HostScene {
 
             chat_log,
             chat_message: String::new(),
+            bookmark_name: String::new(),
+
+            clipboard_sync,
+            last_clipboard,
+
+            file_transfers,
+            send_files_to: None,
+
+            capture_source,
+            pending_source,
+
+            quality_controller,
+            stream_feedback,
+            sent_bytes,
 
-            ffmpeg_command: command,
-            thread_send_screen: Some(thread_send_screen),
+            audio_devices,
+            audio_device,
+            audio_capture,
+            audio_player,
+
+            recording_state,
+
+            encoders,
             thread_read_socket: Some(thread_read_socket),
+            thread_watch_clipboard: Some(thread_watch_clipboard),
+            thread_send_cursor: Some(thread_send_cursor),
         }
     }
 
+    /// Restarts FFmpeg with the current capture source, stream quality and layers
+    ///
+    /// The new streams start with a keyframe, so participants pick them up right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Communication channel for the screen stream
+    fn restart_encoder(&mut self, channel: &mut SecureChannel) {
+        stop_encoders(&mut self.encoders);
+
+        let area = self.capture_source.lock().unwrap().clone();
+        self.encoders = start_encoders(
+            channel,
+            &area,
+            &self.quality_controller,
+            &self.stop_flag,
+            &self.sent_bytes,
+        );
+    }
+
+    /// Switches the shared part of the desktop
+    ///
+    /// Restarts FFmpeg with the new source, which also tells the participants about
+    /// the new screen size.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Communication channel for the screen stream and notifications
+    fn switch_source(&mut self, channel: &mut SecureChannel) {
+        let source = self.pending_source.clone();
+
+        info!(target: LOG_TARGET, "Now sharing {}.", source);
+        self.chat_log
+            .lock()
+            .unwrap()
+            .push(format!("#bYou are now sharing {}.", source));
+        *self.capture_source.lock().unwrap() = CaptureArea::new(source);
+
+        self.restart_encoder(channel);
+    }
+
+    /// Shares the audio of the chosen device, stopping the previous one
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Communication channel for the audio stream
+    fn switch_audio(&mut self, channel: &mut SecureChannel) {
+        if let Some(mut capture) = self.audio_capture.take() {
+            capture.stop();
+        }
+
+        self.audio_capture = self
+            .audio_device
+            .as_ref()
+            .map(|device| AudioCapture::start(channel, device, AudioKind::System));
+    }
+
     /// Cleanly disconnects and shuts down the hosting session
     ///
     /// This method:
     /// 1. Signals background threads to stop
-    /// 2. Waits for screen streaming thread to finish
-    /// 3. Terminates FFmpeg process
+    /// 2. Terminates the FFmpeg processes and waits for the screen and audio streaming threads to finish
+    /// 3. Waits for cursor, clipboard watcher and file sending threads to finish
     /// 4. Sends `SessionExit` message
     /// 5. Waits for network thread to finish
     /// 6. Returns to the main menu
@@ -512,8 +1072,14 @@ impl HostScene {
     fn disconnect(&mut self, channel: &mut SecureChannel) -> SceneChange {
         self.stop_flag.store(true, Ordering::Relaxed);
 
-        let _ = self.thread_send_screen.take().unwrap().join();
-        let _ = self.ffmpeg_command.kill();
+        stop_encoders(&mut self.encoders);
+        if let Some(mut capture) = self.audio_capture.take() {
+            capture.stop();
+        }
+        self.audio_player.lock().unwrap().stop_all();
+        let _ = self.thread_watch_clipboard.take().unwrap().join();
+        let _ = self.thread_send_cursor.take().unwrap().join();
+        stop_transfers(&self.file_transfers);
 
         channel.send(Packet::SessionExit).unwrap();
 
@@ -553,6 +1119,11 @@ impl Scene for HostScene {
             );
         });
 
+        let mut switch_source = false;
+        let mut audio_changed = false;
+        let mut quality_changed = self
+            .quality_controller
+            .update(&self.stream_feedback, &self.sent_bytes);
         egui::SidePanel::left("requests").show(ctx, |ui| {
             let mut requesting_control = self.requesting_control.lock().unwrap();
             let mut user_handled = String::new();
@@ -669,11 +1240,154 @@ impl Scene for HostScene {
                 if !user_handled.is_empty() {
                     requesting_join.remove(&user_handled);
                 }
+                drop(requesting_join);
+
+                // shared screen
+                ui.add_space(20.0);
+                ui.heading("Shared Screen");
+                ui.separator();
+
+                source_picker(ui, &mut self.pending_source);
+                let switch_enabled =
+                    self.pending_source != self.capture_source.lock().unwrap().source;
+                switch_source = ui
+                    .add_enabled(switch_enabled, egui::Button::new("Switch"))
+                    .clicked();
+
+                // stream quality
+                ui.add_space(20.0);
+                ui.heading("Stream Quality");
+                ui.separator();
+
+                quality_changed |= self.quality_controller.ui(ui);
+
+                // audio
+                ui.add_space(20.0);
+                ui.heading("Audio");
+                ui.separator();
+
+                audio_changed = device_picker(
+                    ui,
+                    "Share audio from",
+                    &self.audio_devices,
+                    &mut self.audio_device,
+                );
+                if ui.button("Refresh devices").clicked() {
+                    self.audio_devices = list_audio_devices();
+                }
+
+                let mut audio_player = self.audio_player.lock().unwrap();
+                let mut muted = audio_player.muted();
+                if ui
+                    .checkbox(&mut muted, "Mute participants' microphones")
+                    .changed()
+                {
+                    audio_player.set_muted(muted);
+                }
+                drop(audio_player);
+
+                // file transfers
+                ui.add_space(20.0);
+                ui.heading("File Transfers");
+                ui.separator();
+
+                let participants: Vec<String> = self
+                    .usernames
+                    .lock()
+                    .unwrap()
+                    .keys()
+                    .filter(|username| **username != self.username)
+                    .cloned()
+                    .collect();
+
+                // forget a recipient that left the session
+                if let Some(recipient) = &self.send_files_to {
+                    if !participants.contains(recipient) {
+                        self.send_files_to = None;
+                    }
+                }
+
+                egui::ComboBox::from_label("Send dropped files to")
+                    .selected_text(self.send_files_to.as_deref().unwrap_or("Everyone"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.send_files_to, None, "Everyone");
+                        for participant in &participants {
+                            ui.selectable_value(
+                                &mut self.send_files_to,
+                                Some(participant.clone()),
+                                participant,
+                            );
+                        }
+                    });
+
+                ui.add_space(5.0);
+                transfers_ui(ui, &self.file_transfers, channel);
             });
         });
 
+        if audio_changed {
+            self.switch_audio(channel);
+        }
+
+        if switch_source {
+            self.switch_source(channel);
+        } else if quality_changed {
+            let quality = self.quality_controller.quality();
+            info!(
+                target: LOG_TARGET,
+                "Stream quality changed to {} kbps at {} fps.", quality.bitrate, quality.framerate
+            );
+            self.restart_encoder(channel);
+        }
+
+        // offer dropped files to the chosen participants
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        if !dropped_files.is_empty() {
+            let recipients: Vec<String> = match &self.send_files_to {
+                Some(recipient) => vec![recipient.clone()],
+                None => self
+                    .usernames
+                    .lock()
+                    .unwrap()
+                    .keys()
+                    .filter(|username| **username != self.username)
+                    .cloned()
+                    .collect(),
+            };
+
+            for path in dropped_files.into_iter().filter_map(|file| file.path) {
+                for recipient in &recipients {
+                    if let Err(msg) = offer_file(&self.file_transfers, &path, recipient, channel) {
+                        self.chat_log.lock().unwrap().push(format!("#r{}", msg));
+                        break;
+                    }
+                }
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading(format!("Hosting, code {}", self.session_code));
+
+            // the state changes once the server confirms it
+            ui.horizontal(|ui| {
+                let state = *self.recording_state.lock().unwrap();
+                recording_indicator(ui, state);
+
+                let toggle = match state {
+                    RecordingState::Recording => Some(("Pause recording", RecordingState::Paused)),
+                    RecordingState::Paused => Some(("Resume recording", RecordingState::Recording)),
+                    RecordingState::Off => None,
+                };
+                if let Some((label, state)) = toggle {
+                    if ui
+                        .button(label)
+                        .on_hover_text("Nothing is recorded while the recording is paused")
+                        .clicked()
+                    {
+                        channel.send(Packet::RecordingState { state }).unwrap();
+                    }
+                }
+            });
             ui.separator();
 
             if let Some(controller) = users_list(
@@ -688,6 +1402,33 @@ impl Scene for HostScene {
                 channel.send(deny_packet).unwrap();
             }
 
+            ui.add_space(10.0);
+
+            let mut clipboard_sync = self.clipboard_sync.load(Ordering::Relaxed);
+            if ui
+                .checkbox(&mut clipboard_sync, "Sync clipboard with the controller")
+                .changed()
+            {
+                if clipboard_sync {
+                    // only sync content copied after opting in
+                    let mut last_clipboard = self.last_clipboard.lock().unwrap();
+                    *last_clipboard = read_clipboard().map(|packet| clipboard_digest(&packet));
+                }
+
+                self.clipboard_sync.store(clipboard_sync, Ordering::Relaxed);
+                channel
+                    .send(Packet::ClipboardSync {
+                        enabled: clipboard_sync,
+                    })
+                    .unwrap();
+            }
+
+            ui.add_space(10.0);
+
+            bookmark_ui(ui, &mut self.bookmark_name, channel);
+
+            ui.add_space(10.0);
+
             if ui.button("End Session").clicked() {
                 result = self.disconnect(channel);
             }
@@ -4,10 +4,10 @@ use std::{collections::HashMap, path::Path, sync::MutexGuard};
 use eframe::egui::{
     self,
     text::{LayoutJob, TextWrapping},
-    Color32, FontId, Key, Pos2, Rect, RichText, ScrollArea, TextFormat, Ui,
+    vec2, Color32, FontId, Key, Pos2, Rect, RichText, ScrollArea, Sense, TextFormat, Ui,
 };
 use ftail::Ftail;
-use protocol::Packet;
+use protocol::{Packet, RecordingState, MAX_BOOKMARK_NAME_LENGTH};
 use secure_channel::SecureChannel;
 
 pub mod protocol;
@@ -156,6 +156,9 @@ pub fn egui_key_to_vk(key: &Key) -> Option<u16> {
 /// * `image_rect` - The `Rect` representing the area where the image is displayed.
 ///                  This is used to correctly scale the mouse position relative to the image.
 ///
+/// Positions outside of `image_rect` (e.g. while dragging out of the image) are
+/// clamped to its edges.
+///
 /// # Returns
 ///
 /// A tuple `(u32, u32)` where:
@@ -164,7 +167,7 @@ pub fn egui_key_to_vk(key: &Key) -> Option<u16> {
 pub fn normalize_mouse_position(mouse_position: Pos2, image_rect: Rect) -> (u32, u32) {
     let x = (mouse_position.x - image_rect.left()) * 65535.0 / image_rect.width();
     let y = (mouse_position.y - image_rect.top()) * 65535.0 / image_rect.height();
-    (x as u32, y as u32)
+    (x.clamp(0.0, 65535.0) as u32, y.clamp(0.0, 65535.0) as u32)
 }
 
 /// Displays the list of connected users and their roles (Host, Controller, Participant).
@@ -255,6 +258,108 @@ pub fn users_list(
     result
 }
 
+/// Displays the field and the button for bookmarking the current moment of the
+/// session's recording, which Ctrl+Shift+B does too.
+///
+/// # Arguments
+///
+/// * `ui` - A mutable reference to the `egui::Ui` where the bookmark UI will be drawn.
+/// * `name` - A mutable reference to the `String` holding the name of the next bookmark.
+/// * `channel` - A mutable reference to the `SecureChannel` used for sending the bookmark.
+///
+/// # Panics
+///
+/// Panics if the `channel.send()` operation fails, as `unwrap()` is used.
+pub fn bookmark_ui(ui: &mut Ui, name: &mut String, channel: &mut SecureChannel) {
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(name)
+                .hint_text("Bookmark name")
+                .char_limit(MAX_BOOKMARK_NAME_LENGTH)
+                .desired_width(150.0),
+        );
+
+        let hotkey =
+            ui.input(|i| i.key_pressed(Key::B) && i.modifiers.command && i.modifiers.shift);
+
+        if ui
+            .button("Bookmark")
+            .on_hover_text("Ctrl+Shift+B")
+            .clicked()
+            || hotkey
+        {
+            let bookmark = Packet::AddBookmark {
+                name: name.trim().to_string(),
+            };
+            channel.send(bookmark).unwrap();
+
+            name.clear();
+        }
+    });
+}
+
+/// Creates the chat message announcing a bookmark.
+///
+/// # Arguments
+///
+/// * `time_ms` - The time of the bookmark in the recording, in milliseconds.
+/// * `name` - The name of the bookmark.
+/// * `username` - The username of the user who added the bookmark.
+///
+/// # Returns
+///
+/// The message, in blue.
+pub fn bookmark_message(time_ms: u64, name: &str, username: &str) -> String {
+    let seconds = time_ms / 1000;
+    format!(
+        "#b{} bookmarked \"{}\" at {:02}:{:02}.",
+        username,
+        name,
+        seconds / 60,
+        seconds % 60
+    )
+}
+
+/// Creates the chat message announcing whether the session is recorded.
+///
+/// # Arguments
+///
+/// * `state` - The recording state of the session.
+///
+/// # Returns
+///
+/// The message, in red while the session is recorded.
+pub fn recording_message(state: RecordingState) -> String {
+    match state {
+        RecordingState::Off => "#gThis session isn't recorded.",
+        RecordingState::Recording => "#rThis session is being recorded.",
+        RecordingState::Paused => "#bThe recording is paused.",
+    }
+    .to_string()
+}
+
+/// Displays whether the session is recorded, with a red dot while it is.
+///
+/// # Arguments
+///
+/// * `ui` - A mutable reference to the `egui::Ui` where the indicator will be drawn.
+/// * `state` - The recording state of the session.
+pub fn recording_indicator(ui: &mut Ui, state: RecordingState) {
+    let (text, color) = match state {
+        RecordingState::Off => ("Not recorded", Color32::GRAY),
+        RecordingState::Recording => ("REC", Color32::RED),
+        RecordingState::Paused => ("REC paused", Color32::YELLOW),
+    };
+
+    ui.horizontal(|ui| {
+        if state != RecordingState::Off {
+            let (rect, _) = ui.allocate_exact_size(vec2(12.0, 12.0), Sense::hover());
+            ui.painter().circle_filled(rect.center(), 5.0, color);
+        }
+        ui.label(RichText::new(text).color(color).strong());
+    });
+}
+
 /// Displays the chat user interface, including the chat log and an input field
 /// for sending new messages.
 ///
@@ -7,11 +7,18 @@ use login_scene::LoginScene;
 use stream_desk::secure_channel::SecureChannel;
 use stream_desk::{initialize_logger, Scene, SceneChange, CLIENT_LOG_FILE, LOG_DIR};
 
+mod audio;
+mod capture;
+mod clipboard;
+mod decoder;
+mod download;
+mod file_transfer;
 mod host_scene;
 mod login_scene;
 mod menu_scene;
 mod modifiers_state;
 mod participant_scene;
+mod stream_quality;
 mod watch_scene;
 
 const SERVER_IP: &'static str = "127.0.0.1";
@@ -1,29 +1,240 @@
 use std::{
+    cmp::Reverse,
     collections::HashMap,
+    path::PathBuf,
     sync::mpsc::{self, Receiver},
     thread,
+    time::Duration,
 };
 
-use chrono::{DateTime, Local};
-use eframe::egui::{self, Align, Button, Color32, FontId, Layout, RichText, TextEdit, Ui};
+use chrono::{DateTime, Local, TimeDelta};
+use eframe::egui::{
+    self, load::Bytes, vec2, Align, Button, Color32, FontId, Layout, RichText, TextEdit, Ui, Vec2,
+};
 use log::info;
 use stream_desk::{
-    protocol::{Packet, ResultPacket},
+    protocol::{
+        Packet, ResultPacket, MAX_RECORDING_DESCRIPTION_LENGTH, MAX_RECORDING_TITLE_LENGTH,
+    },
     secure_channel::SecureChannel,
     Scene, SceneChange, LOG_TARGET,
 };
 
 use crate::{
-    host_scene::HostScene, login_scene::LoginScene, participant_scene::ParticipantScene,
-    watch_scene::WatchScene,
+    capture::{source_picker, CaptureSource},
+    download::Download,
+    file_transfer::{format_size, DOWNLOADS_FOLDER},
+    host_scene::HostScene,
+    login_scene::LoginScene,
+    participant_scene::ParticipantScene,
+    watch_scene::{PartyRole, WatchScene},
 };
 
+/// The size of the poster thumbnails in the recordings list.
+const POSTER_SIZE: Vec2 = vec2(96.0, 54.0);
+
+/// The part of the storage quota the user is warned at.
+const STORAGE_WARNING: f32 = 0.9;
+
+/// How many days before a recording is deleted its owner is warned.
+const EXPIRY_WARNING_DAYS: i64 = 7;
+
+/// The retention periods the owner of a recording can choose, in days, 0 to keep it forever.
+const RETENTION_CHOICES: [(&str, u32); 4] = [
+    ("30 days", 30),
+    ("90 days", 90),
+    ("1 year", 365),
+    ("Forever", 0),
+];
+
+/// The time ranges recordings can be filtered by.
+#[derive(PartialEq, Clone, Copy)]
+enum DateFilter {
+    AnyTime,
+    Today,
+    PastWeek,
+    PastMonth,
+}
+
+impl DateFilter {
+    /// All filters, in the order they're offered.
+    const ALL: [DateFilter; 4] = [
+        DateFilter::AnyTime,
+        DateFilter::Today,
+        DateFilter::PastWeek,
+        DateFilter::PastMonth,
+    ];
+
+    /// The name of the filter shown to the user.
+    fn label(self) -> &'static str {
+        match self {
+            DateFilter::AnyTime => "Any time",
+            DateFilter::Today => "Today",
+            DateFilter::PastWeek => "Past 7 days",
+            DateFilter::PastMonth => "Past 30 days",
+        }
+    }
+
+    /// Checks if a recording from a given time passes the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time of the recording.
+    fn matches(self, time: &DateTime<Local>) -> bool {
+        let now = Local::now();
+
+        match self {
+            DateFilter::AnyTime => true,
+            DateFilter::Today => time.date_naive() == now.date_naive(),
+            DateFilter::PastWeek => now.signed_duration_since(time) <= TimeDelta::days(7),
+            DateFilter::PastMonth => now.signed_duration_since(time) <= TimeDelta::days(30),
+        }
+    }
+}
+
+/// A recording in the recordings list.
+struct RecordingEntry {
+    /// The ID of the recording.
+    id: i32,
+    /// When the recording was made.
+    time: DateTime<Local>,
+    /// The title the user gave the recording, empty if none.
+    title: String,
+    /// The description the user gave the recording, empty if none.
+    description: String,
+    /// The poster thumbnail (a JPEG image), if the recording has one.
+    poster: Option<Bytes>,
+    /// The username of the owner if the recording was shared with the user, `None` if it's theirs.
+    owner: Option<String>,
+    /// How many days the recording is kept before it's deleted, 0 if it's kept forever.
+    retention_days: u32,
+}
+
+impl RecordingEntry {
+    /// The time of the recording, formatted for the user.
+    fn formatted_time(&self) -> String {
+        self.time.format("%B %-d, %Y | %T").to_string()
+    }
+
+    /// When the server deletes the recording, `None` if it's kept forever.
+    fn expiry(&self) -> Option<DateTime<Local>> {
+        match self.retention_days {
+            0 => None,
+            days => Some(self.time + TimeDelta::days(days as i64)),
+        }
+    }
+
+    /// Checks if the recording is the user's own and is deleted within
+    /// `EXPIRY_WARNING_DAYS`.
+    fn expires_soon(&self) -> bool {
+        let warning_time = Local::now() + TimeDelta::days(EXPIRY_WARNING_DAYS);
+        self.owner.is_none() && self.expiry().is_some_and(|expiry| expiry <= warning_time)
+    }
+
+    /// The name the recording is shown with: its title, or its time if it has none.
+    fn display_name(&self) -> String {
+        if self.title.is_empty() {
+            self.formatted_time()
+        } else {
+            self.title.clone()
+        }
+    }
+
+    /// The default filename the recording is saved as (without the extension), which is
+    /// its display name without the characters filenames can't have.
+    fn file_stem(&self) -> String {
+        self.display_name()
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect()
+    }
+
+    /// Checks if the recording matches a search, by title, description or time.
+    ///
+    /// # Arguments
+    ///
+    /// * `search` - The lowercase search text.
+    fn matches_search(&self, search: &str) -> bool {
+        [&self.title, &self.description, &self.formatted_time()]
+            .iter()
+            .any(|text| text.to_lowercase().contains(search))
+    }
+}
+
+/// An action the user chose on a recording in the recordings list.
+enum RecordingAction {
+    Watch(i32),
+    WatchParty(i32),
+    Edit(i32),
+    Share(i32),
+    Save(i32),
+    Delete(i32),
+    Retention(i32, u32),
+}
+
+/// The storage the user's recordings take, out of their quota.
+#[derive(Default)]
+struct StorageUsage {
+    used_bytes: u64,
+    quota_bytes: u64,
+}
+
+impl StorageUsage {
+    /// The part of the quota that is used, 1 or more when it's used up.
+    fn fraction(&self) -> f32 {
+        match self.quota_bytes {
+            0 => 1.0,
+            quota_bytes => self.used_bytes as f32 / quota_bytes as f32,
+        }
+    }
+
+    /// Renders the usage as a progress bar, with a warning when the quota is nearly
+    /// or fully used up.
+    ///
+    /// # Arguments
+    ///
+    /// * `ui` - The `Ui` to render in.
+    fn ui(&self, ui: &mut Ui) {
+        let fraction = self.fraction();
+
+        ui.add(egui::ProgressBar::new(fraction.min(1.0)).text(format!(
+            "{} of {} used",
+            format_size(self.used_bytes),
+            format_size(self.quota_bytes)
+        )));
+
+        if fraction >= 1.0 {
+            ui.colored_label(
+                Color32::RED,
+                "Your storage is full, new sessions won't be recorded.",
+            );
+        } else if fraction >= STORAGE_WARNING {
+            ui.colored_label(
+                Color32::ORANGE,
+                "Your storage is almost full. Delete recordings to keep recording sessions.",
+            );
+        }
+    }
+}
+
+/// The title and description of a recording while the user edits them.
+struct RecordingEdit {
+    id: i32,
+    title: String,
+    description: String,
+}
+
 /// Receives and processes a list of available recordings from the server.
 ///
 /// This function continuously receives packets from the server until a `Packet::None`
-/// is encountered, indicating the end of the recording list. It collects recording IDs
-/// and names into a `HashMap` and then converts them into a sorted `Vec` of `(id, name)` tuples,
-/// ordered by creation time in reverse (newest first).
+/// is encountered, indicating the end of the recording list. It collects the recordings,
+/// with the details, owners, retention periods and poster thumbnails sent after each one,
+/// and sorts them by creation time in reverse (newest first). The storage usage of the
+/// user is sent after the recordings.
 ///
 /// # Arguments
 ///
@@ -31,10 +242,10 @@ use crate::{
 ///
 /// # Returns
 ///
-/// A `Vec` of `(i32, String)` tuples, where `i32` is the recording ID and `String` is the recording name (timestamp),
-/// sorted by timestamp in descending order.
-fn receive_recordings(channel: &mut SecureChannel) -> Vec<(i32, String)> {
+/// A `Vec` of `RecordingEntry`, sorted by time in descending order, and the `StorageUsage`.
+fn receive_recordings(channel: &mut SecureChannel) -> (Vec<RecordingEntry>, StorageUsage) {
     let mut recordings = HashMap::new();
+    let mut storage = StorageUsage::default();
 
     loop {
         let packet = channel.receive().unwrap();
@@ -43,19 +254,67 @@ fn receive_recordings(channel: &mut SecureChannel) -> Vec<(i32, String)> {
             Packet::None => break,
 
             Packet::RecordingName { id, name } => {
-                recordings.insert(id, name);
+                let entry = RecordingEntry {
+                    id,
+                    time: name.parse().unwrap(),
+                    title: String::new(),
+                    description: String::new(),
+                    poster: None,
+                    owner: None,
+                    retention_days: 0,
+                };
+                recordings.insert(id, entry);
+            }
+
+            Packet::RecordingDetails {
+                id,
+                title,
+                description,
+            } => {
+                if let Some(entry) = recordings.get_mut(&id) {
+                    entry.title = title;
+                    entry.description = description;
+                }
+            }
+
+            Packet::RecordingPoster { id, bytes } => {
+                if let Some(entry) = recordings.get_mut(&id) {
+                    entry.poster = Some(Bytes::from(bytes));
+                }
+            }
+
+            Packet::RecordingOwner { id, owner } => {
+                if let Some(entry) = recordings.get_mut(&id) {
+                    entry.owner = Some(owner);
+                }
+            }
+
+            Packet::RecordingRetention { id, days } => {
+                if let Some(entry) = recordings.get_mut(&id) {
+                    entry.retention_days = days;
+                }
+            }
+
+            Packet::StorageUsage {
+                used_bytes,
+                quota_bytes,
+            } => {
+                storage = StorageUsage {
+                    used_bytes,
+                    quota_bytes,
+                };
             }
 
             _ => (),
         }
     }
 
-    let mut recordings: Vec<(i32, String)> = recordings.into_iter().collect();
+    let mut recordings: Vec<RecordingEntry> = recordings.into_values().collect();
 
     // sort by the time (in reverse, newest one first)
-    recordings.sort_by(|a, b| b.1.cmp(&a.1));
+    recordings.sort_by_key(|recording| Reverse(recording.time));
 
-    recordings
+    (recordings, storage)
 }
 
 /// Represents the main menu scene of the Remote Desktop client.
@@ -63,7 +322,7 @@ fn receive_recordings(channel: &mut SecureChannel) -> Vec<(i32, String)> {
 /// In this scene, authenticated users can choose to:
 /// - **Host** a new remote desktop session.
 /// - **Join** an existing remote desktop session as a participant.
-/// - **Watch** previously recorded sessions.
+/// - **Watch** previously recorded sessions, on their own or together in a watch party.
 /// - **Sign out** and return to the login screen.
 pub struct MenuScene {
     /// The input field for joining a session.
@@ -75,12 +334,35 @@ pub struct MenuScene {
 
     /// The username of the currently logged-in user.
     username: String,
-    /// A list of available recordings, with their IDs and names (timestamps).
-    recordings: Vec<(i32, String)>,
+    /// The available recordings, newest first.
+    recordings: Vec<RecordingEntry>,
+    /// The storage the user's recordings take.
+    storage: StorageUsage,
+    /// The text the recordings list is searched by.
+    search: String,
+    /// The time range the recordings list is filtered by.
+    date_filter: DateFilter,
+    /// The recording whose title and description are being edited.
+    editing: Option<RecordingEdit>,
+    /// The recording the user asked to delete, waiting for confirmation.
+    deleting: Option<i32>,
+    /// The recording being shared, with the username to share it with.
+    sharing: Option<(i32, String)>,
+    /// The recording being saved, with the path to save it to.
+    saving: Option<(i32, String)>,
+    /// The last recording download, kept until the user dismisses it.
+    download: Option<Download>,
+    /// Whether the recordings list shows the recordings shared with the user
+    /// instead of their own.
+    show_shared: bool,
     /// An optional receiver for handling asynchronous join session results.
     join_receiver: Option<Receiver<(bool, String)>>,
     /// A flag to disable UI elements when a background operation (like joining) is in progress.
     is_disabled: bool,
+    /// The part of the desktop to share when hosting.
+    capture_source: CaptureSource,
+    /// Whether to record the hosted session.
+    record_session: bool,
 }
 
 impl MenuScene {
@@ -99,7 +381,7 @@ impl MenuScene {
     ///
     /// A new `MenuScene` ready to be displayed.
     pub fn new(username: String, channel: &mut SecureChannel, status_message: &str) -> Self {
-        let recordings = receive_recordings(channel);
+        let (recordings, storage) = receive_recordings(channel);
 
         Self {
             session_code: String::new(),
@@ -108,14 +390,26 @@ impl MenuScene {
 
             username,
             recordings,
+            storage,
+            search: String::new(),
+            date_filter: DateFilter::AnyTime,
+            editing: None,
+            deleting: None,
+            sharing: None,
+            saving: None,
+            download: None,
+            show_shared: false,
             join_receiver: None,
             is_disabled: false,
+            capture_source: CaptureSource::default(),
+            record_session: true,
         }
     }
 
     /// Handles the "Host Session" button click.
     ///
-    /// Sends a `Packet::Host` request to the server. Upon a successful response
+    /// Sends a `Packet::Host` request to the server, with whether to record the
+    /// session. Upon a successful response
     /// (which should contain the session code), it transitions the application
     /// to the `HostScene`. Panics if the server response is not a `ResultPacket::Success`.
     ///
@@ -127,7 +421,8 @@ impl MenuScene {
     ///
     /// A `SceneChange` variant indicating a transition to `HostScene`.
     fn host_button(&self, channel: &mut SecureChannel) -> SceneChange {
-        channel.send(Packet::Host).unwrap();
+        let record = self.record_session;
+        channel.send(Packet::Host { record }).unwrap();
 
         let result = channel.receive().unwrap();
         let ResultPacket::Success(code) = result else {
@@ -138,6 +433,7 @@ impl MenuScene {
             code,
             channel,
             self.username.to_string(),
+            self.capture_source.clone(),
         )))
     }
 
@@ -192,6 +488,535 @@ impl MenuScene {
             }
         }
     }
+
+    /// Shows a failed recording request's message to the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The server's response to the request.
+    ///
+    /// # Returns
+    ///
+    /// The message of a `ResultPacket::Success`, or `None` if the request failed.
+    fn check_result(&mut self, result: ResultPacket) -> Option<String> {
+        match result {
+            ResultPacket::Success(msg) => Some(msg),
+            ResultPacket::Failure(msg) => {
+                self.is_error = true;
+                self.status_message = msg;
+                None
+            }
+        }
+    }
+
+    /// Renders the searchable list of recordings in the recordings panel.
+    ///
+    /// The list shows either the user's own recordings or the ones shared with them.
+    /// Clicking a recording watches it, and the context menu of an own recording
+    /// edits, shares or deletes it, or changes how long it's kept. The storage the
+    /// user's recordings take is shown above the list.
+    ///
+    /// # Arguments
+    ///
+    /// * `ui` - The `Ui` of the recordings panel.
+    /// * `channel` - A mutable reference to the `SecureChannel` for communication with the server.
+    ///
+    /// # Returns
+    ///
+    /// A `SceneChange` to the `WatchScene` if the user started watching a recording.
+    fn recordings_panel_ui(
+        &mut self,
+        ui: &mut Ui,
+        channel: &mut SecureChannel,
+    ) -> Option<SceneChange> {
+        self.storage.ui(ui);
+
+        let expiring = self.recordings.iter().filter(|r| r.expires_soon()).count();
+        if expiring > 0 {
+            ui.colored_label(
+                Color32::ORANGE,
+                format!(
+                    "{} of your recordings will be deleted within {} days. \
+                    Choose to keep them longer to save them.",
+                    expiring, EXPIRY_WARNING_DAYS
+                ),
+            );
+        }
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.show_shared, false, "My recordings");
+            ui.selectable_value(&mut self.show_shared, true, "Shared with me");
+        });
+
+        ui.add(TextEdit::singleline(&mut self.search).hint_text("Search recordings"));
+
+        egui::ComboBox::from_id_salt("date_filter")
+            .selected_text(self.date_filter.label())
+            .show_ui(ui, |ui| {
+                for filter in DateFilter::ALL {
+                    ui.selectable_value(&mut self.date_filter, filter, filter.label());
+                }
+            });
+
+        ui.separator();
+
+        let search = self.search.trim().to_lowercase();
+        let mut action = None;
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let visible = self.recordings.iter().filter(|recording| {
+                recording.owner.is_some() == self.show_shared
+                    && self.date_filter.matches(&recording.time)
+                    && recording.matches_search(&search)
+            });
+
+            for recording in visible {
+                ui.horizontal(|ui| {
+                    match &recording.poster {
+                        Some(poster) => {
+                            ui.add(
+                                egui::Image::from_bytes(
+                                    format!("bytes://poster-{}.jpg", recording.id),
+                                    poster.clone(),
+                                )
+                                .fit_to_exact_size(POSTER_SIZE),
+                            );
+                        }
+                        None => {
+                            ui.add_space(POSTER_SIZE.x);
+                        }
+                    }
+
+                    let mut name = RichText::new(recording.display_name());
+                    if recording.expires_soon() {
+                        name = name.color(Color32::ORANGE);
+                    }
+
+                    let response = ui.button(name).on_hover_ui(|ui| {
+                        ui.label(recording.formatted_time());
+                        if let Some(owner) = &recording.owner {
+                            ui.label(format!("Shared by {}", owner));
+                        }
+                        if !recording.description.is_empty() {
+                            ui.label(&recording.description);
+                        }
+                        match recording.expiry() {
+                            Some(expiry) => {
+                                ui.label(format!("Deleted on {}", expiry.format("%B %-d, %Y")))
+                            }
+                            None => ui.label("Kept forever"),
+                        };
+                    });
+
+                    if response.clicked() {
+                        action = Some(RecordingAction::Watch(recording.id));
+                    }
+
+                    response.context_menu(|ui| {
+                        if ui.button("Start watch party").clicked() {
+                            action = Some(RecordingAction::WatchParty(recording.id));
+                            ui.close_menu();
+                        }
+                        if ui.button("Save as…").clicked() {
+                            action = Some(RecordingAction::Save(recording.id));
+                            ui.close_menu();
+                        }
+
+                        // only the owner can change a recording
+                        if recording.owner.is_none() {
+                            if ui.button("Edit title and description…").clicked() {
+                                action = Some(RecordingAction::Edit(recording.id));
+                                ui.close_menu();
+                            }
+                            if ui.button("Share…").clicked() {
+                                action = Some(RecordingAction::Share(recording.id));
+                                ui.close_menu();
+                            }
+                            ui.menu_button("Keep for", |ui| {
+                                for (label, days) in RETENTION_CHOICES {
+                                    let is_chosen = recording.retention_days == days;
+                                    if ui.radio(is_chosen, label).clicked() {
+                                        action =
+                                            Some(RecordingAction::Retention(recording.id, days));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                            if ui.button("Delete").clicked() {
+                                action = Some(RecordingAction::Delete(recording.id));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+            }
+        });
+
+        match action? {
+            RecordingAction::Watch(id) => self.watch_recording(id, channel),
+
+            RecordingAction::WatchParty(id) => self.start_watch_party(id, channel),
+
+            RecordingAction::Edit(id) => {
+                let recording = self.recordings.iter().find(|r| r.id == id)?;
+                self.editing = Some(RecordingEdit {
+                    id,
+                    title: recording.title.clone(),
+                    description: recording.description.clone(),
+                });
+                None
+            }
+
+            RecordingAction::Share(id) => {
+                self.sharing = Some((id, String::new()));
+                None
+            }
+
+            RecordingAction::Save(id) => {
+                let recording = self.recordings.iter().find(|r| r.id == id)?;
+                let path =
+                    PathBuf::from(DOWNLOADS_FOLDER).join(format!("{}.mp4", recording.file_stem()));
+                self.saving = Some((id, path.to_string_lossy().into_owned()));
+                None
+            }
+
+            RecordingAction::Delete(id) => {
+                self.deleting = Some(id);
+                None
+            }
+
+            RecordingAction::Retention(id, days) => {
+                self.change_retention(id, days, channel);
+                None
+            }
+        }
+    }
+
+    /// Asks the server to keep a recording for a number of days.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the recording.
+    /// * `days` - How many days to keep the recording for, 0 to keep it forever.
+    /// * `channel` - A mutable reference to the `SecureChannel` for communication with the server.
+    fn change_retention(&mut self, id: i32, days: u32, channel: &mut SecureChannel) {
+        channel
+            .send(Packet::RecordingRetention { id, days })
+            .unwrap();
+        if self.check_result(channel.receive().unwrap()).is_none() {
+            return;
+        }
+
+        if let Some(recording) = self.recordings.iter_mut().find(|r| r.id == id) {
+            recording.retention_days = days;
+        }
+
+        info!(
+            target: LOG_TARGET,
+            "Changed how long recording {} is kept to {} days (0 is forever).", id, days
+        );
+    }
+
+    /// Asks the server for the storage the user's recordings take, after it changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - A mutable reference to the `SecureChannel` for communication with the server.
+    fn refresh_storage(&mut self, channel: &mut SecureChannel) {
+        channel.send(Packet::GetStorageUsage).unwrap();
+
+        if let Packet::StorageUsage {
+            used_bytes,
+            quota_bytes,
+        } = channel.receive().unwrap()
+        {
+            self.storage = StorageUsage {
+                used_bytes,
+                quota_bytes,
+            };
+        }
+    }
+
+    /// Asks the server to play a recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the recording.
+    /// * `channel` - A mutable reference to the `SecureChannel` for communication with the server.
+    ///
+    /// # Returns
+    ///
+    /// A `SceneChange` to the `WatchScene`, or `None` if the server refused.
+    fn watch_recording(&mut self, id: i32, channel: &mut SecureChannel) -> Option<SceneChange> {
+        channel.send(Packet::WatchRecording { id }).unwrap();
+
+        let duration = self.check_result(channel.receive().unwrap())?;
+        let duration_ms: u64 = duration.parse().expect("duration should be u64");
+        let duration = Duration::from_millis(duration_ms);
+
+        info!(target: LOG_TARGET, "Watching recording {}.", id);
+
+        Some(SceneChange::To(Box::new(WatchScene::new(
+            self.username.clone(),
+            duration,
+            None,
+            channel,
+        ))))
+    }
+
+    /// Asks the server to start a watch party for a recording, which others join with
+    /// its session code.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the recording.
+    /// * `channel` - A mutable reference to the `SecureChannel` for communication with the server.
+    ///
+    /// # Returns
+    ///
+    /// A `SceneChange` to the `WatchScene` leading the party, or `None` if the server refused.
+    fn start_watch_party(&mut self, id: i32, channel: &mut SecureChannel) -> Option<SceneChange> {
+        channel.send(Packet::WatchParty { id }).unwrap();
+
+        let code = self.check_result(channel.receive().unwrap())?;
+        let duration = self.check_result(channel.receive().unwrap())?;
+        let duration_ms: u64 = duration.parse().expect("duration should be u64");
+        let duration = Duration::from_millis(duration_ms);
+
+        info!(target: LOG_TARGET, "Started watch party {} for recording {}.", code, id);
+
+        Some(SceneChange::To(Box::new(WatchScene::new(
+            self.username.clone(),
+            duration,
+            Some(PartyRole::Leader(code)),
+            channel,
+        ))))
+    }
+
+    /// Renders the window for editing a recording's title and description, while open.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The `egui::Context` to show the window in.
+    /// * `channel` - A mutable reference to the `SecureChannel` for communication with the server.
+    fn edit_window(&mut self, ctx: &egui::Context, channel: &mut SecureChannel) {
+        let Some(edit) = &mut self.editing else {
+            return;
+        };
+
+        let mut save = false;
+        let mut close = false;
+
+        egui::Window::new("Edit recording")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("Title");
+                ui.add(
+                    TextEdit::singleline(&mut edit.title)
+                        .char_limit(MAX_RECORDING_TITLE_LENGTH)
+                        .hint_text("Untitled"),
+                );
+
+                ui.label("Description");
+                ui.add(
+                    TextEdit::multiline(&mut edit.description)
+                        .char_limit(MAX_RECORDING_DESCRIPTION_LENGTH),
+                );
+
+                ui.horizontal(|ui| {
+                    save = ui.button("Save").clicked();
+                    close = ui.button("Cancel").clicked();
+                });
+            });
+
+        if save {
+            let edit = self.editing.take().unwrap();
+            let Some(index) = self.recordings.iter().position(|r| r.id == edit.id) else {
+                return;
+            };
+
+            if edit.title != self.recordings[index].title {
+                let rename = Packet::RenameRecording {
+                    id: edit.id,
+                    title: edit.title.trim().to_string(),
+                };
+                channel.send(rename).unwrap();
+
+                if let Some(title) = self.check_result(channel.receive().unwrap()) {
+                    self.recordings[index].title = title;
+                }
+            }
+
+            if edit.description != self.recordings[index].description {
+                let describe = Packet::DescribeRecording {
+                    id: edit.id,
+                    description: edit.description.trim().to_string(),
+                };
+                channel.send(describe).unwrap();
+
+                if let Some(description) = self.check_result(channel.receive().unwrap()) {
+                    self.recordings[index].description = description;
+                }
+            }
+        } else if close {
+            self.editing = None;
+        }
+    }
+
+    /// Renders the window for sharing a recording with another user, while open.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The `egui::Context` to show the window in.
+    /// * `channel` - A mutable reference to the `SecureChannel` for communication with the server.
+    fn share_window(&mut self, ctx: &egui::Context, channel: &mut SecureChannel) {
+        let Some((_, username)) = &mut self.sharing else {
+            return;
+        };
+
+        let mut share = false;
+        let mut close = false;
+
+        egui::Window::new("Share recording")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("Username");
+                let response = ui.add(TextEdit::singleline(username));
+                let submitted =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                ui.horizontal(|ui| {
+                    share = ui.button("Share").clicked() || submitted;
+                    close = ui.button("Cancel").clicked();
+                });
+            });
+
+        if share {
+            let (id, username) = self.sharing.take().unwrap();
+
+            let packet = Packet::ShareRecording {
+                id,
+                username: username.trim().to_string(),
+            };
+            channel.send(packet).unwrap();
+
+            if let Some(msg) = self.check_result(channel.receive().unwrap()) {
+                self.is_error = false;
+                self.status_message = msg;
+            }
+        } else if close {
+            self.sharing = None;
+        }
+    }
+
+    /// Renders the window for choosing where to save a recording, while open.
+    ///
+    /// Saving to the path of a cancelled download resumes it.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The `egui::Context` to show the window in.
+    /// * `channel` - A reference to the `SecureChannel` the recording is downloaded through.
+    fn save_window(&mut self, ctx: &egui::Context, channel: &SecureChannel) {
+        let Some((_, path)) = &mut self.saving else {
+            return;
+        };
+
+        let mut save = false;
+        let mut close = false;
+
+        egui::Window::new("Save recording")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("Save as");
+                let response = ui.add(TextEdit::singleline(path).desired_width(300.0));
+                let submitted =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                ui.horizontal(|ui| {
+                    save = ui
+                        .add_enabled(!path.trim().is_empty(), Button::new("Save"))
+                        .clicked()
+                        || (submitted && !path.trim().is_empty());
+                    close = ui.button("Cancel").clicked();
+                });
+            });
+
+        if save {
+            let (id, path) = self.saving.take().unwrap();
+
+            info!(target: LOG_TARGET, "Downloading recording {} to {}.", id, path.trim());
+
+            self.download = Some(Download::start(channel, id, PathBuf::from(path.trim())));
+        } else if close {
+            self.saving = None;
+        }
+    }
+
+    /// Checks whether a recording download is using the channel.
+    ///
+    /// # Returns
+    ///
+    /// `true` while a download is in progress.
+    fn is_downloading(&self) -> bool {
+        self.download.as_ref().is_some_and(Download::is_active)
+    }
+
+    /// Renders the window confirming the deletion of a recording, while open.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The `egui::Context` to show the window in.
+    /// * `channel` - A mutable reference to the `SecureChannel` for communication with the server.
+    fn delete_window(&mut self, ctx: &egui::Context, channel: &mut SecureChannel) {
+        let Some(id) = self.deleting else {
+            return;
+        };
+        let Some(recording) = self.recordings.iter().find(|r| r.id == id) else {
+            self.deleting = None;
+            return;
+        };
+
+        let mut delete = false;
+        let mut close = false;
+
+        egui::Window::new("Delete recording")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Delete \"{}\"? This can't be undone.",
+                    recording.display_name()
+                ));
+
+                ui.horizontal(|ui| {
+                    delete = ui.button("Delete").clicked();
+                    close = ui.button("Cancel").clicked();
+                });
+            });
+
+        if delete {
+            self.deleting = None;
+
+            channel.send(Packet::DeleteRecording { id }).unwrap();
+            if self.check_result(channel.receive().unwrap()).is_some() {
+                self.recordings.retain(|r| r.id != id);
+                self.refresh_storage(channel);
+
+                info!(target: LOG_TARGET, "Deleted recording {}.", id);
+            }
+        } else if close {
+            self.deleting = None;
+        }
+    }
 }
 
 impl Scene for MenuScene {
@@ -220,14 +1045,27 @@ impl Scene for MenuScene {
                 self.is_disabled = false;
 
                 match join_result {
-                    true => {
-                        info!(target: LOG_TARGET, "Joining session {}.", self.session_code);
+                    // a watch party answers with the duration of its recording
+                    true => match msg.parse::<u64>() {
+                        Ok(duration_ms) => {
+                            info!(target: LOG_TARGET, "Joining watch party {}.", self.session_code);
 
-                        result = SceneChange::To(Box::new(ParticipantScene::new(
-                            channel,
-                            self.username.clone(),
-                        )))
-                    }
+                            result = SceneChange::To(Box::new(WatchScene::new(
+                                self.username.clone(),
+                                Duration::from_millis(duration_ms),
+                                Some(PartyRole::Viewer),
+                                channel,
+                            )))
+                        }
+                        Err(_) => {
+                            info!(target: LOG_TARGET, "Joining session {}.", self.session_code);
+
+                            result = SceneChange::To(Box::new(ParticipantScene::new(
+                                channel,
+                                self.username.clone(),
+                            )))
+                        }
+                    },
 
                     false => {
                         info!(
@@ -252,7 +1090,7 @@ impl Scene for MenuScene {
         egui::TopBottomPanel::bottom("connection_status")
             .resizable(false)
             .show(ctx, |ui| {
-                if self.is_disabled {
+                if self.is_disabled || self.is_downloading() {
                     ui.disable();
                 }
 
@@ -286,42 +1124,29 @@ impl Scene for MenuScene {
             ui.heading("Watch past recordings");
             ui.separator();
 
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                for (id, recording) in &self.recordings {
-                    let time: DateTime<Local> = recording.parse().unwrap();
-                    let recording_display_name = time.format("%B %-d, %Y | %T").to_string();
-
-                    if ui.button(&recording_display_name).clicked() {
-                        channel.send(Packet::WatchRecording { id: *id }).unwrap();
-
-                        let result_packet = channel.receive().unwrap();
-
-                        match result_packet {
-                            ResultPacket::Failure(msg) => {
-                                self.is_error = true;
-                                self.status_message = msg;
-                            }
-
-                            ResultPacket::Success(duration) => {
-                                let duration: i32 =
-                                    duration.parse().expect("duration should be i32");
-
-                                info!(target: LOG_TARGET, "Watching recording {}.", &recording_display_name);
-
-                                result = SceneChange::To(Box::new(WatchScene::new(
-                                    self.username.clone(),
-                                    duration,
-                                    channel,
-                                )));
-                            }
-                        }
-                    }
+            // the download uses the channel, so nothing else can until it stops
+            if let Some(download) = &mut self.download {
+                if download.ui(ui) {
+                    self.download = None;
                 }
-            });
+                ui.separator();
+            }
+            if self.is_downloading() {
+                ui.disable();
+            }
+
+            if let Some(change) = self.recordings_panel_ui(ui, channel) {
+                result = change;
+            }
         });
 
+        self.edit_window(ctx, channel);
+        self.share_window(ctx, channel);
+        self.save_window(ctx, channel);
+        self.delete_window(ctx, channel);
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            if self.is_disabled {
+            if self.is_disabled || self.is_downloading() {
                 ui.disable();
             }
 
@@ -387,6 +1212,11 @@ impl Scene for MenuScene {
                 ui.label(RichText::new("Host Session").size(20.0));
                 ui.add_space(10.0);
 
+                source_picker(ui, &mut self.capture_source);
+                ui.checkbox(&mut self.record_session, "Record this session")
+                    .on_hover_text("Participants are told whether the session is recorded");
+                ui.add_space(10.0);
+
                 let host_button = ui.add_enabled(!self.username.is_empty(), |ui: &mut Ui| {
                     ui.add_sized([100.0, 40.0], Button::new(RichText::new("Host").size(20.0)))
                 });
@@ -409,6 +1239,9 @@ impl Scene for MenuScene {
     ///
     /// * `channel` - A mutable reference to the `SecureChannel` to be closed.
     fn on_exit(&mut self, channel: &mut SecureChannel) {
+        // stops the download before the channel is used
+        self.download = None;
+
         channel.send(Packet::SignOut).unwrap();
         channel.send(Packet::Shutdown).unwrap();
 
@@ -1,17 +1,33 @@
-use eframe::egui::{self, pos2, Color32, Rect, Sense, Stroke, Ui, Vec2};
-use stream_desk::protocol::{ControlPayload, Packet};
+use eframe::egui::{
+    self, pos2, Color32, ColorImage, CursorIcon, MouseWheelUnit, Painter, PointerButton, Pos2,
+    Rect, Sense, Shape, Stroke, TextureHandle, Ui, Vec2,
+};
+use stream_desk::protocol::{
+    ControlPayload, CursorShape, Packet, RecordingState, SCROLL_UNITS_PER_NOTCH,
+};
 use stream_desk::secure_channel::SecureChannel;
 use stream_desk::{
-    chat_ui, egui_key_to_vk, normalize_mouse_position, users_list, Scene, SceneChange, UserType,
+    bookmark_message, bookmark_ui, chat_ui, egui_key_to_vk, normalize_mouse_position,
+    recording_indicator, recording_message, users_list, Scene, SceneChange, UserType,
 };
 
-use crate::{menu_scene::MenuScene, modifiers_state::ModifiersState};
+use crate::{
+    audio::{
+        device_picker, list_audio_devices, AudioCapture, AudioKind, AudioPlayer, SharedAudioPlayer,
+    },
+    clipboard::{read_clipboard, write_clipboard},
+    decoder::{start_decoder, Frame, FrameSink},
+    file_transfer::{
+        handle_transfer_packet, offer_file, stop_transfers, transfers_ui, SharedTransfers,
+    },
+    menu_scene::MenuScene,
+    modifiers_state::ModifiersState,
+    stream_quality::FEEDBACK_INTERVAL,
+};
 use std::{
     collections::{HashMap, VecDeque},
-    io::{Read, Write},
-    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
         Arc, Mutex,
     },
     thread::{self, JoinHandle},
@@ -23,56 +39,33 @@ const REQUEST_CONTROL_MSG: &'static str = "Request Control";
 const WAITING_CONTROL_MSG: &'static str = "Waiting for response...";
 const CONTROLLING_MSG: &'static str = "You're the controller!";
 
+// How many points of smooth scrolling count as one line (one wheel notch).
+const POINTS_PER_SCROLL_LINE: f32 = 50.0;
+// How many lines a page scroll counts as.
+const LINES_PER_SCROLL_PAGE: f32 = 3.0;
+
+/// The last known state of the host's cursor.
+#[derive(Clone, Copy)]
+struct RemoteCursor {
+    /// Normalized X coordinate (0 to 65,535).
+    x: u32,
+    /// Normalized Y coordinate (0 to 65,535).
+    y: u32,
+    shape: CursorShape,
+}
+
 /// Enum to control which panel is currently visible on the right side of the UI.
 #[derive(PartialEq, Eq)]
 enum RightPanelType {
     UsersList,
     Chat,
-}
-
-/// Starts the `ffmpeg` command to decode incoming H.264 video streams.
-///
-/// This function sets up `ffmpeg` as a child process. It configures `ffmpeg`
-/// to receive H.264 data from its standard input, discard corrupted frames,
-/// and output raw RGBA pixel data to its standard output.
-///
-/// # Returns
-///
-/// A `Child` process handle to the spawned `ffmpeg` instance.
-///
-/// # Panics
-///
-/// Panics if `ffmpeg` fails to spawn.
-fn start_ffmpeg() -> Child {
-    let ffmpeg = Command::new("ffmpeg")
-        .args([
-            "-flags",
-            "low_delay", // Prioritize low latency decoding
-            "-fflags",
-            "discardcorrupt", // Discard corrupted frames instead of stopping
-            "-f",
-            "h264", // Input format is H.264
-            "-i",
-            "-", // Read input from stdin
-            "-f",
-            "rawvideo", // Output raw video
-            "-pix_fmt",
-            "rgba", // Output pixel format is RGBA
-            "-",    // Write output to stdout
-        ])
-        .stdin(Stdio::piped()) // Pipe for H.264 input
-        .stdout(Stdio::piped()) // Pipe for RGBA output
-        .stderr(Stdio::null()) // Suppress ffmpeg's stderr output
-        .spawn()
-        .expect("Failed to spawn ffmpeg");
-
-    ffmpeg
+    Files,
 }
 
 /// Spawns a dedicated thread to continuously receive `Packet`s from the `SecureChannel`.
 ///
 /// This thread processes different types of incoming packets:
-/// - `Packet::Screen`: Writes the received H.264 bytes to `ffmpeg`'s stdin for decoding.
+/// - `Packet::Screen`: Feeds the received H.264 bytes to the decoder, which is created on this thread.
 /// - `Packet::UserUpdate`: Updates the shared `usernames` map, adding or removing users
 ///   and pushing status messages to the `chat_log`.
 /// - `Packet::RequestControl`: Updates the `control_msg` to indicate the client is now controlling.
@@ -80,141 +73,309 @@ fn start_ffmpeg() -> Child {
 /// - `Packet::SessionExit` or `Packet::SessionEnd`: Sets a `stop_flag` to signal other threads
 ///   to terminate and then exits the loop, returning control to the main UI thread.
 /// - `Packet::Chat`: Adds the received chat message to the `chat_log`.
+/// - `Packet::ClipboardSync`: Updates whether the host allows clipboard synchronization.
+/// - `Packet::ClipboardText` or `Packet::ClipboardImage`: Writes the host's clipboard content
+///   to the local clipboard.
+/// - `Packet::File*`: Updates the file transfers with the host.
+/// - `Packet::CursorState`: Updates the host's cursor, or hides it.
+/// - `Packet::ScreenSource`: Updates the size of the shared screen, used for its aspect ratio.
+/// - `Packet::Audio` or `Packet::AudioEnd`: Plays or stops the audio of the host or another participant.
+/// - `Packet::RecordingState`: Updates whether the session is recorded and announces it in the `chat_log`.
 ///
 /// # Arguments
 ///
 /// * `channel` - A `SecureChannel` instance (cloned for thread ownership) to receive packets.
-/// * `stdin` - The `ChildStdin` of the `ffmpeg` process, used to feed H.264 data.
+/// * `sink` - Receives the decoded frames.
 /// * `stop_flag` - An `Arc<AtomicBool>` used to signal this thread to stop.
 /// * `usernames` - An `Arc<Mutex<HashMap<String, UserType>>>` to share and update the list of session participants.
 /// * `control_msg` - An `Arc<Mutex<String>>` to share and update the current control status message.
 /// * `chat_log` - An `Arc<Mutex<Vec<String>>>` to share and append chat messages.
+/// * `clipboard_sync` - An `Arc<AtomicBool>` indicating whether the host allows clipboard synchronization.
+/// * `file_transfers` - The shared list of file transfers with the host.
+/// * `remote_cursor` - The host's cursor, `None` while it's hidden or unknown.
+/// * `screen_size` - The size in pixels of the area the host shares.
+/// * `received_bytes` - Counter of the received screen bytes, for the stream feedback.
+/// * `audio_player` - The player of the host's audio and the other participants' microphones.
+/// * `recording_state` - Whether the session is recorded.
 ///
 /// # Returns
 ///
 /// A `JoinHandle` for the spawned thread, allowing the main thread to wait for its completion.
 fn thread_receive_socket(
     mut channel: SecureChannel,
-    mut stdin: ChildStdin,
+    sink: FrameSink,
     stop_flag: Arc<AtomicBool>,
     usernames: Arc<Mutex<HashMap<String, UserType>>>,
     control_msg: Arc<Mutex<String>>,
     chat_log: Arc<Mutex<Vec<String>>>,
+    clipboard_sync: Arc<AtomicBool>,
+    file_transfers: SharedTransfers,
+    remote_cursor: Arc<Mutex<Option<RemoteCursor>>>,
+    screen_size: Arc<Mutex<Vec2>>,
+    received_bytes: Arc<AtomicU64>,
+    audio_player: SharedAudioPlayer,
+    recording_state: Arc<Mutex<RecordingState>>,
 ) -> JoinHandle<()> {
-    thread::spawn(move || loop {
-        // Check stop flag early to react to shutdown signals
-        if stop_flag.load(Ordering::Relaxed) {
-            break;
-        }
-
-        // Receive packet, defaulting if there's an error to prevent crashing the thread
-        let packet = channel.receive().unwrap_or_default();
+    thread::spawn(move || {
+        let mut decoder = start_decoder(sink);
 
-        match packet {
-            Packet::Screen { bytes } => {
-                let _ = stdin.write_all(&bytes);
+        loop {
+            // Check stop flag early to react to shutdown signals
+            if stop_flag.load(Ordering::Relaxed) {
+                break;
             }
 
-            Packet::UserUpdate {
-                user_type,
-                joined_before,
-                username,
-            } => {
-                let mut usernames_guard = usernames.lock().unwrap();
-                let mut chat_log_guard = chat_log.lock().unwrap();
-
-                if user_type == UserType::Leaving {
-                    usernames_guard.remove(&username);
-                    chat_log_guard.push(format!("#r{} has disconnected.", username));
-                } else {
-                    // Only add a "joined" message if the user wasn't already in the list
-                    // and hadn't joined before (i.e., truly new to the session).
-                    if usernames_guard.contains_key(&username) {
-                        chat_log_guard.push(format!("#b{} is now a {}.", username, user_type));
-                    } else if !joined_before {
-                        chat_log_guard.push(format!("#g{} has joined the session.", username));
+            // Receive packet, defaulting if there's an error to prevent crashing the thread
+            let packet = channel.receive().unwrap_or_default();
+
+            match packet {
+                Packet::Screen { bytes, .. } => {
+                    received_bytes.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                    decoder.push(&bytes);
+                }
+
+                Packet::UserUpdate {
+                    user_type,
+                    joined_before,
+                    username,
+                } => {
+                    let mut usernames_guard = usernames.lock().unwrap();
+                    let mut chat_log_guard = chat_log.lock().unwrap();
+
+                    if user_type == UserType::Leaving {
+                        usernames_guard.remove(&username);
+                        audio_player.lock().unwrap().stop(&username);
+                        chat_log_guard.push(format!("#r{} has disconnected.", username));
+                    } else {
+                        // Only add a "joined" message if the user wasn't already in the list
+                        // and hadn't joined before (i.e., truly new to the session).
+                        if usernames_guard.contains_key(&username) {
+                            chat_log_guard.push(format!("#b{} is now a {}.", username, user_type));
+                        } else if !joined_before {
+                            chat_log_guard.push(format!("#g{} has joined the session.", username));
+                        }
+                        usernames_guard.insert(username.clone(), user_type);
                     }
-                    usernames_guard.insert(username.clone(), user_type);
                 }
-            }
 
-            Packet::RequestControl { .. } => {
-                // This client has been granted control
-                let mut control_msg_guard = control_msg.lock().unwrap();
-                *control_msg_guard = CONTROLLING_MSG.to_string();
-            }
+                Packet::RequestControl { .. } => {
+                    // This client has been granted control
+                    let mut control_msg_guard = control_msg.lock().unwrap();
+                    *control_msg_guard = CONTROLLING_MSG.to_string();
+                }
+
+                Packet::DenyControl { .. } => {
+                    // This client's control request was denied
+                    let mut control_msg_guard = control_msg.lock().unwrap();
+                    // Only update message if we weren't already controlling
+                    if *control_msg_guard != CONTROLLING_MSG {
+                        let mut chat_log_guard = chat_log.lock().unwrap();
+                        chat_log_guard
+                            .push("#rYour control request was denied by the host.".to_string());
+                    }
+                    *control_msg_guard = REQUEST_CONTROL_MSG.to_string();
+                }
+
+                Packet::SessionExit => {
+                    stop_flag.store(true, Ordering::Relaxed);
+                    break;
+                }
+
+                Packet::SessionEnd => {
+                    stop_flag.store(true, Ordering::Relaxed);
+                    channel.send(packet).unwrap();
+                    break;
+                }
 
-            Packet::DenyControl { .. } => {
-                // This client's control request was denied
-                let mut control_msg_guard = control_msg.lock().unwrap();
-                // Only update message if we weren't already controlling
-                if *control_msg_guard != CONTROLLING_MSG {
+                Packet::Chat { message } => {
                     let mut chat_log_guard = chat_log.lock().unwrap();
-                    chat_log_guard
-                        .push("#rYour control request was denied by the host.".to_string());
+                    chat_log_guard.push(message);
                 }
-                *control_msg_guard = REQUEST_CONTROL_MSG.to_string();
-            }
 
-            Packet::SessionExit => {
-                stop_flag.store(true, Ordering::Relaxed);
-                break;
+                Packet::Bookmark {
+                    time_ms,
+                    name,
+                    username,
+                } => {
+                    let mut chat_log_guard = chat_log.lock().unwrap();
+                    chat_log_guard.push(bookmark_message(time_ms, &name, &username));
+                }
+
+                Packet::ClipboardSync { enabled } => {
+                    clipboard_sync.store(enabled, Ordering::Relaxed);
+                }
+
+                Packet::RecordingState { state } => {
+                    *recording_state.lock().unwrap() = state;
+
+                    let mut chat_log_guard = chat_log.lock().unwrap();
+                    chat_log_guard.push(recording_message(state));
+                }
+
+                Packet::ClipboardText { .. } | Packet::ClipboardImage { .. } => {
+                    // The server only forwards the host's clipboard to the controller
+                    write_clipboard(&packet);
+                }
+
+                Packet::FileOffer { .. }
+                | Packet::FileAccept { .. }
+                | Packet::FileChunk { .. }
+                | Packet::FileEnd { .. }
+                | Packet::FileCancel { .. } => {
+                    handle_transfer_packet(&file_transfers, packet, &mut channel);
+                }
+
+                Packet::CursorState {
+                    x,
+                    y,
+                    shape,
+                    visible,
+                } => {
+                    let mut remote_cursor = remote_cursor.lock().unwrap();
+                    *remote_cursor = visible.then_some(RemoteCursor { x, y, shape });
+                }
+
+                Packet::ScreenSource { width, height, .. } => {
+                    let mut screen_size = screen_size.lock().unwrap();
+                    let new_size = Vec2::new(width.max(1) as f32, height.max(1) as f32);
+
+                    // the first announcement is the current screen, not a switch
+                    if *screen_size != new_size {
+                        let mut chat_log_guard = chat_log.lock().unwrap();
+                        chat_log_guard.push(format!(
+                            "#bThe host is now sharing a {}x{} screen.",
+                            width, height
+                        ));
+                    }
+                    *screen_size = new_size;
+                }
+
+                Packet::Audio { username, bytes } => {
+                    audio_player.lock().unwrap().play(&username, bytes);
+                }
+
+                Packet::AudioEnd { username } => {
+                    audio_player.lock().unwrap().stop(&username);
+                }
+
+                _ => (),
             }
+        }
+    })
+}
 
-            Packet::SessionEnd => {
-                stop_flag.store(true, Ordering::Relaxed);
-                channel.send(packet).unwrap();
-                break;
+/// Converts the host's cursor shape to the matching `egui` cursor icon.
+///
+/// # Arguments
+///
+/// * `shape` - The shape of the host's cursor.
+///
+/// # Returns
+///
+/// The `CursorIcon` to show for the local pointer.
+fn cursor_icon(shape: CursorShape) -> CursorIcon {
+    match shape {
+        CursorShape::Arrow => CursorIcon::Default,
+        CursorShape::Text => CursorIcon::Text,
+        CursorShape::Hand => CursorIcon::PointingHand,
+        CursorShape::Wait => CursorIcon::Wait,
+        CursorShape::Progress => CursorIcon::Progress,
+        CursorShape::Crosshair => CursorIcon::Crosshair,
+        CursorShape::ResizeHorizontal => CursorIcon::ResizeHorizontal,
+        CursorShape::ResizeVertical => CursorIcon::ResizeVertical,
+        CursorShape::ResizeNwSe => CursorIcon::ResizeNwSe,
+        CursorShape::ResizeNeSw => CursorIcon::ResizeNeSw,
+        CursorShape::Move => CursorIcon::Move,
+        CursorShape::NotAllowed => CursorIcon::NotAllowed,
+        CursorShape::Help => CursorIcon::Help,
+    }
+}
+
+/// Draws the host's cursor on top of the remote screen.
+///
+/// The text and crosshair shapes get their own outline; every other shape is drawn
+/// as an arrow with its tip at `pos`.
+///
+/// # Arguments
+///
+/// * `painter` - The `Painter` of the screen area.
+/// * `pos` - The position of the cursor's hotspot in screen coordinates.
+/// * `shape` - The shape of the host's cursor.
+fn draw_cursor(painter: &Painter, pos: Pos2, shape: CursorShape) {
+    let outline = Stroke::new(1.0, Color32::BLACK);
+
+    match shape {
+        CursorShape::Text => {
+            let stroke = Stroke::new(2.0, Color32::WHITE);
+            let top = pos - Vec2::new(0.0, 8.0);
+            let bottom = pos + Vec2::new(0.0, 8.0);
+            for (from, to) in [
+                (top, bottom),
+                (top - Vec2::new(3.0, 0.0), top + Vec2::new(3.0, 0.0)),
+                (bottom - Vec2::new(3.0, 0.0), bottom + Vec2::new(3.0, 0.0)),
+            ] {
+                painter.line_segment([from, to], Stroke::new(4.0, Color32::BLACK));
+                painter.line_segment([from, to], stroke);
             }
+        }
 
-            Packet::Chat { message } => {
-                let mut chat_log_guard = chat_log.lock().unwrap();
-                chat_log_guard.push(message);
+        CursorShape::Crosshair => {
+            for offset in [Vec2::new(8.0, 0.0), Vec2::new(0.0, 8.0)] {
+                painter.line_segment(
+                    [pos - offset, pos + offset],
+                    Stroke::new(4.0, Color32::BLACK),
+                );
+                painter.line_segment(
+                    [pos - offset, pos + offset],
+                    Stroke::new(2.0, Color32::WHITE),
+                );
             }
+        }
 
-            _ => (),
+        _ => {
+            let points = [
+                (0.0, 0.0),
+                (0.0, 16.0),
+                (4.0, 12.0),
+                (7.0, 18.0),
+                (9.0, 17.0),
+                (6.0, 11.0),
+                (11.0, 11.0),
+            ]
+            .map(|(x, y)| pos + Vec2::new(x, y))
+            .to_vec();
+            painter.add(Shape::convex_polygon(points, Color32::WHITE, outline));
         }
-    })
+    }
 }
 
-/// Spawns a dedicated thread to read decoded RGBA frames from `ffmpeg`'s stdout.
+/// Creates the `FrameSink` that pushes decoded frames into a shared `frame_queue` for rendering
+/// on the main UI thread.
 ///
-/// These raw video frames are then pushed into a shared `frame_queue` for rendering
-/// on the main UI thread. The thread ensures that the queue does not grow excessively
-/// large by removing older frames if it exceeds a certain size (e.g., 3 frames).
+/// The queue is kept from growing excessively large by removing older frames if it
+/// exceeds a certain size (e.g., 3 frames). Removed frames are counted in `dropped_frames`.
 ///
 /// # Arguments
 ///
-/// * `frame_queue` - An `Arc<Mutex<VecDeque<Vec<u8>>>>` to store decoded RGBA frames.
-/// * `stdout` - The `ChildStdout` of the `ffmpeg` process, used to read RGBA data.
-/// * `stop_flag` - An `Arc<AtomicBool>` used to signal this thread to stop.
+/// * `frame_queue` - An `Arc<Mutex<VecDeque<ColorImage>>>` to store decoded frames.
+/// * `dropped_frames` - Counter of the frames dropped because the queue was full.
 ///
 /// # Returns
 ///
-/// A `JoinHandle` for the spawned thread.
-fn thread_read_decoded(
-    frame_queue: Arc<Mutex<VecDeque<Vec<u8>>>>,
-    mut stdout: ChildStdout,
-    stop_flag: Arc<AtomicBool>,
-) -> JoinHandle<()> {
-    thread::spawn(move || {
-        // Buffer for a single 1920x1080 RGBA frame (1920 * 1080 * 4 bytes)
-        let mut rgba_buffer = vec![0u8; 1920 * 1080 * 4];
-        while !stop_flag.load(Ordering::Relaxed) {
-            // Attempt to read exactly one frame's worth of data
-            if let Ok(()) = stdout.read_exact(&mut rgba_buffer) {
-                let mut queue = frame_queue.lock().unwrap();
-
-                // Limit the queue size to prevent excessive memory usage or lag
-                if queue.len() > 3 {
-                    queue.pop_front(); // Discard the oldest frame
-                }
-                queue.push_back(rgba_buffer.clone()); // Add the new frame
-            } else {
-                // If reading fails (e.g., pipe closed, ffmpeg exits), exit the loop
-                break;
-            }
+/// The `FrameSink` to hand to the decoder.
+fn frame_sink(
+    frame_queue: Arc<Mutex<VecDeque<ColorImage>>>,
+    dropped_frames: Arc<AtomicU32>,
+) -> FrameSink {
+    Box::new(move |frame: Frame| {
+        let mut queue = frame_queue.lock().unwrap();
+
+        // Limit the queue size to prevent excessive memory usage or lag
+        if queue.len() > 3 {
+            queue.pop_front(); // Discard the oldest frame
+            dropped_frames.fetch_add(1, Ordering::Relaxed);
         }
+        queue.push_back(frame.image); // Add the new frame, the live stream has no timestamps
     })
 }
 
@@ -222,7 +383,7 @@ fn thread_read_decoded(
 ///
 /// This scene displays the remote screen, handles user input for control
 /// (keyboard, mouse), manages the list of online users, and provides chat functionality.
-/// It integrates with `ffmpeg` for video decoding and uses multiple threads
+/// It decodes the video on its network thread and uses multiple threads
 /// for efficient network and video processing.
 pub struct ParticipantScene {
     /// Tracks time for frame rate limiting of screen updates.
@@ -230,10 +391,10 @@ pub struct ParticipantScene {
     /// Accumulates elapsed time to control frame updates.
     elapsed_time: f32,
 
-    /// A shared queue of decoded RGBA frames from `ffmpeg`.
-    frame_queue: Arc<Mutex<VecDeque<Vec<u8>>>>,
-    /// The currently displayed screen frame.
-    current_frame: Vec<u8>,
+    /// A shared queue of decoded frames.
+    frame_queue: Arc<Mutex<VecDeque<ColorImage>>>,
+    /// The texture of the currently displayed screen frame, once one was decoded.
+    screen_texture: Option<TextureHandle>,
 
     /// State manager for keyboard modifier keys (Ctrl, Alt, Shift).
     modifiers_state: ModifiersState,
@@ -241,6 +402,12 @@ pub struct ParticipantScene {
     stop_flag: Arc<AtomicBool>,
     /// The bounding rectangle where the remote screen image is drawn.
     image_rect: Rect,
+    /// The last normalized mouse position sent to the host.
+    mouse_position: (u32, u32),
+    /// Mouse buttons pressed on the remote screen and not released yet.
+    pressed_buttons: Vec<PointerButton>,
+    /// Scroll amount (in scroll units) that was too small to send yet.
+    scroll_remainder: Vec2,
     /// Determines which side panel (Users List or Chat) is currently active.
     right_panel_type: RightPanelType,
 
@@ -255,24 +422,49 @@ pub struct ParticipantScene {
     chat_log: Arc<Mutex<Vec<String>>>,
     /// The current message being typed by the user in the chat input.
     chat_message: String,
+    /// The name of the next bookmark being typed by the user.
+    bookmark_name: String,
+
+    /// Whether the host allows clipboard synchronization with the controller.
+    clipboard_sync: Arc<AtomicBool>,
+    /// A shared list of file transfers with the host.
+    file_transfers: SharedTransfers,
+    /// The host's cursor, drawn on top of the screen.
+    remote_cursor: Arc<Mutex<Option<RemoteCursor>>>,
+    /// The size in pixels of the area the host shares, used for its aspect ratio.
+    screen_size: Arc<Mutex<Vec2>>,
+
+    /// Number of screen bytes received since the last stream feedback.
+    received_bytes: Arc<AtomicU64>,
+    /// Number of decoded frames dropped since the last stream feedback.
+    dropped_frames: Arc<AtomicU32>,
+    /// When the last stream feedback was sent to the host.
+    last_feedback: Instant,
+
+    /// The audio input devices of this computer.
+    audio_devices: Vec<String>,
+    /// The microphone used for push-to-talk, `None` if talking is off.
+    microphone: Option<String>,
+    /// The microphone capture while the push-to-talk button is held.
+    microphone_capture: Option<AudioCapture>,
+    /// The player of the host's audio and the other participants' microphones.
+    audio_player: SharedAudioPlayer,
+
+    /// Whether the session is recorded, shown so the user knows when they are.
+    recording_state: Arc<Mutex<RecordingState>>,
 
     /// Handle for the thread receiving packets from the server.
     thread_receive_socket: Option<JoinHandle<()>>,
-    /// Handle for the thread reading decoded frames from `ffmpeg`.
-    thread_read_decoded: Option<JoinHandle<()>>,
-    /// The `ffmpeg` child process.
-    ffmpeg_command: Child,
 }
 
 impl ParticipantScene {
     /// Creates a new `ParticipantScene` and initializes all necessary components.
     ///
     /// This involves:
-    /// 1. Spawning the `ffmpeg` process and taking ownership of its stdin/stdout.
-    /// 2. Initializing shared data structures (`frame_queue`, `usernames`, `control_msg`, `chat_log`)
+    /// 1. Initializing shared data structures (`frame_queue`, `usernames`, `control_msg`, `chat_log`)
     ///    using `Arc<Mutex>` for thread-safe access.
-    /// 3. Spawning the `thread_receive_socket` and `thread_read_decoded` background threads,
-    ///    passing them the necessary shared data and the `stop_flag`.
+    /// 2. Spawning the `thread_receive_socket` background thread, which decodes the screen,
+    ///    passing it the necessary shared data and the `stop_flag`.
     ///
     /// # Arguments
     ///
@@ -283,12 +475,7 @@ impl ParticipantScene {
     ///
     /// A new `ParticipantScene` instance.
     pub fn new(channel: &mut SecureChannel, username: String) -> Self {
-        let mut ffmpeg = start_ffmpeg();
-        let stdin = ffmpeg.stdin.take().unwrap();
-        let stdout = ffmpeg.stdout.take().unwrap();
-
         let frame_queue = Arc::new(Mutex::new(VecDeque::new()));
-        let frame_queue_clone = frame_queue.clone(); // Clone for the decoding thread
 
         let stop_flag = Arc::new(AtomicBool::new(false)); // Flag to gracefully stop threads
 
@@ -297,22 +484,40 @@ impl ParticipantScene {
 
         let chat_log = Arc::new(Mutex::new(Vec::new()));
 
+        let clipboard_sync = Arc::new(AtomicBool::new(false)); // Updated by the host
+        let file_transfers = Arc::new(Mutex::new(Vec::new()));
+        let remote_cursor = Arc::new(Mutex::new(None));
+        let screen_size = Arc::new(Mutex::new(Vec2::new(1920.0, 1080.0)));
+        let received_bytes = Arc::new(AtomicU64::new(0));
+        let dropped_frames = Arc::new(AtomicU32::new(0));
+        let audio_player = Arc::new(Mutex::new(AudioPlayer::new()));
+        let recording_state = Arc::new(Mutex::new(RecordingState::Off)); // Updated by the server
+
+        let audio_devices = list_audio_devices();
+        let microphone = audio_devices.first().cloned();
+
         let thread_receive_socket = thread_receive_socket(
             channel.clone(), // Clone channel for the thread
-            stdin,
+            frame_sink(frame_queue.clone(), dropped_frames.clone()),
             stop_flag.clone(),
             usernames.clone(),
             control_msg.clone(),
             chat_log.clone(),
+            clipboard_sync.clone(),
+            file_transfers.clone(),
+            remote_cursor.clone(),
+            screen_size.clone(),
+            received_bytes.clone(),
+            audio_player.clone(),
+            recording_state.clone(),
         );
-        let thread_read_decoded = thread_read_decoded(frame_queue_clone, stdout, stop_flag.clone());
 
         Self {
             now: Instant::now(),
             elapsed_time: 0.,
 
             frame_queue,
-            current_frame: vec![0u8; 1920 * 1080 * 4], // Initialize with a blank frame
+            screen_texture: None, // Created with the first decoded frame
 
             modifiers_state: ModifiersState::new(),
             stop_flag,
@@ -320,6 +525,9 @@ impl ParticipantScene {
                 min: pos2(0.0, 0.0),
                 max: pos2(0.0, 0.0),
             }, // Will be updated during rendering
+            mouse_position: (0, 0),
+            pressed_buttons: Vec::new(),
+            scroll_remainder: Vec2::ZERO,
             right_panel_type: RightPanelType::UsersList, // Default to users list
 
             usernames,
@@ -328,10 +536,25 @@ impl ParticipantScene {
 
             chat_log,
             chat_message: String::new(),
+            bookmark_name: String::new(),
+
+            clipboard_sync,
+            file_transfers,
+            remote_cursor,
+            screen_size,
+
+            received_bytes,
+            dropped_frames,
+            last_feedback: Instant::now(),
 
-            thread_receive_socket: Some(thread_receive_socket), // Store thread handles
-            thread_read_decoded: Some(thread_read_decoded),
-            ffmpeg_command: ffmpeg, // Store the ffmpeg child process handle
+            audio_devices,
+            microphone,
+            microphone_capture: None,
+            audio_player,
+
+            recording_state,
+
+            thread_receive_socket: Some(thread_receive_socket), // Store the thread handle
         }
     }
 
@@ -370,8 +593,21 @@ impl ParticipantScene {
                     pressed,
                     ..
                 } => {
+                    // Keep track of held buttons, so drags outside the screen keep working
+                    if *pressed {
+                        if !self.pressed_buttons.contains(button) {
+                            self.pressed_buttons.push(*button);
+                        }
+                    } else if self.pressed_buttons.contains(button) {
+                        self.pressed_buttons.retain(|held| held != button);
+                    } else {
+                        // The button was pressed outside of the screen
+                        continue;
+                    }
+
                     // Normalize mouse position to be relative to the screen dimensions
                     let (mouse_x, mouse_y) = normalize_mouse_position(*pos, self.image_rect);
+                    self.mouse_position = (mouse_x, mouse_y);
 
                     let click_packet = Packet::Control {
                         payload: ControlPayload::MouseClick {
@@ -407,6 +643,7 @@ impl ParticipantScene {
                 egui::Event::PointerMoved(new_pos) => {
                     // Normalize mouse position for movements
                     let (mouse_x, mouse_y) = normalize_mouse_position(*new_pos, self.image_rect);
+                    self.mouse_position = (mouse_x, mouse_y);
 
                     let mouse_move_packet = Packet::Control {
                         payload: ControlPayload::MouseMove { mouse_x, mouse_y },
@@ -414,15 +651,31 @@ impl ParticipantScene {
                     channel.send(mouse_move_packet).unwrap();
                 }
 
-                egui::Event::MouseWheel { delta, .. } => {
-                    // Send scroll wheel events
-                    let scroll_packet = Packet::Control {
-                        payload: ControlPayload::Scroll {
-                            // Convert scroll delta to a signum (1 for up, -1 for down)
-                            delta: delta.y.signum() as i32,
-                        },
+                egui::Event::MouseWheel { unit, delta, .. } => {
+                    // Convert the delta to lines, then to scroll units
+                    let lines = match unit {
+                        MouseWheelUnit::Point => *delta / POINTS_PER_SCROLL_LINE,
+                        MouseWheelUnit::Line => *delta,
+                        MouseWheelUnit::Page => *delta * LINES_PER_SCROLL_PAGE,
                     };
-                    channel.send(scroll_packet).unwrap();
+                    // egui's positive x moves the content right, which is scrolling left
+                    self.scroll_remainder +=
+                        Vec2::new(-lines.x, lines.y) * SCROLL_UNITS_PER_NOTCH as f32;
+
+                    // Keep the fractions, so slow smooth scrolling still adds up
+                    let delta_x = self.scroll_remainder.x.trunc();
+                    let delta_y = self.scroll_remainder.y.trunc();
+                    self.scroll_remainder -= Vec2::new(delta_x, delta_y);
+
+                    if delta_x != 0.0 || delta_y != 0.0 {
+                        let scroll_packet = Packet::Control {
+                            payload: ControlPayload::Scroll {
+                                delta_x: delta_x as i32,
+                                delta_y: delta_y as i32,
+                            },
+                        };
+                        channel.send(scroll_packet).unwrap();
+                    }
                 }
 
                 _ => { /* Ignore other egui events */ }
@@ -430,6 +683,58 @@ impl ParticipantScene {
         }
     }
 
+    /// Reports to the host how well the stream is received, every `FEEDBACK_INTERVAL`.
+    ///
+    /// The host uses the reports to adjust the bitrate, framerate and resolution.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - A mutable reference to the `SecureChannel` to send the report.
+    fn send_feedback(&mut self, channel: &mut SecureChannel) {
+        let elapsed = self.last_feedback.elapsed();
+        if elapsed < FEEDBACK_INTERVAL {
+            return;
+        }
+        self.last_feedback = Instant::now();
+
+        let received_bytes = self.received_bytes.swap(0, Ordering::Relaxed);
+        let feedback = Packet::StreamFeedback {
+            username: String::new(), // filled in by the server
+            received_kbps: (received_bytes as f32 * 8.0 / 1000.0 / elapsed.as_secs_f32()) as u32,
+            queue_depth: self.frame_queue.lock().unwrap().len() as u32,
+            dropped_frames: self.dropped_frames.swap(0, Ordering::Relaxed),
+        };
+        let _ = channel.send(feedback);
+    }
+
+    /// Releases every mouse button still held on the remote screen.
+    ///
+    /// Used when control is lost mid-drag, so no button stays stuck on the host.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - A mutable reference to the `SecureChannel` to send control packets.
+    fn release_buttons(&mut self, channel: &mut SecureChannel) {
+        self.scroll_remainder = Vec2::ZERO;
+
+        if self.pressed_buttons.is_empty() {
+            return;
+        }
+
+        let (mouse_x, mouse_y) = self.mouse_position;
+        for button in self.pressed_buttons.drain(..) {
+            let release_packet = Packet::Control {
+                payload: ControlPayload::MouseClick {
+                    mouse_x,
+                    mouse_y,
+                    pressed: false,
+                    button,
+                },
+            };
+            let _ = channel.send(release_packet);
+        }
+    }
+
     /// Renders the main central panel of the UI, displaying the remote screen.
     ///
     /// This method fetches the latest decoded frame, creates an `egui::Texture` from it,
@@ -443,19 +748,18 @@ impl ParticipantScene {
     /// * `ctx` - The `egui::Context` for loading textures.
     /// * `channel` - A mutable reference to the `SecureChannel` for sending control packets.
     fn central_panel_ui(&mut self, ui: &mut Ui, ctx: &egui::Context, channel: &mut SecureChannel) {
-        // Create an egui texture from the raw RGBA frame data
-        let texture = egui::ColorImage::from_rgba_unmultiplied([1920, 1080], &self.current_frame);
-        let handle = ctx.load_texture("screen", texture, egui::TextureOptions::default());
+        // The frame is drawn with the shared screen's aspect ratio, even if the decoder scaled it
+        let screen_size = *self.screen_size.lock().unwrap();
 
         // Calculate available space and scaling factor to fit the image
         let available_size = ui.available_size();
         let scale = {
-            let scale_x = available_size.x / 1920.0;
-            let scale_y = available_size.y / 1080.0;
+            let scale_x = available_size.x / screen_size.x;
+            let scale_y = available_size.y / screen_size.y;
             scale_x.min(scale_y) // Use the smaller scale to ensure the whole image fits
         };
 
-        let final_size = Vec2::new(1920.0, 1080.0) * scale; // Scaled dimensions
+        let final_size = screen_size * scale; // Scaled dimensions
 
         // Calculate the centered position for the image
         let available_rect = ui.max_rect();
@@ -467,31 +771,62 @@ impl ParticipantScene {
         self.image_rect = centered_rect; // Store the actual drawn rectangle for input normalization
 
         // Draw the remote screen image
-        ui.painter().image(
-            handle.id(),
-            self.image_rect,
-            Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)), // UV coordinates for the whole texture
-            Color32::WHITE,                                     // Tint color (white means no tint)
-        );
+        if let Some(texture) = &self.screen_texture {
+            ui.painter().image(
+                texture.id(),
+                self.image_rect,
+                Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)), // UV coordinates for the whole texture
+                Color32::WHITE, // Tint color (white means no tint)
+            );
+        }
 
         // Draw a border around the displayed screen
         let stroke = Stroke::new(1.0, Color32::WHITE);
         ui.painter()
             .rect_stroke(centered_rect, 0.0, stroke, egui::StrokeKind::Outside);
 
-        // If the mouse is hovering over the screen area and the client has control,
-        // request focus and handle user input.
-        if response.hovered() && self.control_msg.lock().unwrap().as_str() == CONTROLLING_MSG {
+        let controlling = self.control_msg.lock().unwrap().as_str() == CONTROLLING_MSG;
+        let remote_cursor = *self.remote_cursor.lock().unwrap();
+
+        if let Some(cursor) = remote_cursor {
+            if controlling {
+                // The controller sees their own pointer right away, in the host's shape
+                if response.hovered() {
+                    ctx.set_cursor_icon(cursor_icon(cursor.shape));
+                }
+            } else {
+                let pos = self.image_rect.min
+                    + Vec2::new(
+                        cursor.x as f32 / 65535.0 * self.image_rect.width(),
+                        cursor.y as f32 / 65535.0 * self.image_rect.height(),
+                    );
+                draw_cursor(ui.painter(), pos, cursor.shape);
+            }
+        }
+
+        // If the mouse is hovering over the screen area (or dragging from it) and the
+        // client has control, request focus and handle user input.
+        if controlling && (response.hovered() || !self.pressed_buttons.is_empty()) {
             // Request focus so keyboard events are directed to this widget
             ui.ctx().memory_mut(|mem| mem.request_focus(egui::Id::NULL));
             ui.input(|input| self.handle_input(input, channel));
+        } else if !controlling {
+            self.release_buttons(channel);
         }
     }
 
+    /// Stops talking and playing audio, when leaving the session.
+    fn stop_audio(&mut self) {
+        if let Some(mut capture) = self.microphone_capture.take() {
+            capture.stop();
+        }
+        self.audio_player.lock().unwrap().stop_all();
+    }
+
     /// Handles disconnecting from the current remote session.
     ///
     /// This method sends a `Packet::SessionExit` to the server to signal departure,
-    /// then gracefully shuts down all background threads and the `ffmpeg` process.
+    /// then gracefully shuts down all background threads and the decoder.
     /// Finally, it transitions the application back to the `MenuScene`.
     ///
     /// # Arguments
@@ -502,23 +837,20 @@ impl ParticipantScene {
     ///
     /// A `SceneChange::To` variant, signaling a transition to the `MenuScene`.
     fn disconnect(&mut self, channel: &mut SecureChannel) -> SceneChange {
+        // Stop sending files, partial files are kept so transfers can be resumed later
+        stop_transfers(&self.file_transfers);
+        self.stop_audio();
+
         // Send a signal to the server that this participant is leaving the session
         channel.send(Packet::SessionExit).unwrap();
 
         // Signal background threads to stop (though they might already be stopping via stop_flag)
         self.stop_flag.store(true, Ordering::Relaxed);
 
-        // Join the background threads to ensure they have finished their cleanup
+        // Join the background thread, which also shuts down the decoder
         if let Some(handle) = self.thread_receive_socket.take() {
             let _ = handle.join();
         }
-        if let Some(handle) = self.thread_read_decoded.take() {
-            let _ = handle.join();
-        }
-
-        // Kill the ffmpeg process to release its resources
-        let _ = self.ffmpeg_command.kill();
-        let _ = self.ffmpeg_command.wait(); // Wait for ffmpeg to actually terminate
 
         // Transition back to the MenuScene
         SceneChange::To(Box::new(MenuScene::new(self.username.clone(), channel, "")))
@@ -554,22 +886,28 @@ impl Scene for ParticipantScene {
         // Only update the displayed frame at approximately 30 FPS
         if self.elapsed_time > 1. / 30. {
             self.elapsed_time = 0.;
-            if let Some(image) = self.frame_queue.lock().unwrap().pop_front() {
-                self.current_frame = image; // Display the latest frame from the queue
+            if let Some(frame) = self.frame_queue.lock().unwrap().pop_front() {
+                // Display the latest frame from the queue, reusing the texture
+                match &mut self.screen_texture {
+                    Some(texture) => texture.set(frame, egui::TextureOptions::default()),
+                    None => {
+                        self.screen_texture =
+                            Some(ctx.load_texture("screen", frame, egui::TextureOptions::default()))
+                    }
+                }
             }
         }
 
+        self.send_feedback(channel);
+
         // --- Check for Session End Signal ---
         if self.stop_flag.load(Ordering::Relaxed) {
             // Ensure all related processes and threads are cleaned up if the stop flag is set
-            let _ = self.ffmpeg_command.kill();
-            let _ = self.ffmpeg_command.wait(); // Make sure ffmpeg process truly terminates
+            stop_transfers(&self.file_transfers);
+            self.stop_audio();
             if let Some(handle) = self.thread_receive_socket.take() {
                 let _ = handle.join();
             }
-            if let Some(handle) = self.thread_read_decoded.take() {
-                let _ = handle.join();
-            }
 
             // Transition back to the MenuScene with an informative message
             return SceneChange::To(Box::new(MenuScene::new(
@@ -579,7 +917,35 @@ impl Scene for ParticipantScene {
             )));
         }
 
-        // --- Right Side Panel (Users List / Chat) ---
+        // --- Dropped Files (offered to the host) ---
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        if !dropped_files.is_empty() {
+            let host = self
+                .usernames
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(_, user_type)| **user_type == UserType::Host)
+                .map(|(username, _)| username.clone());
+
+            if let Some(host) = host {
+                for path in dropped_files.into_iter().filter_map(|file| file.path) {
+                    if let Err(msg) = offer_file(&self.file_transfers, &path, &host, channel) {
+                        self.chat_log.lock().unwrap().push(format!("#r{}", msg));
+                    }
+                }
+
+                // Show the transfers so the user can follow the progress
+                self.right_panel_type = RightPanelType::Files;
+            }
+        }
+
+        // --- Top Panel (Recording Indicator) ---
+        egui::TopBottomPanel::top("recording_state").show(ctx, |ui| {
+            recording_indicator(ui, *self.recording_state.lock().unwrap());
+        });
+
+        // --- Right Side Panel (Users List / Chat / Files) ---
         egui::SidePanel::right("participants").show(ctx, |ui| {
             // Toggle between User List and Chat
             ui.horizontal(|ui| {
@@ -589,6 +955,7 @@ impl Scene for ParticipantScene {
                     "User List",
                 );
                 ui.selectable_value(&mut self.right_panel_type, RightPanelType::Chat, "Chat");
+                ui.selectable_value(&mut self.right_panel_type, RightPanelType::Files, "Files");
             });
 
             match self.right_panel_type {
@@ -612,6 +979,14 @@ impl Scene for ParticipantScene {
                         channel,
                     );
                 }
+                RightPanelType::Files => {
+                    ui.heading("File Transfers");
+                    ui.separator();
+                    // Incoming offers, progress and cancellation of transfers with the host
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        transfers_ui(ui, &self.file_transfers, channel);
+                    });
+                }
             }
         });
 
@@ -644,6 +1019,64 @@ impl Scene for ParticipantScene {
                         };
                         channel.send(request_control).unwrap();
                     }
+
+                    // Clipboard push button, only available to the controller when the host allows it
+                    let can_push_clipboard = control_msg_guard.as_str() == CONTROLLING_MSG
+                        && self.clipboard_sync.load(Ordering::Relaxed);
+                    drop(control_msg_guard);
+
+                    if ui
+                        .add_enabled(can_push_clipboard, egui::Button::new("Push Clipboard"))
+                        .on_disabled_hover_text("The host has to allow clipboard sync, and you have to be the controller.")
+                        .clicked()
+                    {
+                        match read_clipboard() {
+                            Some(clipboard_packet) => channel.send(clipboard_packet).unwrap(),
+                            None => self.chat_log.lock().unwrap().push(
+                                "#rYour clipboard is empty or too large to push.".to_string(),
+                            ),
+                        }
+                    }
+
+                    // Bookmark the current moment of the session's recording
+                    bookmark_ui(ui, &mut self.bookmark_name, channel);
+
+                    // Push-to-talk, the microphone is captured only while the button is held
+                    ui.horizontal(|ui| {
+                        if device_picker(ui, "Microphone", &self.audio_devices, &mut self.microphone) {
+                            if let Some(mut capture) = self.microphone_capture.take() {
+                                capture.stop();
+                            }
+                        }
+
+                        let talk_button = ui.add_enabled(
+                            self.microphone.is_some(),
+                            egui::Button::new("Hold to Talk").sense(Sense::click_and_drag()),
+                        );
+                        let talking = talk_button.is_pointer_button_down_on();
+
+                        match (&self.microphone, talking, self.microphone_capture.is_some()) {
+                            (Some(microphone), true, false) => {
+                                self.microphone_capture = Some(AudioCapture::start(
+                                    channel,
+                                    microphone,
+                                    AudioKind::Microphone,
+                                ));
+                            }
+                            (_, false, true) => {
+                                if let Some(mut capture) = self.microphone_capture.take() {
+                                    capture.stop();
+                                }
+                            }
+                            _ => (),
+                        }
+
+                        let mut audio_player = self.audio_player.lock().unwrap();
+                        let mut muted = audio_player.muted();
+                        if ui.checkbox(&mut muted, "Mute").changed() {
+                            audio_player.set_muted(muted);
+                        }
+                    });
                 });
             });
 
@@ -664,7 +1097,7 @@ impl Scene for ParticipantScene {
     ///
     /// * `channel` - A mutable reference to the `SecureChannel` to be closed.
     fn on_exit(&mut self, channel: &mut SecureChannel) {
-        // Perform disconnection logic, including thread cleanup and ffmpeg termination
+        // Perform disconnection logic, including thread and decoder cleanup
         self.disconnect(channel);
 
         // Send final sign-out and shutdown packets to the server
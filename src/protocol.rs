@@ -2,6 +2,70 @@ use crate::UserType;
 use eframe::egui::PointerButton;
 use std::collections::VecDeque;
 
+/// The number of scroll units in one notch of a mouse wheel.
+///
+/// Scroll deltas are sent in these units, so smooth (trackpad) scrolling can be
+/// represented with more precision than whole notches.
+pub const SCROLL_UNITS_PER_NOTCH: i32 = 120;
+
+/// The maximum length in bytes of clipboard text that can be synchronized.
+pub const MAX_CLIPBOARD_TEXT_LENGTH: usize = 1024 * 1024;
+
+/// The maximum size in bytes of clipboard image data (RGBA) that can be synchronized.
+pub const MAX_CLIPBOARD_IMAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// The maximum length in characters of a recording's title.
+pub const MAX_RECORDING_TITLE_LENGTH: usize = 100;
+
+/// The maximum length in characters of a recording's description.
+pub const MAX_RECORDING_DESCRIPTION_LENGTH: usize = 1000;
+
+/// The maximum length in characters of a bookmark's name.
+pub const MAX_BOOKMARK_NAME_LENGTH: usize = 100;
+
+/// The header flag of an Ogg page that starts a new stream.
+const OGG_BEGINNING_OF_STREAM: u8 = 0x02;
+
+/// Checks that a clipboard packet does not exceed the clipboard size limits.
+///
+/// # Arguments
+///
+/// * `packet` - The packet to check.
+///
+/// # Returns
+///
+/// `true` if the packet is a `ClipboardText` or `ClipboardImage` within the limits,
+/// `false` otherwise.
+pub fn is_clipboard_within_limits(packet: &Packet) -> bool {
+    match packet {
+        Packet::ClipboardText { text } => text.len() <= MAX_CLIPBOARD_TEXT_LENGTH,
+        Packet::ClipboardImage { bytes, .. } => bytes.len() <= MAX_CLIPBOARD_IMAGE_SIZE,
+        _ => false,
+    }
+}
+
+/// Checks if an Ogg page, as sent in `Packet::Audio`, starts a new audio stream.
+///
+/// # Arguments
+///
+/// * `page` - The Ogg page.
+pub fn is_audio_stream_start(page: &[u8]) -> bool {
+    page.get(5)
+        .is_some_and(|flags| flags & OGG_BEGINNING_OF_STREAM != 0)
+}
+
+/// Checks if an Ogg page holds the Opus stream headers, which a decoder needs before any audio.
+///
+/// Header pages are the only pages without a granule position (sample count).
+///
+/// # Arguments
+///
+/// * `page` - The Ogg page.
+pub fn is_audio_header(page: &[u8]) -> bool {
+    page.get(6..14)
+        .is_some_and(|granule| granule.iter().all(|byte| *byte == 0))
+}
+
 /// Defines a trait for messages that can be converted to and from bytes for network transmission.
 pub trait ProtocolMessage {
     /// Turns a `ProtocolMessage` into bytes that can be sent over a socket.
@@ -34,6 +98,25 @@ pub fn get_u32_from_packet(bytes: &mut VecDeque<u8>) -> Option<u32> {
     Some(u32::from_be_bytes(data.try_into().ok()?))
 }
 
+/// Extracts a `u64` (unsigned 64-bit integer) from the beginning of a `VecDeque<u8>`.
+///
+/// This function assumes the `u64` is stored in big-endian format. It removes the
+/// 8 bytes corresponding to the `u64` from the `VecDeque`.
+///
+/// # Arguments
+///
+/// * `bytes` - A mutable reference to a `VecDeque<u8>` containing the byte stream.
+///
+/// # Returns
+///
+/// An `Option<u64>` which is:
+/// - `Some(value)` if 8 bytes were successfully read and converted to a `u64`.
+/// - `None` if there were not enough bytes in the `VecDeque` to form a `u64`.
+pub fn get_u64_from_packet(bytes: &mut VecDeque<u8>) -> Option<u64> {
+    let data: Vec<u8> = bytes.drain(0..8).collect();
+    Some(u64::from_be_bytes(data.try_into().ok()?))
+}
+
 /// Extracts an `i32` (signed 32-bit integer) from the beginning of a `VecDeque<u8>`.
 ///
 /// This function assumes the `i32` is stored in big-endian format. It removes the
@@ -186,8 +269,8 @@ pub enum Packet {
     /// Packet for new user registration.
     Register { username: String, password: String },
 
-    /// Packet indicating a user wants to host a session.
-    Host,
+    /// Packet indicating a user wants to host a session, and whether to record it.
+    Host { record: bool },
 
     /// Packet for a user attempting to join an existing session.
     Join { code: u32, username: String },
@@ -202,8 +285,9 @@ pub enum Packet {
     /// Packet containing control input (mouse, keyboard, scroll).
     Control { payload: ControlPayload },
 
-    /// Packet containing screen image data.
-    Screen { bytes: Vec<u8> },
+    /// Packet containing screen image data of one simulcast layer.
+    /// Layer 0 is the full quality stream, higher layers are lower quality.
+    Screen { layer: u8, bytes: Vec<u8> },
 
     /// Packet to signal a user is exiting the current session.
     SessionExit,
@@ -238,8 +322,223 @@ pub enum Packet {
     /// Packet to initialize seeking in a recording.
     SeekInit,
 
-    /// Packet to seek to a specific time in a recording.
-    SeekTo { time_seconds: i32 },
+    /// Packet to seek to a specific time (in milliseconds) in a recording. In a watch
+    /// party, the server sends it to the viewers to follow the leader's seeks.
+    SeekTo { time_ms: u64 },
+
+    /// Packet for the host enabling or disabling clipboard synchronization.
+    ClipboardSync { enabled: bool },
+
+    /// Packet containing clipboard text, sent between the host and the controller.
+    ClipboardText { text: String },
+
+    /// Packet containing a clipboard image as raw RGBA pixels, sent between the host and the controller.
+    ClipboardImage {
+        width: u32,
+        height: u32,
+        bytes: Vec<u8>,
+    },
+
+    /// Packet offering to send a file. `username` is the other side of the transfer.
+    FileOffer {
+        id: u32,
+        username: String,
+        filename: String,
+        size: u64,
+    },
+
+    /// Packet accepting a file offer, starting from `offset` to resume partial transfers.
+    FileAccept {
+        id: u32,
+        username: String,
+        offset: u64,
+    },
+
+    /// Packet containing a chunk of a file, starting at `offset`.
+    FileChunk {
+        id: u32,
+        username: String,
+        offset: u64,
+        bytes: Vec<u8>,
+    },
+
+    /// Packet signaling the end of a file, with the MD5 checksum of the whole file.
+    FileEnd {
+        id: u32,
+        username: String,
+        checksum: String,
+    },
+
+    /// Packet cancelling a file transfer, sent by either side or by the server.
+    FileCancel { id: u32, username: String },
+
+    /// Packet with the host's mouse cursor, with a position normalized like `ControlPayload::MouseMove`.
+    CursorState {
+        x: u32,
+        y: u32,
+        shape: CursorShape,
+        visible: bool,
+    },
+
+    /// Packet announcing the size in pixels of the area the host shares and the number
+    /// of simulcast layers it encodes, sent when either changes.
+    ScreenSource { width: u32, height: u32, layers: u8 },
+
+    /// Packet with a participant's periodic report on how well it receives the stream.
+    /// `username` is filled in by the server before it's forwarded to the host.
+    StreamFeedback {
+        username: String,
+        received_kbps: u32,
+        queue_depth: u32,
+        dropped_frames: u32,
+    },
+
+    /// Packet with a single page of an Ogg Opus audio stream. `username` is the speaker,
+    /// filled in by the server before it's forwarded.
+    Audio { username: String, bytes: Vec<u8> },
+
+    /// Packet signaling the end of a speaker's audio stream, filled in by the server like `Audio`.
+    AudioEnd { username: String },
+
+    /// Packet with the presentation time (in milliseconds) of the frame whose NAL units
+    /// follow, sent when watching a recording.
+    FrameTime { pts_ms: u64 },
+
+    /// Packet to set the playback speed of a recording, in percent of real time, which
+    /// the server paces the frames by. In a watch party, the server forwards the leader's
+    /// speed to the other viewers.
+    PlaybackSpeed { speed_percent: u32 },
+
+    /// Packet with the poster thumbnail (a JPEG image) of a recording, sent after its
+    /// `RecordingName` if the recording has one.
+    RecordingPoster { id: i32, bytes: Vec<u8> },
+
+    /// Packet to request the thumbnails of the recording being watched.
+    GetThumbnails,
+
+    /// Packet with the thumbnails of the recording being watched: a JPEG sprite sheet of
+    /// `columns` × `rows` thumbnails, taken every `interval_ms` milliseconds.
+    /// `bytes` is empty if the recording has no thumbnails.
+    Thumbnails {
+        interval_ms: u32,
+        columns: u32,
+        rows: u32,
+        bytes: Vec<u8>,
+    },
+
+    /// Packet with the title and description of a recording, sent after its `RecordingName`.
+    RecordingDetails {
+        id: i32,
+        title: String,
+        description: String,
+    },
+
+    /// Packet to change the title of a recording.
+    RenameRecording { id: i32, title: String },
+
+    /// Packet to change the description of a recording.
+    DescribeRecording { id: i32, description: String },
+
+    /// Packet to delete a recording, along with its video.
+    DeleteRecording { id: i32 },
+
+    /// Packet with the username of the owner of a recording shared with the user,
+    /// sent after its `RecordingName`.
+    RecordingOwner { id: i32, owner: String },
+
+    /// Packet to share a recording with another user, by username.
+    ShareRecording { id: i32, username: String },
+
+    /// Packet requesting the next chunk of a recording's video file, starting at `offset`.
+    DownloadRecording { id: i32, offset: u64 },
+
+    /// Packet containing a chunk of a recording's video file, starting at `offset`,
+    /// out of a file of `size` bytes.
+    RecordingChunk {
+        id: i32,
+        offset: u64,
+        size: u64,
+        bytes: Vec<u8>,
+    },
+
+    /// Packet signaling the end of a recording's video file, with the MD5 checksum of the whole file.
+    RecordingEnd { id: i32, checksum: String },
+
+    /// Packet refusing a chunk of a recording, with the reason.
+    DownloadFailed { id: i32, message: String },
+
+    /// Packet asking the server to cut a clip out of the watched recording, from `start_ms`
+    /// to `end_ms`. The clip is cut on keyframes without reencoding, unless `accurate`
+    /// asks for the exact frames.
+    CreateClip {
+        start_ms: u64,
+        end_ms: u64,
+        accurate: bool,
+    },
+
+    /// Packet announcing that a clip was saved as a new recording, with its title.
+    ClipCreated { title: String },
+
+    /// Packet announcing that a clip couldn't be created, with the reason.
+    ClipFailed { message: String },
+
+    /// Packet requesting the events of the watched recording.
+    GetEvents,
+
+    /// Packet containing an event that happened during a recorded session, `time_ms`
+    /// into the recording.
+    SessionEvent {
+        time_ms: u64,
+        kind: SessionEventKind,
+        text: String,
+    },
+
+    /// Packet asking the server to bookmark the current moment of the session's recording.
+    /// An empty name lets the server name the bookmark by its time.
+    AddBookmark { name: String },
+
+    /// Packet containing a bookmark `time_ms` into a recording, added by `username`.
+    /// Sent to everyone in a session when it's added, and to viewers of the recording.
+    Bookmark {
+        time_ms: u64,
+        name: String,
+        username: String,
+    },
+
+    /// Packet requesting the bookmarks of the watched recording.
+    GetBookmarks,
+
+    /// Packet to choose the quality a recording is watched in: the height it's scaled
+    /// down to and its bitrate in kilobits per second, or 0 for both to watch it as it
+    /// was recorded. Takes effect when the recording is next sought.
+    PlaybackQuality { height: u32, bitrate: u32 },
+
+    /// Packet to start a watch party for a recording, which others join with the session
+    /// code the server answers with, like a hosted session.
+    WatchParty { id: i32 },
+
+    /// Packet to pause or resume a watch party, sent by its leader and forwarded by the
+    /// server to the other viewers.
+    PlaybackPause { paused: bool },
+
+    /// Packet with how many days a recording is kept before it's deleted, 0 if it's kept
+    /// forever. Sent after its `RecordingDetails`, and by the owner to change it.
+    RecordingRetention { id: i32, days: u32 },
+
+    /// Packet with the storage the user's recordings take and the storage they may take,
+    /// in bytes. Sent after the recordings, and in answer to `GetStorageUsage`.
+    StorageUsage { used_bytes: u64, quota_bytes: u64 },
+
+    /// Packet requesting the storage the user's recordings take.
+    GetStorageUsage,
+
+    /// Packet with whether the session is being recorded. Sent by the server to everyone
+    /// in the session when it changes, and by the host to pause or resume the recording.
+    RecordingState { state: RecordingState },
+
+    /// Packet announcing that the recording can't be watched in the chosen quality, with
+    /// the reason. It's sent as recorded instead.
+    PlaybackQualityFailed { message: String },
 }
 
 impl ProtocolMessage for Packet {
@@ -270,8 +569,10 @@ impl ProtocolMessage for Packet {
                 write_length_and_string(&mut result, &password);
             }
 
-            Packet::Host => {
+            Packet::Host { record } => {
                 result.push(3);
+
+                result.push(*record as u8);
             }
 
             Packet::Join { code, username } => {
@@ -299,17 +600,18 @@ impl ProtocolMessage for Packet {
                 result.extend_from_slice(&payload.to_bytes());
             }
 
-            Packet::Screen { bytes } => {
+            Packet::Screen { layer, bytes } => {
                 result.push(7);
 
+                result.push(*layer);
                 result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
                 result.extend_from_slice(bytes);
             }
 
-            Packet::SeekTo { time_seconds } => {
+            Packet::SeekTo { time_ms } => {
                 result.push(8);
 
-                result.extend_from_slice(&time_seconds.to_be_bytes());
+                result.extend_from_slice(&time_ms.to_be_bytes());
             }
 
             Packet::SessionExit => {
@@ -368,179 +670,1120 @@ impl ProtocolMessage for Packet {
             Packet::SeekInit => {
                 result.push(19);
             }
-        }
-
-        result
-    }
 
-    /// Attempts to create a `Packet` from a byte vector.
-    ///
-    /// The function reads the first byte to determine the packet type and then
-    /// parses the subsequent bytes according to the expected structure of that
-    /// packet type.
-    ///
-    /// # Arguments
-    ///
-    /// * `bytes` - A `Vec<u8>` containing the raw byte data of the packet.
-    ///
-    /// # Returns
-    ///
-    /// An `Option<Packet>` which is:
-    /// - `Some(Packet)` if the bytes represent a valid `Packet`.
-    /// - `None` if the bytes are malformed, incomplete, or the packet type is unknown.
-    ///
-    /// # Panics
-    ///
-    /// Panics if any string data within the packet bytes is not valid UTF-8.
-    fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
-        let mut bytes = VecDeque::from(bytes);
-        let packet_type = bytes.pop_front()?;
+            Packet::ClipboardSync { enabled } => {
+                result.push(20);
 
-        match packet_type {
-            0 => Some(Self::None),
+                result.push(*enabled as u8);
+            }
 
-            // Login/Register
-            1 | 2 => {
-                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
-                    .expect("bytes should be valid utf8.");
-                let password = String::from_utf8(read_length_and_data(&mut bytes)?)
-                    .expect("bytes should be valid utf8.");
+            Packet::ClipboardText { text } => {
+                result.push(21);
 
-                if packet_type == 1 {
-                    Some(Self::Login { username, password })
-                } else {
-                    Some(Self::Register { username, password })
-                }
+                write_length_and_string(&mut result, &text);
             }
 
-            // Host
-            3 => Some(Self::Host),
+            Packet::ClipboardImage {
+                width,
+                height,
+                bytes,
+            } => {
+                result.push(22);
 
-            // Join
-            4 => {
-                let code = get_u32_from_packet(&mut bytes)?;
+                result.extend_from_slice(&width.to_be_bytes());
+                result.extend_from_slice(&height.to_be_bytes());
+                result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                result.extend_from_slice(bytes);
+            }
 
-                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
-                    .expect("bytes should be valid utf8");
+            Packet::FileOffer {
+                id,
+                username,
+                filename,
+                size,
+            } => {
+                result.push(23);
 
-                Some(Self::Join { code, username })
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &username);
+                write_length_and_string(&mut result, &filename);
+                result.extend_from_slice(&size.to_be_bytes());
             }
 
-            // UserUpdate
-            5 => {
-                let user_type_raw = bytes.pop_front()?;
-                let user_type = match user_type_raw {
-                    0 => UserType::Leaving,
-                    1 => UserType::Host,
-                    2 => UserType::Controller,
-                    3 => UserType::Participant,
-                    _ => return None,
-                };
+            Packet::FileAccept {
+                id,
+                username,
+                offset,
+            } => {
+                result.push(24);
 
-                let joined_before = bytes.pop_front()? != 0;
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &username);
+                result.extend_from_slice(&offset.to_be_bytes());
+            }
 
-                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
-                    .expect("bytes should be valid utf8.");
+            Packet::FileChunk {
+                id,
+                username,
+                offset,
+                bytes,
+            } => {
+                result.push(25);
 
-                Some(Self::UserUpdate {
-                    user_type,
-                    joined_before,
-                    username,
-                })
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &username);
+                result.extend_from_slice(&offset.to_be_bytes());
+                result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                result.extend_from_slice(bytes);
             }
 
-            // Control
-            6 => {
-                let payload_type = bytes.pop_front()?;
+            Packet::FileEnd {
+                id,
+                username,
+                checksum,
+            } => {
+                result.push(26);
 
-                let payload_length = ControlPayload::get_length(payload_type);
-                let mut payload_raw: Vec<u8> = bytes.drain(..payload_length).collect();
-                payload_raw.insert(0, payload_type);
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &username);
+                write_length_and_string(&mut result, &checksum);
+            }
 
-                let payload = ControlPayload::from_bytes(payload_raw)?;
+            Packet::FileCancel { id, username } => {
+                result.push(27);
 
-                Some(Self::Control { payload })
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &username);
             }
 
-            // Screen
-            7 => {
-                let bytes = read_length_and_data(&mut bytes)?;
+            Packet::CursorState {
+                x,
+                y,
+                shape,
+                visible,
+            } => {
+                result.push(28);
 
-                Some(Self::Screen { bytes })
+                result.extend_from_slice(&x.to_be_bytes());
+                result.extend_from_slice(&y.to_be_bytes());
+                result.push(*shape as u8);
+                result.push(*visible as u8);
             }
 
-            // SeekTo
-            8 => {
-                let time_seconds = get_i32_from_packet(&mut bytes)?;
+            Packet::ScreenSource {
+                width,
+                height,
+                layers,
+            } => {
+                result.push(29);
 
-                Some(Self::SeekTo { time_seconds })
+                result.extend_from_slice(&width.to_be_bytes());
+                result.extend_from_slice(&height.to_be_bytes());
+                result.push(*layers);
             }
 
-            // SessionExit
-            9 => Some(Self::SessionExit),
+            Packet::StreamFeedback {
+                username,
+                received_kbps,
+                queue_depth,
+                dropped_frames,
+            } => {
+                result.push(30);
 
-            // RequestControl
-            10 => {
-                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
-                    .expect("bytes should be valid utf8.");
-                Some(Self::RequestControl { username })
+                write_length_and_string(&mut result, &username);
+                result.extend_from_slice(&received_kbps.to_be_bytes());
+                result.extend_from_slice(&queue_depth.to_be_bytes());
+                result.extend_from_slice(&dropped_frames.to_be_bytes());
             }
 
-            // DenyControl
-            11 => {
-                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
-                    .expect("bytes should be valid utf8.");
-                Some(Self::DenyControl { username })
+            Packet::Audio { username, bytes } => {
+                result.push(31);
+
+                write_length_and_string(&mut result, &username);
+                result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                result.extend_from_slice(bytes);
             }
 
-            // SignOut
-            12 => Some(Self::SignOut),
+            Packet::AudioEnd { username } => {
+                result.push(32);
 
-            // Shutdown
-            13 => Some(Self::Shutdown),
+                write_length_and_string(&mut result, &username);
+            }
 
-            // SessionEnd
-            14 => Some(Self::SessionEnd),
+            Packet::FrameTime { pts_ms } => {
+                result.push(33);
 
-            // Chat
-            15 => {
-                let message = String::from_utf8(read_length_and_data(&mut bytes)?)
-                    .expect("bytes should be valid utf8");
-                Some(Self::Chat { message })
+                result.extend_from_slice(&pts_ms.to_be_bytes());
             }
 
-            // WatchRecording
-            16 => {
-                let id = get_i32_from_packet(&mut bytes)?;
+            Packet::PlaybackSpeed { speed_percent } => {
+                result.push(34);
 
-                Some(Self::WatchRecording { id })
+                result.extend_from_slice(&speed_percent.to_be_bytes());
             }
 
-            // RecordingName
-            17 => {
-                let id = get_i32_from_packet(&mut bytes)?;
-                let name = String::from_utf8(read_length_and_data(&mut bytes)?)
-                    .expect("bytes should be valid utf8");
+            Packet::RecordingPoster { id, bytes } => {
+                result.push(35);
 
-                Some(Self::RecordingName { id, name })
+                result.extend_from_slice(&id.to_be_bytes());
+                result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                result.extend_from_slice(bytes);
             }
 
-            // DenyJoin
-            18 => {
-                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
-                    .expect("bytes should be valid utf8");
-
-                Some(Self::DenyJoin { username })
+            Packet::GetThumbnails => {
+                result.push(36);
             }
 
-            // SeekInit
-            19 => Some(Self::SeekInit),
+            Packet::Thumbnails {
+                interval_ms,
+                columns,
+                rows,
+                bytes,
+            } => {
+                result.push(37);
+
+                result.extend_from_slice(&interval_ms.to_be_bytes());
+                result.extend_from_slice(&columns.to_be_bytes());
+                result.extend_from_slice(&rows.to_be_bytes());
+                result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                result.extend_from_slice(bytes);
+            }
+
+            Packet::RecordingDetails {
+                id,
+                title,
+                description,
+            } => {
+                result.push(38);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &title);
+                write_length_and_string(&mut result, &description);
+            }
+
+            Packet::RenameRecording { id, title } => {
+                result.push(39);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &title);
+            }
+
+            Packet::DescribeRecording { id, description } => {
+                result.push(40);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &description);
+            }
+
+            Packet::DeleteRecording { id } => {
+                result.push(41);
+
+                result.extend_from_slice(&id.to_be_bytes());
+            }
+
+            Packet::RecordingOwner { id, owner } => {
+                result.push(42);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &owner);
+            }
+
+            Packet::ShareRecording { id, username } => {
+                result.push(43);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &username);
+            }
+
+            Packet::DownloadRecording { id, offset } => {
+                result.push(44);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                result.extend_from_slice(&offset.to_be_bytes());
+            }
+
+            Packet::RecordingChunk {
+                id,
+                offset,
+                size,
+                bytes,
+            } => {
+                result.push(45);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                result.extend_from_slice(&offset.to_be_bytes());
+                result.extend_from_slice(&size.to_be_bytes());
+                result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                result.extend_from_slice(bytes);
+            }
+
+            Packet::RecordingEnd { id, checksum } => {
+                result.push(46);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &checksum);
+            }
+
+            Packet::DownloadFailed { id, message } => {
+                result.push(47);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                write_length_and_string(&mut result, &message);
+            }
+
+            Packet::CreateClip {
+                start_ms,
+                end_ms,
+                accurate,
+            } => {
+                result.push(48);
+
+                result.extend_from_slice(&start_ms.to_be_bytes());
+                result.extend_from_slice(&end_ms.to_be_bytes());
+                result.push(*accurate as u8);
+            }
+
+            Packet::ClipCreated { title } => {
+                result.push(49);
+
+                write_length_and_string(&mut result, &title);
+            }
+
+            Packet::ClipFailed { message } => {
+                result.push(50);
+
+                write_length_and_string(&mut result, &message);
+            }
+
+            Packet::GetEvents => {
+                result.push(51);
+            }
+
+            Packet::SessionEvent {
+                time_ms,
+                kind,
+                text,
+            } => {
+                result.push(52);
+
+                result.extend_from_slice(&time_ms.to_be_bytes());
+                result.push(*kind as u8);
+                write_length_and_string(&mut result, &text);
+            }
+
+            Packet::AddBookmark { name } => {
+                result.push(53);
+
+                write_length_and_string(&mut result, &name);
+            }
+
+            Packet::Bookmark {
+                time_ms,
+                name,
+                username,
+            } => {
+                result.push(54);
+
+                result.extend_from_slice(&time_ms.to_be_bytes());
+                write_length_and_string(&mut result, &name);
+                write_length_and_string(&mut result, &username);
+            }
+
+            Packet::GetBookmarks => {
+                result.push(55);
+            }
+
+            Packet::PlaybackQuality { height, bitrate } => {
+                result.push(56);
+
+                result.extend_from_slice(&height.to_be_bytes());
+                result.extend_from_slice(&bitrate.to_be_bytes());
+            }
+
+            Packet::WatchParty { id } => {
+                result.push(57);
+
+                result.extend_from_slice(&id.to_be_bytes());
+            }
+
+            Packet::PlaybackPause { paused } => {
+                result.push(58);
+
+                result.push(*paused as u8);
+            }
+
+            Packet::RecordingRetention { id, days } => {
+                result.push(59);
+
+                result.extend_from_slice(&id.to_be_bytes());
+                result.extend_from_slice(&days.to_be_bytes());
+            }
+
+            Packet::StorageUsage {
+                used_bytes,
+                quota_bytes,
+            } => {
+                result.push(60);
+
+                result.extend_from_slice(&used_bytes.to_be_bytes());
+                result.extend_from_slice(&quota_bytes.to_be_bytes());
+            }
+
+            Packet::GetStorageUsage => {
+                result.push(61);
+            }
+
+            Packet::RecordingState { state } => {
+                result.push(62);
+
+                result.push(*state as u8);
+            }
+
+            Packet::PlaybackQualityFailed { message } => {
+                result.push(63);
+
+                write_length_and_string(&mut result, &message);
+            }
+        }
+
+        result
+    }
+
+    /// Attempts to create a `Packet` from a byte vector.
+    ///
+    /// The function reads the first byte to determine the packet type and then
+    /// parses the subsequent bytes according to the expected structure of that
+    /// packet type.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - A `Vec<u8>` containing the raw byte data of the packet.
+    ///
+    /// # Returns
+    ///
+    /// An `Option<Packet>` which is:
+    /// - `Some(Packet)` if the bytes represent a valid `Packet`.
+    /// - `None` if the bytes are malformed, incomplete, or the packet type is unknown.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any string data within the packet bytes is not valid UTF-8.
+    fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        let mut bytes = VecDeque::from(bytes);
+        let packet_type = bytes.pop_front()?;
+
+        match packet_type {
+            0 => Some(Self::None),
+
+            // Login/Register
+            1 | 2 => {
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8.");
+                let password = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8.");
+
+                if packet_type == 1 {
+                    Some(Self::Login { username, password })
+                } else {
+                    Some(Self::Register { username, password })
+                }
+            }
+
+            // Host
+            3 => {
+                let record = bytes.pop_front()? != 0;
+
+                Some(Self::Host { record })
+            }
+
+            // Join
+            4 => {
+                let code = get_u32_from_packet(&mut bytes)?;
+
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::Join { code, username })
+            }
+
+            // UserUpdate
+            5 => {
+                let user_type_raw = bytes.pop_front()?;
+                let user_type = match user_type_raw {
+                    0 => UserType::Leaving,
+                    1 => UserType::Host,
+                    2 => UserType::Controller,
+                    3 => UserType::Participant,
+                    _ => return None,
+                };
+
+                let joined_before = bytes.pop_front()? != 0;
+
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8.");
+
+                Some(Self::UserUpdate {
+                    user_type,
+                    joined_before,
+                    username,
+                })
+            }
+
+            // Control
+            6 => {
+                let payload_type = bytes.pop_front()?;
+
+                let payload_length = ControlPayload::get_length(payload_type);
+                let mut payload_raw: Vec<u8> = bytes.drain(..payload_length).collect();
+                payload_raw.insert(0, payload_type);
+
+                let payload = ControlPayload::from_bytes(payload_raw)?;
+
+                Some(Self::Control { payload })
+            }
+
+            // Screen
+            7 => {
+                let layer = bytes.pop_front()?;
+                let bytes = read_length_and_data(&mut bytes)?;
+
+                Some(Self::Screen { layer, bytes })
+            }
+
+            // SeekTo
+            8 => {
+                let time_ms = get_u64_from_packet(&mut bytes)?;
+
+                Some(Self::SeekTo { time_ms })
+            }
+
+            // SessionExit
+            9 => Some(Self::SessionExit),
+
+            // RequestControl
+            10 => {
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8.");
+                Some(Self::RequestControl { username })
+            }
+
+            // DenyControl
+            11 => {
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8.");
+                Some(Self::DenyControl { username })
+            }
+
+            // SignOut
+            12 => Some(Self::SignOut),
+
+            // Shutdown
+            13 => Some(Self::Shutdown),
+
+            // SessionEnd
+            14 => Some(Self::SessionEnd),
+
+            // Chat
+            15 => {
+                let message = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                Some(Self::Chat { message })
+            }
+
+            // WatchRecording
+            16 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+
+                Some(Self::WatchRecording { id })
+            }
+
+            // RecordingName
+            17 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let name = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::RecordingName { id, name })
+            }
+
+            // DenyJoin
+            18 => {
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::DenyJoin { username })
+            }
+
+            // SeekInit
+            19 => Some(Self::SeekInit),
+
+            // ClipboardSync
+            20 => {
+                let enabled = bytes.pop_front()? != 0;
+
+                Some(Self::ClipboardSync { enabled })
+            }
+
+            // ClipboardText
+            21 => {
+                let text = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::ClipboardText { text })
+            }
+
+            // ClipboardImage
+            22 => {
+                let width = get_u32_from_packet(&mut bytes)?;
+                let height = get_u32_from_packet(&mut bytes)?;
+                let bytes = read_length_and_data(&mut bytes)?;
+
+                Some(Self::ClipboardImage {
+                    width,
+                    height,
+                    bytes,
+                })
+            }
+
+            // FileOffer
+            23 => {
+                let id = get_u32_from_packet(&mut bytes)?;
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                let filename = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                let size = get_u64_from_packet(&mut bytes)?;
+
+                Some(Self::FileOffer {
+                    id,
+                    username,
+                    filename,
+                    size,
+                })
+            }
+
+            // FileAccept
+            24 => {
+                let id = get_u32_from_packet(&mut bytes)?;
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                let offset = get_u64_from_packet(&mut bytes)?;
+
+                Some(Self::FileAccept {
+                    id,
+                    username,
+                    offset,
+                })
+            }
+
+            // FileChunk
+            25 => {
+                let id = get_u32_from_packet(&mut bytes)?;
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                let offset = get_u64_from_packet(&mut bytes)?;
+                let bytes = read_length_and_data(&mut bytes)?;
+
+                Some(Self::FileChunk {
+                    id,
+                    username,
+                    offset,
+                    bytes,
+                })
+            }
+
+            // FileEnd
+            26 => {
+                let id = get_u32_from_packet(&mut bytes)?;
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                let checksum = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::FileEnd {
+                    id,
+                    username,
+                    checksum,
+                })
+            }
+
+            // FileCancel
+            27 => {
+                let id = get_u32_from_packet(&mut bytes)?;
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::FileCancel { id, username })
+            }
+
+            // CursorState
+            28 => {
+                let x = get_u32_from_packet(&mut bytes)?;
+                let y = get_u32_from_packet(&mut bytes)?;
+                let shape = CursorShape::from_byte(bytes.pop_front()?)?;
+                let visible = bytes.pop_front()? != 0;
+
+                Some(Self::CursorState {
+                    x,
+                    y,
+                    shape,
+                    visible,
+                })
+            }
+
+            // ScreenSource
+            29 => {
+                let width = get_u32_from_packet(&mut bytes)?;
+                let height = get_u32_from_packet(&mut bytes)?;
+                let layers = bytes.pop_front()?;
+
+                Some(Self::ScreenSource {
+                    width,
+                    height,
+                    layers,
+                })
+            }
+
+            // StreamFeedback
+            30 => {
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                let received_kbps = get_u32_from_packet(&mut bytes)?;
+                let queue_depth = get_u32_from_packet(&mut bytes)?;
+                let dropped_frames = get_u32_from_packet(&mut bytes)?;
+
+                Some(Self::StreamFeedback {
+                    username,
+                    received_kbps,
+                    queue_depth,
+                    dropped_frames,
+                })
+            }
+
+            // Audio
+            31 => {
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                let bytes = read_length_and_data(&mut bytes)?;
+
+                Some(Self::Audio { username, bytes })
+            }
+
+            // AudioEnd
+            32 => {
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::AudioEnd { username })
+            }
+
+            // FrameTime
+            33 => {
+                let pts_ms = get_u64_from_packet(&mut bytes)?;
+
+                Some(Self::FrameTime { pts_ms })
+            }
+
+            // PlaybackSpeed
+            34 => {
+                let speed_percent = get_u32_from_packet(&mut bytes)?;
+
+                Some(Self::PlaybackSpeed { speed_percent })
+            }
+
+            // RecordingPoster
+            35 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let bytes = read_length_and_data(&mut bytes)?;
+
+                Some(Self::RecordingPoster { id, bytes })
+            }
+
+            // GetThumbnails
+            36 => Some(Self::GetThumbnails),
+
+            // Thumbnails
+            37 => {
+                let interval_ms = get_u32_from_packet(&mut bytes)?;
+                let columns = get_u32_from_packet(&mut bytes)?;
+                let rows = get_u32_from_packet(&mut bytes)?;
+                let bytes = read_length_and_data(&mut bytes)?;
+
+                Some(Self::Thumbnails {
+                    interval_ms,
+                    columns,
+                    rows,
+                    bytes,
+                })
+            }
+
+            // RecordingDetails
+            38 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let title = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                let description = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::RecordingDetails {
+                    id,
+                    title,
+                    description,
+                })
+            }
+
+            // RenameRecording
+            39 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let title = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::RenameRecording { id, title })
+            }
+
+            // DescribeRecording
+            40 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let description = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::DescribeRecording { id, description })
+            }
+
+            // DeleteRecording
+            41 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+
+                Some(Self::DeleteRecording { id })
+            }
+
+            // RecordingOwner
+            42 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let owner = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::RecordingOwner { id, owner })
+            }
+
+            // ShareRecording
+            43 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::ShareRecording { id, username })
+            }
+
+            // DownloadRecording
+            44 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let offset = get_u64_from_packet(&mut bytes)?;
+
+                Some(Self::DownloadRecording { id, offset })
+            }
+
+            // RecordingChunk
+            45 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let offset = get_u64_from_packet(&mut bytes)?;
+                let size = get_u64_from_packet(&mut bytes)?;
+                let bytes = read_length_and_data(&mut bytes)?;
+
+                Some(Self::RecordingChunk {
+                    id,
+                    offset,
+                    size,
+                    bytes,
+                })
+            }
+
+            // RecordingEnd
+            46 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let checksum = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::RecordingEnd { id, checksum })
+            }
+
+            // DownloadFailed
+            47 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let message = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::DownloadFailed { id, message })
+            }
+
+            // CreateClip
+            48 => {
+                let start_ms = get_u64_from_packet(&mut bytes)?;
+                let end_ms = get_u64_from_packet(&mut bytes)?;
+                let accurate = bytes.pop_front()? != 0;
+
+                Some(Self::CreateClip {
+                    start_ms,
+                    end_ms,
+                    accurate,
+                })
+            }
+
+            // ClipCreated
+            49 => {
+                let title = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::ClipCreated { title })
+            }
+
+            // ClipFailed
+            50 => {
+                let message = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::ClipFailed { message })
+            }
+
+            // GetEvents
+            51 => Some(Self::GetEvents),
+
+            // SessionEvent
+            52 => {
+                let time_ms = get_u64_from_packet(&mut bytes)?;
+                let kind = SessionEventKind::from_byte(bytes.pop_front()?)?;
+                let text = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::SessionEvent {
+                    time_ms,
+                    kind,
+                    text,
+                })
+            }
+
+            // AddBookmark
+            53 => {
+                let name = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::AddBookmark { name })
+            }
+
+            // Bookmark
+            54 => {
+                let time_ms = get_u64_from_packet(&mut bytes)?;
+                let name = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+                let username = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::Bookmark {
+                    time_ms,
+                    name,
+                    username,
+                })
+            }
+
+            // GetBookmarks
+            55 => Some(Self::GetBookmarks),
+
+            // PlaybackQuality
+            56 => {
+                let height = get_u32_from_packet(&mut bytes)?;
+                let bitrate = get_u32_from_packet(&mut bytes)?;
+
+                Some(Self::PlaybackQuality { height, bitrate })
+            }
+
+            // WatchParty
+            57 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+
+                Some(Self::WatchParty { id })
+            }
+
+            // PlaybackPause
+            58 => {
+                let paused = bytes.pop_front()? != 0;
+
+                Some(Self::PlaybackPause { paused })
+            }
+
+            // RecordingRetention
+            59 => {
+                let id = get_i32_from_packet(&mut bytes)?;
+                let days = get_u32_from_packet(&mut bytes)?;
+
+                Some(Self::RecordingRetention { id, days })
+            }
+
+            // StorageUsage
+            60 => {
+                let used_bytes = get_u64_from_packet(&mut bytes)?;
+                let quota_bytes = get_u64_from_packet(&mut bytes)?;
+
+                Some(Self::StorageUsage {
+                    used_bytes,
+                    quota_bytes,
+                })
+            }
+
+            // GetStorageUsage
+            61 => Some(Self::GetStorageUsage),
+
+            // RecordingState
+            62 => {
+                let state = RecordingState::from_byte(bytes.pop_front()?)?;
+
+                Some(Self::RecordingState { state })
+            }
+
+            // PlaybackQualityFailed
+            63 => {
+                let message = String::from_utf8(read_length_and_data(&mut bytes)?)
+                    .expect("bytes should be valid utf8");
+
+                Some(Self::PlaybackQualityFailed { message })
+            }
 
             _ => None,
         }
     }
 }
 
+/// The shape of the host's mouse cursor, sent in `Packet::CursorState`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum CursorShape {
+    #[default]
+    Arrow = 0,
+    Text = 1,
+    Hand = 2,
+    Wait = 3,
+    Progress = 4,
+    Crosshair = 5,
+    ResizeHorizontal = 6,
+    ResizeVertical = 7,
+    ResizeNwSe = 8,
+    ResizeNeSw = 9,
+    Move = 10,
+    NotAllowed = 11,
+    Help = 12,
+}
+
+impl CursorShape {
+    /// Converts a byte back into a `CursorShape`.
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - The byte identifier of the shape.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the byte is not a known shape.
+    fn from_byte(byte: u8) -> Option<Self> {
+        let shape = match byte {
+            0 => Self::Arrow,
+            1 => Self::Text,
+            2 => Self::Hand,
+            3 => Self::Wait,
+            4 => Self::Progress,
+            5 => Self::Crosshair,
+            6 => Self::ResizeHorizontal,
+            7 => Self::ResizeVertical,
+            8 => Self::ResizeNwSe,
+            9 => Self::ResizeNeSw,
+            10 => Self::Move,
+            11 => Self::NotAllowed,
+            12 => Self::Help,
+            _ => return None,
+        };
+
+        Some(shape)
+    }
+}
+
+/// The kind of an event of a recorded session, sent in `Packet::SessionEvent`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SessionEventKind {
+    /// A chat message, as "Username: Message".
+    Chat = 0,
+    /// A user joined the session.
+    Join = 1,
+    /// A user left the session.
+    Leave = 2,
+    /// The host gave a user control.
+    ControlGranted = 3,
+    /// The host took control back from a user.
+    ControlRevoked = 4,
+    /// The host denied a user's request for control.
+    ControlDenied = 5,
+    /// The host paused the recording, which skips what happens until it's resumed.
+    RecordingPaused = 6,
+    /// The host resumed the recording.
+    RecordingResumed = 7,
+}
+
+impl SessionEventKind {
+    /// Converts a byte back into a `SessionEventKind`.
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - The byte identifier of the kind.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the byte is not a known kind.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        let kind = match byte {
+            0 => Self::Chat,
+            1 => Self::Join,
+            2 => Self::Leave,
+            3 => Self::ControlGranted,
+            4 => Self::ControlRevoked,
+            5 => Self::ControlDenied,
+            6 => Self::RecordingPaused,
+            7 => Self::RecordingResumed,
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+}
+
+/// Whether a session is being recorded, sent in `Packet::RecordingState`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecordingState {
+    /// The session isn't recorded, as the host chose or has no storage left.
+    Off = 0,
+    /// The session is being recorded.
+    Recording = 1,
+    /// The host paused the recording.
+    Paused = 2,
+}
+
+impl RecordingState {
+    /// Converts a byte back into a `RecordingState`.
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - The byte identifier of the state.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the byte is not a known state.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        let state = match byte {
+            0 => Self::Off,
+            1 => Self::Recording,
+            2 => Self::Paused,
+            _ => return None,
+        };
+
+        Some(state)
+    }
+}
+
 /// Represents different types of control inputs that can be sent over the network.
 /// These payloads are typically encapsulated within a `Packet::Control` variant.
 #[derive(PartialEq, Clone)]
@@ -559,8 +1802,10 @@ pub enum ControlPayload {
     /// Represents a keyboard event (key press or release).
     Keyboard { pressed: bool, key: u16 },
 
-    /// Represents a scroll wheel event.
-    Scroll { delta: i32 },
+    /// Represents a scroll wheel event, in `SCROLL_UNITS_PER_NOTCH` units.
+    ///
+    /// A positive `delta_x` scrolls right, and a positive `delta_y` scrolls up.
+    Scroll { delta_x: i32, delta_y: i32 },
 }
 
 impl ControlPayload {
@@ -600,10 +1845,11 @@ impl ControlPayload {
                 result.extend_from_slice(&key.to_be_bytes());
             }
 
-            ControlPayload::Scroll { delta } => {
+            ControlPayload::Scroll { delta_x, delta_y } => {
                 result.push(3);
 
-                result.extend_from_slice(&delta.to_be_bytes());
+                result.extend_from_slice(&delta_x.to_be_bytes());
+                result.extend_from_slice(&delta_y.to_be_bytes());
             }
         }
 
@@ -647,6 +1893,8 @@ impl ControlPayload {
                     0 => PointerButton::Primary,
                     1 => PointerButton::Secondary,
                     2 => PointerButton::Middle,
+                    3 => PointerButton::Extra1,
+                    4 => PointerButton::Extra2,
                     _ => return None,
                 };
 
@@ -668,9 +1916,10 @@ impl ControlPayload {
 
             // Scroll
             3 => {
-                let delta = get_i32_from_packet(&mut bytes)?;
+                let delta_x = get_i32_from_packet(&mut bytes)?;
+                let delta_y = get_i32_from_packet(&mut bytes)?;
 
-                Some(Self::Scroll { delta })
+                Some(Self::Scroll { delta_x, delta_y })
             }
 
             _ => None,
@@ -693,8 +1942,333 @@ impl ControlPayload {
             0 => 8,  // MouseMove
             1 => 10, // MouseClick
             2 => 3,  // Keyboard
-            3 => 4,  // Scroll
+            3 => 8,  // Scroll
             _ => 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashMap, mem};
+
+    /// Creates a packet of every kind, with fields that don't have their default values.
+    fn every_packet() -> Vec<Packet> {
+        let username = "someone".to_owned();
+
+        vec![
+            Packet::None,
+            Packet::Login {
+                username: username.clone(),
+                password: "secret".to_owned(),
+            },
+            Packet::Register {
+                username: username.clone(),
+                password: "secret".to_owned(),
+            },
+            Packet::Host { record: false },
+            Packet::Join {
+                code: 123_456,
+                username: username.clone(),
+            },
+            Packet::UserUpdate {
+                user_type: UserType::Controller,
+                joined_before: true,
+                username: username.clone(),
+            },
+            Packet::Control {
+                payload: ControlPayload::MouseMove {
+                    mouse_x: 1,
+                    mouse_y: 65_535,
+                },
+            },
+            Packet::Control {
+                payload: ControlPayload::MouseClick {
+                    mouse_x: 10,
+                    mouse_y: 20,
+                    pressed: true,
+                    button: PointerButton::Secondary,
+                },
+            },
+            Packet::Control {
+                payload: ControlPayload::Keyboard {
+                    pressed: false,
+                    key: 0x1234,
+                },
+            },
+            Packet::Screen {
+                layer: 0,
+                bytes: vec![0, 0, 1, 0x65, 0xFF],
+            },
+            Packet::SessionExit,
+            Packet::RequestControl {
+                username: username.clone(),
+            },
+            Packet::DenyControl {
+                username: username.clone(),
+            },
+            Packet::SignOut,
+            Packet::Shutdown,
+            Packet::SessionEnd,
+            Packet::Chat {
+                message: "Hello, ünïcode!".to_owned(),
+            },
+            Packet::WatchRecording { id: -7 },
+            Packet::RecordingName {
+                id: 7,
+                name: "2025-01-01".to_owned(),
+            },
+            Packet::DenyJoin {
+                username: username.clone(),
+            },
+            Packet::SeekInit,
+            Packet::SeekTo {
+                time_ms: u64::MAX - 1,
+            },
+            Packet::ClipboardSync { enabled: true },
+            Packet::ClipboardText {
+                text: "copied".to_owned(),
+            },
+            Packet::ClipboardImage {
+                width: 2,
+                height: 1,
+                bytes: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            },
+            Packet::FileOffer {
+                id: 3,
+                username: username.clone(),
+                filename: "notes.txt".to_owned(),
+                size: 5_000_000_000,
+            },
+            Packet::FileAccept {
+                id: 3,
+                username: username.clone(),
+                offset: 4096,
+            },
+            Packet::FileChunk {
+                id: 3,
+                username: username.clone(),
+                offset: 4096,
+                bytes: vec![9; 100],
+            },
+            Packet::FileEnd {
+                id: 3,
+                username: username.clone(),
+                checksum: "d41d8cd98f00b204e9800998ecf8427e".to_owned(),
+            },
+            Packet::FileCancel {
+                id: 3,
+                username: username.clone(),
+            },
+            Packet::Control {
+                payload: ControlPayload::MouseClick {
+                    mouse_x: 10,
+                    mouse_y: 20,
+                    pressed: true,
+                    button: PointerButton::Extra2,
+                },
+            },
+            Packet::Control {
+                payload: ControlPayload::Scroll {
+                    delta_x: -SCROLL_UNITS_PER_NOTCH,
+                    delta_y: 30,
+                },
+            },
+            Packet::CursorState {
+                x: 100,
+                y: 200,
+                shape: CursorShape::ResizeNeSw,
+                visible: true,
+            },
+            Packet::ScreenSource {
+                width: 1920,
+                height: 1080,
+                layers: 1,
+            },
+            Packet::StreamFeedback {
+                username: username.clone(),
+                received_kbps: 2500,
+                queue_depth: 4,
+                dropped_frames: 1,
+            },
+            Packet::Screen {
+                layer: 2,
+                bytes: vec![0, 0, 1, 0x41, 0xFF],
+            },
+            Packet::ScreenSource {
+                width: 1920,
+                height: 1080,
+                layers: 3,
+            },
+            Packet::Audio {
+                username: username.clone(),
+                bytes: b"OggS\0\x02".to_vec(),
+            },
+            Packet::AudioEnd {
+                username: username.clone(),
+            },
+            Packet::FrameTime { pts_ms: 90_000 },
+            Packet::PlaybackSpeed { speed_percent: 25 },
+            Packet::RecordingPoster {
+                id: 7,
+                bytes: vec![0xFF, 0xD8, 0xFF],
+            },
+            Packet::GetThumbnails,
+            Packet::Thumbnails {
+                interval_ms: 10_000,
+                columns: 10,
+                rows: 4,
+                bytes: vec![0xFF, 0xD8],
+            },
+            Packet::RecordingDetails {
+                id: 7,
+                title: "Weekly meeting".to_owned(),
+                description: "Line one\nLine two".to_owned(),
+            },
+            Packet::RenameRecording {
+                id: 7,
+                title: "Renamed".to_owned(),
+            },
+            Packet::DescribeRecording {
+                id: 7,
+                description: String::new(),
+            },
+            Packet::DeleteRecording { id: 7 },
+            Packet::RecordingOwner {
+                id: 7,
+                owner: username.clone(),
+            },
+            Packet::ShareRecording {
+                id: 7,
+                username: username.clone(),
+            },
+            Packet::DownloadRecording {
+                id: 7,
+                offset: 1 << 40,
+            },
+            Packet::RecordingChunk {
+                id: 7,
+                offset: 1 << 40,
+                size: 1 << 41,
+                bytes: vec![1; 64],
+            },
+            Packet::RecordingEnd {
+                id: 7,
+                checksum: "d41d8cd98f00b204e9800998ecf8427e".to_owned(),
+            },
+            Packet::DownloadFailed {
+                id: 7,
+                message: "No access.".to_owned(),
+            },
+            Packet::CreateClip {
+                start_ms: 1000,
+                end_ms: 61_000,
+                accurate: true,
+            },
+            Packet::ClipCreated {
+                title: "Clip (00:01–01:01)".to_owned(),
+            },
+            Packet::ClipFailed {
+                message: "Too short.".to_owned(),
+            },
+            Packet::GetEvents,
+            Packet::SessionEvent {
+                time_ms: 1234,
+                kind: SessionEventKind::ControlDenied,
+                text: username.clone(),
+            },
+            Packet::AddBookmark {
+                name: "Demo".to_owned(),
+            },
+            Packet::Bookmark {
+                time_ms: 5000,
+                name: "Demo".to_owned(),
+                username: username.clone(),
+            },
+            Packet::GetBookmarks,
+            Packet::PlaybackQuality {
+                height: 720,
+                bitrate: 1500,
+            },
+            Packet::PlaybackQualityFailed {
+                message: "No FFmpeg.".to_owned(),
+            },
+            Packet::WatchParty { id: 7 },
+            Packet::PlaybackPause { paused: true },
+            Packet::RecordingRetention { id: 7, days: 30 },
+            Packet::StorageUsage {
+                used_bytes: 1 << 33,
+                quota_bytes: 1 << 34,
+            },
+            Packet::GetStorageUsage,
+            Packet::Host { record: true },
+            Packet::RecordingState {
+                state: RecordingState::Paused,
+            },
+            Packet::SessionEvent {
+                time_ms: 1234,
+                kind: SessionEventKind::RecordingResumed,
+                text: username.clone(),
+            },
+        ]
+    }
+
+    #[test]
+    fn every_packet_round_trips() {
+        for packet in every_packet() {
+            let bytes = packet.to_bytes();
+            assert!(
+                Packet::from_bytes(bytes.clone()) == Some(packet),
+                "packet {} didn't round trip",
+                bytes[0]
+            );
+        }
+    }
+
+    #[test]
+    fn every_packet_has_its_own_type() {
+        let mut kinds = HashMap::new();
+        for packet in every_packet() {
+            let kind = mem::discriminant(&packet);
+            assert_eq!(*kinds.entry(packet.to_bytes()[0]).or_insert(kind), kind);
+        }
+    }
+
+    #[test]
+    fn unknown_packet_type_is_rejected() {
+        assert!(Packet::from_bytes(vec![u8::MAX]).is_none());
+        assert!(Packet::from_bytes(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn result_packet_round_trips() {
+        let Some(ResultPacket::Failure(message)) =
+            ResultPacket::from_bytes(ResultPacket::Failure("Nope.".to_owned()).to_bytes())
+        else {
+            panic!("the failure didn't round trip");
+        };
+        assert_eq!(message, "Nope.");
+
+        let Some(ResultPacket::Success(message)) =
+            ResultPacket::from_bytes(ResultPacket::Success("Done.".to_owned()).to_bytes())
+        else {
+            panic!("the success didn't round trip");
+        };
+        assert_eq!(message, "Done.");
+    }
+
+    #[test]
+    fn audio_pages_are_recognized() {
+        let mut page = b"OggS".to_vec();
+        page.extend_from_slice(&[0, OGG_BEGINNING_OF_STREAM]);
+        page.extend_from_slice(&[0; 8]);
+        assert!(is_audio_stream_start(&page));
+        assert!(is_audio_header(&page));
+
+        page[5] = 0;
+        page[6] = 1;
+        assert!(!is_audio_stream_start(&page));
+        assert!(!is_audio_header(&page));
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     net::TcpStream,
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc,
+        Arc, Mutex,
     },
 };
 
@@ -30,13 +30,15 @@ pub struct SecureChannel {
     cipher: Option<Aes256Gcm>,
     /// A boolean indicating whether this channel instance is operating as a server.
     is_server: bool,
+    /// A lock shared between clones so messages sent from different threads don't interleave.
+    write_lock: Arc<Mutex<()>>,
 }
 
 impl Clone for SecureChannel {
     /// Creates a new `SecureChannel` by cloning the existing one.
     ///
     /// This involves cloning the underlying `TcpStream` (if present),
-    /// the atomic nonce counter, the AES cipher, and the `is_server` flag.
+    /// the atomic nonce counter, the AES cipher, the `is_server` flag, and the write lock.
     ///
     /// # Panics
     ///
@@ -50,6 +52,7 @@ impl Clone for SecureChannel {
             nonce_counter: self.nonce_counter.clone(),
             cipher: self.cipher.clone(),
             is_server: self.is_server.clone(),
+            write_lock: self.write_lock.clone(),
         }
     }
 }
@@ -80,6 +83,7 @@ impl SecureChannel {
             nonce_counter: Arc::new(AtomicU64::new(1)),
             cipher: None,
             is_server: true,
+            write_lock: Arc::new(Mutex::new(())),
         };
 
         // If a socket is provided, perform the key exchange handshake.
@@ -112,6 +116,7 @@ impl SecureChannel {
             nonce_counter: Arc::new(AtomicU64::new(1)),
             cipher: None,
             is_server: false,
+            write_lock: Arc::new(Mutex::new(())),
         };
 
         // If a socket is provided, perform the key exchange handshake.
@@ -333,6 +338,9 @@ impl SecureChannel {
         to_send.extend_from_slice(&nonce);
         to_send.extend_from_slice(&encrypted);
 
+        // hold the lock so a large message isn't interleaved with one from another clone
+        let _write_guard = self.write_lock.lock().unwrap();
+
         let socket = self.socket.as_mut().unwrap();
         socket.write_all(&to_send)?;
 
@@ -0,0 +1,372 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use eframe::egui::{DragValue, Ui};
+
+/// How often participants report how well they receive the stream.
+pub const FEEDBACK_INTERVAL: Duration = Duration::from_secs(2);
+/// How often the host looks at the feedback.
+const EVALUATE_INTERVAL: Duration = Duration::from_secs(4);
+/// The minimum time between encoder restarts, since each one costs a keyframe.
+const CHANGE_COOLDOWN: Duration = Duration::from_secs(10);
+/// The number of healthy evaluations in a row before the quality is raised.
+const HEALTHY_EVALUATIONS_TO_RAISE: u32 = 3;
+/// A participant is congested if it receives less than this share of what the host sends.
+const MIN_THROUGHPUT_RATIO: f32 = 0.75;
+/// Below this rate (in kilobits per second) the throughput is too noisy to compare.
+const MIN_MEASURED_KBPS: f32 = 100.0;
+/// A participant is congested if more decoded frames than this are waiting to be shown.
+const MAX_QUEUE_DEPTH: u32 = 2;
+/// The number of layers encoded when simulcast is enabled.
+const SIMULCAST_LAYERS: u8 = 3;
+/// The lowest bitrate (in kilobits per second) of a simulcast layer.
+const MIN_LAYER_BITRATE: u32 = 100;
+/// The lowest framerate of a simulcast layer.
+const MIN_LAYER_FRAMERATE: u32 = 5;
+/// The heights the stream is scaled down to, in order, when lowering the bitrate
+/// and framerate isn't enough.
+const DOWNSCALE_HEIGHTS: [u32; 2] = [720, 480];
+
+/// The latest stream report of a participant.
+pub struct Feedback {
+    /// The rate the participant received screen data at, in kilobits per second.
+    pub received_kbps: u32,
+    /// The number of decoded frames waiting to be shown.
+    pub queue_depth: u32,
+    /// The number of decoded frames dropped since the last report.
+    pub dropped_frames: u32,
+    /// When the report arrived.
+    pub received: Instant,
+}
+
+/// The latest report of every participant, by username.
+pub type SharedFeedback = Arc<Mutex<HashMap<String, Feedback>>>;
+
+/// The encoder settings of the stream.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct StreamQuality {
+    /// The maximum bitrate in kilobits per second.
+    pub bitrate: u32,
+    /// Frames per second.
+    pub framerate: u32,
+    /// The height the stream is scaled down to, or `None` for the captured size.
+    pub height: Option<u32>,
+}
+
+impl StreamQuality {
+    /// Derives the settings of a simulcast layer from the full quality settings.
+    ///
+    /// Layer 0 is the full quality, layer 1 has half the resolution and bitrate, and
+    /// layer 2 additionally has a third of the framerate.
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The simulcast layer.
+    /// * `source_height` - The height of the captured area.
+    ///
+    /// # Returns
+    ///
+    /// The encoder settings of the layer.
+    pub fn for_layer(&self, layer: u8, source_height: u32) -> StreamQuality {
+        if layer == 0 {
+            return *self;
+        }
+
+        let half_height = self.height.unwrap_or(source_height).min(source_height) / 2;
+        let mut quality = StreamQuality {
+            bitrate: (self.bitrate / 2).max(MIN_LAYER_BITRATE),
+            framerate: self.framerate,
+            height: Some(half_height.max(2)),
+        };
+
+        if layer >= 2 {
+            quality.bitrate = (self.bitrate / 4).max(MIN_LAYER_BITRATE);
+            quality.framerate = (self.framerate / 3).max(MIN_LAYER_FRAMERATE);
+        }
+
+        quality
+    }
+
+    /// Builds the `ffmpeg` output arguments for these settings.
+    ///
+    /// # Returns
+    ///
+    /// The arguments that go right after the encoder preset.
+    pub fn ffmpeg_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-b:v".to_string(),
+            format!("{}k", self.bitrate),
+            "-maxrate".to_string(),
+            format!("{}k", self.bitrate),
+            "-bufsize".to_string(),
+            format!("{}k", self.bitrate / 2),
+        ];
+
+        if let Some(height) = self.height {
+            args.push("-vf".to_string());
+            args.push(format!("scale=-2:'min({},ih)'", height));
+        }
+
+        args
+    }
+}
+
+/// Adjusts the stream quality to the participants' feedback, within the host's bounds.
+///
+/// When any participant is congested, the bitrate is lowered first, then the
+/// framerate, then the resolution. After a while without congestion the quality
+/// is raised again in the opposite order.
+pub struct QualityController {
+    /// Whether the quality follows the feedback, or stays at the maximum.
+    pub adaptive: bool,
+    /// The lowest bitrate in kilobits per second.
+    pub min_bitrate: u32,
+    /// The highest bitrate in kilobits per second.
+    pub max_bitrate: u32,
+    /// The lowest framerate.
+    pub min_framerate: u32,
+    /// The highest framerate.
+    pub max_framerate: u32,
+    /// Whether the stream can be scaled down.
+    pub allow_downscale: bool,
+    /// Whether lower quality layers are encoded too, so the server can pick one per participant.
+    pub simulcast: bool,
+
+    /// The current encoder settings.
+    quality: StreamQuality,
+    /// When the feedback was last looked at.
+    last_evaluation: Instant,
+    /// When the encoder settings last changed.
+    last_change: Instant,
+    /// The number of evaluations in a row without congestion.
+    healthy_evaluations: u32,
+}
+
+impl QualityController {
+    /// Creates a controller that starts at the highest quality.
+    pub fn new() -> Self {
+        let mut controller = Self {
+            adaptive: true,
+            min_bitrate: 500,
+            max_bitrate: 8000,
+            min_framerate: 10,
+            max_framerate: 30,
+            allow_downscale: true,
+            simulcast: false,
+
+            quality: StreamQuality {
+                bitrate: 0,
+                framerate: 0,
+                height: None,
+            },
+            last_evaluation: Instant::now(),
+            last_change: Instant::now(),
+            healthy_evaluations: 0,
+        };
+        controller.quality = controller.highest_quality();
+
+        controller
+    }
+
+    /// Returns the current encoder settings.
+    pub fn quality(&self) -> StreamQuality {
+        self.quality
+    }
+
+    /// Returns the number of layers to encode.
+    pub fn layers(&self) -> u8 {
+        if self.simulcast {
+            SIMULCAST_LAYERS
+        } else {
+            1
+        }
+    }
+
+    /// Returns the highest quality the bounds allow.
+    fn highest_quality(&self) -> StreamQuality {
+        StreamQuality {
+            bitrate: self.max_bitrate,
+            framerate: self.max_framerate,
+            height: None,
+        }
+    }
+
+    /// Sets new encoder settings, resetting the evaluation state.
+    ///
+    /// # Returns
+    ///
+    /// Whether the settings changed.
+    fn set_quality(&mut self, quality: StreamQuality) -> bool {
+        self.healthy_evaluations = 0;
+        if quality == self.quality {
+            return false;
+        }
+
+        self.quality = quality;
+        self.last_change = Instant::now();
+        true
+    }
+
+    /// Looks at the latest feedback and decides on new encoder settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `feedback` - The latest report of every participant.
+    /// * `sent_bytes` - The number of screen bytes sent, reset on every evaluation.
+    ///
+    /// # Returns
+    ///
+    /// Whether the encoder settings changed, in which case the encoder has to be restarted.
+    pub fn update(&mut self, feedback: &SharedFeedback, sent_bytes: &AtomicU64) -> bool {
+        let elapsed = self.last_evaluation.elapsed();
+        if elapsed < EVALUATE_INTERVAL {
+            return false;
+        }
+        self.last_evaluation = Instant::now();
+
+        let sent_bytes = sent_bytes.swap(0, Ordering::Relaxed);
+        let sent_kbps = (sent_bytes * 8 / 1000) as f32 / elapsed.as_secs_f32();
+
+        if !self.adaptive || self.last_change.elapsed() < CHANGE_COOLDOWN {
+            return false;
+        }
+
+        // only reports about the current encoder settings count
+        let feedback = feedback.lock().unwrap();
+        let reports: Vec<&Feedback> = feedback
+            .values()
+            .filter(|report| report.received > self.last_change)
+            .collect();
+        if reports.is_empty() {
+            return false;
+        }
+
+        let is_slow = |report: &Feedback| {
+            sent_kbps >= MIN_MEASURED_KBPS
+                && (report.received_kbps as f32) < sent_kbps * MIN_THROUGHPUT_RATIO
+        };
+        let congested = reports.iter().any(|report| {
+            report.dropped_frames > 0 || report.queue_depth > MAX_QUEUE_DEPTH || is_slow(report)
+        });
+        let slowest = reports
+            .iter()
+            .filter(|report| is_slow(report))
+            .map(|report| report.received_kbps)
+            .min();
+
+        let mut quality = self.quality;
+
+        if congested {
+            if quality.bitrate > self.min_bitrate {
+                // aim below what the slowest participant manages to receive
+                let mut target = quality.bitrate * 7 / 10;
+                if let Some(slowest) = slowest {
+                    target = target.min(slowest * 9 / 10);
+                }
+                quality.bitrate = target.max(self.min_bitrate);
+            } else if quality.framerate > self.min_framerate {
+                quality.framerate = (quality.framerate / 2).max(self.min_framerate);
+            } else if self.allow_downscale {
+                let next_height = match quality.height {
+                    None => DOWNSCALE_HEIGHTS.first(),
+                    Some(height) => DOWNSCALE_HEIGHTS.iter().find(|next| **next < height),
+                };
+                quality.height = next_height.copied().or(quality.height);
+            }
+
+            return self.set_quality(quality);
+        }
+
+        self.healthy_evaluations += 1;
+        if self.healthy_evaluations < HEALTHY_EVALUATIONS_TO_RAISE {
+            return false;
+        }
+
+        if let Some(height) = quality.height {
+            let position = DOWNSCALE_HEIGHTS.iter().position(|step| *step == height);
+            quality.height = match position {
+                Some(0) | None => None,
+                Some(index) => Some(DOWNSCALE_HEIGHTS[index - 1]),
+            };
+        } else if quality.framerate < self.max_framerate {
+            quality.framerate = (quality.framerate * 2).min(self.max_framerate);
+        } else {
+            quality.bitrate = (quality.bitrate * 5 / 4).min(self.max_bitrate);
+        }
+
+        self.set_quality(quality)
+    }
+
+    /// Renders the quality bounds and the current settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `ui` - The `Ui` to draw on.
+    ///
+    /// # Returns
+    ///
+    /// Whether the encoder settings changed, in which case the encoder has to be restarted.
+    pub fn ui(&mut self, ui: &mut Ui) -> bool {
+        ui.checkbox(&mut self.adaptive, "Adapt to the participants");
+
+        ui.horizontal(|ui| {
+            ui.label("Bitrate (kbps)");
+            ui.add(DragValue::new(&mut self.min_bitrate).range(100..=self.max_bitrate));
+            ui.label("to");
+            ui.add(DragValue::new(&mut self.max_bitrate).range(self.min_bitrate..=50000));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Framerate");
+            ui.add(DragValue::new(&mut self.min_framerate).range(1..=self.max_framerate));
+            ui.label("to");
+            ui.add(DragValue::new(&mut self.max_framerate).range(self.min_framerate..=60));
+        });
+        ui.checkbox(&mut self.allow_downscale, "Allow lowering the resolution");
+        let simulcast_toggled = ui
+            .checkbox(
+                &mut self.simulcast,
+                "Send lower quality layers to slow participants",
+            )
+            .changed();
+
+        let quality = self.quality;
+        ui.label(format!(
+            "Streaming at {} kbps, {} fps, {}",
+            quality.bitrate,
+            quality.framerate,
+            quality
+                .height
+                .map_or("full resolution".to_string(), |height| format!(
+                    "{}p",
+                    height
+                ))
+        ));
+
+        // keep the settings within the (possibly new) bounds
+        let mut clamped = if self.adaptive {
+            StreamQuality {
+                bitrate: quality.bitrate.clamp(self.min_bitrate, self.max_bitrate),
+                framerate: quality
+                    .framerate
+                    .clamp(self.min_framerate, self.max_framerate),
+                height: quality.height.filter(|_| self.allow_downscale),
+            }
+        } else {
+            self.highest_quality()
+        };
+
+        // restarting the encoder for every step of a drag would stall the stream
+        if ui.ctx().input(|i| i.pointer.any_down()) {
+            clamped = quality;
+        }
+
+        let quality_changed = clamped != quality && self.set_quality(clamped);
+        simulcast_toggled || quality_changed
+    }
+}
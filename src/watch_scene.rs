@@ -1,77 +1,224 @@
 use std::{
-    collections::VecDeque,
-    io::{Read, Write},
-    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    collections::{HashMap, VecDeque},
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Condvar, Mutex,
     },
     thread::{self, JoinHandle},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use eframe::egui::{self, pos2, Color32, ImageSource, Rect, Sense, Stroke, Ui, Vec2};
-use stream_desk::{protocol::Packet, secure_channel::SecureChannel, Scene, SceneChange};
+use eframe::egui::{
+    self, load::Bytes, pos2, vec2, Color32, ImageSource, Rect, Sense, Stroke, TextureHandle, Ui,
+    Vec2,
+};
+use stream_desk::{
+    chat_ui,
+    protocol::{Packet, SessionEventKind},
+    secure_channel::SecureChannel,
+    users_list, Scene, SceneChange, UserType,
+};
 
-use crate::menu_scene::MenuScene;
+use crate::{
+    decoder::{start_decoder, Frame, FrameSink},
+    menu_scene::MenuScene,
+};
 
 const PLAY_IMAGE: ImageSource = egui::include_image!("../images/play.svg");
 const PAUSE_IMAGE: ImageSource = egui::include_image!("../images/pause.svg");
 const FORWARD_IMAGE: ImageSource = egui::include_image!("../images/forward.svg");
 const BACKWARD_IMAGE: ImageSource = egui::include_image!("../images/backward.svg");
 
-/// Spawns an FFmpeg process configured for H.264 to RGBA conversion.
-///
-/// This function creates an FFmpeg child process with specific arguments for
-/// low-latency H.264 decoding. The process reads H.264 encoded data from stdin
-/// and outputs raw RGBA pixel data to stdout.
+/// How far the playback clock may run ahead of the last shown frame while waiting
+/// for the next one, before it stops and waits for the stream to catch up.
+const MAX_STALL: Duration = Duration::from_millis(200);
+
+/// How far the J and L shortcuts skip, in seconds.
+const LONG_SKIP_SECONDS: i32 = 10;
+
+/// The playback speeds to choose from, in percent of real time.
+const PLAYBACK_SPEEDS: [u32; 9] = [25, 50, 75, 100, 125, 150, 200, 300, 400];
+
+/// The qualities to choose from: their name, the height the recording is scaled down to
+/// and its bitrate in kilobits per second. The first is the recording as it was recorded.
+const PLAYBACK_QUALITIES: [(&str, u32, u32); 4] = [
+    ("Original", 0, 0),
+    ("720p", 720, 2500),
+    ("480p", 480, 1200),
+    ("360p", 360, 600),
+];
+
+/// Formats a playback time as minutes and seconds.
 ///
-/// # Returns
+/// # Arguments
 ///
-/// A `Child` process handle representing the spawned FFmpeg instance.
-/// - The process is configured with piped stdin and stdout for data communication.
-/// - stderr is redirected to null to suppress FFmpeg diagnostic output.
+/// * `time` - The time to format.
 ///
-/// # Panics
+/// # Returns
 ///
-/// Panics if FFmpeg cannot be spawned, typically due to FFmpeg not being
-/// installed or not found in the system PATH.
-fn start_ffmpeg() -> Child {
-    let ffmpeg = Command::new("ffmpeg")
-        .args([
-            "-flags",
-            "low_delay",
-            "-fflags",
-            "discardcorrupt",
-            "-f",
-            "h264",
-            "-i",
-            "-",
-            "-f",
-            "rawvideo",
-            "-pix_fmt",
-            "rgba",
-            "-",
-        ])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()
-        .expect("Failed to spawn ffmpeg");
-
-    ffmpeg
+/// A `String` like `"02:05"`.
+fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// The color the part of the timeline between the clip markers is highlighted with.
+const CLIP_RANGE_COLOR: Color32 = Color32::from_rgba_premultiplied(60, 120, 200, 90);
+
+/// The state of the last clip the user asked for.
+enum ClipStatus {
+    /// The server is cutting the clip.
+    Creating,
+    /// The clip was saved as a new recording, with its title.
+    Created(String),
+    /// The clip couldn't be created, with the reason.
+    Failed(String),
+}
+
+/// The answers of the server to the user's requests, kept by the receiving thread for
+/// the scene to show.
+#[derive(Clone, Default)]
+struct Replies {
+    /// The state of the last clip.
+    clip_status: Arc<Mutex<Option<ClipStatus>>>,
+    /// Why the recording can't be watched in the chosen quality, if it can't.
+    quality_failure: Arc<Mutex<Option<String>>>,
+}
+
+/// An event of the recorded session, replayed along with the recording.
+struct RecordingEvent {
+    /// When the event happened in the recording.
+    time: Duration,
+    /// The kind of the event.
+    kind: SessionEventKind,
+    /// The chat message, or the username of the event's user.
+    text: String,
+}
+
+impl RecordingEvent {
+    /// The color of the event's marker on the timeline.
+    fn color(&self) -> Color32 {
+        match self.kind {
+            SessionEventKind::Chat => Color32::LIGHT_GRAY,
+            SessionEventKind::Join => Color32::GREEN,
+            SessionEventKind::Leave => Color32::RED,
+            SessionEventKind::ControlGranted
+            | SessionEventKind::ControlRevoked
+            | SessionEventKind::ControlDenied => Color32::LIGHT_BLUE,
+            SessionEventKind::RecordingPaused | SessionEventKind::RecordingResumed => {
+                Color32::YELLOW
+            }
+        }
+    }
+
+    /// The event as it's shown in the chat panel and over its marker.
+    fn description(&self) -> String {
+        match self.kind {
+            SessionEventKind::Chat => self.text.clone(),
+            SessionEventKind::Join => format!("{} joined", self.text),
+            SessionEventKind::Leave => format!("{} left", self.text),
+            SessionEventKind::ControlGranted => format!("{} got control", self.text),
+            SessionEventKind::ControlRevoked => format!("{} no longer has control", self.text),
+            SessionEventKind::ControlDenied => format!("{} was denied control", self.text),
+            SessionEventKind::RecordingPaused => format!("{} paused the recording", self.text),
+            SessionEventKind::RecordingResumed => format!("{} resumed the recording", self.text),
+        }
+    }
+}
+
+/// The color of the bookmark markers on the timeline.
+const BOOKMARK_COLOR: Color32 = Color32::YELLOW;
+
+/// A bookmark added to the recording during the session.
+struct RecordingBookmark {
+    /// The time of the bookmark in the recording.
+    time: Duration,
+    name: String,
+    /// The username of the user who added the bookmark.
+    username: String,
+}
+
+/// How the user takes part in a watch party.
+pub enum PartyRole {
+    /// Started the party, with the code others join it with, and controls the playback.
+    Leader(String),
+    /// Joined the party, and follows the leader's playback.
+    Viewer,
+}
+
+/// The leader's playback changes the scene hasn't followed yet.
+#[derive(Default)]
+struct PartyChanges {
+    /// The position the leader sought to.
+    seek: Option<Duration>,
+    paused: Option<bool>,
+    speed_percent: Option<u32>,
+    /// Whether the leader ended the party.
+    ended: bool,
+}
+
+/// What the receiving thread keeps of a watch party, for the scene to show and follow.
+#[derive(Clone, Default)]
+struct PartyState {
+    /// The users in the party, the leader being the host.
+    usernames: Arc<Mutex<HashMap<String, UserType>>>,
+    /// The chat of the party, with the joins and leaves.
+    chat_log: Arc<Mutex<Vec<String>>>,
+    changes: Arc<Mutex<PartyChanges>>,
+}
+
+/// The size of the thumbnail shown when hovering over the timeline.
+const THUMBNAIL_SIZE: Vec2 = vec2(160.0, 90.0);
+
+/// The thumbnails of a recording, as a sprite sheet sent by the server.
+struct SpriteSheet {
+    /// The time between two thumbnails.
+    interval: Duration,
+    /// The number of thumbnails in every row.
+    columns: u32,
+    /// The number of rows of thumbnails.
+    rows: u32,
+    /// The JPEG image.
+    bytes: Bytes,
+}
+
+impl SpriteSheet {
+    /// Finds the part of the sprite sheet showing a time in the recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time in the recording.
+    ///
+    /// # Returns
+    ///
+    /// The UV rectangle of the thumbnail taken right before `time`.
+    fn uv_at(&self, time: Duration) -> Rect {
+        let count = self.columns * self.rows;
+        let index = ((time.as_millis() / self.interval.as_millis().max(1)) as u32).min(count - 1);
+
+        let size = vec2(1.0 / self.columns as f32, 1.0 / self.rows as f32);
+        let column = (index % self.columns) as f32;
+        let row = (index / self.columns) as f32;
+
+        Rect::from_min_size(pos2(column * size.x, row * size.y), size)
+    }
 }
 
 /// Creates a background thread to receive screen data from the secure channel.
 ///
-/// This thread continuously receives packets from the secure channel and forwards
-/// H.264 encoded screen data to the FFmpeg process for decoding. The thread handles
-/// different packet types and manages the FFmpeg stdin stream lifecycle.
+/// This thread continuously receives packets from the secure channel and feeds
+/// H.264 encoded screen data to a decoder created on this thread. The thread handles
+/// different packet types and manages the end of the stream.
 ///
 /// # Arguments
 ///
 /// * `channel` - A `SecureChannel` for receiving packets from the remote source.
-/// * `stdin` - A `ChildStdin` handle to the FFmpeg process for writing H.264 data.
+/// * `sink` - Receives the decoded frames.
+/// * `thumbnails` - Where to put the thumbnails of the recording when they arrive.
+/// * `replies` - Where to put the answers to the user's requests.
+/// * `events` - Where to put the events of the recorded session.
+/// * `bookmarks` - Where to put the bookmarks of the recording.
+/// * `party` - The state of the watch party the user is in, if any.
 ///
 /// # Returns
 ///
@@ -80,26 +227,155 @@ fn start_ffmpeg() -> Child {
 ///
 /// # Behavior
 ///
-/// - `Packet::Screen` packets: H.264 data is written to FFmpeg stdin
-/// - `Packet::None` packets: Closes the FFmpeg stdin stream
+/// - `Packet::FrameTime` packets: Sets the presentation time of the next frame
+/// - `Packet::Screen` packets: H.264 data is fed to the decoder
+/// - `Packet::None` packets: Ends the stream, so the decoder hands out its last frames
+/// - `Packet::Thumbnails` packets: Keeps the sprite sheet, if the recording has one
+/// - `Packet::ClipCreated` and `Packet::ClipFailed` packets: Keeps the result of the clip
+/// - `Packet::PlaybackQualityFailed` packets: Keeps why the chosen quality can't be watched in
+/// - `Packet::SessionEvent` packets: Adds the event to the events of the recording
+/// - `Packet::Bookmark` packets: Adds the bookmark to the bookmarks of the recording
+/// - `Packet::UserUpdate` and `Packet::Chat` packets: Updates the users and the chat of the
+///   watch party
+/// - `Packet::PlaybackPause` and `Packet::PlaybackSpeed` packets: Keeps the leader's change
+///   for the scene to follow
+/// - `Packet::SeekTo` packets: Keeps the position the leader sought to, and terminates the
+///   thread, as the scene restarts it from there
+/// - `Packet::SessionEnd` packets: Marks the watch party as ended and terminates the thread
 /// - `Packet::SeekInit` or `Packet::SessionExit`: Terminates the thread
 /// - Other packet types are ignored
-fn thread_receive_socket(mut channel: SecureChannel, stdin: ChildStdin) -> JoinHandle<()> {
+fn thread_receive_socket(
+    mut channel: SecureChannel,
+    sink: FrameSink,
+    thumbnails: Arc<Mutex<Option<SpriteSheet>>>,
+    replies: Replies,
+    events: Arc<Mutex<Vec<RecordingEvent>>>,
+    bookmarks: Arc<Mutex<Vec<RecordingBookmark>>>,
+    party: Option<PartyState>,
+) -> JoinHandle<()> {
     thread::spawn(move || {
-        let mut stdin = Some(stdin);
+        let mut decoder = start_decoder(sink);
 
         loop {
             let packet = channel.receive().unwrap();
 
             match packet {
-                Packet::Screen { bytes } => {
-                    if let Some(ref mut stdin) = stdin {
-                        let _ = stdin.write_all(&bytes);
-                    }
+                Packet::FrameTime { pts_ms } => {
+                    decoder.start_frame(Duration::from_millis(pts_ms));
+                }
+
+                Packet::Screen { bytes, .. } => {
+                    decoder.push(&bytes);
                 }
 
                 Packet::None => {
-                    stdin = None;
+                    decoder.finish();
+                }
+
+                Packet::Thumbnails {
+                    interval_ms,
+                    columns,
+                    rows,
+                    bytes,
+                } if !bytes.is_empty() && columns > 0 && rows > 0 => {
+                    *thumbnails.lock().unwrap() = Some(SpriteSheet {
+                        interval: Duration::from_millis(interval_ms as u64),
+                        columns,
+                        rows,
+                        bytes: Bytes::from(bytes),
+                    });
+                }
+
+                Packet::ClipCreated { title } => {
+                    *replies.clip_status.lock().unwrap() = Some(ClipStatus::Created(title));
+                }
+
+                Packet::ClipFailed { message } => {
+                    *replies.clip_status.lock().unwrap() = Some(ClipStatus::Failed(message));
+                }
+
+                Packet::PlaybackQualityFailed { message } => {
+                    *replies.quality_failure.lock().unwrap() = Some(message);
+                }
+
+                Packet::SessionEvent {
+                    time_ms,
+                    kind,
+                    text,
+                } => {
+                    events.lock().unwrap().push(RecordingEvent {
+                        time: Duration::from_millis(time_ms),
+                        kind,
+                        text,
+                    });
+                }
+
+                Packet::Bookmark {
+                    time_ms,
+                    name,
+                    username,
+                } => {
+                    bookmarks.lock().unwrap().push(RecordingBookmark {
+                        time: Duration::from_millis(time_ms),
+                        name,
+                        username,
+                    });
+                }
+
+                Packet::UserUpdate {
+                    user_type,
+                    joined_before,
+                    username,
+                } => {
+                    let Some(party) = &party else {
+                        continue;
+                    };
+                    let mut usernames = party.usernames.lock().unwrap();
+                    let mut chat_log = party.chat_log.lock().unwrap();
+
+                    if user_type == UserType::Leaving {
+                        usernames.remove(&username);
+                        chat_log.push(format!("#r{} has left.", username));
+                    } else {
+                        if !joined_before && !usernames.contains_key(&username) {
+                            chat_log.push(format!("#g{} has joined the watch party.", username));
+                        }
+                        usernames.insert(username, user_type);
+                    }
+                }
+
+                Packet::Chat { message } => {
+                    if let Some(party) = &party {
+                        party.chat_log.lock().unwrap().push(message);
+                    }
+                }
+
+                Packet::PlaybackPause { paused } => {
+                    if let Some(party) = &party {
+                        party.changes.lock().unwrap().paused = Some(paused);
+                    }
+                }
+
+                Packet::PlaybackSpeed { speed_percent } => {
+                    if let Some(party) = &party {
+                        party.changes.lock().unwrap().speed_percent = Some(speed_percent);
+                    }
+                }
+
+                // the frames from the new position follow, for a new decoder
+                Packet::SeekTo { time_ms } => {
+                    if let Some(party) = &party {
+                        party.changes.lock().unwrap().seek = Some(Duration::from_millis(time_ms));
+                        break;
+                    }
+                }
+
+                Packet::SessionEnd => {
+                    if let Some(party) = &party {
+                        party.changes.lock().unwrap().ended = true;
+                        channel.send(Packet::SessionEnd).unwrap();
+                        break;
+                    }
                 }
 
                 Packet::SeekInit => break,
@@ -112,179 +388,253 @@ fn thread_receive_socket(mut channel: SecureChannel, stdin: ChildStdin) -> JoinH
     })
 }
 
-/// Creates a background thread to read decoded frames from FFmpeg output.
+/// Creates the `FrameSink` that hands decoded frames to the frame queue.
 ///
-/// This thread continuously reads RGBA frame data from FFmpeg stdout and manages
-/// a synchronized frame queue. It implements backpressure by waiting when the
-/// queue is full and respects stop signals for graceful shutdown.
+/// It implements backpressure by waiting when the queue is full, which in turn
+/// stops the decoder from reading more of the recording, and respects stop signals
+/// for graceful shutdown.
 ///
 /// # Arguments
 ///
-/// * `stdout` - A `ChildStdout` handle to read decoded RGBA data from FFmpeg.
-/// * `frame_queue` - An `Arc<(Mutex<VecDeque<Vec<u8>>>, Condvar)>` for thread-safe
+/// * `frame_queue` - An `Arc<(Mutex<VecDeque<Frame>>, Condvar)>` for thread-safe
 ///                     frame storage with synchronization primitives.
 /// * `stop_flag` - An `Arc<AtomicBool>` for signaling thread termination.
 ///
 /// # Returns
 ///
-/// A `JoinHandle<()>` for the spawned thread that handles frame reading and
-/// queue management in the background.
+/// The `FrameSink` to hand to the decoder.
 ///
 /// # Behavior
 ///
-/// - Reads exactly 1920×1080×4 bytes per frame (RGBA pixels)
 /// - Implements queue size limit of 30 frames to prevent memory overflow
 /// - Blocks when queue is full until space is available or stop signal is received
-/// - Terminates gracefully when FFmpeg stdout closes or stop flag is set
-fn thread_read_decoded(
-    mut stdout: ChildStdout,
-    frame_queue: Arc<(Mutex<VecDeque<Vec<u8>>>, Condvar)>,
+/// - Drops frames once the stop flag is set
+fn frame_sink(
+    frame_queue: Arc<(Mutex<VecDeque<Frame>>, Condvar)>,
     stop_flag: Arc<AtomicBool>,
-) -> JoinHandle<()> {
-    thread::spawn(move || {
+) -> FrameSink {
+    Box::new(move |frame: Frame| {
         let (queue_mutex, condvar) = &*frame_queue;
-        let mut rgba_buf = vec![0u8; 1920 * 1080 * 4];
-
-        loop {
-            // read exactly one decoded frame
-            let Ok(()) = stdout.read_exact(&mut rgba_buf) else {
-                return;
-            };
-
-            let mut queue = queue_mutex.lock().unwrap();
-
-            // wait until queue is less than 30 or stop flag is true
-            queue = condvar
-                .wait_while(queue, |q| {
-                    q.len() >= 30 && !stop_flag.load(Ordering::Relaxed)
-                })
-                .unwrap();
+        let mut queue = queue_mutex.lock().unwrap();
 
-            if stop_flag.load(Ordering::Relaxed) {
-                break;
-            }
+        // wait until queue is less than 30 or stop flag is true
+        queue = condvar
+            .wait_while(queue, |q| {
+                q.len() >= 30 && !stop_flag.load(Ordering::Relaxed)
+            })
+            .unwrap();
 
-            queue.push_back(rgba_buf.clone());
+        if stop_flag.load(Ordering::Relaxed) {
+            return;
         }
+
+        queue.push_back(frame);
     })
 }
 
 /// Represents the video watching scene with playback controls and frame rendering.
 ///
-/// This struct manages the entire video playback experience including FFmpeg
-/// integration, frame decoding, UI rendering, and playback controls. It coordinates
-/// multiple background threads for efficient video streaming and decoding.
+/// This struct manages the entire video playback experience including frame
+/// decoding, UI rendering, and playback controls. It coordinates a background
+/// thread for efficient video streaming and decoding.
 pub struct WatchScene {
-    /// Current timestamp for frame timing calculations
-    now: Instant,
-    /// Accumulated time since last frame update
-    elapsed_time: f32,
-
     /// Username of the user
     username: String,
-    /// Total duration of the recording in frames
-    duration: i32,
-    /// Current playback position in frames
-    current_frame_number: i32,
+    /// Total duration of the recording
+    duration: Duration,
+    /// Presentation time of the currently displayed frame
+    position: Duration,
+    /// The wall clock instant and presentation time playback was last started from,
+    /// unset while paused or waiting for frames
+    clock_anchor: Option<(Instant, Duration)>,
+    /// Playback speed in percent of real time
+    speed_percent: u32,
+    /// Index of the chosen quality in `PLAYBACK_QUALITIES`
+    quality: usize,
+    /// Position of the timeline scrubber in seconds while it's dragged
+    scrub_position: Option<f32>,
 
     /// Atomic boolean for coordinating thread shutdown
     stop_flag: Arc<AtomicBool>,
     /// Current pause state of the playback
     is_paused: bool,
 
-    /// Thread-safe queue containing decoded RGBA frames
-    frame_queue: Arc<(Mutex<VecDeque<Vec<u8>>>, Condvar)>,
-    /// Currently displayed frame data
-    current_frame: Vec<u8>,
-
-    /// Handle to the network receiving thread
+    /// Thread-safe queue containing decoded frames
+    frame_queue: Arc<(Mutex<VecDeque<Frame>>, Condvar)>,
+    /// Texture of the currently displayed frame, once one was decoded
+    screen_texture: Option<TextureHandle>,
+    /// Thumbnails shown when hovering over the timeline, once the server sent them
+    thumbnails: Arc<Mutex<Option<SpriteSheet>>>,
+
+    /// The in marker of the clip to create
+    clip_in: Option<Duration>,
+    /// The out marker of the clip to create
+    clip_out: Option<Duration>,
+    /// Whether the clip is reencoded to start and end at the exact frames
+    clip_accurate: bool,
+    /// The state of the last clip and the quality failure, set by the receiving thread
+    /// once the server answers
+    replies: Replies,
+
+    /// The chat and the other events of the recorded session, in order
+    events: Arc<Mutex<Vec<RecordingEvent>>>,
+    /// The bookmarks of the recording, in order
+    bookmarks: Arc<Mutex<Vec<RecordingBookmark>>>,
+
+    /// The code of the watch party the user leads, which others join it with
+    party_code: Option<String>,
+    /// The watch party the user is in, unless they watch on their own
+    party: Option<PartyState>,
+    /// The message being written to the chat of the watch party
+    chat_message: String,
+
+    /// Handle to the network receiving and decoding thread
     thread_receive_socket: Option<JoinHandle<()>>,
-    /// Handle to the frame decoding thread
-    thread_read_decoded: Option<JoinHandle<()>>,
-    /// FFmpeg child process handle
-    ffmpeg_command: Child,
 }
 
 impl WatchScene {
     /// Creates a new `WatchScene` instance and initializes the video playback system.
     ///
-    /// This constructor sets up the complete video playback pipeline including FFmpeg
-    /// process spawning, background thread creation for network reception and frame
-    /// decoding, and initializes all necessary synchronization primitives.
+    /// This constructor sets up the complete video playback pipeline including the
+    /// background thread for network reception and frame decoding, and initializes
+    /// all necessary synchronization primitives.
     ///
     /// # Arguments
     ///
     /// * `username` - A `String` containing the username for the current session.
-    /// * `duration` - An `i32` representing the total recording duration in frames.
+    /// * `duration` - A `Duration` representing the total recording duration.
+    /// * `party` - How the user takes part in a watch party, or `None` to watch on their own.
     /// * `channel` - A mutable reference to `SecureChannel` for network communication.
     ///
     /// # Returns
     ///
     /// A new `Self` instance with all components initialized and background
     /// threads started for immediate video playback capability.
-    pub fn new(username: String, duration: i32, channel: &mut SecureChannel) -> Self {
-        let mut ffmpeg = start_ffmpeg();
-        let stdin = ffmpeg.stdin.take().unwrap();
-        let stdout = ffmpeg.stdout.take().unwrap();
+    pub fn new(
+        username: String,
+        duration: Duration,
+        party: Option<PartyRole>,
+        channel: &mut SecureChannel,
+    ) -> Self {
+        let party_code = match &party {
+            Some(PartyRole::Leader(code)) => Some(code.clone()),
+            _ => None,
+        };
 
-        let frame_queue = Arc::new((Mutex::new(VecDeque::new()), Condvar::new()));
+        let mut scene = Self {
+            username,
+            duration,
+            position: Duration::ZERO,
+            clock_anchor: None,
+            speed_percent: 100,
+            quality: 0,
+            scrub_position: None,
 
-        let stop_flag = Arc::new(AtomicBool::new(false));
+            stop_flag: Arc::new(AtomicBool::new(false)),
+            is_paused: false,
 
-        let thread_receive_socket = thread_receive_socket(channel.clone(), stdin);
-        let thread_read_decoded =
-            thread_read_decoded(stdout, frame_queue.clone(), stop_flag.clone());
+            frame_queue: Arc::new((Mutex::new(VecDeque::new()), Condvar::new())),
+            screen_texture: None,
+            thumbnails: Arc::new(Mutex::new(None)),
 
-        Self {
-            now: Instant::now(),
-            elapsed_time: 0.0,
+            clip_in: None,
+            clip_out: None,
+            clip_accurate: false,
+            replies: Replies::default(),
 
-            username,
-            duration,
-            current_frame_number: 0,
+            events: Arc::new(Mutex::new(Vec::new())),
+            bookmarks: Arc::new(Mutex::new(Vec::new())),
 
-            stop_flag,
-            is_paused: false,
+            party_code,
+            party: party.map(|_| PartyState::default()),
+            chat_message: String::new(),
+
+            thread_receive_socket: None,
+        };
 
-            frame_queue,
-            current_frame: vec![0u8; 1920 * 1080 * 4],
+        scene.thread_receive_socket = Some(scene.start_receiving(channel));
 
-            thread_receive_socket: Some(thread_receive_socket),
-            thread_read_decoded: Some(thread_read_decoded),
-            ffmpeg_command: ffmpeg,
+        channel.send(Packet::GetThumbnails).unwrap();
+        channel.send(Packet::GetEvents).unwrap();
+        channel.send(Packet::GetBookmarks).unwrap();
+
+        scene
+    }
+
+    /// Starts the thread that receives and decodes the recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - A reference to `SecureChannel` to receive from.
+    ///
+    /// # Returns
+    ///
+    /// The thread's `JoinHandle`.
+    fn start_receiving(&self, channel: &SecureChannel) -> JoinHandle<()> {
+        thread_receive_socket(
+            channel.clone(),
+            frame_sink(self.frame_queue.clone(), self.stop_flag.clone()),
+            self.thumbnails.clone(),
+            self.replies.clone(),
+            self.events.clone(),
+            self.bookmarks.clone(),
+            self.party.clone(),
+        )
+    }
+
+    /// Stops the receiving thread, and with it the decoder, and clears the frame queue.
+    fn stop_receiving(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+
+        {
+            let (queue, condvar) = &*self.frame_queue;
+            let mut queue = queue.lock().unwrap();
+            queue.clear(); // clear old frames
+            condvar.notify_all(); // wake up decoder thread in case it's waiting
+        }
+
+        if let Some(thread_receive_socket) = self.thread_receive_socket.take() {
+            let _ = thread_receive_socket.join();
         }
     }
 
+    /// Checks whether the user controls the playback, which only the leader of a watch
+    /// party does.
+    fn can_control(&self) -> bool {
+        self.party.is_none() || self.party_code.is_some()
+    }
+
     /// Renders the main video display area in the central panel.
     ///
     /// This method handles the video frame rendering with proper scaling and centering
-    /// within the available UI space. It creates a texture from the current frame data
-    /// and displays it with appropriate scaling to maintain aspect ratio.
+    /// within the available UI space. It displays the texture of the current frame
+    /// with appropriate scaling to maintain aspect ratio.
     ///
     /// # Arguments
     ///
     /// * `ui` - A mutable reference to `Ui` for rendering operations.
-    /// * `ctx` - A reference to `egui::Context` for texture management.
     ///
     /// # Behavior
     ///
-    /// - Converts current frame data (1920×1080 RGBA) to an egui texture
+    /// - Uses the size of the current frame, or 1920×1080 before the first frame
     /// - Calculates appropriate scaling to fit within available space
     /// - Centers the video display within the panel
     /// - Applies a white border around the video frame
-    fn central_panel_ui(&mut self, ui: &mut Ui, ctx: &egui::Context) {
-        let texture = egui::ColorImage::from_rgba_unmultiplied([1920, 1080], &self.current_frame);
-        let handle = ctx.load_texture("screen", texture, egui::TextureOptions::default());
+    fn central_panel_ui(&mut self, ui: &mut Ui) {
+        let frame_size = self
+            .screen_texture
+            .as_ref()
+            .map_or(Vec2::new(1920.0, 1080.0), |texture| texture.size_vec2());
 
         let available_size = ui.available_size();
 
         let scale = {
-            let scale_x = available_size.x / 1920.0;
-            let scale_y = available_size.y / 1080.0;
+            let scale_x = available_size.x / frame_size.x;
+            let scale_y = available_size.y / frame_size.y;
             scale_x.min(scale_y)
         };
 
-        let final_size = Vec2::new(1920.0, 1080.0) * scale;
+        let final_size = frame_size * scale;
 
         let available_rect = ui.max_rect();
         let top_left = available_rect.center() - final_size * 0.5;
@@ -293,12 +643,14 @@ impl WatchScene {
         // allocate the space exactly at the centered position
         ui.allocate_rect(centered_rect, Sense::click_and_drag());
 
-        ui.painter().image(
-            handle.id(),
-            centered_rect,
-            Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
-            Color32::WHITE,
-        );
+        if let Some(texture) = &self.screen_texture {
+            ui.painter().image(
+                texture.id(),
+                centered_rect,
+                Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                Color32::WHITE,
+            );
+        }
 
         // put a border
         let stroke = Stroke::new(1.0, Color32::WHITE);
@@ -314,56 +666,392 @@ impl WatchScene {
     ///
     /// # Arguments
     ///
-    /// * `delta` - An `i32` representing the number of seconds to seek (positive
-    ///               for forward, negative for backward).
+    /// * `position` - A `Duration` representing the position to seek to.
     /// * `channel` - A mutable reference to `SecureChannel` for server communication.
     ///
     /// # Behavior
     ///
+    /// - Does nothing for the viewers of a watch party, who follow the leader
     /// - Sends `Packet::SeekInit` to notify the server of seek operation
-    /// - Stops all background threads and clears frame queue
-    /// - Terminates current FFmpeg process
-    /// - Calculates new position with bounds checking (0 to duration/30)
-    /// - Sends `Packet::SeekTo` with new timestamp
-    /// - Restarts FFmpeg and background threads for new position
-    fn seek_recording(&mut self, delta: i32, channel: &mut SecureChannel) {
+    /// - Stops the background thread, and with it the decoder, and clears frame queue
+    /// - Clamps the new position to the duration of the recording
+    /// - Sends `Packet::SeekTo` with the new position in milliseconds
+    /// - Restarts the background thread with a new decoder for new position
+    fn seek_to(&mut self, position: Duration, channel: &mut SecureChannel) {
+        if !self.can_control() {
+            return;
+        }
+
         // send seek init
         channel.send(Packet::SeekInit).unwrap();
 
+        self.restart_receiving(position, channel);
+
+        // send seek to
+        let time_ms = self.position.as_millis() as u64;
+        channel.send(Packet::SeekTo { time_ms }).unwrap();
+    }
+
+    /// Restarts the receiving thread with a new decoder, for the frames from a new position.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - A `Duration` representing the new position.
+    /// * `channel` - A reference to `SecureChannel` to receive from.
+    fn restart_receiving(&mut self, position: Duration, channel: &SecureChannel) {
         // stop everything
-        self.stop_flag.store(true, Ordering::Relaxed);
+        self.stop_receiving();
 
-        {
-            let (queue, condvar) = &*self.frame_queue;
-            let mut queue = queue.lock().unwrap();
-            queue.clear(); // clear old frames
-            condvar.notify_all();
+        self.position = position.min(self.duration);
+        self.clock_anchor = None;
+
+        // start everything from scratch
+        self.stop_flag.store(false, Ordering::Relaxed);
+        self.thread_receive_socket = Some(self.start_receiving(channel));
+    }
+
+    /// Seeks relative to the current position in the recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `delta` - An `i32` representing the number of seconds to seek (positive
+    ///             for forward, negative for backward).
+    /// * `channel` - A mutable reference to `SecureChannel` for server communication.
+    fn seek_recording(&mut self, delta: i32, channel: &mut SecureChannel) {
+        let offset = Duration::from_secs(delta.unsigned_abs() as u64);
+
+        let position = if delta < 0 {
+            self.position.saturating_sub(offset)
+        } else {
+            self.position + offset
+        };
+
+        self.seek_to(position, channel);
+    }
+
+    /// Pauses or resumes playback, for everyone if the user leads a watch party.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - A mutable reference to `SecureChannel` for server communication.
+    fn toggle_pause(&mut self, channel: &mut SecureChannel) {
+        if !self.can_control() {
+            return;
         }
 
-        let _ = self.thread_receive_socket.take().unwrap().join();
-        let _ = self.thread_read_decoded.take().unwrap().join();
-        let _ = self.ffmpeg_command.kill();
+        self.is_paused = !self.is_paused;
+        self.clock_anchor = None;
 
-        // send seek to
-        let time_seconds = (self.current_frame_number / 30 + delta).clamp(0, self.duration / 30);
-        self.current_frame_number = time_seconds * 30;
+        if self.party.is_some() {
+            let paused = self.is_paused;
+            channel.send(Packet::PlaybackPause { paused }).unwrap();
+        }
+    }
 
-        channel.send(Packet::SeekTo { time_seconds }).unwrap();
+    /// Follows the playback changes of the leader of the watch party.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - A mutable reference to `SecureChannel` for server communication.
+    ///
+    /// # Returns
+    ///
+    /// A `SceneChange` to the `MenuScene` if the leader ended the watch party.
+    fn follow_party(&mut self, channel: &mut SecureChannel) -> Option<SceneChange> {
+        let changes = std::mem::take(&mut *self.party.as_ref()?.changes.lock().unwrap());
+
+        if changes.ended {
+            self.stop_receiving();
+
+            return Some(SceneChange::To(Box::new(MenuScene::new(
+                self.username.clone(),
+                channel,
+                "The leader ended the watch party.",
+            ))));
+        }
 
-        // start everything from scratch
+        if let Some(paused) = changes.paused {
+            self.is_paused = paused;
+            self.clock_anchor = None;
+        }
 
-        self.ffmpeg_command = start_ffmpeg();
-        let stdin = self.ffmpeg_command.stdin.take().unwrap();
-        let stdout = self.ffmpeg_command.stdout.take().unwrap();
+        if let Some(speed_percent) = changes.speed_percent {
+            self.speed_percent = speed_percent;
+            self.clock_anchor = None;
+        }
 
-        self.stop_flag.store(false, Ordering::Relaxed);
+        if let Some(position) = changes.seek {
+            self.restart_receiving(position, channel);
+        }
+
+        None
+    }
+
+    /// Renders the users and the chat of the watch party.
+    ///
+    /// # Arguments
+    ///
+    /// * `ui` - A mutable reference to `Ui` for rendering operations.
+    /// * `party` - The state of the watch party.
+    /// * `channel` - A mutable reference to `SecureChannel` for sending chat messages.
+    fn party_panel_ui(&mut self, ui: &mut Ui, party: &PartyState, channel: &mut SecureChannel) {
+        ui.heading("Watch party");
+        if let Some(code) = &self.party_code {
+            ui.label(format!("Session code: {}", code));
+        }
+        ui.separator();
+
+        users_list(
+            ui,
+            party.usernames.lock().unwrap(),
+            self.username.clone(),
+            false,
+        );
+        ui.separator();
+
+        chat_ui(
+            ui,
+            party.chat_log.lock().unwrap(),
+            &mut self.chat_message,
+            channel,
+        );
+    }
+
+    /// Handles the keyboard shortcuts of the player.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - A reference to `egui::Context` for reading the input.
+    /// * `channel` - A mutable reference to `SecureChannel` for server communication.
+    ///
+    /// # Behavior
+    ///
+    /// - Space or K: Pauses or resumes playback
+    /// - Left and Right arrows: Seek 5 seconds backward or forward
+    /// - J and L: Seek 10 seconds backward or forward
+    /// - Home and End: Seek to the start or the end of the recording
+    /// - I and O: Set the in and out markers of a clip at the current position
+    fn handle_shortcuts(&mut self, ctx: &egui::Context, channel: &mut SecureChannel) {
+        // typing in the chat of a watch party isn't a shortcut
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+
+        let pressed = |key: egui::Key| ctx.input(|input| input.key_pressed(key));
+
+        if pressed(egui::Key::Space) || pressed(egui::Key::K) {
+            self.toggle_pause(channel);
+        }
 
-        self.thread_receive_socket = Some(thread_receive_socket(channel.clone(), stdin));
-        self.thread_read_decoded = Some(thread_read_decoded(
-            stdout,
-            self.frame_queue.clone(),
-            self.stop_flag.clone(),
-        ));
+        if pressed(egui::Key::ArrowLeft) {
+            self.seek_recording(-5, channel);
+        } else if pressed(egui::Key::ArrowRight) {
+            self.seek_recording(5, channel);
+        } else if pressed(egui::Key::J) {
+            self.seek_recording(-LONG_SKIP_SECONDS, channel);
+        } else if pressed(egui::Key::L) {
+            self.seek_recording(LONG_SKIP_SECONDS, channel);
+        } else if pressed(egui::Key::Home) {
+            self.seek_to(Duration::ZERO, channel);
+        } else if pressed(egui::Key::End) {
+            self.seek_to(self.duration, channel);
+        }
+
+        if pressed(egui::Key::I) {
+            self.clip_in = Some(self.position);
+        } else if pressed(egui::Key::O) {
+            self.clip_out = Some(self.position);
+        }
+    }
+
+    /// Renders the bookmarks of the recording, and the chat and the other events of the
+    /// recorded session up to the current position, newest at the bottom.
+    ///
+    /// # Arguments
+    ///
+    /// * `ui` - A mutable reference to `Ui` for rendering operations.
+    ///
+    /// # Returns
+    ///
+    /// The time of the bookmark or the event the user clicked, to seek to.
+    fn events_panel_ui(&self, ui: &mut Ui) -> Option<Duration> {
+        let mut clicked = None;
+
+        let bookmarks = self.bookmarks.lock().unwrap();
+        if !bookmarks.is_empty() {
+            ui.heading("Bookmarks");
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .id_salt("bookmarks")
+                .max_height(150.0)
+                .show(ui, |ui| {
+                    for bookmark in bookmarks.iter() {
+                        ui.horizontal_wrapped(|ui| {
+                            let time = egui::RichText::new(format_time(bookmark.time)).weak();
+                            if ui.link(time).clicked() || ui.link(&bookmark.name).clicked() {
+                                clicked = Some(bookmark.time);
+                            }
+                        })
+                        .response
+                        .on_hover_text(format!("Added by {}", bookmark.username));
+                    }
+                });
+
+            ui.add_space(10.0);
+        }
+        drop(bookmarks);
+
+        ui.heading("Chat");
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .id_salt("events")
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                let events = self.events.lock().unwrap();
+                let shown = events
+                    .iter()
+                    .take_while(|event| event.time <= self.position);
+
+                for event in shown {
+                    ui.horizontal_wrapped(|ui| {
+                        let time = egui::RichText::new(format_time(event.time)).weak();
+                        if ui.link(time).clicked() {
+                            clicked = Some(event.time);
+                        }
+
+                        if event.kind == SessionEventKind::Chat {
+                            ui.label(event.description());
+                        } else {
+                            ui.label(
+                                egui::RichText::new(event.description())
+                                    .italics()
+                                    .color(event.color()),
+                            );
+                        }
+                    });
+                }
+            });
+
+        clicked
+    }
+
+    /// Gets the part of the recording between the clip markers.
+    ///
+    /// # Returns
+    ///
+    /// The start and the end of the clip, or `None` unless both markers are set
+    /// with the in marker first.
+    fn clip_range(&self) -> Option<(Duration, Duration)> {
+        match (self.clip_in, self.clip_out) {
+            (Some(start), Some(end)) if start < end => Some((start, end)),
+            _ => None,
+        }
+    }
+
+    /// Renders the clip markers and the button that creates the clip.
+    ///
+    /// # Arguments
+    ///
+    /// * `ui` - A mutable reference to `Ui` for rendering operations.
+    /// * `channel` - A mutable reference to `SecureChannel` for server communication.
+    fn clip_controls_ui(&mut self, ui: &mut Ui, channel: &mut SecureChannel) {
+        let marker_text =
+            |marker: Option<Duration>| marker.map_or("--:--".to_string(), format_time);
+
+        ui.horizontal(|ui| {
+            if ui.button("Set in (I)").clicked() {
+                self.clip_in = Some(self.position);
+            }
+            ui.label(marker_text(self.clip_in));
+
+            if ui.button("Set out (O)").clicked() {
+                self.clip_out = Some(self.position);
+            }
+            ui.label(marker_text(self.clip_out));
+
+            if ui.button("Clear").clicked() {
+                self.clip_in = None;
+                self.clip_out = None;
+            }
+
+            ui.checkbox(&mut self.clip_accurate, "Frame accurate")
+                .on_hover_text("Reencode the clip instead of cutting it on keyframes");
+
+            let mut clip_status = self.replies.clip_status.lock().unwrap();
+            let is_creating = matches!(*clip_status, Some(ClipStatus::Creating));
+
+            let create_button = ui.add_enabled(
+                self.clip_range().is_some() && !is_creating,
+                egui::Button::new("Create clip"),
+            );
+            if create_button.clicked() {
+                let (start, end) = self.clip_range().unwrap();
+
+                let packet = Packet::CreateClip {
+                    start_ms: start.as_millis() as u64,
+                    end_ms: end.as_millis() as u64,
+                    accurate: self.clip_accurate,
+                };
+                channel.send(packet).unwrap();
+
+                *clip_status = Some(ClipStatus::Creating);
+            }
+
+            match &*clip_status {
+                Some(ClipStatus::Creating) => {
+                    ui.spinner();
+                    ui.label("Creating clip…");
+                }
+                Some(ClipStatus::Created(title)) => {
+                    ui.label(
+                        egui::RichText::new(format!("Saved \"{}\" to your recordings", title))
+                            .color(Color32::GREEN),
+                    );
+                }
+                Some(ClipStatus::Failed(message)) => {
+                    ui.label(egui::RichText::new(message).color(Color32::RED));
+                }
+                None => (),
+            }
+        });
+    }
+
+    /// Changes the playback speed, continuing from the current position.
+    ///
+    /// The server is told the new speed, so it sends the frames at that pace, and the
+    /// frames that can't be shown in time are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `speed_percent` - The new playback speed, in percent of real time.
+    /// * `channel` - A mutable reference to `SecureChannel` for server communication.
+    fn set_speed(&mut self, speed_percent: u32, channel: &mut SecureChannel) {
+        self.speed_percent = speed_percent;
+        self.clock_anchor = None;
+
+        channel
+            .send(Packet::PlaybackSpeed { speed_percent })
+            .unwrap();
+    }
+
+    /// Changes the quality the recording is watched in, restarting it from the
+    /// current position.
+    ///
+    /// # Arguments
+    ///
+    /// * `quality` - The index of the new quality in `PLAYBACK_QUALITIES`.
+    /// * `channel` - A mutable reference to `SecureChannel` for server communication.
+    fn set_quality(&mut self, quality: usize, channel: &mut SecureChannel) {
+        self.quality = quality;
+        *self.replies.quality_failure.lock().unwrap() = None;
+
+        let (_, height, bitrate) = PLAYBACK_QUALITIES[quality];
+        channel
+            .send(Packet::PlaybackQuality { height, bitrate })
+            .unwrap();
+        self.seek_to(self.position, channel);
     }
 
     /// Gracefully exits the watch scene and returns to the menu.
@@ -384,22 +1072,12 @@ impl WatchScene {
     /// # Behavior
     ///
     /// - Sends *`Packet::SessionExit`* to notify server of session termination
-    /// - Stops all background threads and clears frame queue
-    /// - Terminates FFmpeg process and releases all resources
+    /// - Stops the background thread and the decoder, releasing all resources
     /// - Creates and returns transition to menu scene
     fn exit(&mut self, channel: &mut SecureChannel) -> SceneChange {
         channel.send(Packet::SessionExit).unwrap();
 
-        self.stop_flag.store(true, Ordering::Relaxed);
-
-        {
-            let (_, condvar) = &*self.frame_queue;
-            condvar.notify_all(); // wake up decoder thread in case it's waiting
-        }
-
-        let _ = self.thread_receive_socket.take().unwrap().join();
-        let _ = self.thread_read_decoded.take().unwrap().join();
-        let _ = self.ffmpeg_command.kill();
+        self.stop_receiving();
 
         SceneChange::To(Box::new(MenuScene::new(self.username.clone(), channel, "")))
     }
@@ -427,50 +1105,82 @@ impl Scene for WatchScene {
     ///
     /// - Updates frame timing and advances playback when not paused
     /// - Renders bottom panel with playback controls (play/pause, seek, progress)
+    /// - Follows the leader's playback in a watch party, and returns to the menu once it ends
+    /// - Renders side panel with the chat and events of the session, in sync with playback
+    /// - Renders side panel with the users and the chat of the watch party, if in one
     /// - Renders central panel with scaled video display
     /// - Handles user interactions for playback control and scene navigation
     fn update(&mut self, ctx: &egui::Context, channel: &mut SecureChannel) -> SceneChange {
         let mut result = SceneChange::None;
 
-        let now = Instant::now();
-        let dt = now.duration_since(self.now).as_secs_f32();
-        self.now = now;
-        self.elapsed_time += dt;
+        if let Some(change) = self.follow_party(channel) {
+            return change;
+        }
 
-        if self.elapsed_time > 1.0 / 30.0 && !self.is_paused {
-            self.elapsed_time = 0.0;
+        self.handle_shortcuts(ctx, channel);
+
+        if !self.is_paused {
+            // the presentation time that should be on screen now
+            let (anchor_instant, anchor_pts) = *self
+                .clock_anchor
+                .get_or_insert((Instant::now(), self.position));
+            let clock = anchor_pts + anchor_instant.elapsed() * self.speed_percent / 100;
 
             let (queue_mutex, condvar) = &*self.frame_queue;
             let mut queue = queue_mutex.lock().unwrap();
 
-            if let Some(frame) = queue.pop_front() {
-                self.current_frame = frame;
-
-                self.current_frame_number += 1;
+            // skip the frames that are already late, and show the latest one that is due
+            let mut due_frame = None;
+            while queue
+                .front()
+                .is_some_and(|frame| frame.pts.is_none_or(|pts| pts <= clock))
+            {
+                due_frame = queue.pop_front();
             }
 
-            condvar.notify_all();
+            if let Some(frame) = due_frame {
+                if let Some(pts) = frame.pts {
+                    self.position = pts;
+                }
+
+                match &mut self.screen_texture {
+                    Some(texture) => texture.set(frame.image, egui::TextureOptions::default()),
+                    None => {
+                        self.screen_texture = Some(ctx.load_texture(
+                            "screen",
+                            frame.image,
+                            egui::TextureOptions::default(),
+                        ))
+                    }
+                }
+
+                condvar.notify_all();
+            } else if queue.is_empty() && clock > self.position + MAX_STALL {
+                // the stream is behind, restart the clock once the next frame arrives
+                self.clock_anchor = None;
+            }
         }
 
         egui::TopBottomPanel::bottom("bottom_panel")
             .resizable(false)
             .show(ctx, |ui| {
-                let current_time = format!(
-                    "{:02}:{:02}",
-                    self.current_frame_number / 1800,
-                    (self.current_frame_number % 1800) / 30
-                );
-
-                let total_time = format!(
-                    "{:02}:{:02}",
-                    self.duration / 1800,
-                    (self.duration % 1800) / 30
+                // while scrubbing, show where the recording will continue from
+                let shown_position = self
+                    .scrub_position
+                    .map_or(self.position, Duration::from_secs_f32);
+
+                let time_string = format!(
+                    "{} / {}",
+                    format_time(shown_position),
+                    format_time(self.duration)
                 );
 
-                let time_string = format!("{} / {}", current_time, total_time);
-                let progress = self.current_frame_number as f32 / self.duration as f32;
-
                 ui.horizontal(|ui| {
+                    // the viewers of a watch party follow the leader's playback
+                    if !self.can_control() {
+                        ui.disable();
+                    }
+
                     let pause_play_button = egui::Button::image(if self.is_paused {
                         PLAY_IMAGE
                     } else {
@@ -486,7 +1196,7 @@ impl Scene for WatchScene {
                     }
                     // toggle pause
                     if response.clicked() {
-                        self.is_paused = !self.is_paused;
+                        self.toggle_pause(channel);
                     }
 
                     let skip_backward_button = egui::Button::image(BACKWARD_IMAGE).frame(false);
@@ -513,11 +1223,130 @@ impl Scene for WatchScene {
                         self.seek_recording(5, channel);
                     }
 
+                    let mut speed_percent = self.speed_percent;
+                    egui::ComboBox::from_id_salt("playback_speed")
+                        .width(60.0)
+                        .selected_text(format!("{}×", speed_percent as f32 / 100.0))
+                        .show_ui(ui, |ui| {
+                            for speed in PLAYBACK_SPEEDS {
+                                ui.selectable_value(
+                                    &mut speed_percent,
+                                    speed,
+                                    format!("{}×", speed as f32 / 100.0),
+                                );
+                            }
+                        });
+                    if speed_percent != self.speed_percent {
+                        self.set_speed(speed_percent, channel);
+                    }
+
+                    // the server sends the recording as recorded if it can't transcode it
+                    let quality_failure = self.replies.quality_failure.lock().unwrap().clone();
+                    if quality_failure.is_some() {
+                        self.quality = 0;
+                    }
+
+                    let mut quality = self.quality;
+                    egui::ComboBox::from_id_salt("playback_quality")
+                        .width(70.0)
+                        .selected_text(PLAYBACK_QUALITIES[quality].0)
+                        .show_ui(ui, |ui| {
+                            for (index, (name, ..)) in PLAYBACK_QUALITIES.iter().enumerate() {
+                                ui.selectable_value(&mut quality, index, *name);
+                            }
+                        });
+                    if quality != self.quality {
+                        self.set_quality(quality, channel);
+                    }
+                    if let Some(message) = quality_failure {
+                        ui.label(egui::RichText::new("⚠").color(Color32::RED))
+                            .on_hover_text(message);
+                    }
+
                     ui.label(time_string);
-                    let progress_bar = egui::ProgressBar::new(progress);
-                    ui.add(progress_bar);
+
+                    // timeline scrubber, seeking once it's released
+                    let mut scrub_seconds = shown_position.as_secs_f32();
+                    ui.spacing_mut().slider_width = ui.available_width();
+                    let response = ui.add(
+                        egui::Slider::new(&mut scrub_seconds, 0.0..=self.duration.as_secs_f32())
+                            .show_value(false),
+                    );
+
+                    // preview the recording at the hovered time
+                    if let Some(pointer) = response.hover_pos() {
+                        let fraction = ((pointer.x - response.rect.left()) / response.rect.width())
+                            .clamp(0.0, 1.0);
+                        let hovered_time = self.duration.mul_f32(fraction);
+
+                        if let Some(sheet) = &*self.thumbnails.lock().unwrap() {
+                            response.clone().on_hover_ui_at_pointer(|ui| {
+                                ui.add(
+                                    egui::Image::from_bytes(
+                                        "bytes://thumbnails.jpg",
+                                        sheet.bytes.clone(),
+                                    )
+                                    .uv(sheet.uv_at(hovered_time))
+                                    .fit_to_exact_size(THUMBNAIL_SIZE),
+                                );
+                                ui.label(format_time(hovered_time));
+                            });
+                        }
+                    }
+
+                    // mark the events of the session on the timeline
+                    {
+                        let rect = response.rect;
+                        let events = self.events.lock().unwrap();
+                        for event in events.iter() {
+                            let x = rect.left()
+                                + rect.width()
+                                    * (event.time.as_secs_f32() / self.duration.as_secs_f32());
+                            ui.painter().vline(
+                                x,
+                                rect.bottom() - 6.0..=rect.bottom(),
+                                Stroke::new(2.0, event.color()),
+                            );
+                        }
+
+                        let bookmarks = self.bookmarks.lock().unwrap();
+                        for bookmark in bookmarks.iter() {
+                            let x = rect.left()
+                                + rect.width()
+                                    * (bookmark.time.as_secs_f32() / self.duration.as_secs_f32());
+                            ui.painter()
+                                .vline(x, rect.y_range(), Stroke::new(2.0, BOOKMARK_COLOR));
+                        }
+                    }
+
+                    // highlight the part of the recording the clip is cut from
+                    if let Some((start, end)) = self.clip_range() {
+                        let rect = response.rect;
+                        let x_at = |time: Duration| {
+                            rect.left()
+                                + rect.width() * (time.as_secs_f32() / self.duration.as_secs_f32())
+                        };
+                        ui.painter().rect_filled(
+                            Rect::from_x_y_ranges(x_at(start)..=x_at(end), rect.y_range()),
+                            2.0,
+                            CLIP_RANGE_COLOR,
+                        );
+                    }
+
+                    if response.dragged() {
+                        self.scrub_position = Some(scrub_seconds);
+                    }
+                    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                        self.scrub_position = None;
+                        self.seek_to(Duration::from_secs_f32(scrub_seconds), channel);
+
+                        // leave the arrow keys to the shortcuts
+                        response.surrender_focus();
+                    }
                 });
 
+                self.clip_controls_ui(ui, channel);
+
                 ui.vertical_centered(|ui| {
                     if ui.button("Exit").clicked() {
                         result = self.exit(channel);
@@ -525,8 +1354,20 @@ impl Scene for WatchScene {
                 });
             });
 
+        if let Some(party) = self.party.clone() {
+            egui::SidePanel::left("party_panel")
+                .show(ctx, |ui| self.party_panel_ui(ui, &party, channel));
+        }
+
+        let seek_event = egui::SidePanel::right("events_panel")
+            .show(ctx, |ui| self.events_panel_ui(ui))
+            .inner;
+        if let Some(time) = seek_event {
+            self.seek_to(time, channel);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            self.central_panel_ui(ui, ctx);
+            self.central_panel_ui(ui);
         });
 
         result